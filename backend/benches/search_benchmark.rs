@@ -1,15 +1,75 @@
-use criterion::{criterion_group, criterion_main, Criterion};
+//! Search benchmarks
+//!
+//! Run with `cargo bench --bench search_benchmark`. Scenarios:
+//! - `hybrid_search_empty`: baseline overhead of a search against an empty corpus
+//! - `hybrid_search_populated/<corpus>`: single search over a seeded corpus
+//! - `concurrent_search/<n>`: `n` searches issued at once; throughput is reported in
+//!   searches/sec, so flat throughput as `n` grows means searches are serializing on a lock
+//! - `ingest`: local-cache ingest of one document (includes cache persistence to disk)
+//! - `merge_results/<hits>`: score fusion in isolation, independent of any backend
+//!
+//! Configuration via env:
+//! - `BENCH_CORPUS_SIZE` (default 1000): documents seeded before the populated benches
+//! - `BENCH_CONCURRENCY` (default `1,4,16,64`): comma-separated concurrency levels
+//!
+//! Benches run against the local cache; leave `AZURE_OPENAI_*` unset or ingest timings will
+//! include embedding network calls. Criterion stores reports under `target/criterion`; compare
+//! runs there (or with `--save-baseline`/`--baseline`) rather than reading absolute numbers.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 use brainvault_backend::core::search_engine::{HybridSearchEngine, SearchWeights};
-use brainvault_backend::db::barq_vector::BarqVectorClient;
+use brainvault_backend::db::barq_vector::{BarqVectorClient, SearchHit};
+use std::sync::Arc;
 use tokio::runtime::Runtime;
 
+const TOPICS: [&str; 8] = [
+    "quantum computing superposition qubits entanglement",
+    "machine learning neural networks training data",
+    "cybersecurity zero trust encryption audits",
+    "cloud computing storage servers networking",
+    "natural language processing translation sentiment",
+    "blockchain ledger smart contracts transactions",
+    "kubernetes containers scaling deployment",
+    "devops pipelines continuous integration testing",
+];
+
+fn env_usize(key: &str, default: usize) -> usize {
+    std::env::var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+fn concurrency_levels() -> Vec<usize> {
+    std::env::var("BENCH_CONCURRENCY")
+        .unwrap_or_else(|_| "1,4,16,64".to_string())
+        .split(',')
+        .filter_map(|s| s.trim().parse().ok())
+        .filter(|n| *n > 0)
+        .collect()
+}
+
+fn new_engine() -> HybridSearchEngine {
+    // Keep the bench cache out of the real data directory
+    let data_path = std::env::temp_dir().join("brainvault-bench");
+    let _ = std::fs::create_dir_all(&data_path);
+    std::env::set_var("DATA_PATH", &data_path);
+
+    HybridSearchEngine::new(
+        BarqVectorClient::new(),
+        SearchWeights { vector_weight: 0.7, bm25_weight: 0.3 }
+    )
+}
+
+fn seed_corpus(rt: &Runtime, engine: &HybridSearchEngine, size: usize) {
+    rt.block_on(async {
+        for i in 0..size {
+            let content = format!("Document {} about {}. {}", i, TOPICS[i % TOPICS.len()], TOPICS[(i * 7) % TOPICS.len()]);
+            engine.ingest_document(&format!("bench-doc-{}", i), &content).await.unwrap();
+        }
+    });
+}
+
 fn bench_hybrid_search(c: &mut Criterion) {
     let rt = Runtime::new().unwrap();
-    let client = BarqVectorClient::new();
-    let engine = HybridSearchEngine::new(
-        client,
-        SearchWeights { vector_weight: 0.7, bm25_weight: 0.3 }
-    );
+    let engine = new_engine();
     
     c.bench_function("hybrid_search_empty", |b| {
         b.to_async(&rt).iter(|| async {
@@ -20,5 +80,83 @@ fn bench_hybrid_search(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, bench_hybrid_search);
+fn bench_populated_search(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let corpus_size = env_usize("BENCH_CORPUS_SIZE", 1000);
+    let engine = Arc::new(new_engine());
+    seed_corpus(&rt, &engine, corpus_size);
+
+    c.bench_with_input(BenchmarkId::new("hybrid_search_populated", corpus_size), &corpus_size, |b, _| {
+        b.to_async(&rt).iter(|| async {
+            engine.search("zero trust encryption", 10).await.unwrap();
+        })
+    });
+
+    let mut group = c.benchmark_group("concurrent_search");
+    for concurrency in concurrency_levels() {
+        group.throughput(Throughput::Elements(concurrency as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(concurrency), &concurrency, |b, &n| {
+            b.to_async(&rt).iter(|| {
+                let engine = engine.clone();
+                async move {
+                    let searches = (0..n).map(|i| {
+                        let engine = engine.clone();
+                        tokio::spawn(async move {
+                            engine.search(TOPICS[i % TOPICS.len()], 10).await.unwrap();
+                        })
+                    });
+                    for handle in futures::future::join_all(searches).await {
+                        handle.unwrap();
+                    }
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_ingest(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let engine = new_engine();
+    let mut counter = 0usize;
+
+    let mut group = c.benchmark_group("ingest");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("local_cache", |b| {
+        b.to_async(&rt).iter(|| {
+            counter += 1;
+            let doc_id = format!("bench-ingest-{}", counter);
+            let engine = &engine;
+            async move {
+                engine.ingest_document(&doc_id, TOPICS[counter % TOPICS.len()]).await.unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+fn bench_merge(c: &mut Criterion) {
+    let engine = new_engine();
+    let mut group = c.benchmark_group("merge_results");
+
+    for size in [10usize, 100, 1000] {
+        let hits = |offset: usize| -> Vec<SearchHit> {
+            (0..size).map(|i| SearchHit {
+                doc_id: format!("doc-{}", (i + offset) % (size * 2)),
+                score: 1.0 / (i + 1) as f32,
+                content: Some(TOPICS[i % TOPICS.len()].to_string()),
+            }).collect()
+        };
+        let vector_hits = hits(0);
+        let bm25_hits = hits(size / 2);
+
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| engine.merge_results("zero trust encryption", vector_hits.clone(), bm25_hits.clone()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_hybrid_search, bench_populated_search, bench_ingest, bench_merge);
 criterion_main!(benches);
@@ -1,55 +1,277 @@
-use actix_web::{get, post, web, HttpResponse, Responder};
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
 use serde::{Deserialize, Serialize};
-use crate::core::agent_orchestrator::{AgentOrchestrator, AgentProfile, AgentType};
+use crate::core::agent_orchestrator::{AgentOrchestrator, AgentProfile, AgentType, ResearchLimits, ResponseFormat, Task, TaskFilter, TaskOptions, TaskStatus};
+use crate::core::search_engine::HybridSearchEngine;
+use crate::core::rbac::RBAC;
+use crate::core::llm::nafs_provider::{message_json, NafsLLMClient};
+use crate::core::agent_orchestrator::build_source_context;
+use crate::error::{BrainVaultError, ErrorBody};
+use crate::api::middleware::request_id::current_request_id;
+use futures::StreamExt;
+use std::collections::VecDeque;
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct TaskRequest {
     pub description: String,
     pub task_type: Option<AgentType>,
+    /// Continue an earlier conversation; tasks sharing a session see its history
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// `Text` (default), `Json` or `Markdown`
+    #[serde(default)]
+    pub response_format: ResponseFormat,
+    /// Chunks and characters per chunk a Researcher retrieves, ahead of the agent's settings
+    #[serde(default)]
+    pub research: ResearchLimits,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct TaskQuery {
+    #[serde(default)]
+    pub raw: bool,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct TaskResponse {
     pub task_id: String,
     pub status: String,
-    pub result: Option<String>,
+    #[schema(value_type = Option<Object>)]
+    pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_result: Option<String>,
     pub audit_log: Vec<crate::core::agent_orchestrator::AuditLogEntry>,
+    pub usage: crate::core::llm::usage::TokenUsage,
+    pub response_format: ResponseFormat,
+    pub submitted_at: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TaskSubmitted {
+    pub task_id: String,
+    pub status: String,
+}
+
+impl From<Task> for TaskResponse {
+    fn from(t: Task) -> Self {
+        TaskResponse {
+            task_id: t.id,
+            status: format!("{:?}", t.status),
+            raw_result: None,
+            result: t.result,
+            audit_log: t.audit_log,
+            usage: t.usage,
+            response_format: t.response_format,
+            submitted_at: t.submitted_at,
+        }
+    }
 }
 
+/// What an agent did with a task, step by step, with the key moments pulled out
+#[derive(Serialize, ToSchema)]
+pub struct TaskAudit {
+    pub task_id: String,
+    pub status: String,
+    pub assigned_agent_id: Option<String>,
+    /// Unix seconds of the first `SUBMITTED`, `ASSIGNED` and `COMPLETED`/`FAILED`/`CANCELLED` entries
+    pub submitted_at: Option<u64>,
+    pub assigned_at: Option<u64>,
+    pub finished_at: Option<u64>,
+    /// Oldest first
+    pub entries: Vec<crate::core::agent_orchestrator::AuditLogEntry>,
+}
+
+impl From<Task> for TaskAudit {
+    fn from(t: Task) -> Self {
+        let first = |actions: &[&str]| t.audit_log.iter()
+            .find(|e| actions.contains(&e.action.as_str()))
+            .map(|e| e.timestamp);
+        TaskAudit {
+            task_id: t.id.clone(),
+            status: format!("{:?}", t.status),
+            assigned_agent_id: t.assigned_agent_id.clone(),
+            submitted_at: first(&["SUBMITTED"]),
+            assigned_at: first(&["ASSIGNED"]),
+            finished_at: first(&["COMPLETED", "FAILED", "CANCELLED"]),
+            entries: t.audit_log.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct TaskAuditQuery {
+    /// Only entries with this action, e.g. `TOOL_CALL` (case-insensitive)
+    pub action: Option<String>,
+}
+
+/// `Unauthorized` unless `user_id` submitted the task or is an Admin. Anonymous callers
+/// own nothing, and tasks submitted without a user are for Admins only.
+async fn authorize_task(task: &Task, user_id: &str, rbac: &RBAC) -> Result<(), BrainVaultError> {
+    let owner = user_id != "anonymous" && task.submitted_by.as_deref() == Some(user_id);
+    if owner || rbac.is_admin(user_id).await {
+        return Ok(());
+    }
+    Err(BrainVaultError::Unauthorized(format!("Task {} belongs to another user", task.id)))
+}
+
+/// Tasks per page when the caller doesn't set `limit`
+const DEFAULT_TASK_PAGE: usize = 50;
+
+#[utoipa::path(
+    tag = "agents",
+    request_body = TaskRequest,
+    responses(
+        (status = 200, description = "Task queued and assigned", body = TaskSubmitted),
+        (status = 400, description = "Research limits out of range", body = ErrorBody),
+        (status = 403, description = "The session belongs to another user", body = ErrorBody),
+    ),
+)]
 #[post("/api/agents/task")]
 pub async fn submit_task(
     req: web::Json<TaskRequest>,
+    req_http: actix_web::HttpRequest,
     orchestrator: web::Data<AgentOrchestrator>,
-) -> impl Responder {
+) -> Result<HttpResponse, BrainVaultError> {
+    req.research.validate()?;
+
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+
+    if let Some(ref session_id) = req.session_id {
+        orchestrator.check_session_owner(user_id, session_id).await?;
+    }
+
     let type_enum = req.task_type.clone().unwrap_or(AgentType::Researcher); // Default to Researcher
-    let task_id = orchestrator.submit_task(req.description.clone(), Some(type_enum)).await;
+    let options = TaskOptions {
+        session_id: req.session_id.clone(),
+        response_format: req.response_format,
+        request_id: current_request_id(),
+        research: req.research,
+    };
+    let task_id = orchestrator.submit_task_with(Some(user_id.to_string()), req.description.clone(), Some(type_enum), options).await;
     
     // Auto-assign for now (Phase 2 requirement says "trigger tasks", not necessarily manual assign)
     // In a real flow, this might happen asynchronously.
     let _ = orchestrator.assign_task(&task_id).await;
     
-    HttpResponse::Ok().json(serde_json::json!({
-        "task_id": task_id,
-        "status": "Submitted"
-    }))
+    Ok(HttpResponse::Ok().json(TaskSubmitted { task_id, status: "Submitted".to_string() }))
 }
 
+#[utoipa::path(
+    tag = "agents",
+    params(("task_id" = String, Path, description = "Id returned on submission"), TaskQuery),
+    responses(
+        (status = 200, description = "Task status, result and audit trail", body = TaskResponse),
+        (status = 404, description = "Unknown task", body = ErrorBody),
+    ),
+)]
 #[get("/api/agents/task/{task_id}")]
 pub async fn get_task_status(
     path: web::Path<String>,
+    query: web::Query<TaskQuery>,
     orchestrator: web::Data<AgentOrchestrator>,
-) -> impl Responder {
+) -> Result<HttpResponse, BrainVaultError> {
     let task_id = path.into_inner();
     
     match orchestrator.get_task(&task_id).await {
-        Some(task) => HttpResponse::Ok().json(TaskResponse {
-            task_id: task.id,
-            status: format!("{:?}", task.status),
-            result: task.result,
-            audit_log: task.audit_log,
-        }),
-        None => HttpResponse::NotFound().body("Task not found"),
+        Some(task) => {
+            // Unfiltered output only on request (?raw=true)
+            let raw_result = if query.raw { task.raw_result.clone().or_else(|| task.result_text()) } else { None };
+            Ok(HttpResponse::Ok().json(TaskResponse { raw_result, ..TaskResponse::from(task) }))
+        }
+        None => Err(BrainVaultError::NotFound(format!("Task {}", task_id))),
+    }
+}
+
+/// The finished result alone, served with the content type of the task's response format
+#[utoipa::path(
+    tag = "agents",
+    params(("task_id" = String, Path, description = "Id returned on submission")),
+    responses(
+        (status = 200, description = "The result as text, JSON or Markdown", body = String,
+            content_type = ["text/plain", "application/json", "text/markdown"]),
+        (status = 404, description = "Unknown task, or no result yet", body = ErrorBody),
+    ),
+)]
+#[get("/api/agents/task/{task_id}/result")]
+pub async fn get_task_result(
+    path: web::Path<String>,
+    orchestrator: web::Data<AgentOrchestrator>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let task_id = path.into_inner();
+    let task = orchestrator.get_task(&task_id).await
+        .ok_or_else(|| BrainVaultError::NotFound(format!("Task {}", task_id)))?;
+
+    let result = task.result_text();
+    match (task.status, result) {
+        (TaskStatus::Completed, Some(result)) => Ok(HttpResponse::Ok()
+            .content_type(task.response_format.content_type())
+            .body(result)),
+        (status, _) => Err(BrainVaultError::NotFound(format!("Task {} has no result yet (status {:?})", task_id, status))),
+    }
+}
+
+/// The task's audit trail: submission, assignment, tool calls and completion or failure
+#[utoipa::path(
+    tag = "agents",
+    params(("task_id" = String, Path, description = "Id returned on submission"), TaskAuditQuery),
+    responses(
+        (status = 200, description = "Audit trail, oldest entry first", body = TaskAudit),
+        (status = 403, description = "Neither the submitter nor an Admin", body = ErrorBody),
+        (status = 404, description = "Unknown task", body = ErrorBody),
+    ),
+)]
+#[get("/api/agents/task/{task_id}/audit")]
+pub async fn get_task_audit(
+    path: web::Path<String>,
+    query: web::Query<TaskAuditQuery>,
+    req_http: actix_web::HttpRequest,
+    orchestrator: web::Data<AgentOrchestrator>,
+    rbac: web::Data<RBAC>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+    let task_id = path.into_inner();
+    let task = orchestrator.get_task(&task_id).await
+        .ok_or_else(|| BrainVaultError::NotFound(format!("Task {}", task_id)))?;
+    authorize_task(&task, user_id, &rbac).await?;
+
+    let mut audit = TaskAudit::from(task);
+    if let Some(ref action) = query.action {
+        audit.entries.retain(|e| e.action.eq_ignore_ascii_case(action));
     }
+    Ok(HttpResponse::Ok().json(audit))
+}
+
+/// Stops a task that hasn't finished, abandoning any LLM call it is making
+#[utoipa::path(
+    tag = "agents",
+    params(("task_id" = String, Path, description = "Id returned on submission")),
+    responses(
+        (status = 200, description = "The cancelled task", body = TaskResponse),
+        (status = 403, description = "Neither the submitter nor an Admin", body = ErrorBody),
+        (status = 404, description = "Unknown task", body = ErrorBody),
+        (status = 409, description = "The task already finished", body = ErrorBody),
+    ),
+)]
+#[post("/api/agents/task/{task_id}/cancel")]
+pub async fn cancel_task(
+    path: web::Path<String>,
+    req_http: actix_web::HttpRequest,
+    orchestrator: web::Data<AgentOrchestrator>,
+    rbac: web::Data<RBAC>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+    let task_id = path.into_inner();
+    let task = orchestrator.get_task(&task_id).await
+        .ok_or_else(|| BrainVaultError::NotFound(format!("Task {}", task_id)))?;
+    authorize_task(&task, user_id, &rbac).await?;
+    let task = orchestrator.cancel_task(&task_id, &format!("Cancelled by {}", user_id)).await?;
+    Ok(HttpResponse::Ok().json(TaskResponse::from(task)))
 }
 
 #[get("/api/agents/stats")]
@@ -64,27 +286,206 @@ pub async fn get_stats(
     }))
 }
 
+/// Task queue, newest first. Filter with `status`, `agent` and `submitted_by`; page with
+/// `offset` and `limit`. The overall match count is returned in `X-Total-Count`.
 #[get("/api/agents/tasks")]
 pub async fn get_all_tasks(
+    query: web::Query<TaskFilter>,
     orchestrator: web::Data<AgentOrchestrator>,
 ) -> impl Responder {
-    let tasks = orchestrator.get_all_tasks().await;
-    // Map to TaskResponse
-    let response: Vec<TaskResponse> = tasks.into_iter().map(|t| TaskResponse {
-        task_id: t.id,
-        status: format!("{:?}", t.status),
-        result: t.result,
-        audit_log: t.audit_log,
-    }).collect();
-    
-    HttpResponse::Ok().json(response)
+    let mut filter = query.into_inner();
+    filter.limit = Some(filter.limit.unwrap_or(DEFAULT_TASK_PAGE));
+    let page = orchestrator.list_tasks(&filter).await;
+    let response: Vec<TaskResponse> = page.tasks.into_iter().map(TaskResponse::from).collect();
+
+    HttpResponse::Ok()
+        .insert_header(("X-Total-Count", page.total.to_string()))
+        .json(response)
+}
+
+#[get("/api/usage")]
+pub async fn get_usage(
+    orchestrator: web::Data<AgentOrchestrator>,
+) -> impl Responder {
+    HttpResponse::Ok().json(orchestrator.get_usage_report().await)
 }
 
+#[utoipa::path(
+    tag = "agents",
+    request_body = AgentProfile,
+    responses((status = 200, description = "Agent registered")),
+)]
 #[post("/api/agents/register")]
 pub async fn register_agent(
     req: web::Json<AgentProfile>,
     orchestrator: web::Data<AgentOrchestrator>,
-) -> impl Responder {
+) -> Result<HttpResponse, BrainVaultError> {
+    req.validate()?;
     orchestrator.register_agent(req.into_inner()).await;
-    HttpResponse::Ok().body("Agent registered")
+    Ok(HttpResponse::Ok().body("Agent registered"))
+}
+
+#[utoipa::path(
+    tag = "agents",
+    responses((status = 200, description = "Registered agents", body = Vec<AgentProfile>)),
+)]
+#[get("/api/agents")]
+pub async fn list_agents(
+    orchestrator: web::Data<AgentOrchestrator>,
+) -> impl Responder {
+    HttpResponse::Ok().json(orchestrator.list_agents().await)
+}
+
+#[delete("/api/agents/{agent_id}")]
+pub async fn deregister_agent(
+    path: web::Path<String>,
+    orchestrator: web::Data<AgentOrchestrator>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let profile = orchestrator.deregister_agent(&path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(profile))
+}
+
+/// Messages kept per chat session; the oldest turns are dropped first
+const MAX_CHAT_HISTORY: usize = 20;
+/// Documents searched for each Researcher or Analyst chat turn
+const CHAT_SOURCES: usize = 5;
+
+/// A client message on `/api/agents/chat`; plain-text frames are treated as `message`
+#[derive(Deserialize)]
+pub struct ChatFrame {
+    pub message: String,
+    /// Agent persona answering this turn; defaults to the session's last choice, then Researcher
+    #[serde(default)]
+    pub agent_type: Option<AgentType>,
+}
+
+/// Interactive agent chat over WebSocket. Each session keeps its own conversation history,
+/// which is sent with every turn so follow-up questions have context. Server frames are JSON
+/// objects with a `type` of `sources`, `delta` (a piece of the reply as the model streams
+/// it), `message` (the whole reply, once it is done) or `error`.
+#[get("/api/agents/chat")]
+pub async fn agent_chat(
+    req_http: actix_web::HttpRequest,
+    body: web::Payload,
+    engine: web::Data<HybridSearchEngine>,
+    rbac: web::Data<RBAC>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous")
+        .to_string();
+    let (response, mut session, mut messages) = actix_ws::handle(&req_http, body)?;
+
+    actix_web::rt::spawn(async move {
+        let client = NafsLLMClient::new();
+        let mut history: Vec<serde_json::Value> = Vec::new();
+        let mut agent_type = AgentType::Researcher;
+
+        // Messages that arrived while a reply was being generated
+        let mut pending: VecDeque<String> = VecDeque::new();
+
+        loop {
+            let text = match pending.pop_front() {
+                Some(text) => text,
+                None => match messages.next().await {
+                    Some(Ok(actix_ws::Message::Text(text))) => text.to_string(),
+                    Some(Ok(actix_ws::Message::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            return;
+                        }
+                        continue;
+                    }
+                    Some(Ok(actix_ws::Message::Close(reason))) => {
+                        let _ = session.close(reason).await;
+                        return;
+                    }
+                    Some(Ok(_)) => continue,
+                    Some(Err(_)) | None => break,
+                },
+            };
+            let frame = serde_json::from_str::<ChatFrame>(&text)
+                .unwrap_or(ChatFrame { message: text, agent_type: None });
+            if let Some(t) = frame.agent_type {
+                agent_type = t;
+            }
+
+            let client = match client {
+                Some(ref c) => c,
+                None => {
+                    let _ = session.text(serde_json::json!({"type": "error", "message": "No LLM provider configured"}).to_string()).await;
+                    continue;
+                }
+            };
+
+            // Ground research-style turns in documents this user may read
+            let mut turn = vec![message_json("system", agent_type.default_system_prompt())];
+            if matches!(agent_type, AgentType::Researcher | AgentType::Analyst) {
+                if let Ok(results) = engine.search(&frame.message, CHAT_SOURCES).await {
+                    let collections = engine.vector_db.document_collections().await;
+                    let permitted = rbac.get_permitted_search_results_in(&user_id, results, &collections).await;
+                    let context = build_source_context(&permitted.hits, 0.0);
+                    if !context.is_empty() {
+                        let doc_ids: Vec<&str> = permitted.hits.iter().map(|h| h.doc_id.as_str()).collect();
+                        if session.text(serde_json::json!({"type": "sources", "doc_ids": doc_ids}).to_string()).await.is_err() {
+                            return;
+                        }
+                        turn.push(message_json("system", &format!("Sources:\n{}", context)));
+                    }
+                }
+            }
+            turn.extend(history.iter().cloned());
+            turn.push(message_json("user", &frame.message));
+
+            // Forward the reply as it streams in, and keep reading the socket meanwhile: if
+            // the client leaves, the stream is dropped and the provider request with it
+            let mut stream = client.chat_stream(turn, 2000);
+            let mut content = String::new();
+            let outcome = loop {
+                tokio::select! {
+                    chunk = stream.next() => match chunk {
+                        Some(Ok(text)) => {
+                            content.push_str(&text);
+                            let delta = serde_json::json!({"type": "delta", "content": text});
+                            if session.text(delta.to_string()).await.is_err() {
+                                break None;
+                            }
+                        }
+                        Some(Err(e)) => break Some(Err(e)),
+                        None => break Some(Ok(())),
+                    },
+                    msg = messages.next() => match msg {
+                        Some(Ok(actix_ws::Message::Text(text))) => pending.push_back(text.to_string()),
+                        Some(Ok(actix_ws::Message::Ping(bytes))) => {
+                            if session.pong(&bytes).await.is_err() {
+                                break None;
+                            }
+                        }
+                        Some(Ok(actix_ws::Message::Close(_))) | Some(Err(_)) | None => break None,
+                        Some(Ok(_)) => {}
+                    },
+                }
+            };
+            let reply = match outcome {
+                Some(Ok(())) => serde_json::json!({"type": "message", "agent_type": agent_type, "content": content}),
+                Some(Err(e)) => serde_json::json!({"type": "error", "message": e}),
+                None => {
+                    println!("INFO: Agent chat for {} closed mid-reply; generation cancelled", user_id);
+                    let _ = session.close(None).await;
+                    return;
+                }
+            };
+            if reply["type"] == "message" {
+                history.push(message_json("user", &frame.message));
+                history.push(message_json("assistant", &content));
+                let overflow = history.len().saturating_sub(MAX_CHAT_HISTORY);
+                history.drain(..overflow);
+            }
+            if session.text(reply.to_string()).await.is_err() {
+                return;
+            }
+        }
+        let _ = session.close(None).await;
+    });
+
+    Ok(response)
 }
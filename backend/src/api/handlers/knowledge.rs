@@ -1,23 +1,241 @@
-use actix_web::{get, post, web, HttpResponse, Responder};
+use actix_web::{delete, get, patch, post, put, web, HttpResponse, Responder};
 use serde::{Deserialize, Serialize};
-use crate::core::search_engine::HybridSearchEngine;
+use crate::core::search_engine::{compare_hits, validate_collection, HybridSearchEngine, SearchHit, SearchPage, SearchResults, MAX_RESULT_WINDOW};
+use crate::core::redaction::Redactor;
+use crate::core::search_history::SearchHistory;
 use crate::core::graph_manager::KnowledgeGraphManager;
-use crate::core::graph_manager::{Entity, Relationship};
-use crate::core::rbac::RBAC;
-use crate::core::audit_manager::AuditManager;
+use crate::core::graph_manager::{ContextGraph, Entity, Relationship};
+use crate::core::rbac::{entity_collection, Permission, Role, RBAC};
+use crate::core::audit_manager::{AuditManager, EventKind, Severity};
+use crate::core::llm::tokenizer::default_tokenizer;
+use crate::error::{BrainVaultError, ErrorBody};
+use crate::config::AppConfig;
+use crate::core::graph_export::{to_cypher, to_graphml, ExportFormat};
+use crate::core::citations::{build_cited_context, extract_citations, Citation};
+use crate::db::barq_vector::{content_hash, version_key, DocumentVersion, IndexOutcome, LexicalOptions, DEFAULT_COLLECTION};
+use crate::core::text_analysis::normalize_language;
+use crate::core::ingest_queue::IngestQueue;
+use crate::api::middleware::request_id::current_request_id;
+use futures::StreamExt;
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct IngestRequest {
     pub doc_id: String,
     pub content: String,
+    #[serde(default)]
     pub entities: Vec<Entity>,
+    #[serde(default)]
     pub relationships: Vec<Relationship>,
+    /// Have the Ingestor agent extract entities and relationships from `content` with the
+    /// LLM. On by default; set `false` to index the document and supplied graph data only.
+    #[serde(default = "default_true")]
+    pub auto_extract: bool,
+    /// Language of `content` (`en`, `fr`, `de`, ...), used for lexical stopwords and stemming
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Index on the background queue and return `202 Accepted` with a job id to poll
+    #[serde(default)]
+    pub background: bool,
+    /// Collection to file the document under for scoped search; the default collection when unset
+    #[serde(default)]
+    pub collection: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+fn default_true() -> bool {
+    true
+}
+
+/// One problem found while validating a request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ValidationProblem {
+    pub field: String,
+    pub message: String,
+}
+
+impl ValidationProblem {
+    fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { field: field.into(), message: message.into() }
+    }
+}
+
+/// Outcome of one ingest stage
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct IngestStage {
+    pub succeeded: usize,
+    pub failed: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+impl IngestStage {
+    pub fn fail(&mut self, error: impl Into<String>) {
+        self.failed += 1;
+        self.errors.push(error.into());
+    }
+}
+
+/// Per-stage report of an ingest, so a failing store doesn't hide what was written elsewhere
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct IngestStages {
+    pub vector: IngestStage,
+    pub entities: IngestStage,
+    pub relationships: IngestStage,
+}
+
+impl IngestStages {
+    /// One line per failure, prefixed with its stage
+    pub fn warnings(&self) -> Vec<String> {
+        [("vector", &self.vector), ("entities", &self.entities), ("relationships", &self.relationships)]
+            .iter()
+            .flat_map(|(name, stage)| stage.errors.iter().map(move |e| format!("{}: {}", name, e)))
+            .collect()
+    }
+
+    pub fn succeeded(&self) -> usize {
+        self.vector.succeeded + self.entities.succeeded + self.relationships.succeeded
+    }
+}
+
+/// Body of `/api/knowledge/ingest`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct IngestResponse {
+    /// `indexed`, `unchanged`, `queued`, `processing` (extraction submitted) or `partial`
+    pub status: String,
+    pub doc_id: String,
+    /// Background job to poll at `/api/knowledge/jobs/{job_id}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    /// Entity extraction task, when `auto_extract` was set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+    /// Entities and relationships supplied with the request
+    pub entities: usize,
+    pub relationships: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    pub stages: IngestStages,
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
+
+/// 200, or 207 Multi-Status when part of the request failed and the body carries warnings
+fn partial_status(warnings: &[String]) -> actix_web::HttpResponseBuilder {
+    if warnings.is_empty() {
+        HttpResponse::Ok()
+    } else {
+        HttpResponse::MultiStatus()
+    }
+}
+
+/// 413 message for a document over the size limit, suggesting how to split it
+fn document_too_large(doc_id: &str, bytes: usize, limit: usize) -> String {
+    format!(
+        "'{}' is {} bytes, over the {}-byte document limit; split it into chunks ingested as separate documents (e.g. {}-part-1, {}-part-2)",
+        doc_id, bytes, limit, doc_id, doc_id
+    )
+}
+
+impl IngestRequest {
+    /// Rejects `content` over `max_document_bytes` with 413. Checked before anything else so
+    /// an oversized document is never validated, embedded or queued.
+    pub fn check_size(&self, max_document_bytes: usize) -> Result<(), BrainVaultError> {
+        if self.content.len() > max_document_bytes {
+            return Err(BrainVaultError::PayloadTooLarge(document_too_large(&self.doc_id, self.content.len(), max_document_bytes)));
+        }
+        Ok(())
+    }
+
+    /// Every problem with the request; empty when it is valid
+    pub fn validate(&self) -> Vec<ValidationProblem> {
+        let mut problems = Vec::new();
+        if self.doc_id.trim().is_empty() {
+            problems.push(ValidationProblem::new("doc_id", "must not be empty"));
+        }
+        if self.content.trim().is_empty() {
+            problems.push(ValidationProblem::new("content", "must not be empty"));
+        }
+        if let Some(ref language) = self.language {
+            if normalize_language(language).is_none() {
+                problems.push(ValidationProblem::new("language", format!("'{}' is not a supported language", language)));
+            }
+        }
+        if let Some(ref collection) = self.collection {
+            if let Err(e) = validate_collection(collection) {
+                problems.push(ValidationProblem::new("collection", e.to_string()));
+            }
+        }
+        for (i, entity) in self.entities.iter().enumerate() {
+            if entity.id.trim().is_empty() {
+                problems.push(ValidationProblem::new(format!("entities[{}].id", i), "must not be empty"));
+            }
+        }
+
+        let declared: std::collections::HashSet<&str> = self.entities.iter().map(|e| e.id.as_str()).collect();
+        for (i, rel) in self.relationships.iter().enumerate() {
+            for (field, endpoint) in [("from_id", &rel.from_id), ("to_id", &rel.to_id)] {
+                if !declared.contains(endpoint.as_str()) {
+                    problems.push(ValidationProblem::new(
+                        format!("relationships[{}].{}", i, field),
+                        format!("'{}' is not a declared entity", endpoint),
+                    ));
+                }
+            }
+        }
+        for (i, rel) in self.relationships.iter().enumerate() {
+            if !rel.weight.is_finite() || rel.weight < 0.0 {
+                problems.push(ValidationProblem::new(format!("relationships[{}].weight", i), "must be a non-negative number"));
+            }
+        }
+        problems
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct SearchQuery {
     pub q: String,
     pub top_k: usize,
+    /// Number of hits to skip; takes precedence over `page`
+    #[serde(default)]
+    pub offset: Option<usize>,
+    /// 1-based page number of `top_k` hits each
+    #[serde(default)]
+    pub page: Option<usize>,
+    /// Also search LLM-suggested related terms; adds an LLM round trip
+    #[serde(default)]
+    pub expand: bool,
+    /// Reorder the leading hits by LLM-judged relevance; adds an LLM round trip
+    #[serde(default)]
+    pub rerank: bool,
+    /// Per-request overrides of the engine's fusion weights
+    #[serde(default)]
+    pub vector_weight: Option<f32>,
+    #[serde(default)]
+    pub bm25_weight: Option<f32>,
+    /// Also match superseded and deleted revisions, returned with their `version` set
+    #[serde(default)]
+    pub include_history: bool,
+    /// Language of the query; by default each document's own language is assumed
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Tolerate typos in lexical matching; slower on large corpora
+    #[serde(default)]
+    pub fuzzy: bool,
+    /// Characters of content returned per hit, ending in `…` when cut; 0 returns it whole.
+    /// Defaults to `SEARCH_MAX_CONTENT_LEN`.
+    #[serde(default)]
+    pub max_content_len: Option<usize>,
+    /// Only search documents filed under this collection; requires access to it
+    #[serde(default)]
+    pub collection: Option<String>,
+}
+
+impl SearchQuery {
+    pub fn effective_offset(&self) -> usize {
+        self.offset
+            .or_else(|| self.page.map(|p| p.saturating_sub(1) * self.top_k))
+            .unwrap_or(0)
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -31,43 +249,198 @@ pub struct ChatResponse {
     pub sources: Vec<String>,
 }
 
-// Generalized LLM Helper
-async fn call_llm(prompt: &str) -> String {
-    let api_key = std::env::var("AZURE_OPENAI_API_KEY").unwrap_or_default();
-    let endpoint = std::env::var("AZURE_OPENAI_ENDPOINT").unwrap_or_default();
-    let deployment = std::env::var("AZURE_OPENAI_DEPLOYMENT").unwrap_or_else(|_| "gpt-4o".to_string());
-    
-    if api_key.is_empty() || endpoint.is_empty() {
-        return String::from("Azure OpenAI not configured.");
-    }
+fn default_path_depth() -> usize {
+    6
+}
 
-    let url = format!("{}/openai/deployments/{}/chat/completions?api-version=2024-02-15-preview", endpoint, deployment);
-    let client = reqwest::Client::new();
+/// Longest path `/api/graph/path` will search for
+const MAX_PATH_DEPTH: usize = 10;
 
-    let body = serde_json::json!({
-        "messages": [
-            {"role": "system", "content": "You are a Knowledge Graph extraction engine."},
-            {"role": "user", "content": prompt}
-        ],
-        "temperature": 0.1,
-        "max_tokens": 1000
-    });
+#[derive(Serialize, Deserialize)]
+pub struct PathQuery {
+    pub from: String,
+    pub to: String,
+    #[serde(default = "default_path_depth")]
+    pub max_depth: usize,
+}
+
+fn default_export_format() -> String {
+    "graphml".to_string()
+}
 
-    match client.post(&url)
-        .header("api-key", &api_key)
-        .header("Content-Type", "application/json")
-        .json(&body)
-        .send()
-        .await 
-    {
-        Ok(res) => {
-            if let Ok(json) = res.json::<serde_json::Value>().await {
-                json["choices"][0]["message"]["content"].as_str().unwrap_or("").to_string()
-            } else {
-                String::new()
+#[derive(Serialize, Deserialize)]
+pub struct ExportQuery {
+    /// `graphml` (default), `cypher` or `json`
+    #[serde(default = "default_export_format")]
+    pub format: String,
+}
+
+fn default_duplicate_threshold() -> f32 {
+    0.8
+}
+
+fn default_duplicate_limit() -> usize {
+    50
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DuplicateQuery {
+    #[serde(default = "default_duplicate_threshold")]
+    pub threshold: f32,
+    #[serde(default = "default_duplicate_limit")]
+    pub limit: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MergeRequest {
+    pub keep_id: String,
+    pub merge_id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UpdateEntityRequest {
+    pub properties: std::collections::HashMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, IntoParams)]
+pub struct ContextQuery {
+    /// Leave out relationships weighing less than this
+    #[serde(default)]
+    pub min_weight: Option<f32>,
+    /// Entities per page, capped at `MAX_CONTEXT_NODES`
+    #[serde(default)]
+    pub max_nodes: Option<usize>,
+    /// `next_cursor` from the previous page
+    #[serde(default)]
+    pub cursor: Option<String>,
+}
+
+/// Most entities one `get_context` page returns
+const MAX_CONTEXT_NODES: usize = 500;
+
+fn default_ask_top_k() -> usize {
+    5
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AskRequest {
+    pub question: String,
+    /// Documents retrieved into the answer context
+    #[serde(default = "default_ask_top_k")]
+    pub top_k: usize,
+    /// Also place the knowledge graph neighborhood of entities named in the retrieved
+    /// documents in the prompt
+    #[serde(default)]
+    pub graph_context: bool,
+    /// Time budget for the whole request in milliseconds, overriding `ASK_TIMEOUT_MS`
+    /// (at most `MAX_ASK_TIMEOUT_MS`)
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AskResponse {
+    pub answer: String,
+    /// Documents that were placed in the prompt, in rank order; `[n]` refers to `sources[n - 1]`
+    pub sources: Vec<String>,
+    /// Source markers the answer actually cites
+    pub citations: Vec<Citation>,
+    /// False when the answer cites no source and may not be supported by the corpus
+    pub grounded: bool,
+    /// Graph entities whose neighborhood was placed in the prompt (`graph_context` only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub graph_entities: Vec<String>,
+    /// The time budget ran out before the model answered; `answer` only points at `sources`
+    #[serde(default)]
+    pub timed_out: bool,
+    /// Steps skipped to stay within the time budget
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Tokens of each retrieved document placed in the answer prompt
+const ASK_CHUNK_TOKENS: usize = 400;
+/// Longest time budget a request may ask for
+pub const MAX_ASK_TIMEOUT_MS: u64 = 600_000;
+
+/// When an ask request must be done: `requested` milliseconds from now, else
+/// `default_ms` (0 for no deadline)
+pub fn ask_deadline(requested: Option<u64>, default_ms: u64) -> Result<Option<tokio::time::Instant>, BrainVaultError> {
+    let budget_ms = match requested {
+        Some(ms) if ms == 0 || ms > MAX_ASK_TIMEOUT_MS => {
+            return Err(BrainVaultError::BadRequest(format!("timeout_ms must be between 1 and {}", MAX_ASK_TIMEOUT_MS)));
+        }
+        Some(ms) => ms,
+        None => default_ms,
+    };
+    Ok((budget_ms > 0).then(|| tokio::time::Instant::now() + std::time::Duration::from_millis(budget_ms)))
+}
+
+/// Runs `stage` until `deadline`, dropping it (and any request it has in flight) if it
+/// hasn't finished by then; `None` when it was cut off
+pub async fn before_deadline<F: std::future::Future>(deadline: Option<tokio::time::Instant>, stage: F) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, stage).await.ok(),
+        None => Some(stage.await),
+    }
+}
+/// Graph entities summarized into the answer prompt when `graph_context` is set
+const ASK_GRAPH_ENTITIES: usize = 5;
+
+/// Summarize the one-hop graph neighborhood of entities named in the retrieved documents,
+/// limited to entities and relationships `user_id` may see. Returns the summary and the
+/// entities it covers.
+async fn ask_graph_context(
+    graph: &KnowledgeGraphManager,
+    rbac: &RBAC,
+    user_id: &str,
+    hits: &[SearchHit],
+) -> (String, Vec<String>) {
+    let mut summary = String::new();
+    let mut covered: Vec<String> = Vec::new();
+
+    for hit in hits {
+        let content = match hit.content.as_deref() {
+            Some(content) => content,
+            None => continue,
+        };
+        for entity in graph.find_entities_mentioned_in(content).await {
+            if covered.len() >= ASK_GRAPH_ENTITIES {
+                return (summary, covered);
             }
-        },
-        Err(_) => String::new()
+            if covered.contains(&entity.id) || !rbac.check_access_in(user_id, &entity.id, entity_collection(&entity)).await.unwrap_or(false) {
+                continue;
+            }
+            let ctx = match graph.find_related_context(&entity.id, 1).await {
+                Ok(ctx) => ctx,
+                Err(_) => continue,
+            };
+            let ctx = match rbac.filter_context(user_id, ctx).await {
+                Ok(ctx) => ctx,
+                Err(_) => continue,
+            };
+            summary.push_str(&ctx.summarize(&entity.id));
+            covered.push(entity.id);
+        }
+    }
+    (summary, covered)
+}
+
+/// Graph extraction and chat completions through the configured provider, so they share its
+/// fallback, retries and response cache. Empty when the call fails.
+async fn call_llm(prompt: &str) -> String {
+    use crate::core::llm::nafs_provider::{GenerationParams, NafsLLMClient};
+
+    let client = match NafsLLMClient::new() {
+        Some(client) => client.with_params(GenerationParams { max_tokens: 1000, temperature: 0.1 }),
+        None => return String::from("No LLM provider configured."),
+    };
+    match client.generate_with_system("You are a Knowledge Graph extraction engine.", prompt).await {
+        Ok(generation) => generation.content,
+        Err(e) => {
+            println!("WARN: LLM call failed: {}", e);
+            String::new()
+        }
     }
 }
 
@@ -82,7 +455,7 @@ async fn extract_graph_data(content: &str) -> (Vec<Entity>, Vec<Relationship>) {
         - id_slug must be lowercase-kebab-case (e.g. quantum-computing)\n\
         - Label like 'Technology', 'Person', 'Company'\n\
         - Do not add explanations.\n\nText:\n{}", 
-        content.chars().take(2000).collect::<String>() // Limit context
+        default_tokenizer().truncate(content, 500) // Limit context
     );
 
     let response = call_llm(&prompt).await;
@@ -104,7 +477,9 @@ async fn extract_graph_data(content: &str) -> (Vec<Entity>, Vec<Relationship>) {
                 from_id: parts[1].trim().to_string(),
                 to_id: parts[2].trim().to_string(),
                 rel_type: parts[3].trim().to_string(),
-                properties: std::collections::HashMap::new()
+                weight: 1.0,
+                properties: std::collections::HashMap::new(),
+                direction: None,
             });
         }
     }
@@ -119,13 +494,14 @@ pub async fn chat_with_knowledge(
     audit: web::Data<AuditManager>,
 ) -> impl Responder {
     // Audit Log
-    audit.log_event("Chat Query", "user", "Processing", "Low").await;
+    audit.record(EventKind::Query, Severity::Low, "Chat Query", "user", "Processing", std::collections::HashMap::new()).await;
 
     // 1. Search for document context (Vector Search)
-    let search_results = match engine.search(&req.query, 5).await {
+    let mut search_results = match engine.search(&req.query, 5).await {
         Ok(res) => res,
         Err(_) => return HttpResponse::InternalServerError().body("Search failed"),
     };
+    search_results.redact(&engine.redactor);
     
     // 2. Search for Graph Context (GraphRAG)
     let mut graph_context_str = String::new();
@@ -185,10 +561,175 @@ pub async fn chat_with_knowledge(
     })
 }
 
+/// Notes a generation whose handler was dropped before the model answered, which happens
+/// when the client disconnects: the provider request is dropped with it
+struct GenerationWatch {
+    request: &'static str,
+    done: bool,
+}
+
+impl Drop for GenerationWatch {
+    fn drop(&mut self) {
+        if !self.done {
+            println!("INFO: {} request abandoned before the model answered; generation cancelled", self.request);
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "knowledge",
+    request_body = AskRequest,
+    responses(
+        (status = 200, description = "Answer grounded in the documents the caller may read; `timed_out` when only the sources could be found in time", body = AskResponse),
+        (status = 400, description = "`timeout_ms` is out of range", body = ErrorBody),
+        (status = 502, description = "No LLM provider configured or the provider failed", body = ErrorBody),
+        (status = 503, description = "Warming up and `SEARCH_REQUIRE_READY` is set; retry shortly", body = ErrorBody),
+        (status = 504, description = "No sources were retrieved within the time budget", body = ErrorBody),
+    ),
+)]
+#[post("/api/ask")]
+pub async fn ask_question(
+    req: web::Json<AskRequest>,
+    req_http: actix_web::HttpRequest,
+    engine: web::Data<HybridSearchEngine>,
+    graph: web::Data<KnowledgeGraphManager>,
+    rbac: web::Data<RBAC>,
+    audit: web::Data<AuditManager>,
+    config: web::Data<AppConfig>,
+    orchestrator: web::Data<crate::core::agent_orchestrator::AgentOrchestrator>,
+) -> Result<HttpResponse, BrainVaultError> {
+    use crate::core::llm::nafs_provider::NafsLLMClient;
+
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+    engine.ensure_ready()?;
+    // One budget covers retrieval, graph context and generation together
+    let deadline = ask_deadline(req.timeout_ms, config.ask_timeout_ms)?;
+    let started = std::time::Instant::now();
+    let mut warnings = vec![];
+    audit.record(EventKind::Query, Severity::Low, "Ask Question", user_id, "Processing", std::collections::HashMap::from([
+        ("question".to_string(), req.question.clone()),
+    ])).await;
+
+    // 1. Retrieve, then drop anything the caller may not read before it reaches the prompt
+    let retrieval = before_deadline(deadline, async {
+        let results = engine.search(&req.question, req.top_k).await?;
+        let retrieved = results.hits.len();
+        let collections = engine.vector_db.document_collections().await;
+        Ok::<_, BrainVaultError>((retrieved, rbac.get_permitted_search_results_in(user_id, results, &collections).await))
+    }).await;
+    let (retrieved, mut permitted) = match retrieval {
+        Some(retrieval) => retrieval?,
+        None => {
+            println!("WARN: Ask for '{}' timed out during retrieval after {}ms", req.question, started.elapsed().as_millis());
+            return Err(BrainVaultError::Timeout("No sources were retrieved within the time budget".to_string()));
+        }
+    };
+    if permitted.hits.len() < retrieved {
+        audit.record(EventKind::Query, Severity::Low, "Ask Sources Withheld", user_id, "Filtered", std::collections::HashMap::from([
+            ("question".to_string(), req.question.clone()),
+            ("withheld".to_string(), (retrieved - permitted.hits.len()).to_string()),
+        ])).await;
+    }
+    // Masked before the prompt is built, so neither the model nor its answer sees the values
+    permitted.redact(&engine.redactor);
+    let hits: Vec<_> = permitted.hits.into_iter()
+        .filter(|h| h.content.as_deref().map_or(false, |c| !c.trim().is_empty()))
+        .take(req.top_k)
+        .collect();
+
+    if hits.is_empty() {
+        return Ok(HttpResponse::Ok().json(AskResponse {
+            answer: "I couldn't find any documents you have access to that answer this question.".to_string(),
+            sources: vec![],
+            citations: vec![],
+            grounded: false,
+            graph_entities: vec![],
+            timed_out: false,
+            warnings: vec![],
+        }));
+    }
+
+    // 2. Build the numbered context block, plus the graph around what the documents mention.
+    // Graph context is optional, so running out of time here only drops it.
+    let context = build_cited_context(&hits, default_tokenizer().as_ref(), ASK_CHUNK_TOKENS);
+    let (graph_summary, graph_entities) = if req.graph_context {
+        before_deadline(deadline, ask_graph_context(&graph, &rbac, user_id, &hits)).await
+            .unwrap_or_else(|| {
+                warnings.push("graph_context: skipped, the time budget ran out".to_string());
+                (String::new(), vec![])
+            })
+    } else {
+        (String::new(), vec![])
+    };
+    let sources: Vec<String> = hits.into_iter().map(|h| h.doc_id).collect();
+
+    // 3. Generate
+    let client = NafsLLMClient::new()
+        .ok_or_else(|| BrainVaultError::Upstream("No LLM provider configured".to_string()))?;
+    let graph_section = if graph_summary.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "\n\nRelated entities from the knowledge graph (background only; cite the numbered sources):\n{}",
+            graph_summary
+        )
+    };
+    let prompt = format!(
+        "Answer the question using only the numbered sources below. \
+        Cite the source of every claim with its number in square brackets, e.g. [1] or [2, 3]. \
+        If the sources do not contain the answer, say so.\n\nSources:\n{}{}\n\nQuestion: {}",
+        context, graph_section, req.question
+    );
+    let mut watch = GenerationWatch { request: "ask", done: false };
+    let answer = before_deadline(deadline, client.generate_with_usage(&prompt)).await;
+    watch.done = true;
+    let answer = match answer {
+        Some(generation) => {
+            let generation = generation.map_err(BrainVaultError::Upstream)?;
+            orchestrator.record_user_usage(user_id, &generation.usage).await;
+            generation.content
+        }
+        None => {
+            // The sources were found in time, which is still worth returning
+            println!("WARN: Ask for '{}' timed out waiting for the model after {}ms", req.question, started.elapsed().as_millis());
+            warnings.push("answer: the model did not answer within the time budget".to_string());
+            return Ok(HttpResponse::Ok().json(AskResponse {
+                answer: "The answer could not be generated in time. These are the most relevant sources found.".to_string(),
+                sources,
+                citations: vec![],
+                grounded: false,
+                graph_entities,
+                timed_out: true,
+                warnings,
+            }));
+        }
+    };
+    let citations = extract_citations(&answer, &sources);
+    if citations.is_empty() {
+        println!("WARN: Answer to '{}' cites no sources", req.question);
+    }
+    Ok(HttpResponse::Ok().json(AskResponse {
+        grounded: !citations.is_empty(),
+        answer,
+        sources,
+        citations,
+        graph_entities,
+        timed_out: false,
+        warnings,
+    }))
+}
+
+#[utoipa::path(
+    tag = "system",
+    responses((status = 200, description = "Status of the vector and graph stores")),
+)]
 #[get("/api/health")]
 pub async fn health_check(
     engine: web::Data<HybridSearchEngine>,
     graph: web::Data<KnowledgeGraphManager>,
+    config: web::Data<AppConfig>,
 ) -> impl Responder {
     // Check vector DB
     let vector_status = engine.check_health().await;
@@ -198,33 +739,258 @@ pub async fn health_check(
     
     HttpResponse::Ok().json(serde_json::json!({
         "api": "running",
+        "ready": engine.is_ready(),
         "vector_db": if vector_status { "connected" } else { "disconnected" },
         "graph_db": if graph_status { "connected" } else { "local_fallback" },
-        "vector_db_url": std::env::var("VECTOR_DB_URL").unwrap_or_else(|_| "http://barq-vector:8080".to_string()),
-        "graph_db_url": std::env::var("GRAPH_DB_URL").unwrap_or_else(|_| "http://barq-graph:8080".to_string())
+        "vector_db_circuit": engine.vector_circuit(),
+        "vector_warmup": engine.vector_db.warmup_status(),
+        "vector_db_url": config.vector_db_url,
+        "graph_db_url": config.graph_db_url
     }))
 }
 
 
+/// Readiness probe: 503 until the startup warm-up has loaded the index. Re-embedding that
+/// follows shows in `vector_warmup` without holding readiness back.
+#[utoipa::path(
+    tag = "system",
+    responses(
+        (status = 200, description = "Ready to serve searches"),
+        (status = 503, description = "Still warming up; `vector_warmup` shows progress"),
+    ),
+)]
+#[get("/api/health/ready")]
+pub async fn readiness_check(engine: web::Data<HybridSearchEngine>) -> impl Responder {
+    let body = serde_json::json!({
+        "ready": engine.is_ready(),
+        "vector_warmup": engine.vector_db.warmup_status(),
+    });
+    if engine.is_ready() {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+#[utoipa::path(
+    tag = "knowledge",
+    request_body = IngestRequest,
+    responses(
+        (status = 200, description = "Document indexed, unchanged, or submitted for extraction", body = IngestResponse),
+        (status = 202, description = "Queued for background indexing", body = IngestResponse),
+        (status = 207, description = "Some stages failed; see `stages` and `warnings`", body = IngestResponse),
+        (status = 400, description = "The request failed validation"),
+        (status = 403, description = "The caller may not write to the target collection", body = ErrorBody),
+        (status = 409, description = "The collection holds vectors from another embedding model; reindex first", body = ErrorBody),
+        (status = 413, description = "`content` exceeds `MAX_DOCUMENT_BYTES`; split it into chunks", body = ErrorBody),
+        (status = 503, description = "The background queue is full", body = ErrorBody),
+    ),
+)]
 #[post("/api/knowledge/ingest")]
 pub async fn ingest_knowledge(
     req: web::Json<IngestRequest>,
+    req_http: actix_web::HttpRequest,
+    engine: web::Data<HybridSearchEngine>,
+    graph: web::Data<KnowledgeGraphManager>,
     orchestrator: web::Data<crate::core::agent_orchestrator::AgentOrchestrator>,
-) -> impl Responder {
-    // Delegate to Ingestor Agent
+    rbac: web::Data<RBAC>,
+    audit: web::Data<AuditManager>,
+    ingest_queue: web::Data<IngestQueue>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+
+    if let Err(e) = req.check_size(config.max_document_bytes) {
+        audit.record(EventKind::Ingest, Severity::Medium, "Document Ingest", user_id, "Rejected", std::collections::HashMap::from([
+            ("doc_id".to_string(), req.doc_id.clone()),
+            ("bytes".to_string(), req.content.len().to_string()),
+        ])).await;
+        return Err(e);
+    }
+    let problems = req.validate();
+    if !problems.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "validation_failed",
+            "problems": problems,
+        })));
+    }
+
+    let collection = req.collection.as_deref().unwrap_or(DEFAULT_COLLECTION);
+    let writable = match rbac.get_permission(user_id).await {
+        Ok(perm) => may_write(&engine, &perm, &req.doc_id, collection).await,
+        Err(_) => false,
+    };
+    if !writable {
+        audit.record_denial(Severity::High, "Document Ingest Denied", user_id, &req.doc_id, std::collections::HashMap::from([
+            ("doc_id".to_string(), req.doc_id.clone()),
+            ("collection".to_string(), collection.to_string()),
+        ])).await;
+        return Err(BrainVaultError::Unauthorized(format!("No write access to collection '{}'", collection)));
+    }
+
+    // Background mode: enqueue before touching the graph so a full queue rejects the whole request
+    let job_id = if req.background {
+        Some(ingest_queue.enqueue(&req.doc_id, &req.content, req.language.as_deref(), req.collection.as_deref()).await?)
+    } else {
+        None
+    };
+    // Each stage runs regardless of the others; failures are reported rather than aborting,
+    // so a graph outage doesn't block indexing (or the reverse)
+    let mut stages = IngestStages::default();
+    let report = match job_id {
+        Some(_) => None,
+        None => match engine.ingest_with_report(&req.doc_id, &req.content, req.language.as_deref(), req.collection.as_deref()).await {
+            Ok(report) => {
+                stages.vector.succeeded = 1;
+                Some(report)
+            }
+            // Invalid input, or vectors that would mix embedding models: nothing to salvage
+            Err(e @ (BrainVaultError::BadRequest(_) | BrainVaultError::Conflict(_))) => return Err(e),
+            Err(e) => {
+                println!("WARN: Indexing {} failed, continuing with the graph: {}", req.doc_id, e);
+                stages.vector.fail(e.to_string());
+                None
+            }
+        },
+    };
+
+    // Supplied entities are merged by normalized name, so relationships follow any renamed ids
+    let mut ids = std::collections::HashMap::new();
+    for entity in &req.entities {
+        // Entities belong to the collection the request was authorized to write
+        let mut entity = entity.clone();
+        entity.properties.insert("collection".to_string(), collection.to_string());
+        match graph.merge_entity(entity.clone()).await {
+            Ok(id) => {
+                ids.insert(entity.id.clone(), id);
+                stages.entities.succeeded += 1;
+            }
+            Err(e) => stages.entities.fail(format!("{}: {}", entity.id, e)),
+        }
+    }
+    for rel in &req.relationships {
+        let mut rel = rel.clone();
+        rel.from_id = ids.get(&rel.from_id).cloned().unwrap_or(rel.from_id);
+        rel.to_id = ids.get(&rel.to_id).cloned().unwrap_or(rel.to_id);
+        let label = format!("{} -{}-> {}", rel.from_id, rel.rel_type, rel.to_id);
+        match graph.add_relationship(rel).await {
+            Ok(()) => stages.relationships.succeeded += 1,
+            Err(e) => stages.relationships.fail(format!("{}: {}", label, e)),
+        }
+    }
+
+    let warnings = stages.warnings();
+    if !warnings.is_empty() && stages.succeeded() == 0 && job_id.is_none() && !req.auto_extract {
+        return Err(BrainVaultError::Upstream(format!("Ingest of {} failed: {}", req.doc_id, warnings.join("; "))));
+    }
+    let vector_failed = stages.vector.failed > 0;
+    // The extraction agent only ever sees the masked text. Queued documents are masked when
+    // the queue indexes them, so the agent gets its own masked copy of the raw content.
+    let (outcome, redaction) = match report {
+        Some(report) => (Some(report.outcome), Some(report.redaction)),
+        None if req.auto_extract => (None, Some(engine.redactor.redact_ingest(&req.content))),
+        None => (None, None),
+    };
+    let extraction_text = redaction.as_ref().map_or(req.content.as_str(), |r| r.text.as_str());
+
+    let mut details = std::collections::HashMap::from([
+        ("doc_id".to_string(), req.doc_id.clone()),
+        ("bytes".to_string(), req.content.len().to_string()),
+        ("auto_extract".to_string(), req.auto_extract.to_string()),
+    ]);
+    if !warnings.is_empty() {
+        details.insert("warnings".to_string(), warnings.join("; "));
+    }
+    if let Some(redaction) = redaction.as_ref().filter(|r| !r.is_empty()) {
+        details.insert("redacted".to_string(), redaction.summary());
+    }
+    let mut response = IngestResponse {
+        status: String::new(),
+        doc_id: req.doc_id.clone(),
+        job_id: None,
+        task_id: None,
+        entities: req.entities.len(),
+        relationships: req.relationships.len(),
+        message: None,
+        stages,
+        warnings: warnings.clone(),
+    };
+
+    if let Some(job_id) = job_id {
+        details.insert("job_id".to_string(), job_id.clone());
+        if req.auto_extract {
+            let task_id = submit_extraction(&orchestrator, user_id, &req.doc_id, extraction_text).await;
+            details.insert("task_id".to_string(), task_id.clone());
+            response.task_id = Some(task_id);
+        }
+        audit.record(EventKind::Ingest, Severity::Medium, "Document Ingest", user_id, "Queued", details).await;
+        response.status = "queued".to_string();
+        response.job_id = Some(job_id);
+        let mut builder = if warnings.is_empty() { HttpResponse::Accepted() } else { HttpResponse::MultiStatus() };
+        return Ok(builder.json(response));
+    }
+
+    // Identical content was already indexed (and extracted, if requested back then)
+    if outcome == Some(IndexOutcome::Unchanged) {
+        audit.record(EventKind::Ingest, Severity::Low, "Document Ingest", user_id, "Unchanged", details).await;
+        response.status = "unchanged".to_string();
+        return Ok(partial_status(&warnings).json(response));
+    }
+
+    if !req.auto_extract {
+        audit.record(EventKind::Ingest, Severity::Medium, "Document Ingest", user_id, if warnings.is_empty() { "Indexed" } else { "Partial" }, details).await;
+        response.status = if vector_failed { "partial" } else { "indexed" }.to_string();
+        return Ok(partial_status(&warnings).json(response));
+    }
+
+    let task_id = submit_extraction(&orchestrator, user_id, &req.doc_id, extraction_text).await;
+    details.insert("task_id".to_string(), task_id.clone());
+    audit.record(EventKind::Ingest, Severity::Medium, "Document Ingest", user_id, if warnings.is_empty() { "Submitted" } else { "Partial" }, details).await;
+
+    response.status = "processing".to_string();
+    response.task_id = Some(task_id);
+    response.message = Some(if vector_failed {
+        "Indexing failed; entity extraction submitted to agent swarm."
+    } else {
+        "Document indexed; entity extraction submitted to agent swarm."
+    }.to_string());
+    Ok(partial_status(&warnings).json(response))
+}
+
+/// Delegates entity extraction for `content` to the Ingestor agent and returns the task id
+async fn submit_extraction(
+    orchestrator: &crate::core::agent_orchestrator::AgentOrchestrator,
+    user_id: &str,
+    doc_id: &str,
+    content: &str,
+) -> String {
     let task_description = format!(
         "INGEST_FILE|{}|{}", 
-        req.doc_id, req.content
+        doc_id, content
     );
-    
-    let task_id = orchestrator.submit_task(task_description, Some(crate::core::agent_orchestrator::AgentType::Ingestor)).await;
+    let options = crate::core::agent_orchestrator::TaskOptions { request_id: current_request_id(), ..Default::default() };
+    let task_id = orchestrator.submit_task_with(
+        Some(user_id.to_string()),
+        task_description,
+        Some(crate::core::agent_orchestrator::AgentType::Ingestor),
+        options,
+    ).await;
     let _ = orchestrator.assign_task(&task_id).await;
+    task_id
+}
 
-    HttpResponse::Ok().json(serde_json::json!({
-        "status": "processing", 
-        "task_id": task_id,
-        "message": "Ingestion task submitted to agent swarm."
-    }))
+/// Progress of a document queued with `"background": true`
+#[get("/api/knowledge/jobs/{job_id}")]
+pub async fn get_ingest_job(
+    path: web::Path<String>,
+    ingest_queue: web::Data<IngestQueue>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let job_id = path.into_inner();
+    let job = ingest_queue.get_job(&job_id).await
+        .ok_or_else(|| BrainVaultError::NotFound(format!("Ingest job {}", job_id)))?;
+    Ok(HttpResponse::Ok().json(job))
 }
 
 #[post("/api/knowledge/seed")]
@@ -246,7 +1012,7 @@ pub async fn seed_test_data(
 
     let mut ingested = 0;
     for (doc_id, content) in &test_docs {
-        if let Ok(()) = engine.ingest_document(doc_id, content).await {
+        if engine.ingest_document(doc_id, content).await.is_ok() {
             ingested += 1;
         }
     }
@@ -268,19 +1034,25 @@ pub async fn seed_test_data(
             from_id: "quantum-comp".to_string(), 
             to_id: "cybersecurity".to_string(), 
             rel_type: "IMPACTS".to_string(),
-            properties: std::collections::HashMap::new() 
+            weight: 1.0,
+            properties: std::collections::HashMap::new(),
+            direction: None,
         },
         Relationship { 
             from_id: "machine-learning".to_string(), 
             to_id: "cybersecurity".to_string(), 
             rel_type: "ENHANCES".to_string(),
-            properties: std::collections::HashMap::new() 
+            weight: 1.0,
+            properties: std::collections::HashMap::new(),
+            direction: None,
         },
         Relationship { 
             from_id: "machine-learning".to_string(), 
             to_id: "quantum-comp".to_string(), 
             rel_type: "RELATED_TO".to_string(),
-            properties: std::collections::HashMap::new() 
+            weight: 1.0,
+            properties: std::collections::HashMap::new(),
+            direction: None,
         },
     ];
 
@@ -295,16 +1067,20 @@ pub async fn seed_test_data(
     }))
 }
 
+/// Corpus and graph sizes for dashboards
 #[get("/api/knowledge/stats")]
 pub async fn get_knowledge_stats(
     engine: web::Data<HybridSearchEngine>,
     graph: web::Data<KnowledgeGraphManager>,
 ) -> impl Responder {
-    let doc_count = engine.get_document_count().await;
+    let corpus = engine.corpus_stats().await;
     let (entity_count, relationship_count) = graph.get_stats().await;
     
     HttpResponse::Ok().json(serde_json::json!({
-        "documents": doc_count,
+        "documents": corpus.documents,
+        "content_bytes": corpus.content_bytes,
+        "deleted_documents": corpus.deleted_documents,
+        "collections": corpus.collections,
         "entities": entity_count,
         "relationships": relationship_count
     }))
@@ -312,54 +1088,401 @@ pub async fn get_knowledge_stats(
 
 #[get("/api/knowledge/documents")]
 pub async fn list_documents(
+    req_http: actix_web::HttpRequest,
     engine: web::Data<HybridSearchEngine>,
+    rbac: web::Data<RBAC>,
 ) -> impl Responder {
-    let documents = engine.get_all_documents().await;
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+    let readable = readable_documents(&engine, &rbac, user_id).await;
+    let mut documents = engine.get_all_documents().await;
+    documents.retain(|doc| doc["doc_id"].as_str().map_or(false, |id| readable(id)));
+    for doc in &mut documents {
+        if let Some(preview) = doc.get_mut("content") {
+            if let Some(text) = preview.as_str() {
+                *preview = serde_json::Value::String(engine.redactor.redact_output(text));
+            }
+        }
+    }
     HttpResponse::Ok().json(serde_json::json!({
         "documents": documents
     }))
 }
 
-#[get("/api/documents/{doc_id}")]
-pub async fn get_document(
-    path: web::Path<String>,
-    engine: web::Data<HybridSearchEngine>,
-) -> impl Responder {
-    let doc_id = path.into_inner();
-    if let Some(doc) = engine.vector_db.get_document(&doc_id).await {
-        HttpResponse::Ok().json(serde_json::json!({
-            "doc_id": doc.doc_id,
-            "content": doc.content,
-            "score": doc.score
-        }))
-    } else {
-        HttpResponse::NotFound().json(serde_json::json!({
-            "error": "Document not found",
-            "doc_id": doc_id
-        }))
+/// One line of an NDJSON corpus dump
+#[derive(Serialize, Deserialize)]
+pub struct DocumentRecord {
+    pub doc_id: String,
+    pub content: String,
+    /// Checked against `content` on import when present
+    #[serde(default)]
+    pub content_hash: Option<String>,
+    #[serde(default)]
+    pub version: Option<u32>,
+    #[serde(default)]
+    pub indexed_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+}
+
+impl DocumentRecord {
+    /// The record as it may be returned to a caller: content masked when query redaction
+    /// is on, with `content_hash` recomputed so a re-import of the masked export validates
+    pub fn redacted(mut self, redactor: &Redactor) -> Self {
+        let content = redactor.redact_output(&self.content);
+        if content != self.content {
+            self.content_hash = Some(content_hash(&content));
+            self.content = content;
+        }
+        self
+    }
+
+    /// The live document `doc_id` with its current revision metadata; `None` when it
+    /// doesn't exist or was deleted
+    async fn load(engine: &HybridSearchEngine, doc_id: String) -> Option<Self> {
+        let content = engine.vector_db.get_document(&doc_id).await?.content?;
+        let current = engine.list_versions(&doc_id).await.pop();
+        Some(Self {
+            content_hash: Some(content_hash(&content)),
+            version: current.as_ref().map(|v| v.version),
+            indexed_at: current.as_ref().map(|v| v.indexed_at),
+            language: current.as_ref().and_then(|v| v.language.clone()),
+            collection: current.and_then(|v| v.collection),
+            doc_id,
+            content,
+        })
     }
 }
 
-#[get("/api/documents")]
-pub async fn list_all_documents(
-    engine: web::Data<HybridSearchEngine>,
-) -> impl Responder {
-    let documents = engine.vector_db.list_all_documents().await;
-    HttpResponse::Ok().json(serde_json::json!({
-        "documents": documents,
-        "count": documents.len()
-    }))
+/// Import line errors reported back; further failures are only counted
+const MAX_IMPORT_ERRORS: usize = 100;
+
+#[derive(Serialize, Default)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub unchanged: usize,
+    pub failed: usize,
+    pub errors: Vec<String>,
 }
 
-#[get("/api/graph/data")]
-pub async fn get_graph_data(
-    graph: web::Data<KnowledgeGraphManager>,
-) -> impl Responder {
-    let data = graph.get_graph_data().await;
-    HttpResponse::Ok().json(data)
+/// Whether `perm` may write `doc_id` into `collection`, and into the collection the
+/// document is filed under now when that differs, so a document can't be moved out of
+/// a collection the writer has no say over
+async fn may_write(engine: &HybridSearchEngine, perm: &Permission, doc_id: &str, collection: &str) -> bool {
+    if !perm.can_write(doc_id, collection) {
+        return false;
+    }
+    match engine.vector_db.collection_of(doc_id).await {
+        Some(current) => perm.can_write(doc_id, &current),
+        None => true,
+    }
 }
 
-#[post("/api/search")]
+/// Parsed import lines waiting to be embedded
+struct ImportBatch {
+    records: Vec<(usize, DocumentRecord)>,
+    concurrency: usize,
+    max_document_bytes: usize,
+    /// Effective permission of the importing user; records it may not write are skipped
+    writer: Permission,
+}
+
+impl ImportSummary {
+    fn tally(&mut self, line_no: usize, outcome: std::result::Result<IndexOutcome, String>) {
+        match outcome {
+            Ok(IndexOutcome::Indexed) => self.imported += 1,
+            Ok(IndexOutcome::Unchanged) => self.unchanged += 1,
+            Err(e) => {
+                self.failed += 1;
+                if self.errors.len() < MAX_IMPORT_ERRORS {
+                    self.errors.push(format!("line {}: {}", line_no, e));
+                }
+            }
+        }
+    }
+
+    /// Validates one NDJSON line and adds it to `batch`, ingesting the batch first when it is
+    /// full or already holds the same document (so a later line still wins)
+    async fn queue_line(&mut self, engine: &HybridSearchEngine, batch: &mut ImportBatch, line_no: usize, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        if line.trim().is_empty() {
+            return;
+        }
+        let record = match serde_json::from_str::<DocumentRecord>(&line) {
+            Ok(record) if record.doc_id.trim().is_empty() => Err("doc_id is empty".to_string()),
+            Ok(record) if record.content.len() > batch.max_document_bytes => {
+                Err(document_too_large(&record.doc_id, record.content.len(), batch.max_document_bytes))
+            }
+            Ok(record) if record.content_hash.as_ref().map_or(false, |h| *h != content_hash(&record.content)) => {
+                Err(format!("content_hash mismatch for {}", record.doc_id))
+            }
+            Ok(record) => Ok(record),
+            Err(e) => Err(format!("invalid JSON: {}", e)),
+        };
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => return self.tally(line_no, Err(e)),
+        };
+        let collection = record.collection.as_deref().unwrap_or(DEFAULT_COLLECTION);
+        if !may_write(engine, &batch.writer, &record.doc_id, collection).await {
+            return self.tally(line_no, Err(format!("no write access to collection '{}' for {}", collection, record.doc_id)));
+        }
+        let repeated = batch.records.iter().any(|(_, queued)| queued.doc_id == record.doc_id);
+        if repeated || batch.records.len() >= batch.concurrency * 4 {
+            self.flush(engine, batch).await;
+        }
+        batch.records.push((line_no, record));
+    }
+
+    /// Ingests the queued records, up to `concurrency` at a time
+    async fn flush(&mut self, engine: &HybridSearchEngine, batch: &mut ImportBatch) {
+        let mut outcomes: Vec<(usize, std::result::Result<IndexOutcome, String>)> = futures::stream::iter(batch.records.drain(..))
+            .map(|(line_no, record)| async move {
+                let outcome = engine.ingest_document_into(&record.doc_id, &record.content, record.language.as_deref(), record.collection.as_deref()).await;
+                (line_no, outcome.map_err(|e| e.to_string()))
+            })
+            .buffer_unordered(batch.concurrency)
+            .collect()
+            .await;
+        // Report errors in file order
+        outcomes.sort_by_key(|(line_no, _)| *line_no);
+        for (line_no, outcome) in outcomes {
+            self.tally(line_no, outcome);
+        }
+    }
+}
+
+/// Streams every live document with its current revision metadata as NDJSON
+#[get("/api/knowledge/export")]
+pub async fn export_documents(
+    req_http: actix_web::HttpRequest,
+    engine: web::Data<HybridSearchEngine>,
+    rbac: web::Data<RBAC>,
+    audit: web::Data<AuditManager>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+    let is_admin = matches!(rbac.get_permission(user_id).await, Ok(p) if p.role == Role::Admin);
+    if !is_admin {
+        audit.record_denial(Severity::High, "Corpus Export Denied", user_id, "corpus", std::collections::HashMap::new()).await;
+        return Err(BrainVaultError::Unauthorized("Exporting the corpus requires the Admin role".to_string()));
+    }
+
+    let ids = engine.vector_db.document_ids().await;
+    audit.record(EventKind::Query, Severity::Medium, "Corpus Export", user_id, "Success", std::collections::HashMap::from([
+        ("documents".to_string(), ids.len().to_string()),
+    ])).await;
+
+    // Only ids are held up front; each document is read as its line is sent
+    let engine = engine.into_inner();
+    let lines = futures::stream::iter(ids)
+        .then(move |doc_id| {
+            let engine = engine.clone();
+            async move {
+                let record = DocumentRecord::load(&engine, doc_id).await?.redacted(&engine.redactor);
+                let mut line = serde_json::to_vec(&record).ok()?;
+                line.push(b'\n');
+                Some(Ok::<_, actix_web::Error>(web::Bytes::from(line)))
+            }
+        })
+        .filter_map(|line| async move { line });
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .insert_header(("Content-Disposition", "attachment; filename=\"brainvault-documents.ndjson\""))
+        .streaming(lines))
+}
+
+/// Re-ingests an NDJSON dump from `/api/knowledge/export` as the body arrives, embedding
+/// up to `INGEST_CONCURRENCY` documents at a time. Documents whose content is already
+/// indexed are counted as unchanged, and those over `MAX_DOCUMENT_BYTES` as failed.
+#[post("/api/knowledge/import")]
+pub async fn import_documents(
+    mut payload: web::Payload,
+    req_http: actix_web::HttpRequest,
+    engine: web::Data<HybridSearchEngine>,
+    rbac: web::Data<RBAC>,
+    audit: web::Data<AuditManager>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+    let writer = match rbac.get_permission(user_id).await {
+        Ok(perm) if perm.role == Role::Admin => perm,
+        _ => {
+            audit.record_denial(Severity::High, "Corpus Import Denied", user_id, "corpus", std::collections::HashMap::new()).await;
+            return Err(BrainVaultError::Unauthorized("Importing documents requires the Admin role".to_string()));
+        }
+    };
+
+    let mut summary = ImportSummary::default();
+    let mut batch = ImportBatch { records: Vec::new(), concurrency: config.ingest_concurrency, max_document_bytes: config.max_document_bytes, writer };
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut line_no = 0;
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|e| BrainVaultError::BadRequest(format!("Reading import body failed: {}", e)))?;
+        buffer.extend_from_slice(&chunk);
+        while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=end).collect();
+            line_no += 1;
+            summary.queue_line(&engine, &mut batch, line_no, &line).await;
+        }
+        // The import body itself is streamed, but a single line is buffered whole
+        if buffer.len() > config.body_limit() {
+            summary.flush(&engine, &mut batch).await;
+            return Err(BrainVaultError::PayloadTooLarge(format!(
+                "Line {} exceeds {} bytes; split large documents into chunks ({} imported before it)",
+                line_no + 1, config.body_limit(), summary.imported
+            )));
+        }
+    }
+    if !buffer.is_empty() {
+        summary.queue_line(&engine, &mut batch, line_no + 1, &buffer).await;
+    }
+    summary.flush(&engine, &mut batch).await;
+
+    audit.record(EventKind::Ingest, Severity::Medium, "Corpus Import", user_id, "Completed", std::collections::HashMap::from([
+        ("imported".to_string(), summary.imported.to_string()),
+        ("unchanged".to_string(), summary.unchanged.to_string()),
+        ("failed".to_string(), summary.failed.to_string()),
+    ])).await;
+    Ok(HttpResponse::Ok().json(summary))
+}
+
+/// Re-embeds every document with the configured embedding model into fresh collections,
+/// after a model change has made the stored vectors incompatible. Runs in the background;
+/// poll `GET /api/knowledge/reindex` for the outcome.
+#[post("/api/knowledge/reindex")]
+pub async fn reindex_corpus(
+    req_http: actix_web::HttpRequest,
+    engine: web::Data<HybridSearchEngine>,
+    rbac: web::Data<RBAC>,
+    audit: web::Data<AuditManager>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous")
+        .to_string();
+    let is_admin = matches!(rbac.get_permission(&user_id).await, Ok(p) if p.role == Role::Admin);
+    if !is_admin {
+        audit.record_denial(Severity::High, "Corpus Reindex Denied", &user_id, "corpus", std::collections::HashMap::new()).await;
+        return Err(BrainVaultError::Unauthorized("Reindexing requires the Admin role".to_string()));
+    }
+
+    // Claimed before responding, so a second request gets a 409 rather than a 202
+    let running = engine.begin_reindex()?;
+    let (task_engine, task_audit) = (engine.clone(), audit.clone());
+    actix_web::rt::spawn(async move {
+        let (status, details) = match task_engine.reindex_with(running).await {
+            Ok(report) => ("Completed", std::collections::HashMap::from([
+                ("model".to_string(), report.model.clone()),
+                ("generation".to_string(), report.generation.to_string()),
+                ("reindexed".to_string(), report.reindexed.to_string()),
+                ("failed".to_string(), report.failed.len().to_string()),
+            ])),
+            Err(e) => {
+                println!("WARN: Reindex failed: {}", e);
+                ("Failed", std::collections::HashMap::from([("error".to_string(), e.to_string())]))
+            }
+        };
+        task_audit.record(EventKind::Ingest, Severity::Medium, "Corpus Reindex", &user_id, status, details).await;
+    });
+    Ok(HttpResponse::Accepted().json(engine.vector_db.reindex_status()))
+}
+
+/// Whether a reindex is running, and the report or error of the last one
+#[get("/api/knowledge/reindex")]
+pub async fn get_reindex_status(engine: web::Data<HybridSearchEngine>) -> impl Responder {
+    HttpResponse::Ok().json(engine.vector_db.reindex_status())
+}
+
+#[get("/api/documents/{doc_id}")]
+pub async fn get_document(
+    path: web::Path<String>,
+    req_http: actix_web::HttpRequest,
+    engine: web::Data<HybridSearchEngine>,
+    rbac: web::Data<RBAC>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let doc_id = path.into_inner();
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+    let collection = engine.vector_db.collection_of(&doc_id).await;
+    if !rbac.check_access_in(user_id, &doc_id, collection.as_deref()).await? {
+        return Err(BrainVaultError::Unauthorized(format!("No access to document {}", doc_id)));
+    }
+
+    if let Some(doc) = engine.vector_db.get_document(&doc_id).await {
+        Ok(HttpResponse::Ok().json(serde_json::json!({
+            "doc_id": doc.doc_id,
+            "content": doc.content.map(|c| engine.redactor.redact_output(&c)),
+            "score": doc.score
+        })))
+    } else {
+        Ok(HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Document not found",
+            "doc_id": doc_id
+        })))
+    }
+}
+
+/// Test for the documents `user_id` may read, judged on their stored collections. Unknown
+/// users read nothing, as in search.
+async fn readable_documents(engine: &HybridSearchEngine, rbac: &RBAC, user_id: &str) -> impl Fn(&str) -> bool {
+    let perm = rbac.get_permission(user_id).await.ok();
+    let collections = engine.vector_db.document_collections().await;
+    move |doc_id: &str| {
+        let collection = collections.get(doc_id).map_or(DEFAULT_COLLECTION, String::as_str);
+        perm.as_ref().map_or(false, |p| p.allows(doc_id, Some(collection)))
+    }
+}
+
+#[get("/api/documents")]
+pub async fn list_all_documents(
+    req_http: actix_web::HttpRequest,
+    engine: web::Data<HybridSearchEngine>,
+    rbac: web::Data<RBAC>,
+) -> impl Responder {
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+    let readable = readable_documents(&engine, &rbac, user_id).await;
+    let mut documents = engine.vector_db.list_all_documents().await;
+    documents.retain(|doc| readable(&doc.doc_id));
+    for doc in &mut documents {
+        doc.content = doc.content.as_deref().map(|c| engine.redactor.redact_output(c));
+    }
+    HttpResponse::Ok().json(serde_json::json!({
+        "documents": documents,
+        "count": documents.len()
+    }))
+}
+
+#[get("/api/graph/data")]
+pub async fn get_graph_data(
+    graph: web::Data<KnowledgeGraphManager>,
+) -> impl Responder {
+    let data = graph.get_graph_data().await;
+    HttpResponse::Ok().json(data)
+}
+
+#[utoipa::path(
+    tag = "search",
+    request_body = SearchQuery,
+    responses(
+        (status = 200, description = "One page of hits the caller may read", body = SearchPage),
+        (status = 207, description = "The search backend failed; hits are partial and `warnings` says why", body = SearchPage),
+        (status = 400, description = "Invalid language or collection", body = ErrorBody),
+        (status = 403, description = "No access to the requested collection", body = ErrorBody),
+        (status = 503, description = "Warming up and `SEARCH_REQUIRE_READY` is set; retry shortly", body = ErrorBody),
+    ),
+)]
+#[post("/api/search")]
 pub async fn hybrid_search(
     query: web::Json<SearchQuery>,
     // In a real app, user_id comes from auth middleware. 
@@ -369,43 +1492,611 @@ pub async fn hybrid_search(
     req_http: actix_web::HttpRequest,
     engine: web::Data<HybridSearchEngine>,
     rbac: web::Data<RBAC>,
-) -> impl Responder {
+    audit: web::Data<AuditManager>,
+    history: web::Data<SearchHistory>,
+) -> Result<HttpResponse, BrainVaultError> {
     let user_id = req_http.headers().get("X-User-ID")
         .and_then(|h| h.to_str().ok())
         .unwrap_or("anonymous");
 
-    // 1. Execute hybrid search
-    match engine.search(&query.q, query.top_k).await {
-        Ok(results) => {
-            // 2. Filter by RBAC
-            let filtered = rbac.get_permitted_search_results(user_id, results).await;
-            HttpResponse::Ok().json(filtered)
+    let page = run_search(&query, user_id, &engine, &rbac, &audit).await?;
+    record_search(&history, user_id, &query, &page).await;
+    Ok(partial_status(&page.warnings).json(page))
+}
+
+/// Search as `user_id`, RBAC-filtered and paged; shared by live searches and replays
+async fn run_search(
+    query: &SearchQuery,
+    user_id: &str,
+    engine: &HybridSearchEngine,
+    rbac: &RBAC,
+    audit: &AuditManager,
+) -> Result<SearchPage, BrainVaultError> {
+    engine.ensure_ready()?;
+    let weights = match (query.vector_weight, query.bm25_weight) {
+        (None, None) => None,
+        (v, b) => Some(engine.lexical_weights.with_overrides(v, b)?),
+    };
+
+    let lexical = LexicalOptions {
+        language: match query.language.as_deref() {
+            Some(name) => Some(normalize_language(name)
+                .ok_or_else(|| BrainVaultError::BadRequest(format!("Unsupported language '{}'", name)))?
+                .to_string()),
+            None => None,
         },
-        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+        fuzzy: query.fuzzy,
+        collection: query.collection.clone(),
+    };
+    if let Some(ref collection) = lexical.collection {
+        validate_collection(collection)?;
+        if !rbac.check_collection_access(user_id, collection).await? {
+            audit.record_denial(Severity::High, "Collection Search Denied", user_id, collection, std::collections::HashMap::from([
+                ("collection".to_string(), collection.clone()),
+            ])).await;
+            return Err(BrainVaultError::Unauthorized(format!("No access to collection '{}'", collection)));
+        }
+    }
+
+    // 1. Execute hybrid search over the full candidate window so totals are known. A failing
+    // backend yields an empty (or history-only) page with a warning rather than an error.
+    let searched = if query.expand {
+        engine.search_expanded(&query.q, MAX_RESULT_WINDOW, weights.as_ref(), &lexical).await
+    } else {
+        engine.search_with(&query.q, MAX_RESULT_WINDOW, weights.as_ref(), &lexical).await.map(|r| (r, vec![]))
+    };
+    let mut warnings = vec![];
+    let (mut results, expansions) = match searched {
+        Ok(found) => found,
+        Err(e @ (BrainVaultError::Upstream(_) | BrainVaultError::Unavailable(_))) => {
+            println!("WARN: Search for '{}' failed, returning partial results: {}", query.q, e);
+            warnings.push(format!("search: {}", e));
+            (SearchResults { hits: vec![] }, vec![])
+        }
+        Err(e) => return Err(e),
+    };
+    if query.include_history {
+        results.hits.extend(engine.search_history(&query.q, MAX_RESULT_WINDOW, weights.as_ref(), &lexical).await);
+        results.hits.sort_by(compare_hits);
+        results.hits.truncate(MAX_RESULT_WINDOW);
+    }
+
+    // 2. Filter by RBAC before paging so counts only reflect visible documents
+    let retrieved = results.hits.len();
+    let collections = engine.vector_db.document_collections().await;
+    let mut filtered = rbac.get_permitted_search_results_in(user_id, results, &collections).await;
+    let withheld = retrieved - filtered.hits.len();
+    // Filtering is routine rather than a refused request, so it stays out of the denial risk score
+    if withheld > 0 {
+        audit.record(EventKind::Query, Severity::Low, "Search Results Withheld", user_id, "Filtered", std::collections::HashMap::from([
+            ("query".to_string(), query.q.clone()),
+            ("withheld".to_string(), withheld.to_string()),
+        ])).await;
+    }
+    if query.rerank {
+        filtered = engine.rerank(&query.q, filtered).await;
     }
+    audit.record(EventKind::Query, Severity::Low, "Search", user_id, "Completed", std::collections::HashMap::from([
+        ("query".to_string(), query.q.clone()),
+        ("hits".to_string(), filtered.hits.len().to_string()),
+        ("withheld".to_string(), withheld.to_string()),
+    ])).await;
+    let mut page = filtered.paginate(query.effective_offset(), query.top_k);
+    page.limit_content(query.max_content_len.unwrap_or(engine.max_content_len));
+    page.redact(&engine.redactor);
+    page.expansions = expansions;
+    page.warnings = warnings;
+    Ok(page)
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct FeedbackRequest {
+    /// The query that returned the document
+    pub query: String,
+    pub doc_id: String,
+    pub relevant: bool,
+}
+
+/// Mark a search hit as relevant or not; later searches for similar queries rank it
+/// slightly higher or lower
+#[post("/api/search/feedback")]
+pub async fn search_feedback(
+    req: web::Json<FeedbackRequest>,
+    req_http: actix_web::HttpRequest,
+    engine: web::Data<HybridSearchEngine>,
+    rbac: web::Data<RBAC>,
+    audit: web::Data<AuditManager>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+    let feedback = engine.feedback.as_ref()
+        .ok_or_else(|| BrainVaultError::Unavailable("Relevance feedback is not enabled".to_string()))?;
+    if req.query.trim().is_empty() || req.doc_id.trim().is_empty() {
+        return Err(BrainVaultError::BadRequest("query and doc_id are required".to_string()));
+    }
+
+    // Only documents the caller can see may be voted on, or feedback could steer others' results
+    let doc_id = req.doc_id.as_str();
+    let collection = engine.vector_db.collection_of(doc_id).await;
+    if !rbac.check_access_in(user_id, doc_id, collection.as_deref()).await? {
+        audit.record_denial(Severity::Medium, "Search Feedback Denied", user_id, doc_id, std::collections::HashMap::from([
+            ("doc_id".to_string(), doc_id.to_string()),
+        ])).await;
+        return Err(BrainVaultError::Unauthorized(format!("No access to document '{}'", doc_id)));
+    }
+
+    engine.record_feedback(user_id, &req.query, doc_id, req.relevant).await?;
+    audit.record(EventKind::Query, Severity::Low, "Search Feedback", user_id, "Recorded", std::collections::HashMap::from([
+        ("query".to_string(), req.query.clone()),
+        ("doc_id".to_string(), doc_id.to_string()),
+        ("relevant".to_string(), req.relevant.to_string()),
+    ])).await;
+    let (for_query, overall) = feedback.tally(&req.query, doc_id);
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "doc_id": doc_id,
+        "query_votes": for_query,
+        "all_votes": overall,
+    })))
+}
+
+/// Remember a search the user ran; anonymous callers share an id, so theirs are not kept
+async fn record_search(history: &SearchHistory, user_id: &str, query: &SearchQuery, page: &SearchPage) {
+    if user_id == "anonymous" {
+        return;
+    }
+    if let Ok(value) = serde_json::to_value(query) {
+        history.record(user_id, value, page.total).await;
+    }
+}
+
+/// Search history and saved searches belong to a named user
+fn history_user(req_http: &actix_web::HttpRequest) -> Result<&str, BrainVaultError> {
+    req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .filter(|id| !id.is_empty() && *id != "anonymous")
+        .ok_or_else(|| BrainVaultError::BadRequest("Search history requires an X-User-ID header".to_string()))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SearchHistoryResponse {
+    /// Whether new searches are being recorded for this user
+    pub recording: bool,
+    pub entries: Vec<crate::core::search_history::HistoryEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HistorySettings {
+    pub enabled: bool,
+}
+
+#[get("/api/search/history")]
+pub async fn get_search_history(
+    req_http: actix_web::HttpRequest,
+    history: web::Data<SearchHistory>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = history_user(&req_http)?;
+    Ok(HttpResponse::Ok().json(SearchHistoryResponse {
+        recording: history.is_recording(user_id).await,
+        entries: history.history(user_id).await,
+    }))
+}
+
+#[delete("/api/search/history")]
+pub async fn clear_search_history(
+    req_http: actix_web::HttpRequest,
+    history: web::Data<SearchHistory>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = history_user(&req_http)?;
+    history.clear_history(user_id).await;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Opt out of (or back into) search history; opting out also forgets past searches
+#[post("/api/search/history/settings")]
+pub async fn update_search_history_settings(
+    settings: web::Json<HistorySettings>,
+    req_http: actix_web::HttpRequest,
+    history: web::Data<SearchHistory>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = history_user(&req_http)?;
+    history.set_history_enabled(user_id, settings.enabled).await;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "recording": history.is_recording(user_id).await,
+    })))
+}
+
+/// Run a stored query again; access is checked as of now, not as of the original search
+async fn replay(
+    stored: serde_json::Value,
+    user_id: &str,
+    engine: &HybridSearchEngine,
+    rbac: &RBAC,
+    audit: &AuditManager,
+    history: &SearchHistory,
+) -> Result<HttpResponse, BrainVaultError> {
+    let query: SearchQuery = serde_json::from_value(stored)
+        .map_err(|e| BrainVaultError::BadRequest(format!("Stored search can no longer be run: {}", e)))?;
+    let page = run_search(&query, user_id, engine, rbac, audit).await?;
+    record_search(history, user_id, &query, &page).await;
+    Ok(partial_status(&page.warnings).json(page))
+}
+
+#[post("/api/search/history/{id}/replay")]
+pub async fn replay_search(
+    path: web::Path<String>,
+    req_http: actix_web::HttpRequest,
+    engine: web::Data<HybridSearchEngine>,
+    rbac: web::Data<RBAC>,
+    audit: web::Data<AuditManager>,
+    history: web::Data<SearchHistory>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = history_user(&req_http)?;
+    let entry = history.history_entry(user_id, &path.into_inner()).await?;
+    replay(entry.query, user_id, &engine, &rbac, &audit, &history).await
+}
+
+#[get("/api/search/saved")]
+pub async fn list_saved_searches(
+    req_http: actix_web::HttpRequest,
+    history: web::Data<SearchHistory>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = history_user(&req_http)?;
+    Ok(HttpResponse::Ok().json(history.saved_searches(user_id).await))
+}
+
+/// Save a search request under a name, replacing any earlier one of the same name
+#[put("/api/search/saved/{name}")]
+pub async fn save_search(
+    path: web::Path<String>,
+    query: web::Json<SearchQuery>,
+    req_http: actix_web::HttpRequest,
+    history: web::Data<SearchHistory>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = history_user(&req_http)?;
+    let value = serde_json::to_value(&*query)
+        .map_err(|e| BrainVaultError::Internal(format!("Failed to store search: {}", e)))?;
+    let saved = history.save(user_id, &path.into_inner(), value).await?;
+    Ok(HttpResponse::Ok().json(saved))
+}
+
+#[delete("/api/search/saved/{name}")]
+pub async fn delete_saved_search(
+    path: web::Path<String>,
+    req_http: actix_web::HttpRequest,
+    history: web::Data<SearchHistory>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = history_user(&req_http)?;
+    history.delete_saved(user_id, &path.into_inner()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[post("/api/search/saved/{name}/run")]
+pub async fn run_saved_search(
+    path: web::Path<String>,
+    req_http: actix_web::HttpRequest,
+    engine: web::Data<HybridSearchEngine>,
+    rbac: web::Data<RBAC>,
+    audit: web::Data<AuditManager>,
+    history: web::Data<SearchHistory>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = history_user(&req_http)?;
+    let saved = history.saved_search(user_id, &path.into_inner()).await?;
+    replay(saved.query, user_id, &engine, &rbac, &audit, &history).await
+}
+
+#[get("/api/graph/export")]
+pub async fn export_graph(
+    query: web::Query<ExportQuery>,
+    req_http: actix_web::HttpRequest,
+    graph: web::Data<KnowledgeGraphManager>,
+    rbac: web::Data<RBAC>,
+    audit: web::Data<AuditManager>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+    let format = ExportFormat::parse(&query.format).ok_or_else(|| {
+        BrainVaultError::BadRequest(format!("Unknown export format '{}'; use graphml, cypher or json", query.format))
+    })?;
+
+    let full = graph.export_graph().await;
+    let filtered = match rbac.filter_context(user_id, full).await {
+        Ok(filtered) => filtered,
+        Err(e) => {
+            audit.record_denial(Severity::High, "Graph Export Denied", user_id, "graph", std::collections::HashMap::from([
+                ("reason".to_string(), e.to_string()),
+            ])).await;
+            return Err(e);
+        }
+    };
+    audit.record(EventKind::Query, Severity::Medium, "Graph Export", user_id, "Success", std::collections::HashMap::from([
+        ("format".to_string(), format.file_extension().to_string()),
+        ("entities".to_string(), filtered.entities.len().to_string()),
+        ("relationships".to_string(), filtered.relationships.len().to_string()),
+    ])).await;
+
+    let body = match format {
+        ExportFormat::GraphMl => to_graphml(&filtered),
+        ExportFormat::Cypher => to_cypher(&filtered),
+        ExportFormat::Json => serde_json::to_string_pretty(&filtered).map_err(|e| BrainVaultError::Internal(e.to_string()))?,
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"brainvault-graph.{}\"", format.file_extension())))
+        .body(body))
+}
+
+#[get("/api/graph/duplicates")]
+pub async fn suggest_duplicates(
+    query: web::Query<DuplicateQuery>,
+    req_http: actix_web::HttpRequest,
+    graph: web::Data<KnowledgeGraphManager>,
+    rbac: web::Data<RBAC>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+    let perm = rbac.get_permission(user_id).await?;
+
+    let mut suggestions = Vec::new();
+    for suggestion in graph.suggest_duplicates(query.threshold.clamp(0.0, 1.0), query.limit.min(500)).await {
+        if entity_allowed(&graph, &perm, &suggestion.keep_id).await && entity_allowed(&graph, &perm, &suggestion.merge_id).await {
+            suggestions.push(suggestion);
+        }
+    }
+    Ok(HttpResponse::Ok().json(suggestions))
+}
+
+/// Whether `perm` covers graph node `entity_id`, through its id or the collection it came from
+async fn entity_allowed(graph: &KnowledgeGraphManager, perm: &Permission, entity_id: &str) -> bool {
+    let entity = graph.get_entity(entity_id).await;
+    perm.allows(entity_id, entity.as_ref().and_then(entity_collection))
+}
+
+#[post("/api/graph/merge")]
+pub async fn merge_entities(
+    req: web::Json<MergeRequest>,
+    req_http: actix_web::HttpRequest,
+    graph: web::Data<KnowledgeGraphManager>,
+    rbac: web::Data<RBAC>,
+    audit: web::Data<AuditManager>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+    // Excluded nodes stay out of reach even for Admins
+    let allowed = match rbac.get_permission(user_id).await {
+        Ok(p) if p.role == Role::Admin => {
+            entity_allowed(&graph, &p, &req.keep_id).await && entity_allowed(&graph, &p, &req.merge_id).await
+        }
+        _ => false,
+    };
+    if !allowed {
+        audit.record_denial(Severity::High, "Entity Merge Denied", user_id, &req.merge_id, std::collections::HashMap::from([
+            ("keep_id".to_string(), req.keep_id.clone()),
+            ("merge_id".to_string(), req.merge_id.clone()),
+        ])).await;
+        return Err(BrainVaultError::Unauthorized("Merging entities requires the Admin role".to_string()));
+    }
+
+    let kept = graph.merge_entities(&req.keep_id, &req.merge_id).await?;
+    audit.record(EventKind::Configuration, Severity::Medium, "Entities Merged", user_id, "Success", std::collections::HashMap::from([
+        ("keep_id".to_string(), req.keep_id.clone()),
+        ("merge_id".to_string(), req.merge_id.clone()),
+    ])).await;
+    Ok(HttpResponse::Ok().json(kept))
+}
+
+/// A revision with its content, as listed by `/api/knowledge/{doc_id}/versions`
+#[derive(Serialize)]
+pub struct VersionView {
+    pub key: String,
+    #[serde(flatten)]
+    pub version: DocumentVersion,
+    pub content: Option<String>,
+}
+
+#[get("/api/knowledge/{doc_id}/versions")]
+pub async fn list_document_versions(
+    path: web::Path<String>,
+    req_http: actix_web::HttpRequest,
+    engine: web::Data<HybridSearchEngine>,
+    rbac: web::Data<RBAC>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let doc_id = path.into_inner();
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+    let collection = engine.vector_db.collection_of(&doc_id).await;
+    if !rbac.check_access_in(user_id, &doc_id, collection.as_deref()).await? {
+        return Err(BrainVaultError::Unauthorized(format!("No access to document {}", doc_id)));
+    }
+
+    let versions = engine.list_versions(&doc_id).await;
+    if versions.is_empty() {
+        return Err(BrainVaultError::NotFound(format!("Document {}", doc_id)));
+    }
+    let mut views = Vec::with_capacity(versions.len());
+    for version in versions {
+        views.push(VersionView {
+            key: version_key(&doc_id, version.version),
+            content: engine.get_version(&doc_id, version.version).await.map(|c| engine.redactor.redact_output(&c)),
+            version,
+        });
+    }
+    Ok(HttpResponse::Ok().json(views))
+}
+
+/// Full content and current revision metadata of one document, e.g. after a search
+/// returned a truncated hit
+#[get("/api/knowledge/{doc_id}")]
+pub async fn get_knowledge_document(
+    path: web::Path<String>,
+    req_http: actix_web::HttpRequest,
+    engine: web::Data<HybridSearchEngine>,
+    rbac: web::Data<RBAC>,
+    audit: web::Data<AuditManager>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let doc_id = path.into_inner();
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+    let collection = engine.vector_db.collection_of(&doc_id).await;
+    let denied = match rbac.check_access_in(user_id, &doc_id, collection.as_deref()).await {
+        Ok(true) => None,
+        Ok(false) => Some(BrainVaultError::Unauthorized(format!("No access to document {}", doc_id))),
+        Err(e) => Some(e),
+    };
+    if let Some(e) = denied {
+        audit.record_denial(Severity::High, "Document Read Denied", user_id, &doc_id, std::collections::HashMap::from([
+            ("doc_id".to_string(), doc_id.clone()),
+            ("reason".to_string(), e.to_string()),
+        ])).await;
+        return Err(e);
+    }
+
+    let record = DocumentRecord::load(&engine, doc_id.clone()).await
+        .ok_or_else(|| BrainVaultError::NotFound(format!("Document {}", doc_id)))?;
+    Ok(HttpResponse::Ok().json(record.redacted(&engine.redactor)))
+}
+
+/// Soft delete: the document leaves search but its revisions stay listed under `/versions`
+#[delete("/api/knowledge/{doc_id}")]
+pub async fn delete_document(
+    path: web::Path<String>,
+    req_http: actix_web::HttpRequest,
+    engine: web::Data<HybridSearchEngine>,
+    rbac: web::Data<RBAC>,
+    audit: web::Data<AuditManager>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let doc_id = path.into_inner();
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+
+    let collection = engine.vector_db.collection_of(&doc_id).await;
+    let allowed = match rbac.get_permission(user_id).await {
+        Ok(p) if matches!(p.role, Role::Admin | Role::DataOwner) => p.allows(&doc_id, collection.as_deref()),
+        _ => false,
+    };
+    if !allowed {
+        audit.record_denial(Severity::High, "Document Delete Denied", user_id, &doc_id, std::collections::HashMap::from([
+            ("doc_id".to_string(), doc_id.clone()),
+        ])).await;
+        return Err(BrainVaultError::Unauthorized(format!("Deleting document {} requires the DataOwner or Admin role", doc_id)));
+    }
+
+    let tombstone = engine.delete_document(&doc_id).await?;
+    audit.record(EventKind::Ingest, Severity::Medium, "Document Deleted", user_id, "Tombstoned", std::collections::HashMap::from([
+        ("doc_id".to_string(), doc_id.clone()),
+        ("version".to_string(), tombstone.version.to_string()),
+    ])).await;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "deleted",
+        "doc_id": doc_id,
+        "version": tombstone.version,
+    })))
+}
+
+#[patch("/api/graph/{entity_id}")]
+pub async fn update_entity(
+    path: web::Path<String>,
+    req: web::Json<UpdateEntityRequest>,
+    req_http: actix_web::HttpRequest,
+    graph: web::Data<KnowledgeGraphManager>,
+    rbac: web::Data<RBAC>,
+    audit: web::Data<AuditManager>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let entity_id = path.into_inner();
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+
+    // Admins may edit any node they aren't excluded from; DataOwners only the nodes they have access to
+    let allowed = match rbac.get_permission(user_id).await {
+        Ok(p) if matches!(p.role, Role::Admin | Role::DataOwner) => entity_allowed(&graph, &p, &entity_id).await,
+        _ => false,
+    };
+    if !allowed {
+        audit.record_denial(Severity::High, "Entity Update Denied", user_id, &entity_id, std::collections::HashMap::from([
+            ("entity_id".to_string(), entity_id.clone()),
+        ])).await;
+        return Err(BrainVaultError::Unauthorized(format!("Updating entity {} requires the DataOwner or Admin role", entity_id)));
+    }
+
+    let keys: Vec<String> = req.properties.keys().cloned().collect();
+    let updated = graph.update_entity(&entity_id, req.into_inner().properties).await?;
+    audit.record(EventKind::Configuration, Severity::Low, "Entity Updated", user_id, "Success", std::collections::HashMap::from([
+        ("entity_id".to_string(), entity_id),
+        ("properties".to_string(), keys.join(",")),
+    ])).await;
+    Ok(HttpResponse::Ok().json(updated))
+}
+
+#[get("/api/graph/path")]
+pub async fn find_graph_path(
+    query: web::Query<PathQuery>,
+    req_http: actix_web::HttpRequest,
+    graph: web::Data<KnowledgeGraphManager>,
+    rbac: web::Data<RBAC>,
+    audit: web::Data<AuditManager>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+
+    let path = graph.find_path(&query.from, &query.to, query.max_depth.min(MAX_PATH_DEPTH)).await?;
+
+    match rbac.filter_context(user_id, path).await {
+        Ok(filtered) => Ok(HttpResponse::Ok().json(filtered)),
+        Err(e) => {
+            audit.record_denial(Severity::High, "Graph Path Denied", user_id, &query.from, std::collections::HashMap::from([
+                ("from".to_string(), query.from.clone()),
+                ("to".to_string(), query.to.clone()),
+                ("reason".to_string(), e.to_string()),
+            ])).await;
+            Err(e)
+        },
+    }
+}
+
+#[utoipa::path(
+    tag = "graph",
+    params(("entity_id" = String, Path, description = "Entity to start from"), ContextQuery),
+    responses(
+        (status = 200, description = "Entities and relationships within three hops, paged", body = ContextGraph),
+        (status = 403, description = "The caller may not see the entity", body = ErrorBody),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+    ),
+)]
 #[get("/api/graph/{entity_id}/context")]
 pub async fn get_context(
     path: web::Path<String>,
+    query: web::Query<ContextQuery>,
     req_http: actix_web::HttpRequest,
     graph: web::Data<KnowledgeGraphManager>,
     rbac: web::Data<RBAC>,
-) -> impl Responder {
+    audit: web::Data<AuditManager>,
+) -> Result<HttpResponse, BrainVaultError> {
     let entity_id = path.into_inner();
     let user_id = req_http.headers().get("X-User-ID")
         .and_then(|h| h.to_str().ok())
         .unwrap_or("anonymous");
 
     // 1. Traverse graph
-    match graph.find_related_context(&entity_id, 3).await {
-        Ok(context) => {
-            // 2. Filter by RBAC
-            match rbac.filter_context(user_id, context).await {
-                Ok(filtered) => HttpResponse::Ok().json(filtered),
-                Err(e) => HttpResponse::Forbidden().body(e),
-            }
+    let max_nodes = query.max_nodes.unwrap_or(MAX_CONTEXT_NODES).min(MAX_CONTEXT_NODES);
+    let context = graph.find_context_page(&entity_id, 3, query.min_weight, max_nodes, query.cursor.as_deref()).await?;
+
+    // 2. Filter by RBAC
+    match rbac.filter_context(user_id, context).await {
+        Ok(filtered) => Ok(HttpResponse::Ok().json(filtered)),
+        Err(e) => {
+            audit.record_denial(Severity::High, "Graph Context Denied", user_id, &entity_id, std::collections::HashMap::from([
+                ("entity_id".to_string(), entity_id.clone()),
+                ("reason".to_string(), e.to_string()),
+            ])).await;
+            Err(e)
         },
-        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
@@ -1,10 +1,152 @@
 use actix_web::{get, web, HttpResponse, Responder};
-use crate::core::audit_manager::AuditManager;
+use serde::{Deserialize, Serialize};
+use crate::core::audit_manager::{AuditManager, AuditQuery, EventKind, Severity};
+use crate::core::graph_manager::KnowledgeGraphManager;
+use crate::core::rbac::{entity_collection, Role, RBAC};
+use crate::core::search_engine::HybridSearchEngine;
+use crate::error::BrainVaultError;
 
+/// Query parameters: `user`, `since`, `until` (unix seconds), `severity`, `kind`, `limit`, `offset`.
+/// Admin only, since entries carry query text and document ids.
 #[get("/api/security/logs")]
 pub async fn get_security_logs(
+    query: web::Query<AuditQuery>,
+    req_http: actix_web::HttpRequest,
     audit: web::Data<AuditManager>,
+    rbac: web::Data<RBAC>,
 ) -> impl Responder {
-    let logs = audit.get_logs().await;
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+    let is_admin = matches!(rbac.get_permission(user_id).await, Ok(p) if p.role == Role::Admin);
+    if !is_admin {
+        return HttpResponse::Forbidden().body("Reading audit logs requires the Admin role");
+    }
+
+    let logs = audit.query_logs(&query).await;
     HttpResponse::Ok().json(logs)
 }
+
+/// Verifies the audit hash chain; admin only
+#[get("/api/security/logs/verify")]
+pub async fn verify_security_logs(
+    req_http: actix_web::HttpRequest,
+    audit: web::Data<AuditManager>,
+    rbac: web::Data<RBAC>,
+) -> impl Responder {
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+    let is_admin = matches!(rbac.get_permission(user_id).await, Ok(p) if p.role == Role::Admin);
+    if !is_admin {
+        return HttpResponse::Forbidden().body("Audit verification requires the Admin role");
+    }
+
+    let verification = audit.verify_chain().await;
+    if !verification.valid {
+        println!("WARN: Audit log chain broken at {:?}: {:?}", verification.broken_at, verification.reason);
+        audit.record(EventKind::Configuration, Severity::Critical, "Audit Chain Verification Failed", user_id, "Tampered", std::collections::HashMap::from([
+            ("broken_at".to_string(), verification.broken_at.clone().unwrap_or_default()),
+        ])).await;
+    }
+    HttpResponse::Ok().json(verification)
+}
+
+/// Users denied access within the audit denial window, riskiest first. Admin only.
+#[get("/api/security/risk")]
+pub async fn get_denial_risk(
+    req_http: actix_web::HttpRequest,
+    audit: web::Data<AuditManager>,
+    rbac: web::Data<RBAC>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+    if !rbac.is_admin(user_id).await {
+        audit.record_denial(Severity::High, "Denial Risk Report Denied", user_id, "security/risk", std::collections::HashMap::new()).await;
+        return Err(BrainVaultError::Unauthorized("The denial risk report requires the Admin role".to_string()));
+    }
+    Ok(HttpResponse::Ok().json(audit.denial_summary().await))
+}
+
+#[derive(Deserialize)]
+pub struct AccessCheckQuery {
+    pub entity: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AccessCheckResponse {
+    pub user_id: String,
+    pub entity: String,
+    pub collection: Option<String>,
+    pub allowed: bool,
+}
+
+/// The stored collection of the document or graph entity `entity`. Never taken from the
+/// caller, who could otherwise name a collection they can read to pass the check.
+async fn resolve_collection(
+    entity: &str,
+    engine: &HybridSearchEngine,
+    graph: &KnowledgeGraphManager,
+) -> Option<String> {
+    match engine.vector_db.collection_of(entity).await {
+        Some(collection) => Some(collection),
+        None => graph.get_entity(entity).await
+            .and_then(|e| entity_collection(&e).map(str::to_string)),
+    }
+}
+
+/// Lets clients find out whether the caller may read a document or graph entity
+/// before requesting it
+#[get("/api/rbac/check")]
+pub async fn check_entity_access(
+    query: web::Query<AccessCheckQuery>,
+    req_http: actix_web::HttpRequest,
+    engine: web::Data<HybridSearchEngine>,
+    graph: web::Data<KnowledgeGraphManager>,
+    rbac: web::Data<RBAC>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+
+    let collection = resolve_collection(&query.entity, &engine, &graph).await;
+    let allowed = rbac.check_access_in(user_id, &query.entity, collection.as_deref()).await?;
+    Ok(HttpResponse::Ok().json(AccessCheckResponse {
+        user_id: user_id.to_string(),
+        entity: query.entity.clone(),
+        collection,
+        allowed,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct AccessExplainQuery {
+    pub user: String,
+    pub entity: String,
+}
+
+/// Why `user` may or may not read `entity`: the deciding grant and the reason (excluded,
+/// expired, not in the allow-list, collection denied). Admin only.
+#[get("/api/rbac/explain")]
+pub async fn explain_entity_access(
+    query: web::Query<AccessExplainQuery>,
+    req_http: actix_web::HttpRequest,
+    engine: web::Data<HybridSearchEngine>,
+    graph: web::Data<KnowledgeGraphManager>,
+    rbac: web::Data<RBAC>,
+    audit: web::Data<AuditManager>,
+) -> Result<HttpResponse, BrainVaultError> {
+    let user_id = req_http.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("anonymous");
+    let is_admin = matches!(rbac.get_permission(user_id).await, Ok(p) if p.role == Role::Admin);
+    if !is_admin {
+        audit.record_denial(Severity::Medium, "Access Explanation Denied", user_id, &query.user, std::collections::HashMap::new()).await;
+        return Err(BrainVaultError::Unauthorized("Explaining access decisions requires the Admin role".to_string()));
+    }
+
+    let collection = resolve_collection(&query.entity, &engine, &graph).await;
+    let explanation = rbac.explain_access(&query.user, &query.entity, collection.as_deref()).await;
+    Ok(HttpResponse::Ok().json(explanation))
+}
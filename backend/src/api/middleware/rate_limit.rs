@@ -0,0 +1,127 @@
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpResponse};
+use crate::core::rbac::RBAC;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Key of the bucket shared by every caller without a known user id
+pub const ANONYMOUS_BUCKET: &str = "anonymous";
+
+/// Routes that reach the LLM or run full searches
+const DEFAULT_LIMITED_PATHS: &[&str] = &["/api/search", "/api/ask", "/api/chat", "/api/agents/chat"];
+
+/// Buckets kept before idle, fully refilled ones are dropped
+const MAX_TRACKED_BUCKETS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Per-user token buckets. Each user may burst up to `per_minute` requests and refills
+/// at `per_minute / 60` tokens per second; unauthenticated traffic shares one bucket
+/// with its own, usually stricter, limit. A limit of 0 disables that class of limiting.
+pub struct RateLimiter {
+    per_minute: u32,
+    anonymous_per_minute: u32,
+    paths: Vec<String>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32, anonymous_per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            anonymous_per_minute,
+            paths: DEFAULT_LIMITED_PATHS.iter().map(|p| p.to_string()).collect(),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// `RATE_LIMIT_RPM` (default 60), `RATE_LIMIT_ANON_RPM` (default 10) and
+    /// `RATE_LIMIT_PATHS` (comma-separated path prefixes)
+    pub fn from_env() -> Self {
+        let read = |name: &str, default: u32| {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        };
+        let mut limiter = Self::new(read("RATE_LIMIT_RPM", 60), read("RATE_LIMIT_ANON_RPM", 10));
+        if let Ok(paths) = std::env::var("RATE_LIMIT_PATHS") {
+            limiter.paths = paths.split(',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect();
+        }
+        limiter
+    }
+
+    pub fn applies_to(&self, path: &str) -> bool {
+        self.paths.iter().any(|p| path.starts_with(p.as_str()))
+    }
+
+    /// Takes a token from `key`'s bucket, or returns how long until one is available
+    pub fn check(&self, key: &str, authenticated: bool) -> Result<(), Duration> {
+        let limit = if authenticated { self.per_minute } else { self.anonymous_per_minute };
+        if limit == 0 {
+            return Ok(());
+        }
+        let capacity = limit as f64;
+        let refill_per_sec = capacity / 60.0;
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= MAX_TRACKED_BUCKETS {
+            buckets.retain(|_, b| now.duration_since(b.updated).as_secs_f64() * refill_per_sec + b.tokens < capacity);
+        }
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket { tokens: capacity, updated: now });
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * refill_per_sec).min(capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / refill_per_sec))
+        }
+    }
+}
+
+/// Middleware applying `RateLimiter` (registered as app data) to limited paths.
+/// Callers are keyed by `X-User-ID` when RBAC knows the user; everyone else shares
+/// `ANONYMOUS_BUCKET`, so made-up ids cannot be used to dodge the limit.
+pub async fn rate_limit<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, actix_web::Error> {
+    let limiter = match req.app_data::<web::Data<RateLimiter>>() {
+        Some(l) if l.applies_to(req.path()) => l.clone(),
+        _ => return next.call(req).await.map(ServiceResponse::map_into_left_body),
+    };
+
+    let user_id = req.headers().get("X-User-ID")
+        .and_then(|h| h.to_str().ok())
+        .map(|u| u.trim().to_string())
+        .filter(|u| !u.is_empty());
+    let mut known_user = None;
+    if let (Some(user), Some(rbac)) = (user_id, req.app_data::<web::Data<RBAC>>()) {
+        if rbac.get_permission(&user).await.is_ok() {
+            known_user = Some(user);
+        }
+    }
+
+    let (key, authenticated) = match known_user {
+        Some(ref user) => (user.as_str(), true),
+        None => (ANONYMOUS_BUCKET, false),
+    };
+    if let Err(retry_after) = limiter.check(key, authenticated) {
+        let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+        let response = HttpResponse::TooManyRequests()
+            .insert_header(("Retry-After", secs.to_string()))
+            .json(serde_json::json!({
+                "error": "rate_limited",
+                "message": format!("Too many requests; retry in {} seconds", secs),
+            }));
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+
+    next.call(req).await.map(ServiceResponse::map_into_left_body)
+}
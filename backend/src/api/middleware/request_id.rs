@@ -0,0 +1,58 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::HttpMessage;
+use tracing::Instrument;
+
+/// Header carrying the request id in both directions
+pub const REQUEST_ID_HEADER: &str = "X-Request-ID";
+
+/// Longest incoming id that is honored; anything longer gets a fresh id
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Id of the request being handled, stored in the request extensions
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestId(pub String);
+
+/// The caller's id if it is short and printable (letters, digits, `-_.:`),
+/// otherwise a new UUID
+pub fn resolve_request_id(incoming: Option<&str>) -> String {
+    incoming
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .filter(|id| id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':')))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+/// Id of the request the current task is serving, if any. Work that outlives the
+/// request, such as queued ingestion, has none.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Middleware giving every request an id: the incoming `X-Request-ID` when valid,
+/// otherwise a new one. The id is available to handlers through `current_request_id`
+/// and the `RequestId` extension, recorded on the request's tracing span, added to
+/// error bodies, and echoed in the `X-Request-ID` response header.
+pub async fn request_id<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<B>, actix_web::Error> {
+    let incoming = req.headers().get(REQUEST_ID_HEADER).and_then(|h| h.to_str().ok());
+    let id = resolve_request_id(incoming);
+    req.extensions_mut().insert(RequestId(id.clone()));
+
+    let span = tracing::info_span!("request", request_id = %id, method = %req.method(), path = %req.path());
+    let mut response = REQUEST_ID.scope(id.clone(), next.call(req).instrument(span)).await?;
+
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(HeaderName::from_static("x-request-id"), value);
+    }
+    Ok(response)
+}
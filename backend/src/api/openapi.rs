@@ -0,0 +1,89 @@
+//! OpenAPI description of the HTTP API
+//!
+//! Served as JSON at `/api/openapi.json`, with Swagger UI at `/api/docs/`. Covers the
+//! ingest, search, ask, graph context and agent task endpoints.
+
+use crate::api::handlers::{agents, knowledge};
+use crate::core::agent_orchestrator::{AgentProfile, AgentType, AuditLogEntry, ResearchLimits, ResponseFormat};
+use crate::core::citations::Citation;
+use crate::core::graph_manager::{ContextGraph, EdgeDirection, Entity, Relationship};
+use crate::core::llm::usage::TokenUsage;
+use crate::core::search_engine::{SearchHit, SearchPage};
+use crate::error::ErrorBody;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+/// Where the spec is served
+pub const OPENAPI_PATH: &str = "/api/openapi.json";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "BrainVault API",
+        description = "Enterprise knowledge base: hybrid search, a knowledge graph and an agent swarm, filtered by RBAC."
+    ),
+    paths(
+        knowledge::health_check,
+        knowledge::readiness_check,
+        knowledge::ingest_knowledge,
+        knowledge::hybrid_search,
+        knowledge::ask_question,
+        knowledge::get_context,
+        agents::submit_task,
+        agents::get_task_status,
+        agents::get_task_result,
+        agents::get_task_audit,
+        agents::cancel_task,
+        agents::register_agent,
+        agents::list_agents,
+    ),
+    components(schemas(
+        knowledge::IngestRequest,
+        knowledge::IngestResponse,
+        knowledge::IngestStage,
+        knowledge::IngestStages,
+        knowledge::ValidationProblem,
+        knowledge::SearchQuery,
+        knowledge::AskRequest,
+        knowledge::AskResponse,
+        agents::TaskRequest,
+        agents::TaskSubmitted,
+        agents::TaskResponse,
+        agents::TaskAudit,
+        AgentProfile,
+        AgentType,
+        AuditLogEntry,
+        ResearchLimits,
+        ResponseFormat,
+        TokenUsage,
+        Entity,
+        Relationship,
+        ContextGraph,
+        EdgeDirection,
+        SearchHit,
+        SearchPage,
+        Citation,
+        ErrorBody,
+    )),
+    modifiers(&UserIdHeader),
+    security(("user_id" = [])),
+    tags(
+        (name = "knowledge", description = "Ingestion and question answering"),
+        (name = "search", description = "Hybrid vector and lexical search"),
+        (name = "graph", description = "Knowledge graph traversal"),
+        (name = "agents", description = "Agent tasks and registry"),
+        (name = "system", description = "Health"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Callers identify themselves with `X-User-ID`; results are filtered by that user's
+/// permissions and requests without it run as `anonymous`
+struct UserIdHeader;
+
+impl Modify for UserIdHeader {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme("user_id", SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-User-ID"))));
+    }
+}
@@ -0,0 +1,288 @@
+//! Application settings loaded once at startup.
+//!
+//! Values come from the same environment variables the individual modules have always
+//! read, so existing deployments keep working. `AppConfig::from_env` validates everything
+//! up front and reports every invalid setting at once.
+
+use crate::core::llm::nafs_provider::get_default_model;
+use std::env;
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    /// Address the HTTP server binds to (`BIND_ADDRESS`, default `0.0.0.0:8080`)
+    pub bind_address: SocketAddr,
+    /// Origins allowed by CORS (`CORS_ALLOWED_ORIGINS`, comma-separated; `*` allows any)
+    pub cors_allowed_origins: Vec<String>,
+    /// Directory for persisted caches, graph state and audit logs (`DATA_PATH`)
+    pub data_path: String,
+    pub vector_db_url: String,
+    pub graph_db_url: String,
+    /// Provider name from `LLM_PROVIDER`
+    pub llm_provider: String,
+    /// Model used for generation (`LLM_MODEL`, else the provider default)
+    pub llm_model: String,
+    /// Ceiling on any JSON request body or import line in bytes (`MAX_BODY_BYTES`, default
+    /// 50MB); the enforced limit is usually the smaller `body_limit`
+    pub max_body_bytes: usize,
+    /// Largest document `content` accepted by ingest and import, in bytes
+    /// (`MAX_DOCUMENT_BYTES`, default 10MB); at most `max_body_bytes`
+    pub max_document_bytes: usize,
+    /// Time budget for one `/api/ask` request in milliseconds (`ASK_TIMEOUT_MS`, default
+    /// 60s); 0 means no deadline
+    pub ask_timeout_ms: u64,
+    /// Load the vector index in the background at startup (`VECTOR_WARMUP`, default false)
+    pub vector_warmup: bool,
+    /// Also embed documents that have no vector during warm-up (`VECTOR_WARMUP_REEMBED`,
+    /// default false)
+    pub vector_warmup_reembed: bool,
+    /// Documents embedded in parallel during import (`INGEST_CONCURRENCY`, default 4)
+    pub ingest_concurrency: usize,
+}
+
+const DEFAULT_MAX_BODY_BYTES: usize = 52_428_800;
+const DEFAULT_MAX_DOCUMENT_BYTES: usize = 10_485_760;
+const DEFAULT_ASK_TIMEOUT_MS: u64 = 60_000;
+const DEFAULT_INGEST_CONCURRENCY: usize = 4;
+/// Room in a request body for everything besides the document: the other fields,
+/// entities and relationships
+const ENVELOPE_OVERHEAD_BYTES: usize = 1_048_576;
+
+/// Names accepted by `ProviderType::parse`
+const SUPPORTED_PROVIDERS: &[&str] = &["openai", "azure", "anthropic", "together", "groq", "fireworks", "ollama", "custom"];
+
+impl AppConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let mut problems = Vec::new();
+
+        let bind = env::var("BIND_ADDRESS").unwrap_or_else(|_| "0.0.0.0:8080".to_string());
+        let bind_address = bind.parse::<SocketAddr>().unwrap_or_else(|_| {
+            problems.push(format!("BIND_ADDRESS '{}' is not a valid host:port address", bind));
+            SocketAddr::from(([0, 0, 0, 0], 8080))
+        });
+
+        let cors_allowed_origins: Vec<String> = env::var("CORS_ALLOWED_ORIGINS")
+            .unwrap_or_else(|_| "http://localhost:3000".to_string())
+            .split(',')
+            .map(|o| o.trim().trim_end_matches('/').to_string())
+            .filter(|o| !o.is_empty())
+            .collect();
+        for origin in &cors_allowed_origins {
+            if origin != "*" && !is_http_url(origin) {
+                problems.push(format!("CORS_ALLOWED_ORIGINS entry '{}' must be '*' or an http(s) origin", origin));
+            }
+        }
+
+        let data_path = env::var("DATA_PATH").unwrap_or_else(|_| "/data".to_string());
+        if let Err(e) = std::fs::create_dir_all(&data_path) {
+            problems.push(format!("DATA_PATH '{}' is not a usable directory: {}", data_path, e));
+        }
+
+        let vector_db_url = env::var("VECTOR_DB_URL").unwrap_or_else(|_| "http://barq-vector:8080".to_string());
+        let graph_db_url = env::var("GRAPH_DB_URL").unwrap_or_else(|_| "http://barq-graph:8080".to_string());
+        for (name, url) in [("VECTOR_DB_URL", &vector_db_url), ("GRAPH_DB_URL", &graph_db_url)] {
+            if !is_http_url(url) {
+                problems.push(format!("{} '{}' must be an http(s) URL", name, url));
+            }
+        }
+
+        let llm_provider = LlmConfig::global().provider.clone();
+        if !SUPPORTED_PROVIDERS.contains(&llm_provider.trim().to_lowercase().as_str()) {
+            problems.push(format!("LLM_PROVIDER '{}' must be one of: {}", llm_provider, SUPPORTED_PROVIDERS.join(", ")));
+        }
+        let llm_model = get_default_model();
+
+        let max_body_bytes = match env::var("MAX_BODY_BYTES") {
+            Ok(v) => v.parse::<usize>().unwrap_or_else(|_| {
+                problems.push(format!("MAX_BODY_BYTES '{}' must be a number of bytes", v));
+                DEFAULT_MAX_BODY_BYTES
+            }),
+            Err(_) => DEFAULT_MAX_BODY_BYTES,
+        };
+
+        let max_document_bytes = match env::var("MAX_DOCUMENT_BYTES") {
+            Ok(v) => match v.parse::<usize>() {
+                Ok(0) | Err(_) => {
+                    problems.push(format!("MAX_DOCUMENT_BYTES '{}' must be a positive number of bytes", v));
+                    DEFAULT_MAX_DOCUMENT_BYTES
+                }
+                Ok(bytes) => bytes,
+            },
+            Err(_) => DEFAULT_MAX_DOCUMENT_BYTES.min(max_body_bytes),
+        };
+        if max_document_bytes > max_body_bytes {
+            problems.push(format!(
+                "MAX_DOCUMENT_BYTES ({}) exceeds MAX_BODY_BYTES ({}); bodies that large are rejected before the document is read",
+                max_document_bytes, max_body_bytes
+            ));
+        }
+
+        let ask_timeout_ms = match env::var("ASK_TIMEOUT_MS") {
+            Ok(v) => v.parse::<u64>().unwrap_or_else(|_| {
+                problems.push(format!("ASK_TIMEOUT_MS '{}' must be a number of milliseconds", v));
+                DEFAULT_ASK_TIMEOUT_MS
+            }),
+            Err(_) => DEFAULT_ASK_TIMEOUT_MS,
+        };
+
+        let vector_warmup = parse_flag("VECTOR_WARMUP", &mut problems);
+        let vector_warmup_reembed = parse_flag("VECTOR_WARMUP_REEMBED", &mut problems);
+
+        let ingest_concurrency = match env::var("INGEST_CONCURRENCY") {
+            Ok(v) => match v.parse::<usize>() {
+                Ok(0) | Err(_) => {
+                    problems.push(format!("INGEST_CONCURRENCY '{}' must be a positive number", v));
+                    DEFAULT_INGEST_CONCURRENCY
+                }
+                Ok(n) => n,
+            },
+            Err(_) => DEFAULT_INGEST_CONCURRENCY,
+        };
+
+        if !problems.is_empty() {
+            return Err(format!("Invalid configuration:\n  - {}", problems.join("\n  - ")));
+        }
+
+        Ok(Self {
+            bind_address,
+            cors_allowed_origins,
+            data_path,
+            vector_db_url,
+            graph_db_url,
+            llm_provider,
+            llm_model,
+            max_body_bytes,
+            max_document_bytes,
+            ask_timeout_ms,
+            vector_warmup,
+            vector_warmup_reembed,
+            ingest_concurrency,
+        })
+    }
+
+    /// Largest request body or import line worth reading: a `max_document_bytes` document,
+    /// a quarter again for JSON escaping, and the envelope around it, within `max_body_bytes`
+    pub fn body_limit(&self) -> usize {
+        self.max_document_bytes
+            .saturating_add(self.max_document_bytes / 4)
+            .saturating_add(ENVELOPE_OVERHEAD_BYTES)
+            .min(self.max_body_bytes)
+    }
+
+    /// JSON extractor limited to `body_limit`. Larger bodies are refused from their
+    /// `Content-Length` before being read, with a 413 pointing at chunking.
+    pub fn json_config(&self) -> actix_web::web::JsonConfig {
+        let limit = self.body_limit();
+        actix_web::web::JsonConfig::default()
+            .limit(limit)
+            .error_handler(move |err, _req| match err {
+                actix_web::error::JsonPayloadError::Overflow { .. }
+                | actix_web::error::JsonPayloadError::OverflowKnownLength { .. } => {
+                    crate::error::BrainVaultError::PayloadTooLarge(format!(
+                        "Request body exceeds {} bytes; split large documents into chunks and ingest them separately",
+                        limit
+                    )).into()
+                }
+                // Malformed JSON and the like keep actix's own response
+                err => err.into(),
+            })
+    }
+
+    /// CORS policy for the configured origins
+    pub fn cors(&self) -> actix_cors::Cors {
+        if self.cors_allowed_origins.iter().any(|o| o == "*") {
+            return actix_cors::Cors::permissive();
+        }
+        self.cors_allowed_origins.iter()
+            .fold(actix_cors::Cors::default(), |cors, origin| cors.allowed_origin(origin))
+            .allow_any_method()
+            .allow_any_header()
+            // Readable by browser clients: request ids for support, totals for pagination
+            .expose_headers(["X-Request-ID", "X-Total-Count"])
+            .max_age(3600)
+    }
+}
+
+/// LLM provider settings: `LLM_*` plus each provider's own key and model variables.
+/// Read from the environment once, on first use.
+#[derive(Debug, Clone)]
+pub struct LlmConfig {
+    /// `LLM_PROVIDER`, default `openai`
+    pub provider: String,
+    /// `LLM_MODEL`, the generation model whichever provider is used
+    pub model: Option<String>,
+    /// `LLM_SYSTEM_PROMPT`, when set and non-blank
+    pub system_prompt: Option<String>,
+    /// `LLM_LEGACY_CLIENTS=true`
+    pub legacy_clients: bool,
+    /// `LLM_API_KEY`: for Ollama and custom endpoints, and Together, Groq or Fireworks
+    /// without their own key
+    pub api_key: Option<String>,
+    /// `LLM_BASE_URL` of an Ollama or custom OpenAI-compatible endpoint
+    pub base_url: Option<String>,
+    pub openai_api_key: Option<String>,
+    pub openai_model: Option<String>,
+    pub azure_api_key: Option<String>,
+    pub azure_endpoint: Option<String>,
+    /// `AZURE_OPENAI_DEPLOYMENT`, which names the model on Azure
+    pub azure_deployment: Option<String>,
+    pub anthropic_api_key: Option<String>,
+    pub anthropic_model: Option<String>,
+    pub together_api_key: Option<String>,
+    pub groq_api_key: Option<String>,
+    pub fireworks_api_key: Option<String>,
+}
+
+impl LlmConfig {
+    pub fn from_env() -> Self {
+        let var = |name: &str| env::var(name).ok();
+        Self {
+            provider: var("LLM_PROVIDER").unwrap_or_else(|| "openai".to_string()),
+            model: var("LLM_MODEL"),
+            system_prompt: var("LLM_SYSTEM_PROMPT")
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty()),
+            legacy_clients: var("LLM_LEGACY_CLIENTS").map_or(false, |v| v == "true"),
+            api_key: var("LLM_API_KEY"),
+            base_url: var("LLM_BASE_URL"),
+            openai_api_key: var("OPENAI_API_KEY"),
+            openai_model: var("OPENAI_MODEL"),
+            azure_api_key: var("AZURE_OPENAI_API_KEY"),
+            azure_endpoint: var("AZURE_OPENAI_ENDPOINT"),
+            azure_deployment: var("AZURE_OPENAI_DEPLOYMENT"),
+            anthropic_api_key: var("ANTHROPIC_API_KEY"),
+            anthropic_model: var("ANTHROPIC_MODEL"),
+            together_api_key: var("TOGETHER_API_KEY"),
+            groq_api_key: var("GROQ_API_KEY"),
+            fireworks_api_key: var("FIREWORKS_API_KEY"),
+        }
+    }
+
+    /// The process-wide settings
+    pub fn global() -> &'static Self {
+        static CONFIG: OnceLock<LlmConfig> = OnceLock::new();
+        CONFIG.get_or_init(Self::from_env)
+    }
+}
+
+/// Boolean setting `name`, false when unset. Accepts true/false, 1/0, yes/no and on/off
+/// in any case; anything else is reported as a problem.
+fn parse_flag(name: &str, problems: &mut Vec<String>) -> bool {
+    let value = match env::var(name) {
+        Ok(v) => v,
+        Err(_) => return false,
+    };
+    match value.trim().to_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => true,
+        "false" | "0" | "no" | "off" | "" => false,
+        _ => {
+            problems.push(format!("{} '{}' must be true or false", name, value));
+            false
+        }
+    }
+}
+
+fn is_http_url(value: &str) -> bool {
+    value.starts_with("http://") || value.starts_with("https://")
+}
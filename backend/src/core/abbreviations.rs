@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+/// Deterministic acronym/abbreviation expansion applied to lexical queries.
+///
+/// Loaded from a JSON object mapping each abbreviation to its full forms, e.g.
+/// `{"k8s": ["kubernetes"], "ml": ["machine learning"]}`.
+#[derive(Debug, Clone)]
+pub struct AbbreviationMap {
+    map: HashMap<String, Vec<String>>,
+    /// Credit given to a query term matched only through its expansion (original match = 1.0)
+    pub expansion_weight: f32,
+}
+
+impl Default for AbbreviationMap {
+    fn default() -> Self {
+        Self { map: HashMap::new(), expansion_weight: 0.6 }
+    }
+}
+
+impl AbbreviationMap {
+    pub fn new(map: HashMap<String, Vec<String>>) -> Self {
+        let map = map.into_iter()
+            .map(|(k, v)| (k.to_lowercase(), v.into_iter().map(|f| f.to_lowercase()).collect()))
+            .collect();
+        Self { map, ..Self::default() }
+    }
+
+    /// Reads `ABBREVIATIONS_PATH` (default `{DATA_PATH}/abbreviations.json`); empty when absent
+    pub fn from_env() -> Self {
+        let data_path = std::env::var("DATA_PATH").unwrap_or_else(|_| "/data".to_string());
+        let path = std::env::var("ABBREVIATIONS_PATH")
+            .unwrap_or_else(|_| format!("{}/abbreviations.json", data_path));
+
+        let mut abbreviations = match std::fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str::<HashMap<String, Vec<String>>>(&content) {
+                Ok(map) => {
+                    println!("INFO: Loaded {} abbreviations from {}", map.len(), path);
+                    Self::new(map)
+                }
+                Err(e) => {
+                    println!("WARN: Invalid abbreviations file {}: {}", path, e);
+                    Self::default()
+                }
+            },
+            Err(_) => Self::default(),
+        };
+
+        if let Some(weight) = std::env::var("ABBREVIATION_WEIGHT").ok().and_then(|v| v.parse().ok()) {
+            abbreviations.expansion_weight = weight;
+        }
+        abbreviations
+    }
+
+    /// Full forms for a (lowercased) query term, if it is a known abbreviation
+    pub fn expansions(&self, term: &str) -> &[String] {
+        self.map
+            .get(term.trim_matches(|c: char| !c.is_alphanumeric()))
+            .map(|v| v.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
@@ -1,10 +1,17 @@
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::{Mutex, Notify};
+use utoipa::ToSchema;
 use uuid::Uuid;
+use crate::core::llm::usage::TokenUsage;
+use crate::core::llm::nafs_provider::GenerationParams;
+use crate::core::output_filter::OutputFilter;
+use crate::error::{BrainVaultError, Result};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
 pub enum AgentType {
     Researcher,
     Analyst,
@@ -14,21 +21,154 @@ pub enum AgentType {
     Ingestor,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl AgentType {
+    /// Persona used when an agent's profile doesn't set its own system prompt
+    pub fn default_system_prompt(&self) -> &'static str {
+        match self {
+            AgentType::Researcher => "You are a research assistant for an enterprise knowledge base. Ground answers in the provided sources and say when they are insufficient.",
+            AgentType::Analyst => "You are an analyst. Identify patterns, trade-offs and implications in the provided sources and the conversation so far.",
+            AgentType::Coder => "You are a senior software engineer. Answer with concise, working code and brief explanations.",
+            AgentType::Reviewer => "You are a meticulous reviewer. Point out errors, risks and gaps in what you are shown.",
+            AgentType::Manager => "You are a project manager. Break objectives into concrete steps and track progress across the conversation.",
+            AgentType::Ingestor => "You are a knowledge engineer. Describe the entities and relationships in the text you are given.",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct AgentProfile {
     pub id: String,
     pub name: String,
     pub agent_type: AgentType,
     pub capabilities: Vec<String>,
+    /// Persona for this agent's LLM calls, e.g. "You are a legal analyst..."
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// Completion length cap for this agent's LLM calls (1-32768); 2000 when unset
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
+    /// Sampling temperature (0-2); lower is more deterministic. 0.7 when unset.
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// How much retrieved material this agent reads per research query
+    #[serde(default)]
+    pub research: ResearchLimits,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Most chunks a single research query may retrieve
+pub const MAX_RESEARCH_CHUNKS: usize = 50;
+
+/// Retrieval budget for a Researcher. Unset fields fall back from the task to the agent's
+/// profile, then to `AGENT_RESEARCH_CHUNKS` / `AGENT_RESEARCH_CHUNK_CHARS`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ResearchLimits {
+    /// Chunks retrieved per search query (1-50)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunks: Option<usize>,
+    /// Characters kept from each chunk; 0 keeps whole chunks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_chars: Option<usize>,
+}
+
+impl ResearchLimits {
+    /// These limits, with unset fields taken from `fallback`
+    pub fn or(self, fallback: ResearchLimits) -> Self {
+        Self {
+            chunks: self.chunks.or(fallback.chunks),
+            chunk_chars: self.chunk_chars.or(fallback.chunk_chars),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self.chunks {
+            Some(n) if n == 0 || n > MAX_RESEARCH_CHUNKS => Err(BrainVaultError::BadRequest(format!(
+                "research.chunks must be between 1 and {}", MAX_RESEARCH_CHUNKS
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl AgentProfile {
+    /// The profile's own system prompt, else its type's default
+    pub fn effective_system_prompt(&self) -> &str {
+        self.system_prompt.as_deref()
+            .filter(|p| !p.trim().is_empty())
+            .unwrap_or_else(|| self.agent_type.default_system_prompt())
+    }
+
+    /// The profile's generation settings, taking unset ones from `defaults`
+    pub fn generation_params(&self, defaults: GenerationParams) -> GenerationParams {
+        GenerationParams {
+            max_tokens: self.max_tokens.unwrap_or(defaults.max_tokens),
+            temperature: self.temperature.unwrap_or(defaults.temperature),
+        }
+    }
+
+    /// Rejects out-of-range `max_tokens`, `temperature` or research chunk count
+    pub fn validate(&self) -> Result<()> {
+        self.generation_params(GenerationParams::default())
+            .validate()
+            .map_err(BrainVaultError::BadRequest)?;
+        self.research.validate()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum TaskStatus {
     Pending,
     InProgress, // Assigned but not started execution logic
     Executing,  // Currently running in thread
     Completed,
     Failed,
+    /// Stopped before finishing; any generation in progress was abandoned
+    Cancelled,
+}
+
+impl TaskStatus {
+    /// Completed, failed or cancelled: the task will not run again
+    pub fn is_finished(&self) -> bool {
+        matches!(self, TaskStatus::Completed | TaskStatus::Failed | TaskStatus::Cancelled)
+    }
+}
+
+/// Shape the submitter wants the final result in
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default, ToSchema)]
+pub enum ResponseFormat {
+    #[default]
+    Text,
+    Json,
+    Markdown,
+}
+
+impl ResponseFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ResponseFormat::Text => "text/plain; charset=utf-8",
+            ResponseFormat::Json => "application/json",
+            ResponseFormat::Markdown => "text/markdown; charset=utf-8",
+        }
+    }
+
+    /// Sentence appended to an agent's final prompt asking for this format
+    pub fn prompt_instruction(&self) -> &'static str {
+        match self {
+            ResponseFormat::Text => "",
+            ResponseFormat::Json => "\n\nReturn the final answer as a single valid JSON document only, with no commentary or code fences.",
+            ResponseFormat::Markdown => "\n\nFormat the final answer as well-structured Markdown, with headings and lists where useful.",
+        }
+    }
+}
+
+/// Optional settings for a submitted task
+#[derive(Debug, Clone, Default)]
+pub struct TaskOptions {
+    pub session_id: Option<String>,
+    pub response_format: ResponseFormat,
+    /// Id of the HTTP request that submitted the task, carried into its audit trail
+    pub request_id: Option<String>,
+    /// Overrides the assigned agent's research limits for this task
+    pub research: ResearchLimits,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,16 +178,63 @@ pub struct Task {
     pub status: TaskStatus,
     pub assigned_agent_id: Option<String>,
     pub preferred_agent_type: Option<AgentType>,
-    pub result: Option<String>,
+    /// Structured for `Json` tasks, a string otherwise. Results stored as plain strings
+    /// deserialize unchanged; `result_text` gives the text form of either.
+    pub result: Option<serde_json::Value>,
     pub audit_log: Vec<AuditLogEntry>,
+    #[serde(default)]
+    pub submitted_by: Option<String>,
+    #[serde(default)]
+    pub usage: TokenUsage,
+    /// Unfiltered LLM output, kept only when post-processing changed it
+    #[serde(default)]
+    pub raw_result: Option<String>,
+    /// Conversation this task continues; its earlier turns are included in the prompt
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub response_format: ResponseFormat,
+    /// Unix time in milliseconds when the task was submitted
+    #[serde(default)]
+    pub submitted_at: u64,
+    /// Request that submitted the task (or its parent task), for correlating with server logs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Research limits set on submission, ahead of the agent's own
+    #[serde(default)]
+    pub research: ResearchLimits,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Criteria for `list_tasks`; unset fields match every task
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TaskFilter {
+    pub status: Option<TaskStatus>,
+    #[serde(rename = "agent")]
+    pub agent_id: Option<String>,
+    pub submitted_by: Option<String>,
+    /// Oldest first when set, newest first otherwise
+    #[serde(default)]
+    pub oldest_first: bool,
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+/// One page of `list_tasks` results with the number of tasks matching overall
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskPage {
+    pub tasks: Vec<Task>,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct AuditLogEntry {
     pub timestamp: u64, // simplified ts
     pub agent_id: Option<String>,
     pub action: String,
     pub details: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
 }
 
 impl Task {
@@ -57,19 +244,325 @@ impl Task {
             agent_id,
             action,
             details,
+            request_id: self.request_id.clone(),
         });
     }
+
+    pub fn result_text(&self) -> Option<String> {
+        self.result.as_ref().map(result_text)
+    }
+}
+
+/// Text form of a task result: strings as-is, structured values as compact JSON
+pub fn result_text(result: &serde_json::Value) -> String {
+    match result {
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Characters of each earlier response repeated in follow-up prompts
+const SESSION_TURN_CHARS: usize = 1500;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionTurn {
+    pub request: String,
+    pub response: String,
+}
+
+/// History of one multi-turn session. Recent turns are kept verbatim; older ones are
+/// folded into `summary` once the session grows past the configured cap.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionMemory {
+    pub summary: String,
+    pub turns: Vec<SessionTurn>,
+    /// Unix time in milliseconds of the last turn; idle sessions expire after the session TTL
+    #[serde(default)]
+    pub last_active: u64,
+}
+
+/// Sessions belong to the user who started them: `(user_id, session_id)`
+type SessionKey = (String, String);
+
+/// Sessions kept when `AGENT_MAX_SESSIONS` is unset; the least recently used go first
+const DEFAULT_MAX_SESSIONS: usize = 1000;
+
+/// Idle time after which a session is forgotten when `AGENT_SESSION_TTL_SECS` is unset
+const DEFAULT_SESSION_TTL_SECS: u64 = 24 * 3600;
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+/// Owner of the sessions a task's turns are remembered under; tasks submitted without a
+/// user share the anonymous caller's sessions, as they do over HTTP
+fn session_user(task: &Task) -> String {
+    task.submitted_by.clone().unwrap_or_else(|| "anonymous".to_string())
+}
+
+impl SessionMemory {
+    /// Prompt section describing the conversation so far; empty for a new session
+    pub fn to_prompt(&self) -> String {
+        if self.summary.is_empty() && self.turns.is_empty() {
+            return String::new();
+        }
+        let mut section = String::from("\n\nConversation so far:\n");
+        if !self.summary.is_empty() {
+            section.push_str(&format!("Summary of earlier turns: {}\n", self.summary));
+        }
+        for turn in &self.turns {
+            let response: String = turn.response.chars().take(SESSION_TURN_CHARS).collect();
+            section.push_str(&format!("User: {}\nAgent: {}\n", turn.request, response));
+        }
+        section
+    }
+}
+
+use crate::core::search_engine::{truncate_content, HybridSearchEngine, SearchHit};
+use crate::core::llm::tokenizer::Tokenizer;
+use crate::core::audit_manager::{AuditManager, EventKind, Severity};
+use crate::core::result_processor::{self, ResultProcessor};
+use crate::core::graph_manager::{Entity, KnowledgeGraphManager, Relationship};
+use crate::core::rbac::RBAC;
+use crate::core::llm::tools::{tool_result_message, ToolCall, ToolDefinition, ToolTurn, TOOL_TEMPERATURE};
+
+/// Characters of a tool's output passed back to the model
+const TOOL_OUTPUT_CHARS: usize = 6000;
+
+/// Characters of a chunk kept on its graph node as `content_preview`
+const CHUNK_PREVIEW_CHARS: usize = 200;
+
+/// Parses `ENTITY|<id>|<Label>|<name>` and `REL|<from>|<to>|<TYPE>[|<weight>]` lines
+/// from an extraction response. Malformed lines are skipped; weights default to 1.0.
+pub fn parse_extraction(response: &str) -> (Vec<Entity>, Vec<Relationship>) {
+    let mut entities = Vec::new();
+    let mut relationships = Vec::new();
+    for line in response.lines() {
+        let parts: Vec<&str> = line.split('|').map(|p| p.trim()).collect();
+        if parts.len() < 4 || parts[1].is_empty() || parts[2].is_empty() {
+            continue;
+        }
+        match parts[0] {
+            "ENTITY" => entities.push(Entity {
+                id: parts[1].to_string(),
+                label: parts[2].to_string(),
+                properties: HashMap::from([("name".to_string(), parts[3].to_string())]),
+            }),
+            "REL" => relationships.push(Relationship {
+                from_id: parts[1].to_string(),
+                to_id: parts[2].to_string(),
+                rel_type: parts[3].to_string(),
+                weight: parts.get(4).and_then(|w| w.parse::<f32>().ok()).filter(|w| w.is_finite()).unwrap_or(1.0),
+                properties: HashMap::new(),
+                direction: None,
+            }),
+            _ => {}
+        }
+    }
+    (entities, relationships)
+}
+
+/// Parses the JSON document in a model reply, tolerating surrounding prose and
+/// Markdown code fences
+pub fn parse_json_reply(reply: &str) -> std::result::Result<serde_json::Value, String> {
+    let trimmed = reply.trim();
+    let unfenced = trimmed.strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|rest| rest.trim_end().strip_suffix("```"))
+        .unwrap_or(trimmed)
+        .trim();
+    let first_error = match serde_json::from_str::<serde_json::Value>(unfenced) {
+        Ok(value) => return Ok(value),
+        Err(e) => e.to_string(),
+    };
+
+    // Fall back to the outermost object or array embedded in prose
+    let start = unfenced.find(['{', '[']);
+    let end = unfenced.rfind(['}', ']']);
+    if let (Some(start), Some(end)) = (start, end) {
+        if start < end {
+            if let Ok(value) = serde_json::from_str(&unfenced[start..=end]) {
+                return Ok(value);
+            }
+        }
+    }
+    Err(first_error)
+}
+
+/// Leading politeness and imperative phrasing that say what to do rather than what to look for:
+/// "Please look up", "Can you find out", "Search for", "Tell me about", "Find everything on"...
+fn imperative_prefix() -> &'static Regex {
+    static PREFIX: OnceLock<Regex> = OnceLock::new();
+    PREFIX.get_or_init(|| {
+        Regex::new(concat!(
+            r"(?i)^\s*(?:(?:please|kindly|can you|could you|would you|will you|i need you to|i want you to|help me)[\s,]+)*",
+            r"(?:find(?:\s+out)?|search(?:\s+(?:for|the\s+knowledge\s+base\s+for))?|look\s+(?:up|for|into)|",
+            r"research|investigate|explore|dig\s+into|tell\s+me(?:\s+(?:about|more\s+about))?|show\s+me|",
+            r"what\s+do\s+we\s+know\s+about|get|fetch|retrieve|locate|gather)\b[\s:,\-]*",
+            r"(?:(?:all\s+)?(?:information|info|details|everything|anything|documents?|docs|sources)\s+(?:about|on|regarding|for|related\s+to)\s+)?",
+        ))
+        .expect("valid imperative prefix pattern")
+    })
+}
+
+/// The subject of a request phrased as an instruction, for use as a search query:
+/// "Please look up the Q3 revenue figures." becomes "the Q3 revenue figures". Only prefixes
+/// anchored at the start are removed, so "Where did we find the leak" is left alone. Text that
+/// is nothing but a prefix is returned trimmed rather than emptied.
+pub fn extract_search_query(text: &str) -> String {
+    let text = text.trim();
+    // One pass only: in "Find research papers on X" the second verb is part of the subject
+    let query = match imperative_prefix().find(text) {
+        Some(m) => &text[m.end()..],
+        None => text,
+    };
+    let query = query.trim_end_matches(|c: char| c.is_whitespace() || matches!(c, '?' | '.' | '!'));
+    let query = query.strip_suffix(" please").or_else(|| query.strip_suffix(", please")).unwrap_or(query);
+    if query.trim().is_empty() {
+        text.to_string()
+    } else {
+        query.trim().to_string()
+    }
+}
+
+/// An agent's result in the task's format: a parsed JSON value for `Json`, a string
+/// otherwise. A `Json` result that doesn't parse is re-requested once through `retry`,
+/// which is given the parse error; `Err` if that reply doesn't parse either.
+pub async fn format_result<F, Fut>(format: ResponseFormat, result: String, retry: F) -> std::result::Result<serde_json::Value, String>
+where
+    F: FnOnce(String) -> Fut,
+    Fut: std::future::Future<Output = std::result::Result<String, String>>,
+{
+    match format {
+        ResponseFormat::Json => {
+            let error = match parse_json_reply(&result) {
+                Ok(value) => return Ok(value),
+                Err(e) => e,
+            };
+            let reply = retry(error).await?;
+            parse_json_reply(&reply)
+                .map_err(|e| format!("Agent did not return valid JSON: {}", e))
+        }
+        ResponseFormat::Text | ResponseFormat::Markdown => Ok(result.into()),
+    }
+}
+
+/// Formats the hits scoring at or above `min_relevance` as a source block for agent prompts.
+/// Returns an empty string when nothing qualifies.
+pub fn build_source_context(hits: &[SearchHit], min_relevance: f32) -> String {
+    hits.iter()
+        .filter(|h| h.score >= min_relevance)
+        .map(|h| format!("[Source {}]: {}", h.doc_id, h.content.as_deref().unwrap_or("")))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Like `build_source_context`, but cuts each source to `chunk_chars` characters (0 keeps it
+/// whole) and stops once the block would exceed `max_tokens`; the source that crosses the
+/// budget is truncated to fit. Hits are taken in rank order, so the best sources survive.
+pub fn build_budgeted_context(
+    hits: &[SearchHit],
+    min_relevance: f32,
+    chunk_chars: usize,
+    max_tokens: usize,
+    tokenizer: &dyn Tokenizer,
+) -> String {
+    let mut sources = Vec::new();
+    let mut used = 0;
+    for hit in hits.iter().filter(|h| h.score >= min_relevance) {
+        let content = truncate_content(hit.content.as_deref().unwrap_or(""), chunk_chars);
+        let source = format!("[Source {}]: {}", hit.doc_id, content);
+        let tokens = tokenizer.count_tokens(&source);
+        if used + tokens <= max_tokens {
+            used += tokens;
+            sources.push(source);
+            continue;
+        }
+        let remaining = max_tokens.saturating_sub(used);
+        if remaining > 0 {
+            let cut = tokenizer.truncate(&source, remaining);
+            if !cut.is_empty() {
+                sources.push(cut);
+            }
+        }
+        break;
+    }
+    sources.join("\n")
+}
+
+/// An agent and how much work it currently holds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentSummary {
+    #[serde(flatten)]
+    pub profile: AgentProfile,
+    /// Tasks assigned to the agent that haven't finished
+    pub active_tasks: usize,
+    pub completed_tasks: usize,
+    pub failed_tasks: usize,
+    pub cancelled_tasks: usize,
 }
 
-use crate::core::search_engine::HybridSearchEngine;
-use crate::core::graph_manager::KnowledgeGraphManager;
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub total: TokenUsage,
+    pub by_user: HashMap<String, TokenUsage>,
+}
 
 #[derive(Clone)]
 pub struct AgentOrchestrator {
     agents: Arc<Mutex<HashMap<String, AgentProfile>>>,
     tasks: Arc<Mutex<HashMap<String, Task>>>,
+    usage_by_user: Arc<Mutex<HashMap<String, TokenUsage>>>,
     search_engine: Option<Arc<HybridSearchEngine>>,
     graph_manager: Option<Arc<KnowledgeGraphManager>>,
+    min_relevance: f32,
+    /// Chunks per research query when neither the task nor the agent sets them
+    research_chunks: usize,
+    /// Characters kept per research chunk by default; 0 keeps whole chunks
+    research_chunk_chars: usize,
+    /// Token budget for the sources in one research prompt
+    context_tokens: usize,
+    graph_enrichment: Vec<AgentType>,
+    output_filter: OutputFilter,
+    audit: Option<AuditManager>,
+    sessions: Arc<Mutex<HashMap<SessionKey, SessionMemory>>>,
+    session_max_turns: usize,
+    /// Sessions kept in memory at once
+    max_sessions: usize,
+    /// Idle time after which a session expires
+    session_ttl: Duration,
+    tool_agents: Vec<AgentType>,
+    max_tool_rounds: usize,
+    /// Longest the agent loop sleeps between scans for assigned tasks
+    loop_interval: Duration,
+    /// Signalled by `assign_task` so the loop picks up new work without waiting a full interval
+    wakeup: Arc<Notify>,
+    /// Tasks the loop has started and not yet finished
+    in_flight: Arc<std::sync::Mutex<HashSet<String>>>,
+    /// Handles for aborting the runs in `in_flight`
+    running: Arc<std::sync::Mutex<HashMap<String, tokio::task::AbortHandle>>>,
+    /// Applied in order to every result before it is stored
+    result_processors: Vec<Arc<dyn ResultProcessor>>,
+    /// Limits what the search and get_context tools return to the task's submitter
+    rbac: Option<Arc<RBAC>>,
+}
+
+/// Removes a task from the in-flight set when its run ends
+struct InFlightGuard {
+    set: Arc<std::sync::Mutex<HashSet<String>>>,
+    running: Arc<std::sync::Mutex<HashMap<String, tokio::task::AbortHandle>>>,
+    task_id: String,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if let Ok(mut set) = self.set.lock() {
+            set.remove(&self.task_id);
+        }
+        if let Ok(mut running) = self.running.lock() {
+            running.remove(&self.task_id);
+        }
+    }
 }
 
 impl AgentOrchestrator {
@@ -77,20 +570,272 @@ impl AgentOrchestrator {
         search_engine: Option<Arc<HybridSearchEngine>>,
         graph_manager: Option<Arc<KnowledgeGraphManager>>,
     ) -> Self {
+        // Minimum fused search score a document needs to enter an agent's context
+        let min_relevance = std::env::var("AGENT_MIN_RELEVANCE")
+            .ok()
+            .and_then(|v| v.parse::<f32>().ok())
+            .unwrap_or(0.3);
+
+        // Default retrieval for research queries, overridable per agent and per task
+        let research_chunks = std::env::var("AGENT_RESEARCH_CHUNKS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(5)
+            .clamp(1, MAX_RESEARCH_CHUNKS);
+        let research_chunk_chars = std::env::var("AGENT_RESEARCH_CHUNK_CHARS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(2000);
+
+        // Sources per research prompt are trimmed to this many tokens so the prompt fits
+        // the model's context window alongside the instructions and the completion
+        let context_tokens = std::env::var("AGENT_CONTEXT_TOKENS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(6000);
+
+        // Agent types whose prompts get graph context for entities named in the task
+        let graph_enrichment = std::env::var("AGENT_GRAPH_ENRICHMENT")
+            .unwrap_or_else(|_| "Analyst".to_string())
+            .split(',')
+            .filter_map(|t| serde_json::from_value::<AgentType>(serde_json::Value::String(t.trim().to_string())).ok())
+            .collect();
+
+        // Agent types that decide for themselves when to search or consult the graph
+        let tool_agents = std::env::var("AGENT_TOOL_AGENTS")
+            .unwrap_or_else(|_| "Researcher,Analyst".to_string())
+            .split(',')
+            .filter_map(|t| serde_json::from_value::<AgentType>(serde_json::Value::String(t.trim().to_string())).ok())
+            .collect();
+
+        // Model turns allowed to request tools before a final answer is forced
+        let max_tool_rounds = std::env::var("AGENT_MAX_TOOL_ROUNDS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(5);
+
+        // Turns kept verbatim per session before the oldest are summarized
+        let session_max_turns = std::env::var("AGENT_SESSION_MAX_TURNS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(6)
+            .max(1);
+
+        // Sessions are evicted when idle for the TTL, or least recently used first at the cap
+        let max_sessions = std::env::var("AGENT_MAX_SESSIONS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_SESSIONS)
+            .max(1);
+        let session_ttl = std::env::var("AGENT_SESSION_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_SESSION_TTL_SECS);
+
+        // Idle polling period of the agent loop; new assignments wake it immediately
+        let loop_interval = std::env::var("AGENT_LOOP_INTERVAL_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(1000)
+            .max(10);
+
         Self {
             agents: Arc::new(Mutex::new(HashMap::new())),
             tasks: Arc::new(Mutex::new(HashMap::new())),
+            usage_by_user: Arc::new(Mutex::new(HashMap::new())),
             search_engine,
             graph_manager,
+            min_relevance,
+            research_chunks,
+            research_chunk_chars,
+            context_tokens,
+            graph_enrichment,
+            output_filter: OutputFilter::from_env(),
+            audit: None,
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            session_max_turns,
+            max_sessions,
+            session_ttl: Duration::from_secs(session_ttl),
+            tool_agents,
+            max_tool_rounds,
+            loop_interval: Duration::from_millis(loop_interval),
+            wakeup: Arc::new(Notify::new()),
+            in_flight: Arc::new(std::sync::Mutex::new(HashSet::new())),
+            running: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            result_processors: result_processor::processors_from_env(),
+            rbac: None,
         }
     }
 
+    /// Record task submissions and completions in the security audit log
+    pub fn with_audit(mut self, audit: AuditManager) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Filter tool results by the permissions of the user who submitted the task. Without
+    /// it, tools see everything the search engine and graph hold.
+    pub fn with_rbac(mut self, rbac: Arc<RBAC>) -> Self {
+        self.rbac = Some(rbac);
+        self
+    }
+
+    pub fn with_graph_enrichment(mut self, agent_types: Vec<AgentType>) -> Self {
+        self.graph_enrichment = agent_types;
+        self
+    }
+
+    /// Summarize the graph neighborhood of every entity mentioned in `text`.
+    /// Empty when no graph is configured or nothing is mentioned.
+    pub async fn build_graph_context(&self, text: &str) -> String {
+        let graph = match self.graph_manager {
+            Some(ref graph) => graph,
+            None => return String::new(),
+        };
+
+        let mut summary = String::new();
+        for entity in graph.find_entities_mentioned_in(text).await.iter().take(5) {
+            let ctx = match graph.find_related_context(&entity.id, 2).await {
+                Ok(ctx) => ctx,
+                Err(_) => continue,
+            };
+            summary.push_str(&ctx.summarize(&entity.id));
+        }
+        summary
+    }
+
+    /// Agent types that run a tool-calling loop instead of their fixed pipeline
+    pub fn with_tool_agents(mut self, agent_types: Vec<AgentType>) -> Self {
+        self.tool_agents = agent_types;
+        self
+    }
+
+    pub fn with_loop_interval(mut self, interval: Duration) -> Self {
+        self.loop_interval = interval;
+        self
+    }
+
+    pub fn with_session_max_turns(mut self, max_turns: usize) -> Self {
+        self.session_max_turns = max_turns.max(1);
+        self
+    }
+
+    /// Sessions kept at once and how long an idle one lasts
+    pub fn with_session_limits(mut self, max_sessions: usize, ttl: Duration) -> Self {
+        self.max_sessions = max_sessions.max(1);
+        self.session_ttl = ttl;
+        self
+    }
+
+    pub fn with_min_relevance(mut self, min_relevance: f32) -> Self {
+        self.min_relevance = min_relevance;
+        self
+    }
+
+    /// Default chunks per research query and characters kept per chunk
+    pub fn with_research_limits(mut self, chunks: usize, chunk_chars: usize) -> Self {
+        self.research_chunks = chunks.clamp(1, MAX_RESEARCH_CHUNKS);
+        self.research_chunk_chars = chunk_chars;
+        self
+    }
+
+    pub fn with_context_tokens(mut self, context_tokens: usize) -> Self {
+        self.context_tokens = context_tokens;
+        self
+    }
+
+    /// Runs `processor` on every result after the ones already registered, including
+    /// those configured by `AGENT_RESULT_SCHEMA` and `AGENT_RESULT_MAX_CHARS`
+    pub fn with_result_processor(mut self, processor: impl ResultProcessor + 'static) -> Self {
+        self.result_processors.push(Arc::new(processor));
+        self
+    }
+
+    /// Chunks and characters per chunk a research query for `task_id` uses: the task's
+    /// limits, then `profile`'s, then the orchestrator defaults
+    pub async fn research_limits(&self, profile: &AgentProfile, task_id: &str) -> (usize, usize) {
+        let task_limits = self.get_task(task_id).await.map(|t| t.research).unwrap_or_default();
+        let limits = task_limits.or(profile.research);
+        (
+            limits.chunks.unwrap_or(self.research_chunks).clamp(1, MAX_RESEARCH_CHUNKS),
+            limits.chunk_chars.unwrap_or(self.research_chunk_chars),
+        )
+    }
+
     pub async fn register_agent(&self, profile: AgentProfile) {
         let mut agents = self.agents.lock().await;
         agents.insert(profile.id.clone(), profile);
     }
 
+    /// Remove an agent from the roster. Refused while it still has tasks assigned that
+    /// haven't completed or failed.
+    pub async fn deregister_agent(&self, agent_id: &str) -> Result<AgentProfile> {
+        let tasks = self.tasks.lock().await;
+        let mut agents = self.agents.lock().await;
+        if !agents.contains_key(agent_id) {
+            return Err(BrainVaultError::NotFound(format!("Agent {}", agent_id)));
+        }
+        let in_flight = tasks.values()
+            .filter(|t| t.assigned_agent_id.as_deref() == Some(agent_id))
+            .filter(|t| !t.status.is_finished())
+            .count();
+        if in_flight > 0 {
+            return Err(BrainVaultError::Conflict(format!("Agent {} has {} task(s) in flight", agent_id, in_flight)));
+        }
+        let profile = agents.remove(agent_id).expect("checked above");
+        println!("INFO: Deregistered agent {}", agent_id);
+        Ok(profile)
+    }
+
+    /// Registered agents with their task counts, ordered by id
+    pub async fn list_agents(&self) -> Vec<AgentSummary> {
+        let tasks = self.tasks.lock().await;
+        let agents = self.agents.lock().await;
+        let mut summaries: Vec<AgentSummary> = agents.values()
+            .map(|profile| {
+                let mut summary = AgentSummary { profile: profile.clone(), active_tasks: 0, completed_tasks: 0, failed_tasks: 0, cancelled_tasks: 0 };
+                for task in tasks.values().filter(|t| t.assigned_agent_id.as_deref() == Some(profile.id.as_str())) {
+                    match task.status {
+                        TaskStatus::Completed => summary.completed_tasks += 1,
+                        TaskStatus::Failed => summary.failed_tasks += 1,
+                        TaskStatus::Cancelled => summary.cancelled_tasks += 1,
+                        _ => summary.active_tasks += 1,
+                    }
+                }
+                summary
+            })
+            .collect();
+        summaries.sort_by(|a, b| a.profile.id.cmp(&b.profile.id));
+        summaries
+    }
+
     pub async fn submit_task(&self, description: String, agent_type: Option<AgentType>) -> String {
+        self.submit_task_as(None, description, agent_type).await
+    }
+
+    /// Submit a task on behalf of a user so its token usage is attributed to them
+    pub async fn submit_task_as(&self, user_id: Option<String>, description: String, agent_type: Option<AgentType>) -> String {
+        self.submit_task_in_session(user_id, None, description, agent_type).await
+    }
+
+    /// Submit a task that continues `session_id`, so the agent sees the session's earlier turns
+    pub async fn submit_task_in_session(
+        &self,
+        user_id: Option<String>,
+        session_id: Option<String>,
+        description: String,
+        agent_type: Option<AgentType>,
+    ) -> String {
+        self.submit_task_with(user_id, description, agent_type, TaskOptions { session_id, ..Default::default() }).await
+    }
+
+    pub async fn submit_task_with(
+        &self,
+        user_id: Option<String>,
+        description: String,
+        agent_type: Option<AgentType>,
+        options: TaskOptions,
+    ) -> String {
         let task_id = Uuid::new_v4().to_string();
         let mut task = Task {
             id: task_id.clone(),
@@ -100,18 +845,39 @@ impl AgentOrchestrator {
             preferred_agent_type: agent_type,
             result: None,
             audit_log: Vec::new(),
+            submitted_by: user_id,
+            usage: TokenUsage::default(),
+            raw_result: None,
+            session_id: options.session_id,
+            response_format: options.response_format,
+            submitted_at: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as u64,
+            request_id: options.request_id,
+            research: options.research,
         };
         
         task.add_log(None, "SUBMITTED".to_string(), format!("Task submitted: {}", description));
+        let user = task.submitted_by.clone().unwrap_or_else(|| "system".to_string());
+        let agent_type = task.preferred_agent_type.as_ref().map(|t| format!("{:?}", t)).unwrap_or_else(|| "Any".to_string());
+        let request_id = task.request_id.clone();
         
-        let mut tasks = self.tasks.lock().await;
-        tasks.insert(task_id.clone(), task);
+        self.tasks.lock().await.insert(task_id.clone(), task);
+
+        if let Some(ref audit) = self.audit {
+            let mut details = HashMap::from([
+                ("task_id".to_string(), task_id.clone()),
+                ("agent_type".to_string(), agent_type),
+            ]);
+            if let Some(id) = request_id {
+                details.insert("request_id".to_string(), id);
+            }
+            audit.record(EventKind::TaskSubmitted, Severity::Low, "Agent Task Submitted", &user, "Pending", details).await;
+        }
         task_id
     }
 
-    pub async fn assign_task(&self, task_id: &str) -> Result<String, String> {
+    pub async fn assign_task(&self, task_id: &str) -> Result<String> {
         let mut tasks = self.tasks.lock().await;
-        let task = tasks.get_mut(task_id).ok_or("Task not found")?;
+        let task = tasks.get_mut(task_id).ok_or_else(|| BrainVaultError::NotFound(format!("Task {}", task_id)))?;
         
         if task.assigned_agent_id.is_some() {
             return Ok(task.assigned_agent_id.clone().unwrap());
@@ -133,63 +899,335 @@ impl AgentOrchestrator {
             task.assigned_agent_id = Some(agent_id.clone());
             task.status = TaskStatus::InProgress;
             task.add_log(Some("system".to_string()), "ASSIGNED".to_string(), format!("Assigned to agent {}", agent_id));
+            // A permit is stored if the loop is busy, so the wakeup isn't lost
+            self.wakeup.notify_one();
             return Ok(agent_id);
         }
 
-        Err("No suitable agents available".to_string())
+        Err(BrainVaultError::Internal("No suitable agents available".to_string()))
     }
     
-    pub async fn complete_task(&self, task_id: &str, result: String) -> Result<(), String> {
-        let mut tasks = self.tasks.lock().await;
-        let task = tasks.get_mut(task_id).ok_or("Task not found")?;
-        task.status = TaskStatus::Completed;
-        task.result = Some(result.clone());
-        task.add_log(task.assigned_agent_id.clone(), "COMPLETED".to_string(), format!("Task completed with result: {}", result)); 
+    /// Marks a task completed; `result` may be plain text (a `String`) or structured JSON.
+    /// The result passes through every registered `ResultProcessor` first; if one rejects
+    /// it, the task fails instead and `BadRequest` carries the processor's reason.
+    pub async fn complete_task(&self, task_id: &str, result: impl Into<serde_json::Value>) -> Result<()> {
+        let mut result = result.into();
+        let (user, agent_id) = {
+            let mut tasks = self.tasks.lock().await;
+            let task = tasks.get_mut(task_id).ok_or_else(|| BrainVaultError::NotFound(format!("Task {}", task_id)))?;
+            // A run that finishes just as it is cancelled doesn't bring the task back
+            if task.status == TaskStatus::Cancelled {
+                return Ok(());
+            }
+            for processor in &self.result_processors {
+                let before = result.clone();
+                match processor.process(task, result) {
+                    Ok(processed) => {
+                        result = processed;
+                        if result != before {
+                            task.add_log(task.assigned_agent_id.clone(), "POSTPROCESSED".to_string(), format!("Result modified by {}", processor.name()));
+                            if task.raw_result.is_none() {
+                                task.raw_result = Some(result_text(&before));
+                            }
+                        }
+                    }
+                    Err(reason) => {
+                        let reason = format!("Result rejected by {}: {}", processor.name(), reason);
+                        drop(tasks);
+                        self.fail_task(task_id, reason.clone()).await?;
+                        return Err(BrainVaultError::BadRequest(reason));
+                    }
+                }
+            }
+            task.status = TaskStatus::Completed;
+            task.add_log(task.assigned_agent_id.clone(), "COMPLETED".to_string(), format!("Task completed with result: {}", result_text(&result)));
+            task.result = Some(result);
+            (task.submitted_by.clone().unwrap_or_else(|| "system".to_string()), task.assigned_agent_id.clone().unwrap_or_default())
+        };
+
+        if let Some(ref audit) = self.audit {
+            audit.record(EventKind::TaskCompleted, Severity::Low, "Agent Task Completed", &user, "Completed", HashMap::from([
+                ("task_id".to_string(), task_id.to_string()),
+                ("agent_id".to_string(), agent_id),
+            ])).await;
+        }
         Ok(())
     }
 
+    pub async fn fail_task(&self, task_id: &str, reason: String) -> Result<()> {
+        let user = {
+            let mut tasks = self.tasks.lock().await;
+            let task = tasks.get_mut(task_id).ok_or_else(|| BrainVaultError::NotFound(format!("Task {}", task_id)))?;
+            if task.status == TaskStatus::Cancelled {
+                return Ok(());
+            }
+            task.status = TaskStatus::Failed;
+            task.add_log(task.assigned_agent_id.clone(), "FAILED".to_string(), reason.clone());
+            task.submitted_by.clone().unwrap_or_else(|| "system".to_string())
+        };
+
+        if let Some(ref audit) = self.audit {
+            audit.record(EventKind::TaskCompleted, Severity::Medium, "Agent Task Failed", &user, "Failed", HashMap::from([
+                ("task_id".to_string(), task_id.to_string()),
+                ("reason".to_string(), reason),
+            ])).await;
+        }
+        Ok(())
+    }
+
+    /// Stops a task that hasn't finished. A running task's execution is aborted at its next
+    /// await point, dropping any LLM request in flight, so it consumes no further tokens.
+    pub async fn cancel_task(&self, task_id: &str, reason: &str) -> Result<Task> {
+        let task = {
+            let mut tasks = self.tasks.lock().await;
+            let task = tasks.get_mut(task_id).ok_or_else(|| BrainVaultError::NotFound(format!("Task {}", task_id)))?;
+            if task.status.is_finished() {
+                return Err(BrainVaultError::Conflict(format!("Task {} is already {:?}", task_id, task.status)));
+            }
+            task.status = TaskStatus::Cancelled;
+            task.add_log(task.assigned_agent_id.clone(), "CANCELLED".to_string(), reason.to_string());
+            task.clone()
+        };
+        let running = self.running.lock().unwrap().remove(task_id);
+        if let Some(handle) = running {
+            handle.abort();
+            println!("INFO: Aborted execution of cancelled task {}", task_id);
+        }
+
+        if let Some(ref audit) = self.audit {
+            let user = task.submitted_by.clone().unwrap_or_else(|| "system".to_string());
+            audit.record(EventKind::TaskCompleted, Severity::Low, "Agent Task Cancelled", &user, "Cancelled", HashMap::from([
+                ("task_id".to_string(), task_id.to_string()),
+                ("reason".to_string(), reason.to_string()),
+            ])).await;
+        }
+        Ok(task)
+    }
+
     pub async fn get_task(&self, task_id: &str) -> Option<Task> {
         let tasks = self.tasks.lock().await;
         tasks.get(task_id).cloned()
     }
 
+    fn session_expired(&self, memory: &SessionMemory, now: u64) -> bool {
+        now.saturating_sub(memory.last_active) > self.session_ttl.as_millis() as u64
+    }
+
+    /// `user_id`'s session `session_id`, unless it has expired
+    pub async fn get_session(&self, user_id: &str, session_id: &str) -> Option<SessionMemory> {
+        let now = now_millis();
+        let key = (user_id.to_string(), session_id.to_string());
+        self.sessions.lock().await.get(&key)
+            .filter(|memory| !self.session_expired(memory, now))
+            .cloned()
+    }
+
+    pub async fn clear_session(&self, user_id: &str, session_id: &str) -> bool {
+        self.sessions.lock().await.remove(&(user_id.to_string(), session_id.to_string())).is_some()
+    }
+
+    /// `Unauthorized` when a live session `session_id` was started by someone other than
+    /// `user_id`; checked before a task joins a session
+    pub async fn check_session_owner(&self, user_id: &str, session_id: &str) -> Result<()> {
+        let now = now_millis();
+        let sessions = self.sessions.lock().await;
+        let taken = sessions.iter()
+            .any(|((owner, sid), memory)| sid == session_id && owner != user_id && !self.session_expired(memory, now));
+        if taken {
+            return Err(BrainVaultError::Unauthorized(format!("Session {} belongs to another user", session_id)));
+        }
+        Ok(())
+    }
+
+    /// Drops expired sessions, then the least recently used ones beyond the cap,
+    /// keeping `keep`
+    fn evict_sessions(&self, sessions: &mut HashMap<SessionKey, SessionMemory>, keep: &SessionKey, now: u64) {
+        sessions.retain(|key, memory| key == keep || !self.session_expired(memory, now));
+        while sessions.len() > self.max_sessions {
+            let oldest = sessions.iter()
+                .filter(|(key, _)| *key != keep)
+                .min_by_key(|(_, memory)| memory.last_active)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(key) => sessions.remove(&key),
+                None => break,
+            };
+        }
+    }
+
+    /// Append a completed turn to the session, summarizing the oldest turns once it exceeds the cap
+    async fn remember_turn(&self, task_id: &str, user_id: &str, session_id: &str, request: &str, response: &str) {
+        let key: SessionKey = (user_id.to_string(), session_id.to_string());
+        let overflow = {
+            let now = now_millis();
+            let mut sessions = self.sessions.lock().await;
+            if sessions.get(&key).map_or(false, |memory| self.session_expired(memory, now)) {
+                sessions.remove(&key);
+            }
+            let memory = sessions.entry(key.clone()).or_default();
+            memory.last_active = now;
+            memory.turns.push(SessionTurn { request: request.to_string(), response: response.to_string() });
+            let over_cap = memory.turns.len() > self.session_max_turns;
+            let overflow = if over_cap {
+                let keep = self.session_max_turns / 2;
+                let drained: Vec<SessionTurn> = memory.turns.drain(..memory.turns.len() - keep).collect();
+                Some((memory.summary.clone(), drained))
+            } else {
+                None
+            };
+            self.evict_sessions(&mut sessions, &key, now);
+            match overflow {
+                Some(overflow) => overflow,
+                None => return,
+            }
+        };
+
+        let (previous, old_turns) = overflow;
+        let transcript = old_turns.iter()
+            .map(|t| format!("User: {}\nAgent: {}", t.request, t.response.chars().take(SESSION_TURN_CHARS).collect::<String>()))
+            .collect::<Vec<String>>()
+            .join("\n");
+        let prompt = format!(
+            "Summarize this conversation between a user and a research agent in a short paragraph. \
+            Keep names, figures and conclusions the user may refer back to.\n\nEarlier summary: {}\n\nTranscript:\n{}",
+            if previous.is_empty() { "(none)" } else { previous.as_str() }, transcript
+        );
+        let summary = match self.call_llm(task_id, &prompt).await {
+            Ok(s) if !s.trim().is_empty() => s.trim().to_string(),
+            _ => format!("{} {}", previous, old_turns.iter().map(|t| t.request.as_str()).collect::<Vec<_>>().join("; ")).trim().to_string(),
+        };
+
+        if let Some(memory) = self.sessions.lock().await.get_mut(&key) {
+            memory.summary = summary;
+        }
+    }
+
     pub async fn get_all_tasks(&self) -> Vec<Task> {
         let tasks = self.tasks.lock().await;
         tasks.values().cloned().collect()
     }
+
+    /// Tasks matching `filter`, sorted by submission time and paginated
+    pub async fn list_tasks(&self, filter: &TaskFilter) -> TaskPage {
+        let tasks = self.tasks.lock().await;
+        let mut matching: Vec<&Task> = tasks.values()
+            .filter(|t| filter.status.as_ref().map_or(true, |s| &t.status == s))
+            .filter(|t| filter.agent_id.as_ref().map_or(true, |a| t.assigned_agent_id.as_ref() == Some(a)))
+            .filter(|t| filter.submitted_by.as_ref().map_or(true, |u| t.submitted_by.as_ref() == Some(u)))
+            .collect();
+        // Task ids break ties so pages stay stable between requests
+        matching.sort_by(|a, b| a.submitted_at.cmp(&b.submitted_at).then_with(|| a.id.cmp(&b.id)));
+        if !filter.oldest_first {
+            matching.reverse();
+        }
+
+        let total = matching.len();
+        let tasks = matching.into_iter()
+            .skip(filter.offset)
+            .take(filter.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect();
+        TaskPage { tasks, total }
+    }
     
+    /// Append an entry to a task's audit trail on behalf of its assigned agent
+    pub async fn log_task_event(&self, task_id: &str, action: &str, details: String) {
+        let mut tasks = self.tasks.lock().await;
+        if let Some(task) = tasks.get_mut(task_id) {
+            let agent_id = task.assigned_agent_id.clone();
+            task.add_log(agent_id, action.to_string(), details);
+        }
+    }
+
+    /// Add an LLM call's usage to the task and to its submitting user's running total
+    pub async fn record_usage(&self, task_id: &str, usage: &TokenUsage) {
+        let user = {
+            let mut tasks = self.tasks.lock().await;
+            match tasks.get_mut(task_id) {
+                Some(task) => {
+                    task.usage.add(usage);
+                    task.submitted_by.clone().unwrap_or_else(|| "system".to_string())
+                }
+                None => "system".to_string(),
+            }
+        };
+        self.record_user_usage(&user, usage).await;
+    }
+
+    /// Add usage from an LLM call made outside any task, such as `/api/ask`, to the user's total
+    pub async fn record_user_usage(&self, user: &str, usage: &TokenUsage) {
+        let mut by_user = self.usage_by_user.lock().await;
+        by_user.entry(user.to_string()).or_default().add(usage);
+    }
+
+    pub async fn get_usage_report(&self) -> UsageReport {
+        let by_user = self.usage_by_user.lock().await.clone();
+        let mut total = TokenUsage::default();
+        for usage in by_user.values() {
+            total.add(usage);
+        }
+        UsageReport { total, by_user }
+    }
+
     pub async fn get_stats(&self) -> (usize, usize) {
         let tasks = self.tasks.lock().await;
         let agents = self.agents.lock().await;
         (tasks.len(), agents.len())
     }
     
-    // The background worker that processes tasks
+    // The background worker that processes tasks. Scans every `loop_interval`, or as soon
+    // as a task is assigned.
     pub async fn run_agent_loop(&self) {
         loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-            
-            // 1. Identify tasks involved and mark Executing
-            let mut tasks_to_launch: Vec<(String, String)> = Vec::new();
-            
-            {
-                let mut tasks = self.tasks.lock().await;
-                for (id, task) in tasks.iter_mut() {
-                    if matches!(task.status, TaskStatus::InProgress) && task.assigned_agent_id.is_some() {
-                         task.status = TaskStatus::Executing;
-                         tasks_to_launch.push((id.clone(), task.assigned_agent_id.clone().unwrap()));
-                    }
+            let _ = tokio::time::timeout(self.loop_interval, self.wakeup.notified()).await;
+            self.dispatch_assigned_tasks().await;
+        }
+    }
+
+    /// Start every assigned task that is not already executing; returns the ids started.
+    /// Tasks stay in the in-flight set until their run finishes, so a task whose status
+    /// goes back to `InProgress` mid-run is not executed twice.
+    pub async fn dispatch_assigned_tasks(&self) -> Vec<String> {
+        // 1. Identify tasks involved and mark Executing
+        let mut tasks_to_launch: Vec<(String, String)> = Vec::new();
+
+        {
+            let mut tasks = self.tasks.lock().await;
+            let mut in_flight = self.in_flight.lock().unwrap();
+            for (id, task) in tasks.iter_mut() {
+                if matches!(task.status, TaskStatus::InProgress) && task.assigned_agent_id.is_some() && !in_flight.contains(id) {
+                     in_flight.insert(id.clone());
+                     task.status = TaskStatus::Executing;
+                     tasks_to_launch.push((id.clone(), task.assigned_agent_id.clone().unwrap()));
                 }
             }
-            
-            // 2. Spawn execution
-            for (task_id, agent_id) in tasks_to_launch {
-                let orchestrator = self.clone();
-                tokio::spawn(async move {
-                    orchestrator.process_single_task(task_id, agent_id).await;
-                });
-            }
         }
+
+        // 2. Spawn execution
+        let mut launched = Vec::with_capacity(tasks_to_launch.len());
+        for (task_id, agent_id) in tasks_to_launch {
+            launched.push(task_id.clone());
+            let orchestrator = self.clone();
+            // Held across the spawn so the run can't finish (and deregister) before it is registered
+            let mut running = self.running.lock().unwrap();
+            let id = task_id.clone();
+            let handle = tokio::spawn(async move {
+                // Leaves the in-flight set however the run ends, including a panic or an abort
+                let _guard = InFlightGuard {
+                    set: orchestrator.in_flight.clone(),
+                    running: orchestrator.running.clone(),
+                    task_id: task_id.clone(),
+                };
+                orchestrator.process_single_task(task_id, agent_id).await;
+            });
+            running.insert(id, handle.abort_handle());
+        }
+        launched
+    }
+
+    /// Whether the task is currently being executed by the agent loop
+    pub fn is_in_flight(&self, task_id: &str) -> bool {
+        self.in_flight.lock().unwrap().contains(task_id)
     }
 
     async fn process_single_task(&self, task_id: String, agent_id: String) {
@@ -199,45 +1237,287 @@ impl AgentOrchestrator {
         };
         
         if let Some(profile) = agent_profile {
-            let description = {
+            let (description, session_id, owner, format) = {
                  let tasks = self.tasks.lock().await;
                  if let Some(t) = tasks.get(&task_id) {
-                     t.description.clone()
+                     (t.description.clone(), t.session_id.clone(), session_user(t), t.response_format)
                  } else {
                      return;
                  }
             };
             
             // Pass task_id to logic for Manager recursive capabilities
-            let result = self.execute_agent_logic(&profile, &description, &task_id).await;
-            
+            let raw_result = self.execute_agent_logic(&profile, &description, &task_id).await;
+            let cleaned = self.output_filter.clean(&raw_result);
+            let (id, original) = (task_id.as_str(), cleaned.as_str());
+            let formatted = format_result(format, cleaned.clone(), |error| async move {
+                self.log_task_event(id, "FORMAT_RETRY", format!("JSON parse failed: {}", error)).await;
+                let prompt = format!(
+                    "Convert the following result into a single valid JSON document that captures all of its content. \
+                    Your previous reply was not valid JSON ({}). Return only the JSON, with no commentary or code fences.\n\n{}",
+                    error, original
+                );
+                self.call_llm(id, &prompt).await.map_err(|e| e.to_string())
+            }).await;
+            let result = match formatted {
+                Ok(result) => result,
+                Err(reason) => {
+                    let _ = self.fail_task(&task_id, reason).await;
+                    return;
+                }
+            };
+            if result_text(&result) != raw_result {
+                let mut tasks = self.tasks.lock().await;
+                if let Some(t) = tasks.get_mut(&task_id) {
+                    t.raw_result = Some(raw_result);
+                }
+            }
+
+            // Result processors may rewrite the result or fail the task; only a stored
+            // result is indexed and remembered
+            if self.complete_task(&task_id, result).await.is_err() {
+                return;
+            }
+            let text = match self.get_task(&task_id).await.and_then(|t| t.result) {
+                Some(result) => result_text(&result),
+                None => return,
+            };
+
             // Store result
             if let Some(ref engine) = self.search_engine {
                 let doc_id = format!("agent-result-{}", task_id);
                 let content = format!(
                     "Agent Task Result\nTask ID: {}\nAgent: {} ({})\nQuery: {}\n\n{}",
-                    task_id, profile.name, format!("{:?}", profile.agent_type), description, result
+                    task_id, profile.name, format!("{:?}", profile.agent_type), description, text
                 );
                 let _ = engine.ingest_document(&doc_id, &content).await;
             }
             
-            let _ = self.complete_task(&task_id, result).await;
+            if let Some(ref sid) = session_id {
+                self.remember_turn(&task_id, &owner, sid, &description, &text).await;
+            }
         }
     }
     
-    async fn execute_agent_logic(&self, profile: &AgentProfile, description: &str, _current_task_id: &str) -> String {
+    /// Profile of the agent assigned to a task
+    async fn assigned_profile(&self, task_id: &str) -> Option<AgentProfile> {
+        let agent_id = self.get_task(task_id).await?.assigned_agent_id?;
+        self.agents.lock().await.get(&agent_id).cloned()
+    }
+
+    // Call the LLM via the NAFS-4 provider chain under the assigned agent's persona,
+    // attributing token usage to the task
+    async fn call_llm(&self, task_id: &str, prompt: &str) -> Result<String> {
+        use crate::core::llm::fallback::FallbackLLMClient;
+        use crate::core::llm::nafs_provider::system_prompt;
+
+        if let Some(client) = FallbackLLMClient::from_env() {
+             let profile = self.assigned_profile(task_id).await;
+             let system = profile.as_ref()
+                 .map_or(system_prompt(), |p| p.effective_system_prompt())
+                 .to_string();
+             let params = profile.map(|p| p.generation_params(GenerationParams::default())).unwrap_or_default();
+             match client.with_params(params).generate_with_system(&system, prompt).await {
+                Ok(res) => {
+                    if res.cached {
+                        self.log_task_event(task_id, "LLM_CACHE_HIT", format!("Served cached {} response", res.provider)).await;
+                    } else {
+                        self.record_usage(task_id, &res.usage).await;
+                    }
+                    return Ok(res.content);
+                }
+                 Err(e) => println!("WARN: {}", e),
+             }
+        }
+        // Fallback for demo if no LLM key
+        Ok("LLM Output Mock".to_string())
+    }
+    
+    /// Tools offered to agents, limited to the backends this orchestrator has
+    pub fn agent_tools(&self) -> Vec<ToolDefinition> {
+        let mut tools = Vec::new();
+        if self.search_engine.is_some() {
+            tools.push(ToolDefinition {
+                name: "search".to_string(),
+                description: "Hybrid keyword and semantic search over the knowledge base. Returns matching passages with their doc_id.".to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "query": {"type": "string", "description": "What to look for"},
+                        "top_k": {"type": "integer", "description": "Number of results (default 5, max 20)"}
+                    },
+                    "required": ["query"]
+                }),
+            });
+        }
+        if self.graph_manager.is_some() {
+            tools.push(ToolDefinition {
+                name: "get_context".to_string(),
+                description: "Entities and relationships around a knowledge graph entity.".to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "entity_id": {"type": "string", "description": "Id of the entity to expand"},
+                        "depth": {"type": "integer", "description": "Hops to follow (default 2, max 3)"}
+                    },
+                    "required": ["entity_id"]
+                }),
+            });
+        }
+        tools
+    }
+
+    /// Run one tool call for a task, recording it in the task trail and the audit log
+    /// Runs one tool call for `task_id` and returns the text reported back to the model.
+    /// Failures are reported as `Error: ...` text, so every call gets a result.
+    pub async fn execute_tool(&self, task_id: &str, call: &ToolCall) -> String {
+        let args = &call.arguments;
+        let submitter = self.get_task(task_id).await.and_then(|t| t.submitted_by);
+        // Tasks nobody submitted get what an anonymous caller would
+        let user = submitter.as_deref().unwrap_or("anonymous");
+        let result: std::result::Result<String, String> = match call.name.as_str() {
+            "search" => match (self.search_engine.as_ref(), args["query"].as_str()) {
+                (Some(engine), Some(query)) => {
+                    let top_k = args["top_k"].as_u64().unwrap_or(5).clamp(1, 20) as usize;
+                    match engine.search(query, top_k).await {
+                        Ok(results) => {
+                            let results = match self.rbac {
+                                Some(ref rbac) => {
+                                    let collections = engine.vector_db.document_collections().await;
+                                    rbac.get_permitted_search_results_in(user, results, &collections).await
+                                }
+                                None => results,
+                            };
+                            let context = build_source_context(&results.hits, self.min_relevance);
+                            Ok(if context.is_empty() { "No relevant sources found.".to_string() } else { context })
+                        }
+                        Err(e) => Err(e.to_string()),
+                    }
+                }
+                _ => Err("search needs a 'query' string".to_string()),
+            },
+            "get_context" => match (self.graph_manager.as_ref(), args["entity_id"].as_str()) {
+                (Some(graph), Some(entity_id)) => {
+                    let depth = args["depth"].as_u64().unwrap_or(2).clamp(1, 3) as usize;
+                    let context = match graph.find_related_context(entity_id, depth).await {
+                        Ok(ctx) => match self.rbac {
+                            Some(ref rbac) => rbac.filter_context(user, ctx).await,
+                            None => Ok(ctx),
+                        },
+                        Err(e) => Err(e),
+                    };
+                    context
+                        .map_err(|e| e.to_string())
+                        .and_then(|ctx| serde_json::to_string(&ctx).map_err(|e| e.to_string()))
+                }
+                _ => Err("get_context needs an 'entity_id' string".to_string()),
+            },
+            other => Err(format!("Unknown tool '{}'", other)),
+        };
+
+        let (status, output) = match result {
+            Ok(output) => ("Success", output.chars().take(TOOL_OUTPUT_CHARS).collect()),
+            Err(e) => ("Failed", format!("Error: {}", e)),
+        };
+        self.log_task_event(task_id, "TOOL_CALL", format!("{}({}) -> {}", call.name, call.arguments, status)).await;
+
+        if let Some(ref audit) = self.audit {
+            let user = submitter.as_deref().unwrap_or("system");
+            audit.record(EventKind::ToolCall, Severity::Low, "Agent Tool Call", user, status, HashMap::from([
+                ("task_id".to_string(), task_id.to_string()),
+                ("tool".to_string(), call.name.clone()),
+                ("arguments".to_string(), call.arguments.to_string()),
+            ])).await;
+        }
+        output
+    }
+
+    /// Let the model call tools until it produces an answer. `None` when no tools are
+    /// available or the provider can't do function calling, so the caller falls back
+    /// to the agent's fixed pipeline.
+    async fn run_with_tools(&self, task_id: &str, profile: &AgentProfile, description: &str, guidance: &str) -> Option<String> {
         use crate::core::llm::nafs_provider::NafsLLMClient;
+
+        let tools = self.agent_tools();
+        if tools.is_empty() {
+            return None;
+        }
+        let client = NafsLLMClient::new()?;
+        let params = profile.generation_params(GenerationParams { temperature: TOOL_TEMPERATURE, ..GenerationParams::default() });
+
+        let system = format!(
+            "{}\n\nUse the tools to look up facts before answering, and cite the doc_id of every source \
+            you rely on. Answer only from what the tools return.{}",
+            profile.effective_system_prompt(), guidance
+        );
+        let mut messages = vec![
+            serde_json::json!({"role": "system", "content": system}),
+            serde_json::json!({"role": "user", "content": description}),
+        ];
+
+        for _ in 0..self.max_tool_rounds {
+            let (turn, usage) = match client.chat_with_tools(&messages, &tools, &params).await {
+                Ok(turn) => turn,
+                Err(e) => {
+                    println!("WARN: Tool calling unavailable, using fixed pipeline: {}", e);
+                    return None;
+                }
+            };
+            self.record_usage(task_id, &usage).await;
+
+            match turn {
+                ToolTurn::Final(content) => return Some(content),
+                ToolTurn::Calls { message, calls } => {
+                    messages.push(message);
+                    for call in calls {
+                        let output = self.execute_tool(task_id, &call).await;
+                        messages.push(tool_result_message(&call.id, &output));
+                    }
+                }
+            }
+        }
+
+        // Out of rounds: ask for an answer from what has been gathered, without tools
+        messages.push(serde_json::json!({
+            "role": "user",
+            "content": "Tool budget exhausted. Give your final answer using the information gathered so far."
+        }));
+        match client.chat_with_tools(&messages, &[], &params).await {
+            Ok((ToolTurn::Final(content), usage)) => {
+                self.record_usage(task_id, &usage).await;
+                Some(content)
+            }
+            _ => None,
+        }
+    }
+
+    async fn execute_agent_logic(&self, profile: &AgentProfile, description: &str, task_id: &str) -> String {
+        use crate::core::llm::tokenizer::{chunk_by_tokens, default_tokenizer};
         
-        // Helper to call LLM using NAFS-4 multi-provider
-        async fn call_llm(prompt: &str) -> Result<String, String> {
-            if let Some(client) = NafsLLMClient::new() {
-                 match client.generate(prompt).await {
-                    Ok(res) => return Ok(res),
-                     Err(e) => println!("WARN: LLM ({}) failed: {}", client.provider_name(), e),
-                 }
+        let graph_context = if self.graph_enrichment.contains(&profile.agent_type) {
+            self.build_graph_context(description).await
+        } else {
+            String::new()
+        };
+        let graph_section = if graph_context.is_empty() {
+            String::new()
+        } else {
+            format!("\n\nKnowledge Graph Context:\n{}", graph_context)
+        };
+        let task = self.get_task(task_id).await;
+        // Only prompts that produce the final answer carry the format instruction
+        let format_section = task.as_ref().map(|t| t.response_format.prompt_instruction()).unwrap_or_default();
+        let session = task.and_then(|t| t.session_id.clone().map(|sid| (session_user(&t), sid)));
+        let session_section = match session {
+            Some((ref user, ref sid)) => self.get_session(user, sid).await.map(|m| m.to_prompt()).unwrap_or_default(),
+            None => String::new(),
+        };
+
+        if self.tool_agents.contains(&profile.agent_type) {
+            let guidance = format!("{}{}", session_section, format_section);
+            if let Some(answer) = self.run_with_tools(task_id, profile, description, &guidance).await {
+                return answer;
             }
-            // Fallback for demo if no LLM key
-            Ok("LLM Output Mock".to_string())
         }
         
         match profile.agent_type {
@@ -246,11 +1526,14 @@ impl AgentOrchestrator {
                     "You are a Project Manager. Break down this objective into specialized steps.\nObjective: '{}'\n\
                     Available Agents: Researcher (data gathering), Analyst (pattern finding), Coder (implementation).\n\
                     Output strict format per line: PLAN|<AgentType>|<TaskDescription>\n\
-                    Example: PLAN|Researcher|Find libraries for X", 
-                    description
+                    Example: PLAN|Researcher|Find libraries for X{}", 
+                    description, session_section
                 );
                 
-                let response = call_llm(&plan_prompt).await.unwrap_or_default();
+                let response = self.call_llm(task_id, &plan_prompt).await.unwrap_or_default();
+                let (submitted_by, request_id) = self.get_task(task_id).await
+                    .map(|t| (t.submitted_by, t.request_id))
+                    .unwrap_or_default();
                 let mut subtask_ids = Vec::new();
                 
                 for line in response.lines() {
@@ -266,7 +1549,9 @@ impl AgentOrchestrator {
                             _ => AgentType::Researcher
                         };
                         
-                        let sid = self.submit_task(task_desc.to_string(), Some(target_type)).await;
+                        // Subtasks carry the request id of the task that planned them
+                        let options = TaskOptions { request_id: request_id.clone(), ..Default::default() };
+                        let sid = self.submit_task_with(submitted_by.clone(), task_desc.to_string(), Some(target_type), options).await;
                         let _ = self.assign_task(&sid).await; // Kickoff
                         subtask_ids.push(sid);
                     }
@@ -293,8 +1578,8 @@ impl AgentOrchestrator {
                     for sid in &subtask_ids {
                         if let Some(t) = self.get_task(sid).await {
                             match t.status {
-                                TaskStatus::Completed => results.push(format!("Task {}: {}", sid, t.result.unwrap_or_default())),
-                                TaskStatus::Failed => results.push(format!("Task {}: Failed", sid)),
+                                TaskStatus::Completed => results.push(format!("Task {}: {}", sid, t.result_text().unwrap_or_default())),
+                                TaskStatus::Failed | TaskStatus::Cancelled => results.push(format!("Task {}: {:?}", sid, t.status)),
                                 _ => all_done = false,
                             }
                         }
@@ -306,42 +1591,45 @@ impl AgentOrchestrator {
                 
                 // Synthesize
                 let synthesis_prompt = format!(
-                    "You are a Project Manager. Synthesize these subtask results into a final report for: '{}'.\n\nResults:\n{}",
-                    description, results.join("\n---\n")
+                    "You are a Project Manager. Synthesize these subtask results into a final report for: '{}'.\n\nResults:\n{}{}{}",
+                    description, results.join("\n---\n"), session_section, format_section
                 );
-                call_llm(&synthesis_prompt).await.unwrap_or("Synthesis Failed".into())
+                self.call_llm(task_id, &synthesis_prompt).await.unwrap_or("Synthesis Failed".into())
             },
             AgentType::Researcher => {
                 // Multi-step Research: Planning -> Search -> Fact Extraction -> Synthesis
                 let plan_prompt = format!(
-                    "You are a Lead Researcher. Break down this research topic into 3 specific investigative search queries.\nTopic: '{}'\nOutput Format: just the queries, one per line.", 
-                    description
+                    "You are a Lead Researcher. Break down this research topic into 3 specific investigative search queries.\nTopic: '{}'\nOutput Format: just the queries, one per line.{}", 
+                    description, session_section
                 );
                 
-                let queries = match call_llm(&plan_prompt).await {
+                let queries = match self.call_llm(task_id, &plan_prompt).await {
                     Ok(res) => res.lines()
-                        .map(|s| s.trim().trim_start_matches(|c: char| !c.is_alphanumeric()).to_string())
+                        .map(|s| extract_search_query(s.trim().trim_start_matches(|c: char| !c.is_alphanumeric())))
                         .filter(|l| !l.is_empty())
                         .take(3)
                         .collect::<Vec<String>>(),
-                    Err(_) => vec![description.to_string()]
+                    Err(_) => vec![extract_search_query(description)]
                 };
                 
+                let (chunks, chunk_chars) = self.research_limits(profile, task_id).await;
+                let tokenizer = default_tokenizer();
                 let mut facts = Vec::new();
+                let mut found_sources = false;
                 if let Some(ref engine) = self.search_engine {
                     for query in queries {
-                        if let Ok(results) = engine.search(&query, 5).await {
-                             let context = results.hits.iter()
-                                .map(|h| format!("[Source {}]: {}", h.doc_id, h.content.as_deref().unwrap_or("")))
-                                .collect::<Vec<String>>()
-                                .join("\n");
+                        if let Ok(results) = engine.search(&query, chunks).await {
+                             let context = build_budgeted_context(
+                                 &results.hits, self.min_relevance, chunk_chars, self.context_tokens, tokenizer.as_ref(),
+                             );
                              
                              if !context.is_empty() {
+                                 found_sources = true;
                                  let extract_prompt = format!(
                                      "As a Researcher, extract key technical details and specific facts related to '{}' from these sources:\n{}\n\nReturn a bulleted list of facts.",
                                      query, context
                                  );
-                                 if let Ok(extracted) = call_llm(&extract_prompt).await {
+                                 if let Ok(extracted) = self.call_llm(task_id, &extract_prompt).await {
                                      facts.push(extracted);
                                  }
                              }
@@ -349,51 +1637,44 @@ impl AgentOrchestrator {
                     }
                 }
                 
+                if !found_sources {
+                    return format!("No relevant sources found in the knowledge base for: '{}'.", description);
+                }
+                
                 let report_prompt = format!(
-                    "You are an expert Research Agent. Compile a comprehensive, highly detailed final research report on: '{}'.\n\nAggregated Research Facts gathered from the database:\n{}\n\nFinal Report Structure: Executive Summary, Key Findings (grouped by topic), and Technical Deep-Dive.", 
-                    description, facts.join("\n\n")
+                    "You are an expert Research Agent. Compile a comprehensive, highly detailed final research report on: '{}'.\n\nAggregated Research Facts gathered from the database:\n{}{}{}\n\nFinal Report Structure: Executive Summary, Key Findings (grouped by topic), and Technical Deep-Dive.{}", 
+                    description, facts.join("\n\n"), graph_section, session_section, format_section
                 );
                 
-                call_llm(&report_prompt).await.unwrap_or_else(|_| "Research synthesis failed.".into())
+                self.call_llm(task_id, &report_prompt).await.unwrap_or_else(|_| "Research synthesis failed.".into())
             },
             AgentType::Analyst => {
                 // Analyst uses Graph context and Vector context to find correlations
-                let mut graph_context = String::new();
-                if let Some(ref graph) = self.graph_manager {
-                    let entities = graph.find_nodes_by_text(description).await;
-                    for ent in entities.iter().take(5) {
-                        if let Ok(ctx) = graph.find_related_context(&ent.id, 2).await {
-                            for rel in ctx.relationships {
-                                graph_context.push_str(&format!("Relationship: {} --[{}]--> {}\n", rel.from_id, rel.rel_type, rel.to_id));
-                            }
-                        }
-                    }
-                }
-
                 let analysis_prompt = format!(
-                    "You are a Senior Data Analyst. Analyze this objective: '{}'.\n\nKnowledge Graph Context:\n{}\n\nIdentify patterns, hidden correlations, and potential anomalies in this data. Provide an analytical summary with actionable insights.", 
-                    description, graph_context
+                    "You are a Senior Data Analyst. Analyze this objective: '{}'.\n\nKnowledge Graph Context:\n{}\n\nIdentify patterns, hidden correlations, and potential anomalies in this data. Provide an analytical summary with actionable insights.{}{}", 
+                    description, graph_context, session_section, format_section
                 );
-                call_llm(&analysis_prompt).await.unwrap_or_else(|_| "Analysis failed.".into())
+                self.call_llm(task_id, &analysis_prompt).await.unwrap_or_else(|_| "Analysis failed.".into())
             },
             AgentType::Coder => {
                 // Coder looks for existing patterns
                 let mut code_patterns = String::new();
                 if let Some(ref engine) = self.search_engine {
-                    if let Ok(hits) = engine.search(description, 3).await {
+                    if let Ok(hits) = engine.search(&extract_search_query(description), 3).await {
                         for hit in hits.hits {
                             if hit.doc_id.contains(".rs") || hit.doc_id.contains(".ts") || hit.doc_id.contains(".js") {
-                                code_patterns.push_str(&format!("// Reference from {}\n{}\n", hit.doc_id, hit.content.unwrap_or_default()));
+                                let reference = truncate_content(hit.content.as_deref().unwrap_or(""), engine.max_content_len);
+                                code_patterns.push_str(&format!("// Reference from {}\n{}\n", hit.doc_id, reference));
                             }
                         }
                     }
                 }
 
                 let coder_prompt = format!(
-                    "You are a Senior Software Engineer. Task: {}.\n\nReference Material Found:\n{}\n\nGenerate high-quality, production-ready code. Include comments and ensure best practices. Output blocks in Markdown.", 
-                    description, code_patterns
+                    "You are a Senior Software Engineer. Task: {}.\n\nReference Material Found:\n{}{}{}\n\nGenerate high-quality, production-ready code. Include comments and ensure best practices. Output blocks in Markdown.{}", 
+                    description, code_patterns, graph_section, session_section, format_section
                 );
-                call_llm(&coder_prompt).await.unwrap_or_else(|_| "Coding task failed.".into())
+                self.call_llm(task_id, &coder_prompt).await.unwrap_or_else(|_| "Coding task failed.".into())
             },
             AgentType::Ingestor => {
                 // Parse "INGEST_FILE|<doc_id>|<content>"
@@ -409,11 +1690,9 @@ impl AgentOrchestrator {
                 };
 
                 // Chunking logic
-                let chunks: Vec<String> = if raw_content.len() > 3000 {
-                    raw_content.as_bytes()
-                        .chunks(2000)
-                        .map(|c| String::from_utf8_lossy(c).to_string())
-                        .collect()
+                let tokenizer = default_tokenizer();
+                let chunks: Vec<String> = if tokenizer.count_tokens(&raw_content) > 750 {
+                    chunk_by_tokens(tokenizer.as_ref(), &raw_content, 500)
                 } else {
                     vec![raw_content.to_string()]
                 };
@@ -422,6 +1701,11 @@ impl AgentOrchestrator {
                 let mut total_rels = 0;
 
                 if let Some(ref graph) = self.graph_manager {
+                    // Extracted nodes inherit the document's collection so collection grants cover them
+                    let collection = match self.search_engine {
+                        Some(ref engine) => engine.vector_db.collection_of(&doc_id).await,
+                        None => None,
+                    };
                     for (i, chunk) in chunks.iter().enumerate() {
                         let chunk_id = format!("chunk-{}-{}", doc_id, i);
                         
@@ -431,47 +1715,51 @@ impl AgentOrchestrator {
                             Extract important entities and their relationships.\n\
                             Output Format per line:\n\
                             ENTITY|<id_slug>|<Label>|<name_property>\n\
-                            REL|<from_id_slug>|<to_id_slug>|<TYPE>\n\n\
+                            REL|<from_id_slug>|<to_id_slug>|<TYPE>|<strength from 0.0 to 1.0>\n\n\
                             Chunk Segment:\n{}", 
                             doc_id, chunk
                         );
 
-                        if let Ok(response) = call_llm(&extraction_prompt).await {
+                        if let Ok(response) = self.call_llm(task_id, &extraction_prompt).await {
+                             // 2. Merge into the graph, reusing nodes that already carry the same name
+                             let (entities, relationships) = parse_extraction(&response);
+                             let mut ids: HashMap<String, String> = HashMap::new();
                              let mut chunk_entities = Vec::new();
-                             for line in response.lines() {
-                                 let parts: Vec<&str> = line.split('|').collect();
-                                 if parts.len() >= 4 && parts[0].trim() == "ENTITY" {
-                                     let ent = crate::core::graph_manager::Entity {
-                                         id: parts[1].trim().to_string(),
-                                         label: parts[2].trim().to_string(),
-                                         properties: std::collections::HashMap::from([
-                                             ("name".to_string(), parts[3].trim().to_string())
-                                         ])
-                                     };
-                                     chunk_entities.push(ent.id.clone());
-                                     let _ = graph.add_entity(ent).await;
+                             for mut ent in entities {
+                                 let slug = ent.id.clone();
+                                 if let Some(ref collection) = collection {
+                                     ent.properties.insert("collection".to_string(), collection.clone());
+                                 }
+                                 if let Ok(id) = graph.merge_entity(ent).await {
+                                     ids.insert(slug, id.clone());
+                                     chunk_entities.push(id);
                                      total_entities += 1;
-                                 } else if parts.len() >= 4 && parts[0].trim() == "REL" {
-                                     let rel = crate::core::graph_manager::Relationship {
-                                         from_id: parts[1].trim().to_string(),
-                                         to_id: parts[2].trim().to_string(),
-                                         rel_type: parts[3].trim().to_string(),
-                                         properties: std::collections::HashMap::new()
-                                     };
-                                     let _ = graph.add_relationship(rel).await;
-                                     total_rels += 1;
                                  }
                              }
+                             for mut rel in relationships {
+                                 for endpoint in [&mut rel.from_id, &mut rel.to_id] {
+                                     if let Some(id) = ids.get(endpoint.as_str()) {
+                                         *endpoint = id.clone();
+                                     } else if let Some(existing) = graph.find_entity_by_name(endpoint).await {
+                                         *endpoint = existing.id;
+                                     }
+                                 }
+                                 let _ = graph.add_relationship(rel).await;
+                                 total_rels += 1;
+                             }
 
                              // 3. Create a Chunk node and link everything
-                             let chunk_node = crate::core::graph_manager::Entity {
+                             let mut chunk_node = crate::core::graph_manager::Entity {
                                  id: chunk_id.clone(),
                                  label: "Chunk".to_string(),
                                  properties: std::collections::HashMap::from([
                                      ("doc_source".to_string(), doc_id.clone()),
-                                     ("content_preview".to_string(), chunk.chars().take(200).collect())
+                                     ("content_preview".to_string(), truncate_content(chunk, CHUNK_PREVIEW_CHARS))
                                  ])
                              };
+                             if let Some(ref collection) = collection {
+                                 chunk_node.properties.insert("collection".to_string(), collection.clone());
+                             }
                              let _ = graph.add_entity(chunk_node).await;
 
                              for ent_id in chunk_entities {
@@ -479,7 +1767,9 @@ impl AgentOrchestrator {
                                      from_id: chunk_id.clone(),
                                      to_id: ent_id,
                                      rel_type: "EXTRACTED_FROM".to_string(),
-                                     properties: std::collections::HashMap::new()
+                                     weight: 1.0,
+                                     properties: std::collections::HashMap::new(),
+                                     direction: None,
                                  }).await;
                              }
                         }
@@ -489,7 +1779,8 @@ impl AgentOrchestrator {
                 format!("Ingestion Complete for {}. Extracted {} entities and {} correlations across {} graph chunks.", doc_id, total_entities, total_rels, chunks.len())
             },
             _ => {
-                call_llm(description).await.unwrap_or_else(|e| format!("Generic Agent execution failed: {}", e))
+                let prompt = format!("{}{}{}", description, session_section, format_section);
+                self.call_llm(task_id, &prompt).await.unwrap_or_else(|e| format!("Generic Agent execution failed: {}", e))
             }
         }
     }
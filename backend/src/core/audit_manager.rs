@@ -1,8 +1,66 @@
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+pub enum EventKind {
+    Query,
+    Ingest,
+    AccessDenied,
+    TaskSubmitted,
+    TaskCompleted,
+    Configuration,
+    ToolCall,
+    #[default]
+    Other,
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            EventKind::Query => "Query",
+            EventKind::Ingest => "Ingest",
+            EventKind::AccessDenied => "Access Denied",
+            EventKind::TaskSubmitted => "Task Submitted",
+            EventKind::TaskCompleted => "Task Completed",
+            EventKind::Configuration => "Configuration",
+            EventKind::ToolCall => "Tool Call",
+            EventKind::Other => "Other",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Default)]
+pub enum Severity {
+    #[default]
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    /// Parses the legacy free-form risk strings; unknown values map to `Low`
+    pub fn parse(risk: &str) -> Self {
+        match risk.trim().to_lowercase().as_str() {
+            "medium" => Severity::Medium,
+            "high" => Severity::High,
+            "critical" => Severity::Critical,
+            _ => Severity::Low,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct SecurityLog {
     pub id: String,
@@ -10,17 +68,151 @@ pub struct SecurityLog {
     pub event: String,
     pub user: String,
     pub status: String,
+    /// String rendering of `severity`, kept for existing consumers
     pub risk: String,
+    #[serde(default)]
+    pub kind: EventKind,
+    #[serde(default)]
+    pub severity: Severity,
+    #[serde(default)]
+    pub details: HashMap<String, String>,
+    /// Hash of the preceding entry; empty for the first entry in the chain
+    #[serde(default)]
+    pub prev_hash: String,
+    /// SHA-256 over this entry's content and `prev_hash`; empty for entries written before chaining
+    #[serde(default)]
+    pub hash: String,
+}
+
+impl SecurityLog {
+    /// Hex SHA-256 of the entry's fields (details in key order) and `prev_hash`
+    pub fn compute_hash(&self) -> String {
+        use sha2::{Digest, Sha256};
+
+        let details: std::collections::BTreeMap<&String, &String> = self.details.iter().collect();
+        let canonical = serde_json::json!({
+            "id": self.id,
+            "timestamp": self.timestamp,
+            "event": self.event,
+            "user": self.user,
+            "status": self.status,
+            "risk": self.risk,
+            "kind": self.kind,
+            "severity": self.severity,
+            "details": details,
+            "prev_hash": self.prev_hash,
+        });
+        Sha256::digest(canonical.to_string().as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+/// Outcome of `AuditManager::verify_chain`
+#[derive(Debug, Clone, Serialize)]
+pub struct ChainVerification {
+    pub valid: bool,
+    /// Chained entries checked, across the archive and memory
+    pub entries_checked: usize,
+    /// Entries predating hash chaining, which cannot be verified
+    pub legacy_entries: usize,
+    /// First entry at which the chain breaks
+    pub broken_at: Option<String>,
+    pub reason: Option<String>,
+}
+
+/// Filters for `AuditManager::query_logs`; timestamps are unix seconds, inclusive
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditQuery {
+    pub user: Option<String>,
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    /// Minimum severity to include
+    pub severity: Option<Severity>,
+    pub kind: Option<EventKind>,
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub offset: usize,
+}
+
+impl AuditQuery {
+    fn matches(&self, log: &SecurityLog) -> bool {
+        self.user.as_deref().map_or(true, |u| log.user == u)
+            && self.since.map_or(true, |t| log.timestamp >= t)
+            && self.until.map_or(true, |t| log.timestamp <= t)
+            && self.severity.map_or(true, |s| log.severity >= s)
+            && self.kind.map_or(true, |k| log.kind == k)
+    }
+}
+
+/// Entries kept in memory (and in `audit_logs.json`) before older ones are archived
+const DEFAULT_MAX_ENTRIES: usize = 100;
+/// Window over which a user's access denials are counted when `AUDIT_DENIAL_WINDOW_SECS` is unset
+pub const DEFAULT_DENIAL_WINDOW_SECS: u64 = 300;
+/// Denials within the window at which a user's denials are logged as High, then Critical
+const HIGH_RISK_DENIALS: usize = 3;
+const CRITICAL_RISK_DENIALS: usize = 10;
+
+/// Risk implied by `denials` access denials within the window
+pub fn denial_risk(denials: usize) -> Severity {
+    if denials >= CRITICAL_RISK_DENIALS {
+        Severity::Critical
+    } else if denials >= HIGH_RISK_DENIALS {
+        Severity::High
+    } else {
+        Severity::Low
+    }
+}
+
+/// A user's recent access denials, as reported by `AuditManager::denial_summary`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DenialRisk {
+    pub user: String,
+    /// Denials within the window
+    pub denials: usize,
+    pub risk: Severity,
+    pub last_denied: u64,
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+}
+
+/// `YYYY-MM-DD` (UTC) for a unix timestamp
+fn utc_date(timestamp: u64) -> String {
+    // Civil-from-days, http://howardhinnant.github.io/date_algorithms.html
+    let z = (timestamp / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
 }
 
 #[derive(Clone)]
 pub struct AuditManager {
     logs: Arc<Mutex<Vec<SecurityLog>>>,
+    data_path: String,
+    max_entries: usize,
+    /// Recent denial timestamps per user, oldest first
+    denials: Arc<Mutex<HashMap<String, VecDeque<u64>>>>,
+    denial_window_secs: u64,
 }
 
 impl AuditManager {
     pub fn new() -> Self {
         let data_path = std::env::var("DATA_PATH").unwrap_or_else(|_| "/data".to_string());
+        Self::at_path(&data_path)
+    }
+
+    /// Manager persisting under `data_path`; the in-memory cap comes from `AUDIT_MAX_ENTRIES`
+    /// and the denial window from `AUDIT_DENIAL_WINDOW_SECS`
+    pub fn at_path(data_path: &str) -> Self {
         let log_file = format!("{}/audit_logs.json", data_path);
         
         let mut logs = Vec::new();
@@ -30,41 +222,261 @@ impl AuditManager {
             }
         }
 
-        Self { logs: Arc::new(Mutex::new(logs)) }
+        let max_entries = std::env::var("AUDIT_MAX_ENTRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|n: &usize| *n > 0)
+            .unwrap_or(DEFAULT_MAX_ENTRIES);
+        let denial_window_secs = std::env::var("AUDIT_DENIAL_WINDOW_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|n: &u64| *n > 0)
+            .unwrap_or(DEFAULT_DENIAL_WINDOW_SECS);
+
+        Self {
+            logs: Arc::new(Mutex::new(logs)),
+            data_path: data_path.to_string(),
+            max_entries,
+            denials: Arc::new(Mutex::new(HashMap::new())),
+            denial_window_secs,
+        }
+    }
+
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries.max(1);
+        self
+    }
+
+    pub fn with_denial_window(mut self, window_secs: u64) -> Self {
+        self.denial_window_secs = window_secs.max(1);
+        self
+    }
+
+    fn archive_dir(&self) -> String {
+        format!("{}/audit_archive", self.data_path)
     }
 
     pub async fn save_logs(&self) {
-        let data_path = std::env::var("DATA_PATH").unwrap_or_else(|_| "/data".to_string());
-        let log_file = format!("{}/audit_logs.json", data_path);
+        let log_file = format!("{}/audit_logs.json", self.data_path);
         let logs = self.logs.lock().await;
         if let Ok(content) = serde_json::to_string(&*logs) {
             let _ = std::fs::write(log_file, content);
         }
     }
+
+    /// Appends evicted entries to `audit_archive/audit-YYYY-MM-DD.jsonl` by entry date
+    fn archive(&self, evicted: &[SecurityLog]) {
+        use std::io::Write;
+
+        let dir = self.archive_dir();
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            println!("WARN: Failed to create audit archive {}: {}", dir, e);
+            return;
+        }
+        let mut by_date: HashMap<String, Vec<&SecurityLog>> = HashMap::new();
+        for log in evicted {
+            by_date.entry(utc_date(log.timestamp)).or_default().push(log);
+        }
+        for (date, entries) in by_date {
+            let path = format!("{}/audit-{}.jsonl", dir, date);
+            let file = std::fs::OpenOptions::new().create(true).append(true).open(&path);
+            match file {
+                Ok(mut f) => {
+                    for entry in entries {
+                        if let Ok(line) = serde_json::to_string(entry) {
+                            let _ = writeln!(f, "{}", line);
+                        }
+                    }
+                }
+                Err(e) => println!("WARN: Failed to archive audit logs to {}: {}", path, e),
+            }
+        }
+    }
+
+    /// Archived entries from the dated files overlapping `[since, until]`
+    fn load_archived(&self, since: Option<u64>, until: Option<u64>) -> Vec<SecurityLog> {
+        let first = since.map(utc_date);
+        let last = until.map(utc_date);
+        let entries = match std::fs::read_dir(self.archive_dir()) {
+            Ok(e) => e,
+            Err(_) => return vec![],
+        };
+
+        let mut logs = Vec::new();
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let date = match name.strip_prefix("audit-").and_then(|n| n.strip_suffix(".jsonl")) {
+                Some(d) => d.to_string(),
+                None => continue,
+            };
+            // ISO dates compare correctly as strings
+            if first.as_ref().map_or(false, |f| &date < f) || last.as_ref().map_or(false, |l| &date > l) {
+                continue;
+            }
+            if let Ok(content) = std::fs::read_to_string(entry.path()) {
+                logs.extend(content.lines().filter_map(|l| serde_json::from_str::<SecurityLog>(l).ok()));
+            }
+        }
+        logs
+    }
     
+    /// Free-form entry; `risk` is parsed into a `Severity`
     pub async fn log_event(&self, event: &str, user: &str, status: &str, risk: &str) {
-        let log = SecurityLog {
+        self.record(EventKind::Other, Severity::parse(risk), event, user, status, HashMap::new()).await;
+    }
+
+    /// Structured entry with a typed kind and severity plus arbitrary detail fields
+    pub async fn record(&self, kind: EventKind, severity: Severity, event: &str, user: &str, status: &str, details: HashMap<String, String>) {
+        let mut log = SecurityLog {
             id: Uuid::new_v4().to_string(),
-            timestamp: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs(),
+            timestamp: now_secs(),
             event: event.to_string(),
             user: user.to_string(),
             status: status.to_string(),
-            risk: risk.to_string(),
+            risk: severity.to_string(),
+            kind,
+            severity,
+            details,
+            prev_hash: String::new(),
+            hash: String::new(),
         };
         {
             let mut logs = self.logs.lock().await;
+            log.prev_hash = logs.last().map(|l| l.hash.clone()).unwrap_or_default();
+            log.hash = log.compute_hash();
             logs.push(log);
-            // Keep last 100 logs
-            if logs.len() > 100 {
-                logs.remove(0);
+            let overflow = logs.len().saturating_sub(self.max_entries);
+            let evicted: Vec<SecurityLog> = logs.drain(..overflow).collect();
+            // Archive under the lock so archive files keep chain order
+            if !evicted.is_empty() {
+                self.archive(&evicted);
             }
         }
         self.save_logs().await;
     }
     
-    pub async fn get_logs(&self) -> Vec<SecurityLog> {
-        let mut logs = self.logs.lock().await.clone();
-        logs.reverse(); // Newest first
-        logs
+    /// Records an access denial of `resource` to `user`. The entry's severity is `base`,
+    /// raised when the user keeps getting denied within the window so that probing stands
+    /// out in the security logs. Returns the severity recorded.
+    pub async fn record_denial(&self, base: Severity, event: &str, user: &str, resource: &str, mut details: HashMap<String, String>) -> Severity {
+        let now = now_secs();
+        let recent = {
+            let mut denials = self.denials.lock().await;
+            let times = denials.entry(user.to_string()).or_default();
+            times.push_back(now);
+            while times.front().map_or(false, |&t| t + self.denial_window_secs <= now) {
+                times.pop_front();
+            }
+            times.len()
+        };
+
+        let severity = base.max(denial_risk(recent));
+        if severity > base {
+            println!("WARN: {} access denials for user {} in the last {}s (risk {})", recent, user, self.denial_window_secs, severity);
+        }
+        details.insert("resource".to_string(), resource.to_string());
+        details.insert("denials_in_window".to_string(), recent.to_string());
+        self.record(EventKind::AccessDenied, severity, event, user, "Forbidden", details).await;
+        severity
+    }
+
+    /// Users denied access within the window, riskiest first
+    pub async fn denial_summary(&self) -> Vec<DenialRisk> {
+        let now = now_secs();
+        let mut denials = self.denials.lock().await;
+        denials.retain(|_, times| {
+            while times.front().map_or(false, |&t| t + self.denial_window_secs <= now) {
+                times.pop_front();
+            }
+            !times.is_empty()
+        });
+        let mut summary: Vec<DenialRisk> = denials.iter()
+            .map(|(user, times)| DenialRisk {
+                user: user.clone(),
+                denials: times.len(),
+                risk: denial_risk(times.len()),
+                last_denied: times.back().copied().unwrap_or_default(),
+            })
+            .collect();
+        summary.sort_by(|a, b| b.denials.cmp(&a.denials).then_with(|| a.user.cmp(&b.user)));
+        summary
+    }
+
+    /// Newest first, optionally limited to a minimum severity and an event kind
+    pub async fn get_logs(&self, min_severity: Option<Severity>, kind: Option<EventKind>) -> Vec<SecurityLog> {
+        self.query_logs(&AuditQuery { severity: min_severity, kind, ..Default::default() }).await
+    }
+
+    /// Newest-first page of entries matching `query`. Archived entries are included
+    /// only when the query is bounded by `since` or `until`.
+    pub async fn query_logs(&self, query: &AuditQuery) -> Vec<SecurityLog> {
+        let mut matched: Vec<SecurityLog> = if query.since.is_some() || query.until.is_some() {
+            self.load_archived(query.since, query.until).into_iter().filter(|l| query.matches(l)).collect()
+        } else {
+            vec![]
+        };
+        {
+            let logs = self.logs.lock().await;
+            let archived: HashSet<String> = matched.iter().map(|l| l.id.clone()).collect();
+            matched.extend(logs.iter().filter(|l| !archived.contains(&l.id) && query.matches(l)).cloned());
+        }
+        // Stable sort keeps insertion order within a second; reverse for newest first
+        matched.sort_by_key(|l| l.timestamp);
+        matched.reverse();
+        matched.into_iter()
+            .skip(query.offset)
+            .take(query.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+
+    /// Walks the archived and in-memory entries in order, checking every hash and link.
+    /// Any edited, inserted or removed entry breaks the chain from that point on.
+    pub async fn verify_chain(&self) -> ChainVerification {
+        let mut entries = self.load_archive_in_order();
+        entries.extend(self.logs.lock().await.iter().cloned());
+
+        // Entries written before chaining carry no hash; the chain starts after them
+        let legacy_entries = entries.iter().take_while(|l| l.hash.is_empty()).count();
+        let mut prev_hash: Option<String> = None;
+        let mut checked = 0;
+        for log in entries.iter().skip(legacy_entries) {
+            let broken = |reason: &str| ChainVerification {
+                valid: false,
+                entries_checked: checked,
+                legacy_entries,
+                broken_at: Some(log.id.clone()),
+                reason: Some(reason.to_string()),
+            };
+            if let Some(ref expected) = prev_hash {
+                if &log.prev_hash != expected {
+                    return broken("prev_hash does not match the preceding entry");
+                }
+            } else if !log.prev_hash.is_empty() && legacy_entries == 0 {
+                return broken("first entry links to a missing predecessor");
+            }
+            if log.compute_hash() != log.hash {
+                return broken("entry content does not match its hash");
+            }
+            prev_hash = Some(log.hash.clone());
+            checked += 1;
+        }
+
+        ChainVerification { valid: true, entries_checked: checked, legacy_entries, broken_at: None, reason: None }
+    }
+
+    /// Every archived entry in write order (files by date, lines in append order)
+    fn load_archive_in_order(&self) -> Vec<SecurityLog> {
+        let mut files: Vec<std::path::PathBuf> = match std::fs::read_dir(self.archive_dir()) {
+            Ok(entries) => entries.flatten()
+                .map(|e| e.path())
+                .filter(|p| p.file_name().map_or(false, |n| n.to_string_lossy().starts_with("audit-")))
+                .collect(),
+            Err(_) => return vec![],
+        };
+        files.sort();
+        files.iter()
+            .filter_map(|p| std::fs::read_to_string(p).ok())
+            .flat_map(|content| content.lines().filter_map(|l| serde_json::from_str::<SecurityLog>(l).ok()).collect::<Vec<_>>())
+            .collect()
     }
 }
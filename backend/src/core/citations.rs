@@ -0,0 +1,38 @@
+use crate::core::llm::tokenizer::Tokenizer;
+use crate::core::search_engine::SearchHit;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A `[n]` marker in a generated answer resolved to the document it refers to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Citation {
+    pub index: usize,
+    pub doc_id: String,
+}
+
+/// Numbers each hit `[1]`, `[2]`, ... so the model can cite them, truncating
+/// each document to `max_tokens`.
+pub fn build_cited_context(hits: &[SearchHit], tokenizer: &dyn Tokenizer, max_tokens: usize) -> String {
+    hits.iter()
+        .enumerate()
+        .map(|(i, h)| format!("[{}] (doc {}): {}", i + 1, h.doc_id, tokenizer.truncate(h.content.as_deref().unwrap_or(""), max_tokens)))
+        .collect::<Vec<String>>()
+        .join("\n\n")
+}
+
+/// Resolves `[n]` and `[n, m]` markers against the numbered sources, in order of first
+/// appearance. Markers outside the source range are ignored.
+pub fn extract_citations(answer: &str, sources: &[String]) -> Vec<Citation> {
+    let marker = Regex::new(r"\[(\d+(?:\s*,\s*\d+)*)\]").expect("valid citation pattern");
+    let mut citations: Vec<Citation> = Vec::new();
+    for cap in marker.captures_iter(answer) {
+        for n in cap[1].split(',').filter_map(|n| n.trim().parse::<usize>().ok()) {
+            if n == 0 || n > sources.len() || citations.iter().any(|c| c.index == n) {
+                continue;
+            }
+            citations.push(Citation { index: n, doc_id: sources[n - 1].clone() });
+        }
+    }
+    citations
+}
@@ -0,0 +1,132 @@
+//! Relevance feedback on search results
+//!
+//! Users mark a hit as relevant or not for the query that returned it. Votes are kept
+//! one per user per (query, document) in `{DATA_PATH}/search_feedback.json`, and turn into
+//! a bounded score multiplier applied when results are fused.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+/// Largest fraction by which feedback may raise or lower a score when
+/// `SEARCH_FEEDBACK_WEIGHT` is unset
+pub const DEFAULT_FEEDBACK_WEIGHT: f32 = 0.1;
+/// Neutral votes assumed for every document, so a single vote only nudges the ranking
+const PRIOR_VOTES: f32 = 3.0;
+/// Share of the adjustment driven by votes on the same query; the rest comes from
+/// all votes on the document
+const QUERY_SHARE: f32 = 0.7;
+
+/// Votes on one document: query key -> user -> relevant
+type DocumentVotes = HashMap<String, HashMap<String, bool>>;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct FeedbackTally {
+    pub relevant: usize,
+    pub not_relevant: usize,
+}
+
+impl FeedbackTally {
+    fn add(&mut self, relevant: bool) {
+        if relevant {
+            self.relevant += 1;
+        } else {
+            self.not_relevant += 1;
+        }
+    }
+
+    /// Net vote share in [-1, 1], damped towards 0 while there are few votes
+    fn signal(&self) -> f32 {
+        let (up, down) = (self.relevant as f32, self.not_relevant as f32);
+        (up - down) / (up + down + PRIOR_VOTES)
+    }
+}
+
+/// Queries are compared case-insensitively with whitespace collapsed
+pub fn feedback_query_key(query: &str) -> String {
+    query.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+pub struct FeedbackStore {
+    path: Option<String>,
+    /// Maximum relative score change in [0, 1]
+    weight: f32,
+    votes: Mutex<HashMap<String, DocumentVotes>>,
+    /// Held across a save so an older snapshot never overwrites a newer one
+    persist_lock: tokio::sync::Mutex<()>,
+}
+
+impl FeedbackStore {
+    /// In-memory store, for tests and ephemeral deployments
+    pub fn new(weight: f32) -> Self {
+        Self { path: None, weight: weight.clamp(0.0, 1.0), votes: Mutex::new(HashMap::new()), persist_lock: tokio::sync::Mutex::new(()) }
+    }
+
+    /// Store persisted to `{data_path}/search_feedback.json`, weighted by `SEARCH_FEEDBACK_WEIGHT`
+    pub fn at_path(data_path: &str) -> Self {
+        let path = format!("{}/search_feedback.json", data_path);
+        let votes = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        let weight = std::env::var("SEARCH_FEEDBACK_WEIGHT")
+            .ok()
+            .and_then(|v| v.parse::<f32>().ok())
+            .unwrap_or(DEFAULT_FEEDBACK_WEIGHT);
+        Self { path: Some(path), weight: weight.clamp(0.0, 1.0), votes: Mutex::new(votes), persist_lock: tokio::sync::Mutex::new(()) }
+    }
+
+    /// Votes are plain data, so a panic elsewhere while holding the lock leaves them usable
+    fn votes(&self) -> MutexGuard<'_, HashMap<String, DocumentVotes>> {
+        self.votes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Record `user_id`'s vote on `doc_id` for `query`, replacing their earlier vote
+    pub async fn record(&self, user_id: &str, query: &str, doc_id: &str, relevant: bool) {
+        self.votes().entry(doc_id.to_string())
+            .or_default()
+            .entry(feedback_query_key(query))
+            .or_default()
+            .insert(user_id.to_string(), relevant);
+
+        if let Some(ref path) = self.path {
+            // Snapshot under the vote lock, write without it so scoring isn't held up
+            let _persisting = self.persist_lock.lock().await;
+            let content = serde_json::to_string(&*self.votes());
+            if let Ok(content) = content {
+                if let Err(e) = tokio::fs::write(path, content).await {
+                    println!("WARN: Failed to save search feedback: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Votes on `doc_id` for `query`, and across all queries
+    pub fn tally(&self, query: &str, doc_id: &str) -> (FeedbackTally, FeedbackTally) {
+        let votes = self.votes();
+        let mut for_query = FeedbackTally::default();
+        let mut overall = FeedbackTally::default();
+        if let Some(by_query) = votes.get(doc_id) {
+            let key = feedback_query_key(query);
+            for (q, users) in by_query {
+                for &relevant in users.values() {
+                    overall.add(relevant);
+                    if *q == key {
+                        for_query.add(relevant);
+                    }
+                }
+            }
+        }
+        (for_query, overall)
+    }
+
+    /// Score multiplier for `doc_id` under `query`, within `1 ± weight`
+    pub fn score_factor(&self, query: &str, doc_id: &str) -> f32 {
+        if self.weight == 0.0 {
+            return 1.0;
+        }
+        let (for_query, overall) = self.tally(query, doc_id);
+        let signal = QUERY_SHARE * for_query.signal() + (1.0 - QUERY_SHARE) * overall.signal();
+        1.0 + self.weight * signal
+    }
+}
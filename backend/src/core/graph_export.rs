@@ -0,0 +1,148 @@
+use crate::core::graph_manager::{ContextGraph, Entity};
+use std::collections::{BTreeSet, HashSet};
+
+/// Serialization formats offered by `/api/graph/export`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    GraphMl,
+    Cypher,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "graphml" | "xml" => Some(Self::GraphMl),
+            "cypher" => Some(Self::Cypher),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::GraphMl => "application/graphml+xml",
+            Self::Cypher => "text/plain; charset=utf-8",
+            Self::Json => "application/json",
+        }
+    }
+
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            Self::GraphMl => "graphml",
+            Self::Cypher => "cypher",
+            Self::Json => "json",
+        }
+    }
+}
+
+/// Entities plus a bare `Entity` node for every relationship endpoint that has no
+/// entity record, so every edge in the output refers to a declared node
+fn nodes_of(graph: &ContextGraph) -> Vec<Entity> {
+    let mut nodes = graph.entities.clone();
+    let mut declared: HashSet<String> = nodes.iter().map(|e| e.id.clone()).collect();
+    for rel in &graph.relationships {
+        for id in [&rel.from_id, &rel.to_id] {
+            if declared.insert(id.clone()) {
+                nodes.push(Entity { id: id.clone(), label: "Entity".to_string(), properties: Default::default() });
+            }
+        }
+    }
+    nodes
+}
+
+fn xml_escape(value: &str) -> String {
+    value.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// GraphML document loadable by Gephi, yEd or Neo4j's APOC import. Node labels,
+/// relationship types and every property key become `<data>` attributes.
+pub fn to_graphml(graph: &ContextGraph) -> String {
+    let nodes = nodes_of(graph);
+    let node_keys: BTreeSet<&str> = nodes.iter().flat_map(|n| n.properties.keys().map(|k| k.as_str())).collect();
+    let edge_keys: BTreeSet<&str> = graph.relationships.iter().flat_map(|r| r.properties.keys().map(|k| k.as_str())).collect();
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+    out.push_str("  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n");
+    out.push_str("  <key id=\"rel_type\" for=\"edge\" attr.name=\"rel_type\" attr.type=\"string\"/>\n");
+    out.push_str("  <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"double\"/>\n");
+    for key in &node_keys {
+        out.push_str(&format!("  <key id=\"n_{0}\" for=\"node\" attr.name=\"{0}\" attr.type=\"string\"/>\n", xml_escape(key)));
+    }
+    for key in &edge_keys {
+        out.push_str(&format!("  <key id=\"e_{0}\" for=\"edge\" attr.name=\"{0}\" attr.type=\"string\"/>\n", xml_escape(key)));
+    }
+    out.push_str("  <graph id=\"brainvault\" edgedefault=\"directed\">\n");
+
+    for node in &nodes {
+        out.push_str(&format!("    <node id=\"{}\">\n", xml_escape(&node.id)));
+        out.push_str(&format!("      <data key=\"label\">{}</data>\n", xml_escape(&node.label)));
+        let mut props: Vec<_> = node.properties.iter().collect();
+        props.sort();
+        for (key, value) in props {
+            out.push_str(&format!("      <data key=\"n_{}\">{}</data>\n", xml_escape(key), xml_escape(value)));
+        }
+        out.push_str("    </node>\n");
+    }
+    for (i, rel) in graph.relationships.iter().enumerate() {
+        out.push_str(&format!(
+            "    <edge id=\"e{}\" source=\"{}\" target=\"{}\">\n",
+            i, xml_escape(&rel.from_id), xml_escape(&rel.to_id)
+        ));
+        out.push_str(&format!("      <data key=\"rel_type\">{}</data>\n", xml_escape(&rel.rel_type)));
+        out.push_str(&format!("      <data key=\"weight\">{}</data>\n", rel.weight));
+        let mut props: Vec<_> = rel.properties.iter().collect();
+        props.sort();
+        for (key, value) in props {
+            out.push_str(&format!("      <data key=\"e_{}\">{}</data>\n", xml_escape(key), xml_escape(value)));
+        }
+        out.push_str("    </edge>\n");
+    }
+
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
+
+fn cypher_string(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Backtick-quotes labels, types and keys so arbitrary names stay valid identifiers
+fn cypher_identifier(value: &str) -> String {
+    format!("`{}`", value.replace('`', "``"))
+}
+
+fn cypher_fields<'a>(props: impl Iterator<Item = (&'a String, &'a String)>) -> Vec<String> {
+    let mut props: Vec<_> = props.collect();
+    props.sort();
+    props.into_iter()
+        .map(|(k, v)| format!("{}: {}", cypher_identifier(k), cypher_string(v)))
+        .collect()
+}
+
+/// Cypher script for Neo4j: one `CREATE` per node keyed by its `id` property, then one
+/// `MATCH ... CREATE` per relationship
+pub fn to_cypher(graph: &ContextGraph) -> String {
+    let mut out = String::new();
+    for node in nodes_of(graph) {
+        let id = String::from("id");
+        let fields = cypher_fields(
+            std::iter::once((&id, &node.id)).chain(node.properties.iter().filter(|(k, _)| k.as_str() != "id"))
+        );
+        out.push_str(&format!("CREATE (:{} {{{}}});\n", cypher_identifier(&node.label), fields.join(", ")));
+    }
+    for rel in &graph.relationships {
+        let mut fields = vec![format!("`weight`: {}", rel.weight)];
+        fields.extend(cypher_fields(rel.properties.iter().filter(|(k, _)| k.as_str() != "weight")));
+        out.push_str(&format!(
+            "MATCH (a {{id: {}}}), (b {{id: {}}}) CREATE (a)-[:{} {{{}}}]->(b);\n",
+            cypher_string(&rel.from_id), cypher_string(&rel.to_id), cypher_identifier(&rel.rel_type), fields.join(", ")
+        ));
+    }
+    out
+}
@@ -1,41 +1,172 @@
 use crate::db::barq_graph::BarqGraphClient;
+use crate::error::{BrainVaultError, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use utoipa::ToSchema;
 
 pub struct KnowledgeGraphManager {
     graph_db: BarqGraphClient,
     entities: Arc<RwLock<HashMap<String, Entity>>>,
     relationships: Arc<RwLock<Vec<Relationship>>>,
+    data_path: String,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 pub struct Entity {
     pub id: String,
     pub label: String,
     pub properties: HashMap<String, String>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 pub struct Relationship {
     pub from_id: String,
     pub to_id: String,
     pub rel_type: String,
+    /// Connection strength; higher-weighted neighbors are returned first
+    #[serde(default = "default_weight")]
+    pub weight: f32,
     #[serde(default)]
     pub properties: HashMap<String, String>,
+    /// How a traversal followed this relationship; set on the edges of a `ContextGraph`
+    /// returned by a traversal, never stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direction: Option<EdgeDirection>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+fn default_weight() -> f32 {
+    1.0
+}
+
+/// Which way a traversal followed a relationship
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeDirection {
+    /// From `from_id`, the node already reached, to `to_id`
+    Outgoing,
+    /// Against the edge: from `to_id` back to `from_id`
+    Incoming,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 pub struct ContextGraph {
     pub entities: Vec<Entity>,
     pub relationships: Vec<Relationship>,
+    /// Hops from the start entity, keyed by entity id; set by traversals only
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub depths: HashMap<String, usize>,
+    /// Pass back as `cursor` to fetch the next page of a paged traversal
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// Longest `ContextGraph::summarize` output, so a dense neighborhood can't crowd out the
+/// rest of a prompt
+pub const MAX_SUMMARY_CHARS: usize = 2000;
+
+/// Largest name bucket `suggest_duplicates` compares pairwise
+pub const MAX_DUPLICATE_BLOCK: usize = 200;
+
+impl ContextGraph {
+    /// Compact text form of the neighborhood of `root_id` for LLM prompts: the entity,
+    /// then one line per relationship naming its source and target, in traversal order.
+    /// Stops at `MAX_SUMMARY_CHARS`, noting how many relationships were left out.
+    pub fn summarize(&self, root_id: &str) -> String {
+        let entity = |id: &str| self.entities.iter().find(|e| e.id == id);
+        let name = |id: &str| match entity(id) {
+            Some(e) => format!("{} [{}]", e.properties.get("name").unwrap_or(&e.id), e.id),
+            None => format!("[{}]", id),
+        };
+        let root = match entity(root_id) {
+            Some(e) => format!("{} ({}) [{}]", e.properties.get("name").unwrap_or(&e.id), e.label, e.id),
+            None => format!("[{}]", root_id),
+        };
+
+        let mut summary = format!("Entity: {}\n", root);
+        for (i, rel) in self.relationships.iter().enumerate() {
+            let line = format!("  - {} -{}-> {}\n", name(&rel.from_id), rel.rel_type, name(&rel.to_id));
+            if summary.len() + line.len() > MAX_SUMMARY_CHARS {
+                summary.push_str(&format!("  - ... {} more relationships\n", self.relationships.len() - i));
+                break;
+            }
+            summary.push_str(&line);
+        }
+        summary
+    }
+}
+
+/// Canonical form of an entity name for duplicate detection: lowercase words separated by
+/// single spaces, so "Machine-Learning" and "machine learning" match. `+` and `#` count as
+/// word characters, keeping "C++" and "C#" apart from "C".
+pub fn normalize_entity_name(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !(c.is_alphanumeric() || c == '+' || c == '#'))
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Similarity of two entity names in [0, 1]: the share of words in the longer name
+/// matched by a word in the other, where a word of 3+ characters also matches words it
+/// prefixes ("corp" ~ "corporation"). Identical normalized names score 1.0.
+pub fn name_similarity(a: &str, b: &str) -> f32 {
+    let a = normalize_entity_name(a);
+    let b = normalize_entity_name(b);
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    if a == b {
+        return 1.0;
+    }
+    let a_words: Vec<&str> = a.split(' ').collect();
+    let b_words: Vec<&str> = b.split(' ').collect();
+    let (short, long) = if a_words.len() <= b_words.len() { (a_words, b_words) } else { (b_words, a_words) };
+
+    let word_match = |x: &str, y: &str| x == y || (x.len().min(y.len()) >= 3 && (x.starts_with(y) || y.starts_with(x)));
+    let mut used = vec![false; long.len()];
+    let mut matched = 0;
+    for word in &short {
+        if let Some(i) = (0..long.len()).find(|&i| !used[i] && word_match(word, long[i])) {
+            used[i] = true;
+            matched += 1;
+        }
+    }
+    matched as f32 / long.len() as f32
+}
+
+/// Write both graph files under `data_path`. Each goes to a temporary file first and is
+/// renamed into place only once both are written, so a failure leaves the old pair.
+fn write_state(data_path: &str, entities: &HashMap<String, Entity>, relationships: &[Relationship]) -> std::io::Result<()> {
+    let ents_file = format!("{}/graph_entities.json", data_path);
+    let rels_file = format!("{}/graph_relationships.json", data_path);
+
+    let ents = serde_json::to_string(entities)?;
+    let rels = serde_json::to_string(relationships)?;
+    std::fs::write(format!("{}.tmp", ents_file), ents)?;
+    std::fs::write(format!("{}.tmp", rels_file), rels)?;
+    std::fs::rename(format!("{}.tmp", ents_file), ents_file)?;
+    std::fs::rename(format!("{}.tmp", rels_file), rels_file)
+}
+
+/// Two entities that probably describe the same thing
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DuplicateCandidate {
+    /// Better-connected entity, suggested as the one to keep
+    pub keep_id: String,
+    pub merge_id: String,
+    pub score: f32,
 }
 
 impl KnowledgeGraphManager {
     pub fn new(graph_db: BarqGraphClient) -> Self {
         let data_path = std::env::var("DATA_PATH").unwrap_or_else(|_| "/data".to_string());
-        
+        Self::at_path(graph_db, &data_path)
+    }
+
+    /// Manager whose graph files live in `data_path` instead of DATA_PATH
+    pub fn at_path(graph_db: BarqGraphClient, data_path: &str) -> Self {
         let mut entity_map = HashMap::new();
         let mut rel_list = Vec::new();
 
@@ -57,26 +188,19 @@ impl KnowledgeGraphManager {
             graph_db,
             entities: Arc::new(RwLock::new(entity_map)),
             relationships: Arc::new(RwLock::new(rel_list)),
+            data_path: data_path.to_string(),
         }
     }
 
     pub async fn save_state(&self) {
-        let data_path = std::env::var("DATA_PATH").unwrap_or_else(|_| "/data".to_string());
-        let ents_file = format!("{}/graph_entities.json", data_path);
-        let rels_file = format!("{}/graph_relationships.json", data_path);
-
         let ents = self.entities.read().await;
-        if let Ok(content) = serde_json::to_string(&*ents) {
-            let _ = std::fs::write(ents_file, content);
-        }
-
         let rels = self.relationships.read().await;
-        if let Ok(content) = serde_json::to_string(&*rels) {
-            let _ = std::fs::write(rels_file, content);
+        if let Err(e) = write_state(&self.data_path, &ents, &rels) {
+            println!("WARN: Failed to save graph state: {}", e);
         }
     }
 
-    pub async fn add_entity(&self, entity: Entity) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    pub async fn add_entity(&self, entity: Entity) -> Result<()> {
         // Try to create in Barq GraphDB using ID as key
         match self.graph_db.create_node(&entity.id, &entity.label, None).await {
             Ok(node_id) => {
@@ -96,7 +220,47 @@ impl KnowledgeGraphManager {
         Ok(())
     }
     
-    pub async fn add_relationship(&self, rel: Relationship) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    pub async fn get_entity(&self, id: &str) -> Option<Entity> {
+        self.entities.read().await.get(id).cloned()
+    }
+
+    /// Existing entity whose name, or failing that id, normalizes the same as `name`
+    pub async fn find_entity_by_name(&self, name: &str) -> Option<Entity> {
+        let wanted = normalize_entity_name(name);
+        if wanted.is_empty() {
+            return None;
+        }
+        let entities = self.entities.read().await;
+        entities.values()
+            .find(|e| e.properties.get("name").map(|n| normalize_entity_name(n) == wanted).unwrap_or(false))
+            .or_else(|| entities.values().find(|e| normalize_entity_name(&e.id) == wanted))
+            .cloned()
+    }
+
+    /// Add an entity unless one with the same normalized name exists, in which case its
+    /// missing properties are copied onto the existing node. Returns the id now in the graph.
+    pub async fn merge_entity(&self, entity: Entity) -> Result<String> {
+        let name = entity.properties.get("name").cloned().unwrap_or_else(|| entity.id.clone());
+        if let Some(existing) = self.find_entity_by_name(&name).await {
+            {
+                let mut entities = self.entities.write().await;
+                if let Some(node) = entities.get_mut(&existing.id) {
+                    for (key, value) in entity.properties {
+                        node.properties.entry(key).or_insert(value);
+                    }
+                }
+            }
+            self.save_state().await;
+            return Ok(existing.id);
+        }
+        let id = entity.id.clone();
+        self.add_entity(entity).await?;
+        Ok(id)
+    }
+
+    pub async fn add_relationship(&self, mut rel: Relationship) -> Result<()> {
+        // Directions describe a traversal, not the stored edge
+        rel.direction = None;
         // Try to get node IDs from Barq by their names (slugs)
         let from_id = self.graph_db.get_node_id_by_name(&rel.from_id).await;
         let to_id = self.graph_db.get_node_id_by_name(&rel.to_id).await;
@@ -117,33 +281,172 @@ impl KnowledgeGraphManager {
         Ok(())
     }
     
-    pub async fn find_related_context(&self, entity_id: &str, _depth: usize) -> Result<ContextGraph, Box<dyn std::error::Error + Send + Sync>> {
+    pub async fn find_related_context(&self, entity_id: &str, depth: usize) -> Result<ContextGraph> {
+        self.find_weighted_context(entity_id, depth, None).await
+    }
+
+    /// Breadth-first neighborhood of `entity_id` up to `depth` hops (at least one),
+    /// following edges in either direction. Each hop's relationships come strongest first,
+    /// and entities follow in the order those relationships reach them. Relationships
+    /// weighing less than `min_weight` are neither returned nor followed.
+    pub async fn find_weighted_context(&self, entity_id: &str, depth: usize, min_weight: Option<f32>) -> Result<ContextGraph> {
         let entities = self.entities.read().await;
         let relationships = self.relationships.read().await;
-        
-        // Filter relationships that involve this entity
-        let related_rels: Vec<Relationship> = relationships
-            .iter()
-            .filter(|r| r.from_id == entity_id || r.to_id == entity_id)
-            .cloned()
-            .collect();
-        
-        // Get related entity IDs
-        let related_entity_ids: std::collections::HashSet<String> = related_rels
+
+        let mut depths: HashMap<&str, usize> = HashMap::from([(entity_id, 0)]);
+        let mut taken: HashSet<usize> = HashSet::new();
+        let mut path: Vec<(usize, EdgeDirection)> = Vec::new();
+        let mut frontier: HashSet<&str> = HashSet::from([entity_id]);
+        for hop in 1..=depth.max(1) {
+            // Untaken relationships touching the frontier, oriented away from it
+            let mut level: Vec<(usize, EdgeDirection, &str)> = relationships.iter().enumerate()
+                .filter(|(i, r)| !taken.contains(i) && min_weight.map(|min| r.weight >= min).unwrap_or(true))
+                .filter_map(|(i, r)| {
+                    if frontier.contains(r.from_id.as_str()) {
+                        Some((i, EdgeDirection::Outgoing, r.to_id.as_str()))
+                    } else if frontier.contains(r.to_id.as_str()) {
+                        Some((i, EdgeDirection::Incoming, r.from_id.as_str()))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            level.sort_by(|a, b| relationships[b.0].weight.total_cmp(&relationships[a.0].weight));
+
+            let mut next = HashSet::new();
+            for (i, direction, neighbor) in level {
+                taken.insert(i);
+                path.push((i, direction));
+                if !depths.contains_key(neighbor) {
+                    depths.insert(neighbor, hop);
+                    next.insert(neighbor);
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            frontier = next;
+        }
+
+        // Related entity ids, ordered by the relationship that first reached them
+        let mut seen = HashSet::new();
+        let related_entity_ids: Vec<&str> = path
             .iter()
-            .flat_map(|r| vec![r.from_id.clone(), r.to_id.clone()])
+            .flat_map(|&(i, _)| [relationships[i].from_id.as_str(), relationships[i].to_id.as_str()])
+            .filter(|id| seen.insert(*id))
             .collect();
-        
-        // Get those entities
-        let related_entities: Vec<Entity> = entities
-            .values()
-            .filter(|e| related_entity_ids.contains(&e.id))
+
+        Ok(ContextGraph {
+            entities: related_entity_ids.iter().filter_map(|id| entities.get(*id).cloned()).collect(),
+            depths: related_entity_ids.iter().map(|id| (id.to_string(), depths[id])).collect(),
+            relationships: path.iter()
+                .map(|&(i, direction)| Relationship { direction: Some(direction), ..relationships[i].clone() })
+                .collect(),
+            next_cursor: None,
+        })
+    }
+
+    /// `find_weighted_context` one page at a time: up to `max_nodes` entities from the
+    /// position in `cursor`, with `next_cursor` set while more remain. A relationship comes
+    /// with the page holding the later-reached of its endpoints, so merged pages contain
+    /// each edge once. The traversal is recomputed per page; an edited graph can shift pages.
+    pub async fn find_context_page(
+        &self,
+        entity_id: &str,
+        depth: usize,
+        min_weight: Option<f32>,
+        max_nodes: usize,
+        cursor: Option<&str>,
+    ) -> Result<ContextGraph> {
+        let offset = match cursor {
+            Some(token) => token.parse::<usize>()
+                .map_err(|_| BrainVaultError::BadRequest(format!("Invalid cursor '{}'", token)))?,
+            None => 0,
+        };
+        let full = self.find_weighted_context(entity_id, depth, min_weight).await?;
+
+        // Node positions in traversal order, matching the order of `entities`
+        let mut position: HashMap<&str, usize> = HashMap::new();
+        for rel in &full.relationships {
+            for id in [rel.from_id.as_str(), rel.to_id.as_str()] {
+                let next = position.len();
+                position.entry(id).or_insert(next);
+            }
+        }
+        let end = offset.saturating_add(max_nodes.max(1)).min(position.len());
+        let on_page = |id: &str| (offset..end).contains(&position[id]);
+
+        let relationships = full.relationships.iter()
+            .filter(|rel| (offset..end).contains(&position[rel.from_id.as_str()].max(position[rel.to_id.as_str()])))
             .cloned()
             .collect();
-        
         Ok(ContextGraph {
-            entities: related_entities,
-            relationships: related_rels,
+            entities: full.entities.iter().filter(|e| on_page(&e.id)).cloned().collect(),
+            depths: full.depths.iter().filter(|(id, _)| on_page(id)).map(|(id, d)| (id.clone(), *d)).collect(),
+            relationships,
+            next_cursor: (end < position.len()).then(|| end.to_string()),
+        })
+    }
+
+    /// Shortest chain of relationships linking `from_id` to `to_id`, following edges in
+    /// either direction and at most `max_depth` hops. Entities and relationships are
+    /// returned in path order; `NotFound` when no such path exists.
+    pub async fn find_path(&self, from_id: &str, to_id: &str, max_depth: usize) -> Result<ContextGraph> {
+        let entities = self.entities.read().await;
+        let relationships = self.relationships.read().await;
+
+        let mut adjacency: HashMap<&str, Vec<(&str, usize)>> = HashMap::new();
+        for (i, rel) in relationships.iter().enumerate() {
+            adjacency.entry(rel.from_id.as_str()).or_default().push((rel.to_id.as_str(), i));
+            adjacency.entry(rel.to_id.as_str()).or_default().push((rel.from_id.as_str(), i));
+        }
+        let known = |id: &str| entities.contains_key(id) || adjacency.contains_key(id);
+        for id in [from_id, to_id] {
+            if !known(id) {
+                return Err(BrainVaultError::NotFound(format!("Entity {}", id)));
+            }
+        }
+
+        // Breadth-first search, remembering how each node was reached
+        let mut came_from: HashMap<&str, (&str, usize)> = HashMap::new();
+        let mut visited: HashSet<&str> = HashSet::from([from_id]);
+        let mut queue: VecDeque<(&str, usize)> = VecDeque::from([(from_id, 0)]);
+        while let Some((node, depth)) = queue.pop_front() {
+            if node == to_id || depth >= max_depth {
+                continue;
+            }
+            for &(next, rel_idx) in adjacency.get(node).map(|v| v.as_slice()).unwrap_or(&[]) {
+                if visited.insert(next) {
+                    came_from.insert(next, (node, rel_idx));
+                    queue.push_back((next, depth + 1));
+                }
+            }
+        }
+        if from_id != to_id && !came_from.contains_key(to_id) {
+            return Err(BrainVaultError::NotFound(format!(
+                "No path from {} to {} within {} hops", from_id, to_id, max_depth
+            )));
+        }
+
+        let mut node_ids = vec![to_id];
+        let mut path_rels = Vec::new();
+        let mut current = to_id;
+        while let Some(&(prev, rel_idx)) = came_from.get(current) {
+            // Walking back from `current` to `prev`; the path itself runs `prev` -> `current`
+            let rel = &relationships[rel_idx];
+            let direction = if rel.from_id == prev { EdgeDirection::Outgoing } else { EdgeDirection::Incoming };
+            path_rels.push(Relationship { direction: Some(direction), ..rel.clone() });
+            node_ids.push(prev);
+            current = prev;
+        }
+        node_ids.reverse();
+        path_rels.reverse();
+
+        Ok(ContextGraph {
+            entities: node_ids.iter().filter_map(|id| entities.get(*id).cloned()).collect(),
+            relationships: path_rels,
+            depths: node_ids.iter().enumerate().map(|(hop, id)| (id.to_string(), hop)).collect(),
+            next_cursor: None,
         })
     }
 
@@ -166,13 +469,220 @@ impl KnowledgeGraphManager {
         ContextGraph {
             entities: entities.values().cloned().collect(),
             relationships: relationships.clone(),
+            depths: HashMap::new(),
+            next_cursor: None,
+        }
+    }
+
+    /// Merge `properties` into an existing entity, overwriting keys it already has.
+    /// Returns the updated entity.
+    pub async fn update_entity(&self, id: &str, properties: HashMap<String, String>) -> Result<Entity> {
+        let updated = {
+            let mut entities = self.entities.write().await;
+            let entity = entities.get_mut(id).ok_or_else(|| BrainVaultError::NotFound(format!("Entity {}", id)))?;
+            entity.properties.extend(properties);
+            entity.clone()
+        };
+        self.save_state().await;
+        Ok(updated)
+    }
+
+    /// Fold `merge_id` into `keep_id`: relationships are re-pointed to the kept entity
+    /// (dropping self-loops and exact duplicates), properties it lacks are copied over,
+    /// the merged name is recorded under `aliases`, and the duplicate is deleted.
+    /// The merged graph is written to disk before it replaces the live one, so a failed
+    /// write leaves both untouched. Barq then gets the re-pointed edges, and the
+    /// duplicate's node is retired into the kept entity. Returns the kept entity.
+    pub async fn merge_entities(&self, keep_id: &str, merge_id: &str) -> Result<Entity> {
+        if keep_id == merge_id {
+            return Err(BrainVaultError::BadRequest("Cannot merge an entity into itself".to_string()));
+        }
+        let (kept, moved) = {
+            let mut entities = self.entities.write().await;
+            let mut rels = self.relationships.write().await;
+            if !entities.contains_key(keep_id) {
+                return Err(BrainVaultError::NotFound(format!("Entity {}", keep_id)));
+            }
+            if !entities.contains_key(merge_id) {
+                return Err(BrainVaultError::NotFound(format!("Entity {}", merge_id)));
+            }
+
+            let mut next_entities = entities.clone();
+            let merged = next_entities.remove(merge_id).expect("checked above");
+            let kept = next_entities.get_mut(keep_id).expect("checked above");
+            let alias = merged.properties.get("name").cloned().unwrap_or_else(|| merged.id.clone());
+            let mut aliases: Vec<String> = kept.properties.get("aliases")
+                .map(|a| a.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+                .unwrap_or_default();
+            if !aliases.contains(&alias) && kept.properties.get("name") != Some(&alias) {
+                aliases.push(alias);
+            }
+            for (key, value) in merged.properties {
+                kept.properties.entry(key).or_insert(value);
+            }
+            if !aliases.is_empty() {
+                kept.properties.insert("aliases".to_string(), aliases.join(", "));
+            }
+            let kept = kept.clone();
+
+            let mut seen = HashSet::new();
+            let mut moved = Vec::new();
+            let next_rels: Vec<Relationship> = rels.iter().cloned()
+                .map(|mut r| {
+                    let touched = r.from_id == merge_id || r.to_id == merge_id;
+                    if r.from_id == merge_id { r.from_id = keep_id.to_string(); }
+                    if r.to_id == merge_id { r.to_id = keep_id.to_string(); }
+                    (r, touched)
+                })
+                .filter(|(r, _)| !(r.from_id == keep_id && r.to_id == keep_id))
+                .filter(|(r, _)| seen.insert((r.from_id.clone(), r.to_id.clone(), r.rel_type.clone())))
+                .map(|(r, touched)| {
+                    if touched { moved.push(r.clone()); }
+                    r
+                })
+                .collect();
+
+            write_state(&self.data_path, &next_entities, &next_rels)
+                .map_err(|e| BrainVaultError::Internal(format!("Merge not saved: {}", e)))?;
+            *entities = next_entities;
+            *rels = next_rels;
+            (kept, moved)
+        };
+
+        self.graph_db.retire_node(merge_id, keep_id).await;
+        for rel in &moved {
+            let from = self.graph_db.get_node_id_by_name(&rel.from_id).await;
+            let to = self.graph_db.get_node_id_by_name(&rel.to_id).await;
+            if let (Some(from), Some(to)) = (from, to) {
+                if let Err(e) = self.graph_db.create_edge(from, to, &rel.rel_type).await {
+                    println!("WARN: Re-pointed edge creation failed: {}", e);
+                }
+            }
         }
+        println!("INFO: Merged entity {} into {}", merge_id, keep_id);
+        Ok(kept)
+    }
+
+    /// Pairs of entities with the same label whose names score at least `threshold`
+    /// under `name_similarity`, best first. Chunk nodes are ignored.
+    ///
+    /// Names are bucketed by label and by the first three characters of each word (the
+    /// whole word when shorter), which every pair of matching words shares, so only
+    /// names that could score above zero are compared. Buckets larger than
+    /// `MAX_DUPLICATE_BLOCK` come from words too common to tell entities apart and
+    /// are skipped.
+    pub async fn suggest_duplicates(&self, threshold: f32, limit: usize) -> Vec<DuplicateCandidate> {
+        let entities = self.entities.read().await;
+        let relationships = self.relationships.read().await;
+
+        let mut degree: HashMap<&str, usize> = HashMap::new();
+        for rel in relationships.iter() {
+            *degree.entry(rel.from_id.as_str()).or_default() += 1;
+            *degree.entry(rel.to_id.as_str()).or_default() += 1;
+        }
+
+        let mut candidates: Vec<&Entity> = entities.values().filter(|e| e.label != "Chunk").collect();
+        candidates.sort_by(|a, b| a.id.cmp(&b.id));
+        let name = |e: &Entity| e.properties.get("name").cloned().unwrap_or_else(|| e.id.clone());
+
+        let mut blocks: HashMap<(&str, String), Vec<usize>> = HashMap::new();
+        for (i, e) in candidates.iter().enumerate() {
+            let normalized = normalize_entity_name(&name(e));
+            let keys: HashSet<String> = normalized.split(' ')
+                .filter(|w| !w.is_empty())
+                .map(|w| w.chars().take(3).collect())
+                .collect();
+            for key in keys {
+                blocks.entry((e.label.as_str(), key)).or_default().push(i);
+            }
+        }
+
+        let mut compared = HashSet::new();
+        let mut pairs = Vec::new();
+        for members in blocks.values().filter(|m| m.len() <= MAX_DUPLICATE_BLOCK) {
+            for (n, &i) in members.iter().enumerate() {
+                for &j in &members[n + 1..] {
+                    if !compared.insert((i, j)) {
+                        continue;
+                    }
+                    let (a, b) = (candidates[i], candidates[j]);
+                    let score = name_similarity(&name(a), &name(b));
+                    if score < threshold {
+                        continue;
+                    }
+                    let (da, db) = (degree.get(a.id.as_str()).copied().unwrap_or(0), degree.get(b.id.as_str()).copied().unwrap_or(0));
+                    let (keep, merge) = if da >= db { (a, b) } else { (b, a) };
+                    pairs.push(DuplicateCandidate { keep_id: keep.id.clone(), merge_id: merge.id.clone(), score });
+                }
+            }
+        }
+        pairs.sort_by(|x, y| y.score.partial_cmp(&x.score).unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| x.keep_id.cmp(&y.keep_id))
+            .then_with(|| x.merge_id.cmp(&y.merge_id)));
+        pairs.truncate(limit);
+        pairs
+    }
+
+    /// Complete graph for export: the local store, plus any nodes and edges that exist
+    /// only in Barq (named `barq-<id>` when this process didn't create them)
+    pub async fn export_graph(&self) -> ContextGraph {
+        let mut graph = self.get_graph_data().await;
+
+        let (nodes, edges) = match (self.graph_db.list_nodes().await, self.graph_db.list_edges().await) {
+            (Ok(nodes), Ok(edges)) => (nodes, edges),
+            (Err(e), _) | (_, Err(e)) => {
+                println!("WARN: Graph export using local store only: {}", e);
+                return graph;
+            }
+        };
+
+        let mut names = HashMap::new();
+        for node in &nodes {
+            let name = self.graph_db.get_name_by_node_id(node.id).await.unwrap_or_else(|| format!("barq-{}", node.id));
+            names.insert(node.id, name);
+        }
+        let mut known: HashSet<String> = graph.entities.iter().map(|e| e.id.clone()).collect();
+        for node in &nodes {
+            let id = names[&node.id].clone();
+            if known.insert(id.clone()) {
+                graph.entities.push(Entity { id, label: node.label.clone(), properties: HashMap::new() });
+            }
+        }
+
+        let mut seen: HashSet<(String, String, String)> = graph.relationships.iter()
+            .map(|r| (r.from_id.clone(), r.to_id.clone(), r.rel_type.clone()))
+            .collect();
+        for edge in edges {
+            let name = |id: u64| names.get(&id).cloned().unwrap_or_else(|| format!("barq-{}", id));
+            let key = (name(edge.from), name(edge.to), edge.edge_type.clone());
+            if seen.insert(key.clone()) {
+                graph.relationships.push(Relationship { from_id: key.0, to_id: key.1, rel_type: key.2, weight: 1.0, properties: HashMap::new(), direction: None });
+            }
+        }
+        graph
     }
 
     pub async fn check_health(&self) -> bool {
         self.graph_db.health().await.unwrap_or(false)
     }
 
+    /// Entities whose id or name appears verbatim in free text such as a task description
+    pub async fn find_entities_mentioned_in(&self, text: &str) -> Vec<Entity> {
+        let text = text.to_lowercase();
+        let entities = self.entities.read().await;
+
+        // Very short names ("AI", "HR") match too much incidental text
+        let mentioned = |needle: &str| needle.len() > 2 && text.contains(needle);
+
+        entities.values()
+            .filter(|e| {
+                mentioned(&e.id.to_lowercase()) ||
+                e.properties.get("name").map(|n| mentioned(&n.to_lowercase())).unwrap_or(false)
+            })
+            .cloned()
+            .collect()
+    }
+
     pub async fn find_nodes_by_text(&self, query: &str) -> Vec<Entity> {
         let query = query.to_lowercase();
         let entities = self.entities.read().await;
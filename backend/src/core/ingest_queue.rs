@@ -0,0 +1,158 @@
+use crate::core::search_engine::HybridSearchEngine;
+use crate::db::barq_vector::IndexOutcome;
+use crate::error::{BrainVaultError, Result};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::{mpsc, RwLock};
+use uuid::Uuid;
+
+/// Finished jobs kept for polling; the oldest are forgotten first
+const MAX_FINISHED_JOBS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IngestJobState {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+/// A background ingestion as seen by `GET /api/knowledge/jobs/{job_id}`
+#[derive(Debug, Clone, Serialize)]
+pub struct IngestJob {
+    pub job_id: String,
+    pub doc_id: String,
+    pub state: IngestJobState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<IndexOutcome>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Unix time in milliseconds
+    pub submitted_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<u64>,
+}
+
+struct Work {
+    job_id: String,
+    doc_id: String,
+    content: String,
+    language: Option<String>,
+    collection: Option<String>,
+}
+
+#[derive(Default)]
+struct JobTable {
+    jobs: HashMap<String, IngestJob>,
+    /// Finished job ids, oldest first
+    finished: VecDeque<String>,
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+/// Indexes documents on a background task so ingestion requests can return immediately.
+///
+/// Jobs go through a bounded channel to a single worker; when it is full `enqueue` fails
+/// instead of buffering without limit, so callers see back-pressure.
+#[derive(Clone)]
+pub struct IngestQueue {
+    sender: mpsc::Sender<Work>,
+    table: Arc<RwLock<JobTable>>,
+}
+
+impl IngestQueue {
+    /// Spawns the worker; must be called from within a Tokio runtime
+    pub fn start(engine: Arc<HybridSearchEngine>, capacity: usize) -> Self {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        let table = Arc::new(RwLock::new(JobTable::default()));
+        tokio::spawn(Self::run_worker(engine, receiver, table.clone()));
+        Self { sender, table }
+    }
+
+    /// Reads `INGEST_QUEUE_CAPACITY` (default 256)
+    pub fn from_env(engine: Arc<HybridSearchEngine>) -> Self {
+        let capacity = std::env::var("INGEST_QUEUE_CAPACITY").ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(256);
+        Self::start(engine, capacity)
+    }
+
+    /// Queues `content` for indexing and returns the job id to poll
+    pub async fn enqueue(&self, doc_id: &str, content: &str, language: Option<&str>, collection: Option<&str>) -> Result<String> {
+        let job_id = Uuid::new_v4().to_string();
+        self.table.write().await.jobs.insert(job_id.clone(), IngestJob {
+            job_id: job_id.clone(),
+            doc_id: doc_id.to_string(),
+            state: IngestJobState::Queued,
+            outcome: None,
+            error: None,
+            submitted_at: now_ms(),
+            finished_at: None,
+        });
+
+        let work = Work {
+            job_id: job_id.clone(),
+            doc_id: doc_id.to_string(),
+            content: content.to_string(),
+            language: language.map(str::to_string),
+            collection: collection.map(str::to_string),
+        };
+        if let Err(e) = self.sender.try_send(work) {
+            self.table.write().await.jobs.remove(&job_id);
+            return Err(match e {
+                mpsc::error::TrySendError::Full(_) => BrainVaultError::Unavailable("Ingest queue is full; retry later".to_string()),
+                mpsc::error::TrySendError::Closed(_) => BrainVaultError::Internal("Ingest worker has stopped".to_string()),
+            });
+        }
+        Ok(job_id)
+    }
+
+    pub async fn get_job(&self, job_id: &str) -> Option<IngestJob> {
+        self.table.read().await.jobs.get(job_id).cloned()
+    }
+
+    /// Jobs waiting for or held by the worker
+    pub async fn pending(&self) -> usize {
+        self.table.read().await.jobs.values()
+            .filter(|job| matches!(job.state, IngestJobState::Queued | IngestJobState::Running))
+            .count()
+    }
+
+    async fn run_worker(engine: Arc<HybridSearchEngine>, mut receiver: mpsc::Receiver<Work>, table: Arc<RwLock<JobTable>>) {
+        while let Some(work) = receiver.recv().await {
+            if let Some(job) = table.write().await.jobs.get_mut(&work.job_id) {
+                job.state = IngestJobState::Running;
+            }
+
+            let result = engine.ingest_document_into(&work.doc_id, &work.content, work.language.as_deref(), work.collection.as_deref()).await;
+            if let Err(ref e) = result {
+                println!("WARN: Background ingestion of '{}' failed: {}", work.doc_id, e);
+            }
+
+            let mut table = table.write().await;
+            if let Some(job) = table.jobs.get_mut(&work.job_id) {
+                match result {
+                    Ok(outcome) => {
+                        job.state = IngestJobState::Completed;
+                        job.outcome = Some(outcome);
+                    }
+                    Err(e) => {
+                        job.state = IngestJobState::Failed;
+                        job.error = Some(e.to_string());
+                    }
+                }
+                job.finished_at = Some(now_ms());
+            }
+            table.finished.push_back(work.job_id);
+            while table.finished.len() > MAX_FINISHED_JOBS {
+                if let Some(oldest) = table.finished.pop_front() {
+                    table.jobs.remove(&oldest);
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,11 @@
+//! Legacy direct Azure OpenAI client, available only with `LLM_LEGACY_CLIENTS=true`.
+//! Azure is otherwise served through `NafsLLMClient` with `LLM_PROVIDER=azure`.
+
 use serde::{Deserialize, Serialize};
 use reqwest::Client;
 use std::env;
+use crate::core::llm::nafs_provider::{legacy_clients_enabled, system_prompt, GenerationParams};
+use crate::core::llm::retry::{retry_with_backoff, ProviderError, RetryPolicy};
 
 #[derive(Debug, Clone)]
 pub struct AzureOpenAIClient {
@@ -9,6 +14,8 @@ pub struct AzureOpenAIClient {
     api_version: String,
     deployment: String,
     client: Client,
+    retry: RetryPolicy,
+    params: GenerationParams,
 }
 
 #[derive(Serialize)]
@@ -40,7 +47,11 @@ struct ChatResponse {
 }
 
 impl AzureOpenAIClient {
+    /// `None` unless legacy clients are enabled and the Azure endpoint and key are set
     pub fn new() -> Option<Self> {
+        if !legacy_clients_enabled() {
+            return None;
+        }
         let endpoint = env::var("AZURE_OPENAI_ENDPOINT").ok()?;
         let api_key = env::var("AZURE_OPENAI_API_KEY").ok()?;
         let api_version = env::var("AZURE_OPENAI_API_VERSION").unwrap_or("2024-12-01-preview".to_string());
@@ -55,10 +66,18 @@ impl AzureOpenAIClient {
             api_key,
             api_version,
             deployment,
-            client: Client::new(),
+            client: crate::http_client::shared(),
+            retry: RetryPolicy::from_env(),
+            params: GenerationParams { max_tokens: 500, ..GenerationParams::default() },
         })
     }
 
+    /// Completion length and temperature for `generate`
+    pub fn with_params(mut self, params: GenerationParams) -> Self {
+        self.params = params;
+        self
+    }
+
     pub async fn generate(&self, prompt: &str) -> Result<String, String> {
         let url = format!(
             "{}/openai/deployments/{}/chat/completions?api-version={}",
@@ -71,41 +90,47 @@ impl AzureOpenAIClient {
             messages: vec![
                 Message {
                     role: "system".to_string(),
-                    content: "You are an intelligent AI assistant for an enterprise knowledge management system.".to_string(),
+                    content: system_prompt().to_string(),
                 },
                 Message {
                     role: "user".to_string(),
                     content: prompt.to_string(),
                 },
             ],
-            max_tokens: 500,
-            temperature: 0.7,
+            max_tokens: self.params.max_tokens as i32,
+            temperature: self.params.temperature,
         };
 
-        let response = self.client
-            .post(&url)
-            .header("api-key", &self.api_key)
-            .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| format!("Azure OpenAI request failed: {}", e))?;
-
-        if response.status().is_success() {
-            let resp_json: ChatResponse = response.json().await
-                .map_err(|e| format!("Azure OpenAI parse error: {}", e))?;
-
-            if let Some(choice) = resp_json.choices.first() {
-                Ok(choice.message.content.clone())
+        let url = &url;
+        let body = &request_body;
+
+        retry_with_backoff(&self.retry, "Azure OpenAI", || async move {
+            let response = self.client
+                .post(url)
+                .header("api-key", &self.api_key)
+                .header("Content-Type", "application/json")
+                .json(body)
+                .timeout(crate::http_client::request_timeout())
+                .send()
+                .await
+                .map_err(|e| ProviderError::new(None, format!("Azure OpenAI request failed: {}", e)))?;
+
+            let status = response.status();
+            if status.is_success() {
+                let resp_json: ChatResponse = response.json().await
+                    .map_err(|e| ProviderError::new(None, format!("Azure OpenAI parse error: {}", e)))?;
+
+                resp_json.choices.first()
+                    .map(|choice| choice.message.content.clone())
+                    .ok_or_else(|| ProviderError::new(None, "No response from Azure OpenAI"))
+            } else if status.as_u16() == 429 {
+                Err(ProviderError::new(Some(429), "Azure OpenAI Rate Limit Exceeded (429). Please try again later."))
             } else {
-                Err("No response from Azure OpenAI".to_string())
+                let body = response.text().await.unwrap_or_default();
+                Err(ProviderError::new(Some(status.as_u16()), format!("Azure OpenAI Error {}: {}", status, body)))
             }
-        } else if response.status().as_u16() == 429 {
-            Err("Azure OpenAI Rate Limit Exceeded (429). Please try again later.".to_string())
-        } else {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            Err(format!("Azure OpenAI Error {}: {}", status, body))
-        }
+        })
+        .await
+        .map_err(|e| e.to_string())
     }
 }
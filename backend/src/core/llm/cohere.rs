@@ -1,11 +1,17 @@
+//! Legacy direct Cohere client, available only with `LLM_LEGACY_CLIENTS=true`
+
 use serde::{Deserialize, Serialize};
 use reqwest::Client;
 use std::env;
+use crate::core::llm::nafs_provider::{legacy_clients_enabled, GenerationParams};
+use crate::core::llm::retry::{retry_with_backoff, ProviderError, RetryPolicy};
 
 #[derive(Debug, Clone)]
 pub struct CohereClient {
     api_key: String,
     client: Client,
+    retry: RetryPolicy,
+    params: GenerationParams,
 }
 
 #[derive(Serialize)]
@@ -27,13 +33,19 @@ struct GenerateResponse {
 }
 
 impl CohereClient {
+    /// `None` unless legacy clients are enabled and `COHERE_API_KEY` is set
     pub fn new() -> Option<Self> {
+        if !legacy_clients_enabled() {
+            return None;
+        }
         let api_key = env::var("COHERE_API_KEY").ok();
         if let Some(key) = api_key {
             if key.is_empty() { return None; }
             Some(Self {
                 api_key: key,
-                client: Client::new(),
+                client: crate::http_client::shared(),
+                retry: RetryPolicy::from_env(),
+                params: GenerationParams { max_tokens: 300, ..GenerationParams::default() },
             })
         } else {
             // Log warning?
@@ -41,48 +53,46 @@ impl CohereClient {
         }
     }
 
-    pub async fn generate(&self, prompt: &str) -> Result<String, String> {
-        let max_retries = 3;
-        let mut retry_count = 0;
-        let mut wait_time = 2; // Start with 2 seconds
+    /// Completion length and temperature for `generate`
+    pub fn with_params(mut self, params: GenerationParams) -> Self {
+        self.params = params;
+        self
+    }
 
-        loop {
-            let request_body = GenerateRequest {
-                model: "command".to_string(), // Using standard Cohere command model
-                prompt: prompt.to_string(),
-                max_tokens: 300,
-                temperature: 0.7,
-            };
+    pub async fn generate(&self, prompt: &str) -> Result<String, String> {
+        let request_body = GenerateRequest {
+            model: "command".to_string(), // Using standard Cohere command model
+            prompt: prompt.to_string(),
+            max_tokens: self.params.max_tokens as i32,
+            temperature: self.params.temperature,
+        };
+        let body = &request_body;
 
+        retry_with_backoff(&self.retry, "Cohere", || async move {
             let response = self.client
                 .post("https://api.cohere.ai/v1/generate")
                 .header("Authorization", format!("Bearer {}", self.api_key))
-                .json(&request_body)
+                .json(body)
+                .timeout(crate::http_client::request_timeout())
                 .send()
                 .await
-                .map_err(|e| format!("Request failed: {}", e))?;
+                .map_err(|e| ProviderError::new(None, format!("Request failed: {}", e)))?;
 
-            if response.status().is_success() {
+            let status = response.status();
+            if status.is_success() {
                 let resp_json: GenerateResponse = response.json().await
-                    .map_err(|e| format!("Parse error: {}", e))?;
+                    .map_err(|e| ProviderError::new(None, format!("Parse error: {}", e)))?;
 
-                if let Some(gen) = resp_json.generations.first() {
-                    return Ok(gen.text.clone());
-                } else {
-                    return Err("No generations returned".to_string());
-                }
-            } else if response.status().as_u16() == 429 {
-                 if retry_count >= max_retries {
-                     return Err("Cohere API Rate Limit Exceeded (429). Please try again in 1 minute.".to_string());
-                 }
-                 println!("WARN: Cohere Rate Limit 429. Retrying in {}s...", wait_time);
-                 tokio::time::sleep(tokio::time::Duration::from_secs(wait_time)).await;
-                 retry_count += 1;
-                 wait_time *= 2; // Exponential backoff
-                 continue;
+                resp_json.generations.first()
+                    .map(|gen| gen.text.clone())
+                    .ok_or_else(|| ProviderError::new(None, "No generations returned"))
+            } else if status.as_u16() == 429 {
+                Err(ProviderError::new(Some(429), "Cohere API Rate Limit Exceeded (429). Please try again in 1 minute."))
             } else {
-                 return Err(format!("Cohere API Error: {}", response.status()));
+                Err(ProviderError::new(Some(status.as_u16()), format!("Cohere API Error: {}", status)))
             }
-        }
+        })
+        .await
+        .map_err(|e| e.to_string())
     }
 }
@@ -1,6 +1,189 @@
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use reqwest::Client;
+use std::collections::HashMap;
 use std::env;
+use std::sync::Arc;
+use crate::core::llm::nafs_provider::{NafsLLMClient, ProviderType};
+use crate::core::llm::retry::{retry_with_backoff, ProviderError, RetryPolicy};
+
+/// Source of document/query embeddings, independent of the chat provider
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    fn name(&self) -> &str;
+    fn model(&self) -> &str;
+    /// Length of the vectors this model returns
+    fn dimension(&self) -> usize;
+    /// Failures carry the provider's HTTP status when there was one, which decides
+    /// whether they are retried
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, ProviderError>;
+}
+
+/// Known output dimensions; unknown models are assumed to be 1536 (ada-002 / 3-small)
+pub fn dimension_for_model(model: &str) -> usize {
+    let model = model.to_lowercase();
+    let model = model.rsplit('/').next().unwrap_or(&model);
+    match model {
+        m if m.starts_with("text-embedding-3-large") => 3072,
+        m if m.starts_with("text-embedding-3-small") || m.starts_with("text-embedding-ada-002") => 1536,
+        m if m.starts_with("nomic-embed-text") => 768,
+        m if m.starts_with("mxbai-embed-large") => 1024,
+        m if m.starts_with("all-minilm") => 384,
+        m if m.starts_with("bge-large") || m.starts_with("voyage-3") || m.starts_with("embed-english-v3") => 1024,
+        m if m.starts_with("bge-base") || m.starts_with("jina-embeddings-v2") => 768,
+        _ => 1536,
+    }
+}
+
+/// Embedding provider from `EMBEDDING_PROVIDER`, falling back to `LLM_PROVIDER`.
+/// `local` selects the offline `LocalEmbedder`, sized by `EMBEDDING_DIM`.
+pub fn create_embedding_provider() -> Option<Arc<dyn EmbeddingProvider>> {
+    let name = env::var("EMBEDDING_PROVIDER")
+        .or_else(|_| env::var("LLM_PROVIDER"))
+        .unwrap_or_else(|_| "openai".to_string());
+
+    if name.trim().eq_ignore_ascii_case("local") {
+        let dimension = env::var("EMBEDDING_DIM").ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(LOCAL_EMBEDDING_DIM);
+        println!("INFO: Using local hashed embeddings ({} dimensions); no embedding API is called", dimension);
+        return Some(Arc::new(LocalEmbedder::new(dimension)));
+    }
+
+    // `RateLimitedEmbedder` is the only retry layer; clients retrying underneath it would
+    // multiply attempts and delays
+    let policy = RetryPolicy::from_env();
+    let single_attempt = RetryPolicy { max_retries: 0, ..policy.clone() };
+    let provider = match ProviderType::parse(&name) {
+        ProviderType::Azure => AzureEmbeddingClient::new()
+            .map(|c| Arc::new(c.with_retry_policy(single_attempt)) as Arc<dyn EmbeddingProvider>),
+        provider_type => NafsEmbeddingClient::new(provider_type)
+            .map(|c| Arc::new(c.with_retry_policy(single_attempt)) as Arc<dyn EmbeddingProvider>),
+    }?;
+    let provider: Arc<dyn EmbeddingProvider> = Arc::new(RateLimitedEmbedder::new(provider, policy));
+    Some(CachedEmbedder::from_env(provider))
+}
+
+/// Vector size of `LocalEmbedder` when `EMBEDDING_DIM` is unset
+pub const LOCAL_EMBEDDING_DIM: usize = 384;
+/// Weight of a word's character trigrams relative to the word itself
+const LOCAL_TRIGRAM_WEIGHT: f32 = 0.3;
+
+/// Deterministic embeddings computed in-process, for tests, CI and air-gapped demos.
+///
+/// Words and their character trigrams are hashed into `dimension` buckets with a
+/// pseudo-random sign (the hashing trick), weighted by sublinear term frequency and
+/// normalized to unit length. Texts sharing words, or word stems through shared
+/// trigrams, land close together; there is no notion of synonyms. The same text
+/// always yields the same vector, on every machine and across restarts.
+pub struct LocalEmbedder {
+    dimension: usize,
+}
+
+impl LocalEmbedder {
+    pub fn new(dimension: usize) -> Self {
+        Self { dimension: dimension.max(1) }
+    }
+
+    /// FNV-1a; unlike `DefaultHasher` its output is fixed, so stored vectors stay valid
+    fn hash(feature: &str) -> u64 {
+        feature.bytes().fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+    }
+
+    fn add(&self, vector: &mut [f32], feature: &str, weight: f32) {
+        let h = Self::hash(feature);
+        let bucket = (h % self.dimension as u64) as usize;
+        let sign = if h >> 63 == 0 { 1.0 } else { -1.0 };
+        vector[bucket] += sign * weight;
+    }
+
+    pub fn embed_text(&self, text: &str) -> Result<Vec<f32>, String> {
+        let mut counts: HashMap<String, f32> = HashMap::new();
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+            *counts.entry(word.to_lowercase()).or_insert(0.0) += 1.0;
+        }
+        if counts.is_empty() {
+            return Err("Nothing to embed: text has no words".to_string());
+        }
+
+        let mut vector = vec![0.0f32; self.dimension];
+        for (word, tf) in &counts {
+            let weight = 1.0 + tf.ln();
+            self.add(&mut vector, &format!("w:{}", word), weight);
+            let padded: Vec<char> = format!("^{}$", word).chars().collect();
+            for trigram in padded.windows(3) {
+                let trigram: String = trigram.iter().collect();
+                self.add(&mut vector, &format!("t:{}", trigram), weight * LOCAL_TRIGRAM_WEIGHT);
+            }
+        }
+
+        let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|v| *v /= norm);
+        }
+        Ok(vector)
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for LocalEmbedder {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    fn model(&self) -> &str {
+        "local-hash"
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, ProviderError> {
+        self.embed_text(text).map_err(|e| ProviderError::new(None, e))
+    }
+}
+
+/// Embeddings through any NAFS-4 provider (OpenAI, Ollama, Together, ...)
+pub struct NafsEmbeddingClient {
+    client: NafsLLMClient,
+    model: String,
+    dimension: usize,
+}
+
+impl NafsEmbeddingClient {
+    pub fn new(provider_type: ProviderType) -> Option<Self> {
+        let client = NafsLLMClient::for_provider(provider_type)?;
+        let model = env::var("EMBEDDING_MODEL").unwrap_or_else(|_| "text-embedding-3-small".to_string());
+        let dimension = dimension_for_model(&model);
+        Some(Self { client, model, dimension })
+    }
+
+    /// Backoff for failed embedding calls, instead of `RetryPolicy::from_env`
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.client = self.client.with_retry_policy(retry);
+        self
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for NafsEmbeddingClient {
+    fn name(&self) -> &str {
+        self.client.provider_name()
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, ProviderError> {
+        self.client.embed_with_model(text, &self.model).await
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct AzureEmbeddingClient {
@@ -9,6 +192,7 @@ pub struct AzureEmbeddingClient {
     api_version: String,
     deployment: String,
     client: Client,
+    retry: RetryPolicy,
 }
 
 #[derive(Serialize)]
@@ -43,11 +227,18 @@ impl AzureEmbeddingClient {
             api_key,
             api_version,
             deployment,
-            client: Client::new(),
+            client: crate::http_client::shared(),
+            retry: RetryPolicy::from_env(),
         })
     }
 
-    pub async fn get_embedding(&self, text: &str) -> Result<Vec<f32>, String> {
+    /// Backoff for failed embedding calls, instead of `RetryPolicy::from_env`
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub async fn get_embedding(&self, text: &str) -> Result<Vec<f32>, ProviderError> {
         let url = format!(
             "{}/openai/deployments/{}/embeddings?api-version={}",
             self.endpoint.trim_end_matches('/'),
@@ -59,28 +250,238 @@ impl AzureEmbeddingClient {
             input: vec![text.to_string()],
         };
 
-        let response = self.client
-            .post(&url)
-            .header("api-key", &self.api_key)
-            .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
-            .await
-            .map_err(|e| format!("Embedding request failed: {}", e))?;
-
-        if response.status().is_success() {
-            let resp_json: EmbeddingResponse = response.json().await
-                .map_err(|e| format!("Embedding parse error: {}", e))?;
-
-            if let Some(data) = resp_json.data.first() {
-                Ok(data.embedding.clone())
+        let url = &url;
+        let body = &request_body;
+
+        retry_with_backoff(&self.retry, "Embedding", || async move {
+            let response = self.client
+                .post(url)
+                .header("api-key", &self.api_key)
+                .header("Content-Type", "application/json")
+                .json(body)
+                .timeout(crate::http_client::request_timeout())
+                .send()
+                .await
+                .map_err(|e| ProviderError::new(None, format!("Embedding request failed: {}", e)))?;
+
+            let status = response.status();
+            if status.is_success() {
+                let resp_json: EmbeddingResponse = response.json().await
+                    .map_err(|e| ProviderError::new(None, format!("Embedding parse error: {}", e)))?;
+
+                resp_json.data.first()
+                    .map(|data| data.embedding.clone())
+                    .ok_or_else(|| ProviderError::new(None, "No embedding returned"))
             } else {
-                Err("No embedding returned".to_string())
+                let body = response.text().await.unwrap_or_default();
+                Err(ProviderError::new(Some(status.as_u16()), format!("Embedding Error {}: {}", status, body)))
+            }
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for AzureEmbeddingClient {
+    fn name(&self) -> &str {
+        "azure"
+    }
+
+    fn model(&self) -> &str {
+        &self.deployment
+    }
+
+    fn dimension(&self) -> usize {
+        dimension_for_model(&self.deployment)
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, ProviderError> {
+        self.get_embedding(text).await
+    }
+}
+
+/// Retries failed embedding calls, making every caller wait out a provider's rate limit,
+/// not just the one that hit it.
+///
+/// When a call fails with a 429, all calls through this wrapper hold off for the retry
+/// policy's backoff before trying again, so parallel ingestion slows down together instead
+/// of each worker hitting the provider on its own schedule. Other retriable failures (5xx)
+/// are retried by the failing call alone.
+pub struct RateLimitedEmbedder {
+    inner: Arc<dyn EmbeddingProvider>,
+    policy: RetryPolicy,
+    /// No call starts before this instant
+    paused_until: std::sync::Mutex<Option<tokio::time::Instant>>,
+    /// 429s since the last success; drives the shared backoff exponent
+    strikes: std::sync::atomic::AtomicU32,
+}
+
+impl RateLimitedEmbedder {
+    pub fn new(inner: Arc<dyn EmbeddingProvider>, policy: RetryPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            paused_until: std::sync::Mutex::new(None),
+            strikes: std::sync::atomic::AtomicU32::new(0),
+        }
+    }
+
+    async fn wait_turn(&self) {
+        let until = *self.paused_until.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(until) = until {
+            tokio::time::sleep_until(until).await;
+        }
+    }
+
+    /// Extends the shared pause after a 429 and returns its length
+    fn pause(&self) -> tokio::time::Duration {
+        let strike = self.strikes.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let delay = self.policy.delay_for(strike);
+        let until = tokio::time::Instant::now() + delay;
+        let mut paused = self.paused_until.lock().unwrap_or_else(|e| e.into_inner());
+        if paused.map_or(true, |current| current < until) {
+            *paused = Some(until);
+        }
+        delay
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for RateLimitedEmbedder {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn dimension(&self) -> usize {
+        self.inner.dimension()
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, ProviderError> {
+        let mut attempt = 0;
+        loop {
+            self.wait_turn().await;
+            match self.inner.embed(text).await {
+                Ok(embedding) => {
+                    self.strikes.store(0, std::sync::atomic::Ordering::SeqCst);
+                    return Ok(embedding);
+                }
+                Err(e) if attempt < self.policy.max_retries => {
+                    match e.status {
+                        Some(429) => {
+                            let delay = self.pause();
+                            println!(
+                                "WARN: {} embeddings rate limited; pausing all embedding calls for {}ms ({}/{})",
+                                self.inner.name(), delay.as_millis(), attempt + 1, self.policy.max_retries
+                            );
+                        }
+                        Some(status) if (self.policy.is_retriable)(status) => {
+                            let delay = self.policy.delay_for(attempt);
+                            println!(
+                                "WARN: {} embeddings returned {}. Retrying in {}ms ({}/{})...",
+                                self.inner.name(), status, delay.as_millis(), attempt + 1, self.policy.max_retries
+                            );
+                            tokio::time::sleep(delay).await;
+                        }
+                        _ => return Err(e),
+                    }
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Remembers the embeddings of recently seen text so repeated queries and re-ingested
+/// content skip the provider entirely.
+///
+/// Entries are keyed on a SHA-256 of the text; once `capacity` is reached the least
+/// recently used one is evicted.
+pub struct CachedEmbedder {
+    inner: Arc<dyn EmbeddingProvider>,
+    capacity: usize,
+    /// Text hash -> (embedding, tick of last use)
+    entries: std::sync::Mutex<HashMap<String, (Vec<f32>, u64)>>,
+    tick: std::sync::atomic::AtomicU64,
+}
+
+impl CachedEmbedder {
+    pub fn new(inner: Arc<dyn EmbeddingProvider>, capacity: usize) -> Self {
+        Self {
+            inner,
+            capacity,
+            entries: std::sync::Mutex::new(HashMap::new()),
+            tick: std::sync::atomic::AtomicU64::new(0),
+        }
+    }
+
+    /// Wraps `inner` with `EMBEDDING_CACHE_CAPACITY` entries (default 1024); 0 disables caching
+    pub fn from_env(inner: Arc<dyn EmbeddingProvider>) -> Arc<dyn EmbeddingProvider> {
+        let capacity = env::var("EMBEDDING_CACHE_CAPACITY").ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(1024);
+        if capacity == 0 {
+            return inner;
+        }
+        Arc::new(Self::new(inner, capacity))
+    }
+
+    fn key(text: &str) -> String {
+        use sha2::{Digest, Sha256};
+
+        Sha256::digest(text.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn next_tick(&self) -> u64 {
+        self.tick.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for CachedEmbedder {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn dimension(&self) -> usize {
+        self.inner.dimension()
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, ProviderError> {
+        let key = Self::key(text);
+        {
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some((embedding, last_used)) = entries.get_mut(&key) {
+                *last_used = self.next_tick();
+                return Ok(embedding.clone());
+            }
+        }
+
+        let embedding = self.inner.embed(text).await?;
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            let oldest = entries.iter().min_by_key(|(_, (_, last_used))| *last_used).map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
             }
-        } else {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            Err(format!("Embedding Error {}: {}", status, body))
         }
+        entries.insert(key, (embedding.clone(), self.next_tick()));
+        Ok(embedding)
     }
 }
@@ -0,0 +1,102 @@
+//! Ordered provider fallback chain
+//!
+//! Tries the primary `LLM_PROVIDER` first, then each provider listed in
+//! `LLM_FALLBACK_PROVIDERS` (comma-separated) until one succeeds.
+
+use crate::core::llm::nafs_provider::{system_prompt, GenerationParams, NafsLLMClient, ProviderType};
+use crate::core::llm::usage::TokenUsage;
+use std::env;
+
+/// A generation result along with the provider that produced it
+#[derive(Debug, Clone)]
+pub struct FallbackResponse {
+    pub content: String,
+    pub provider: String,
+    pub usage: TokenUsage,
+    pub cached: bool,
+}
+
+pub struct FallbackLLMClient {
+    clients: Vec<NafsLLMClient>,
+}
+
+impl FallbackLLMClient {
+    /// Build the chain from an ordered list of clients; `None` if the list is empty
+    pub fn new(clients: Vec<NafsLLMClient>) -> Option<Self> {
+        if clients.is_empty() {
+            return None;
+        }
+        Some(Self { clients })
+    }
+
+    /// Primary provider from `LLM_PROVIDER`, followed by `LLM_FALLBACK_PROVIDERS`.
+    /// Providers without credentials configured are skipped.
+    pub fn from_env() -> Option<Self> {
+        let primary = ProviderType::from_env();
+        let mut clients: Vec<NafsLLMClient> = NafsLLMClient::new().into_iter().collect();
+        let mut seen = vec![primary];
+
+        for provider_type in parse_provider_list(&env::var("LLM_FALLBACK_PROVIDERS").unwrap_or_default()) {
+            if seen.contains(&provider_type) {
+                continue;
+            }
+            seen.push(provider_type.clone());
+            match NafsLLMClient::for_provider(provider_type.clone()) {
+                Some(client) => clients.push(client),
+                None => println!("WARN: Fallback provider {:?} has no credentials configured, skipping", provider_type),
+            }
+        }
+
+        Self::new(clients)
+    }
+
+    /// Applies the same completion length and temperature to every provider in the chain
+    pub fn with_params(mut self, params: GenerationParams) -> Self {
+        self.clients = self.clients.into_iter().map(|client| client.with_params(params)).collect();
+        self
+    }
+
+    /// Generate with the first provider that succeeds, reporting which one served the request
+    pub async fn generate(&self, prompt: &str) -> Result<FallbackResponse, String> {
+        self.generate_with_system(system_prompt(), prompt).await
+    }
+
+    pub async fn generate_with_system(&self, system: &str, prompt: &str) -> Result<FallbackResponse, String> {
+        let mut errors = Vec::new();
+
+        for client in &self.clients {
+            match client.generate_with_system(system, prompt).await {
+                Ok(generation) => {
+                    if !errors.is_empty() {
+                        println!("INFO: LLM request served by fallback provider {}", client.provider_name());
+                    }
+                    return Ok(FallbackResponse {
+                        content: generation.content,
+                        provider: client.provider_name().to_string(),
+                        usage: generation.usage,
+                        cached: generation.cached,
+                    });
+                }
+                Err(e) => {
+                    println!("WARN: LLM ({}) failed: {}. Trying next provider.", client.provider_name(), e);
+                    errors.push(format!("{}: {}", client.provider_name(), e));
+                }
+            }
+        }
+
+        Err(format!("All LLM providers failed: {}", errors.join("; ")))
+    }
+
+    pub fn provider_names(&self) -> Vec<&str> {
+        self.clients.iter().map(|c| c.provider_name()).collect()
+    }
+}
+
+/// Parse a comma-separated provider list such as `"anthropic, groq"`
+pub fn parse_provider_list(list: &str) -> Vec<ProviderType> {
+    list.split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(ProviderType::parse)
+        .collect()
+}
@@ -2,3 +2,9 @@ pub mod cohere;
 pub mod azure_openai;
 pub mod embeddings;
 pub mod nafs_provider;
+pub mod fallback;
+pub mod tokenizer;
+pub mod retry;
+pub mod usage;
+pub mod response_cache;
+pub mod tools;
@@ -11,8 +11,62 @@ use nafs_llm::{
     FireworksConfig, FireworksProvider,
     AzureConfig, AzureOpenAIProvider,
 };
-use std::env;
+use futures::{Stream, StreamExt};
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+use crate::config::LlmConfig;
+use crate::core::llm::retry::{retry_with_backoff, ProviderError, RetryPolicy};
+use crate::core::llm::tokenizer::tokenizer_for_model;
+use crate::core::llm::response_cache::LlmResponseCache;
+use crate::core::llm::tools::{chat_completion_with_tools, ToolDefinition, ToolTurn};
+use crate::core::llm::usage::TokenUsage;
+
+/// Built-in system prompt, used unless `LLM_SYSTEM_PROMPT` overrides it
+pub const DEFAULT_SYSTEM_PROMPT: &str = "You are an intelligent AI assistant for an enterprise knowledge management system.";
+
+/// System prompt used when the caller doesn't supply one: `LLM_SYSTEM_PROMPT` when set
+/// and non-blank, else `DEFAULT_SYSTEM_PROMPT`. Read once per process.
+pub fn system_prompt() -> &'static str {
+    LlmConfig::global().system_prompt.as_deref().unwrap_or(DEFAULT_SYSTEM_PROMPT)
+}
+
+/// Whether the standalone `CohereClient` and `AzureOpenAIClient` may be used. They predate
+/// the NAFS-4 provider chain and are off unless `LLM_LEGACY_CLIENTS=true`; everything else,
+/// agents included, generates through `NafsLLMClient` as selected by `LLM_PROVIDER`.
+pub fn legacy_clients_enabled() -> bool {
+    LlmConfig::global().legacy_clients
+}
+
+/// Largest completion length a caller may request
+pub const MAX_COMPLETION_TOKENS: usize = 32_768;
+
+/// Completion length and sampling temperature for a generation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GenerationParams {
+    pub max_tokens: usize,
+    pub temperature: f32,
+}
+
+impl Default for GenerationParams {
+    fn default() -> Self {
+        Self { max_tokens: 2000, temperature: 0.7 }
+    }
+}
+
+impl GenerationParams {
+    /// `max_tokens` must be in 1..=`MAX_COMPLETION_TOKENS` and `temperature` in [0, 2]
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_tokens == 0 || self.max_tokens > MAX_COMPLETION_TOKENS {
+            return Err(format!("max_tokens must be between 1 and {}, got {}", MAX_COMPLETION_TOKENS, self.max_tokens));
+        }
+        if !(0.0..=2.0).contains(&self.temperature) {
+            return Err(format!("temperature must be between 0 and 2, got {}", self.temperature));
+        }
+        Ok(())
+    }
+}
 
 /// Provider types supported
 #[derive(Debug, Clone, PartialEq)]
@@ -29,7 +83,11 @@ pub enum ProviderType {
 
 impl ProviderType {
     pub fn from_env() -> Self {
-        match env::var("LLM_PROVIDER").unwrap_or("openai".into()).to_lowercase().as_str() {
+        Self::parse(&LlmConfig::global().provider)
+    }
+
+    pub fn parse(name: &str) -> Self {
+        match name.trim().to_lowercase().as_str() {
             "azure" => Self::Azure,
             "anthropic" => Self::Anthropic,
             "together" => Self::Together,
@@ -44,48 +102,49 @@ impl ProviderType {
 
 /// Create a provider based on environment configuration
 pub fn create_provider() -> Option<Arc<dyn LLMProvider>> {
-    let provider_type = ProviderType::from_env();
-    
+    create_provider_for(&ProviderType::from_env())
+}
+
+/// Create a specific provider using its credentials from `LlmConfig`
+pub fn create_provider_for(provider_type: &ProviderType) -> Option<Arc<dyn LLMProvider>> {
+    let settings = LlmConfig::global();
     match provider_type {
         ProviderType::OpenAI => {
-            let api_key = env::var("OPENAI_API_KEY").ok()?;
+            let api_key = settings.openai_api_key.clone()?;
             let config = OpenAIConfig::new(api_key);
             Some(Arc::new(OpenAIProvider::new(config)))
         }
         ProviderType::Azure => {
-            let api_key = env::var("AZURE_OPENAI_API_KEY").ok()?;
-            let endpoint = env::var("AZURE_OPENAI_ENDPOINT").ok()?;
-            let deployment = env::var("AZURE_OPENAI_DEPLOYMENT").unwrap_or("gpt-4o".into());
+            let api_key = settings.azure_api_key.clone()?;
+            let endpoint = settings.azure_endpoint.clone()?;
+            let deployment = settings.azure_deployment.clone().unwrap_or("gpt-4o".into());
             let config = AzureConfig::new(api_key, endpoint, deployment);
             Some(Arc::new(AzureOpenAIProvider::new(config)))
         }
         ProviderType::Anthropic => {
-            let api_key = env::var("ANTHROPIC_API_KEY").ok()?;
+            let api_key = settings.anthropic_api_key.clone()?;
             let config = AnthropicConfig::new(api_key);
             Some(Arc::new(AnthropicProvider::new(config)))
         }
         ProviderType::Together => {
-            let api_key = env::var("TOGETHER_API_KEY")
-                .or_else(|_| env::var("LLM_API_KEY")).ok()?;
+            let api_key = settings.together_api_key.clone().or_else(|| settings.api_key.clone())?;
             let config = TogetherConfig::new(api_key);
             Some(Arc::new(TogetherProvider::new(config)))
         }
         ProviderType::Groq => {
-            let api_key = env::var("GROQ_API_KEY")
-                .or_else(|_| env::var("LLM_API_KEY")).ok()?;
+            let api_key = settings.groq_api_key.clone().or_else(|| settings.api_key.clone())?;
             let config = GroqConfig::new(api_key);
             Some(Arc::new(GroqProvider::new(config)))
         }
         ProviderType::Fireworks => {
-            let api_key = env::var("FIREWORKS_API_KEY")
-                .or_else(|_| env::var("LLM_API_KEY")).ok()?;
+            let api_key = settings.fireworks_api_key.clone().or_else(|| settings.api_key.clone())?;
             let config = FireworksConfig::new(api_key);
             Some(Arc::new(FireworksProvider::new(config)))
         }
         ProviderType::Ollama | ProviderType::Custom => {
             // Use OpenAI-compatible endpoint with custom base URL
-            let api_key = env::var("LLM_API_KEY").unwrap_or("ollama".into());
-            let base_url = env::var("LLM_BASE_URL").unwrap_or("http://localhost:11434/v1".into());
+            let api_key = settings.api_key.clone().unwrap_or("ollama".into());
+            let base_url = settings.base_url.clone().unwrap_or("http://localhost:11434/v1".into());
             let config = OpenAIConfig::new(api_key).with_base_url(base_url);
             Some(Arc::new(OpenAIProvider::new(config)))
         }
@@ -94,22 +153,29 @@ pub fn create_provider() -> Option<Arc<dyn LLMProvider>> {
 
 /// Get the default model for the current provider
 pub fn get_default_model() -> String {
-    let provider_type = ProviderType::from_env();
-    
-    // Check explicit model env var first
-    if let Ok(model) = env::var("LLM_MODEL") {
-        return model;
-    }
-    if let Ok(model) = env::var("OPENAI_MODEL") {
-        return model;
-    }
-    if let Ok(model) = env::var("ANTHROPIC_MODEL") {
-        return model;
-    }
-    if let Ok(deployment) = env::var("AZURE_OPENAI_DEPLOYMENT") {
-        return deployment;
+    let settings = LlmConfig::global();
+
+    // An explicit model setting wins
+    let explicit = [&settings.model, &settings.openai_model, &settings.anthropic_model, &settings.azure_deployment];
+    match explicit.into_iter().flatten().next() {
+        Some(model) => model.clone(),
+        None => builtin_default_model(&ProviderType::from_env()),
     }
-    
+}
+
+/// Default model for a provider that isn't the primary one, honoring only its own model env var
+pub fn get_model_for(provider_type: &ProviderType) -> String {
+    let settings = LlmConfig::global();
+    let specific = match provider_type {
+        ProviderType::OpenAI => settings.openai_model.clone(),
+        ProviderType::Azure => settings.azure_deployment.clone(),
+        ProviderType::Anthropic => settings.anthropic_model.clone(),
+        _ => None,
+    };
+    specific.unwrap_or_else(|| builtin_default_model(provider_type))
+}
+
+fn builtin_default_model(provider_type: &ProviderType) -> String {
     // Provider-specific defaults
     match provider_type {
         ProviderType::OpenAI => "gpt-4o".to_string(),
@@ -123,54 +189,271 @@ pub fn get_default_model() -> String {
     }
 }
 
+/// OpenAI-compatible chat endpoint (base URL, API key), used for SSE streaming and tool calling
+fn streaming_endpoint(provider_type: &ProviderType) -> Option<(String, String)> {
+    let settings = LlmConfig::global();
+    let shared_key = || settings.api_key.clone();
+    match provider_type {
+        ProviderType::OpenAI => Some((
+            "https://api.openai.com/v1".to_string(),
+            settings.openai_api_key.clone()?,
+        )),
+        ProviderType::Together => Some((
+            "https://api.together.xyz/v1".to_string(),
+            settings.together_api_key.clone().or_else(shared_key)?,
+        )),
+        ProviderType::Groq => Some((
+            "https://api.groq.com/openai/v1".to_string(),
+            settings.groq_api_key.clone().or_else(shared_key)?,
+        )),
+        ProviderType::Fireworks => Some((
+            "https://api.fireworks.ai/inference/v1".to_string(),
+            settings.fireworks_api_key.clone().or_else(shared_key)?,
+        )),
+        ProviderType::Ollama | ProviderType::Custom => Some((
+            settings.base_url.clone().unwrap_or("http://localhost:11434/v1".into()),
+            settings.api_key.clone().unwrap_or("ollama".into()),
+        )),
+        // Azure and Anthropic use non-OpenAI streaming formats; served as a single chunk
+        ProviderType::Azure | ProviderType::Anthropic => None,
+    }
+}
+
+/// Stream of completion text chunks produced by [`NafsLLMClient::generate_stream`].
+/// Dropping it (e.g. when an SSE client disconnects) aborts the provider request.
+pub struct CompletionStream {
+    rx: mpsc::Receiver<Result<String, String>>,
+    producer: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for CompletionStream {
+    fn drop(&mut self) {
+        self.producer.abort();
+    }
+}
+
+impl Stream for CompletionStream {
+    type Item = Result<String, String>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+/// A completion along with its token cost
+#[derive(Debug, Clone)]
+pub struct Generation {
+    pub content: String,
+    pub usage: TokenUsage,
+    /// Served from the response cache; no tokens were consumed
+    pub cached: bool,
+}
+
 /// Convenience wrapper for simple text generation
 pub struct NafsLLMClient {
     provider: Arc<dyn LLMProvider>,
+    provider_type: ProviderType,
     model: String,
+    retry: RetryPolicy,
+    use_cache: bool,
+    params: GenerationParams,
 }
 
 impl NafsLLMClient {
     pub fn new() -> Option<Self> {
         let provider = create_provider()?;
         let model = get_default_model();
-        Some(Self { provider, model })
+        Some(Self { provider, provider_type: ProviderType::from_env(), model, retry: RetryPolicy::from_env(), use_cache: true, params: GenerationParams::default() })
     }
     
     pub fn with_model(model: impl Into<String>) -> Option<Self> {
         let provider = create_provider()?;
-        Some(Self { provider, model: model.into() })
+        Some(Self { provider, provider_type: ProviderType::from_env(), model: model.into(), retry: RetryPolicy::from_env(), use_cache: true, params: GenerationParams::default() })
+    }
+
+    /// Client for a specific provider regardless of `LLM_PROVIDER`
+    pub fn for_provider(provider_type: ProviderType) -> Option<Self> {
+        let provider = create_provider_for(&provider_type)?;
+        let model = get_model_for(&provider_type);
+        Some(Self { provider, provider_type, model, retry: RetryPolicy::from_env(), use_cache: true, params: GenerationParams::default() })
+    }
+    
+    /// Backoff for failed provider calls, instead of `RetryPolicy::from_env`
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Skip the response cache for prompts whose answers must be fresh
+    pub fn without_cache(mut self) -> Self {
+        self.use_cache = false;
+        self
+    }
+
+    /// Completion length and temperature for `generate*` and `chat` calls
+    pub fn with_params(mut self, params: GenerationParams) -> Self {
+        self.params = params;
+        self
     }
     
     /// Simple prompt -> response
     pub async fn generate(&self, prompt: &str) -> Result<String, String> {
+        self.generate_with_usage(prompt).await.map(|g| g.content)
+    }
+
+    /// Prompt -> response plus the tokens it consumed
+    pub async fn generate_with_usage(&self, prompt: &str) -> Result<Generation, String> {
+        self.generate_with_system(system_prompt(), prompt).await
+    }
+
+    /// Like `generate_with_usage`, under a caller-supplied system prompt
+    pub async fn generate_with_system(&self, system: &str, prompt: &str) -> Result<Generation, String> {
+        let temperature = self.params.temperature;
+        let cache = if self.use_cache { LlmResponseCache::global() } else { None };
+        let cache_key = LlmResponseCache::key(self.provider_name(), &self.model, system, prompt, temperature, self.params.max_tokens);
+        if let Some(content) = cache.and_then(|c| c.get(&cache_key)) {
+            return Ok(Generation { content, usage: TokenUsage::default(), cached: true });
+        }
+
         let messages = vec![
-            ChatMessage::system("You are an intelligent AI assistant for an enterprise knowledge management system."),
+            ChatMessage::system(system),
             ChatMessage::user(prompt),
         ];
         
         let config = ChatConfig::for_model(&self.model)
-            .with_max_tokens(2000)
-            .with_temperature(0.7);
+            .with_max_tokens(self.params.max_tokens)
+            .with_temperature(temperature);
         
-        self.provider.chat(&messages, &config).await
-            .map(|r| r.content)
-            .map_err(|e| format!("LLM error: {}", e))
+        let response = self.chat_with_retry(&messages, &config).await?;
+        let usage = self.usage_of(&response, &format!("{}\n{}", system, prompt));
+        if let Some(cache) = cache {
+            cache.put(cache_key, response.content.clone()).await;
+        }
+        Ok(Generation { content: response.content, usage, cached: false })
+    }
+
+    /// Usage reported by the provider, or a tokenizer estimate when the response has none
+    pub fn usage_of(&self, response: &ChatResponse, prompt_text: &str) -> TokenUsage {
+        match response.usage.as_ref() {
+            Some(u) => TokenUsage {
+                prompt_tokens: u.prompt_tokens as u64,
+                completion_tokens: u.completion_tokens as u64,
+                estimated: false,
+            },
+            None => {
+                let tokenizer = tokenizer_for_model(&self.model);
+                TokenUsage {
+                    prompt_tokens: tokenizer.count_tokens(prompt_text) as u64,
+                    completion_tokens: tokenizer.count_tokens(&response.content) as u64,
+                    estimated: true,
+                }
+            }
+        }
+    }
+    
+    /// Prompt -> stream of response chunks as they arrive from the provider.
+    /// Providers without an OpenAI-compatible streaming API yield the full completion as one chunk.
+    pub fn generate_stream(&self, prompt: &str) -> CompletionStream {
+        let messages = vec![message_json("system", system_prompt()), message_json("user", prompt)];
+        self.chat_stream(messages, self.params.max_tokens)
+    }
+
+    /// Like `chat`, streamed: `messages` are OpenAI-format `{"role", "content"}` objects
+    /// (see `message_json`), and chunks arrive as the provider produces them
+    pub fn chat_stream(&self, messages: Vec<serde_json::Value>, max_tokens: usize) -> CompletionStream {
+        let (tx, rx) = mpsc::channel(64);
+        let model = self.model.clone();
+        let params = GenerationParams { max_tokens, ..self.params };
+
+        let producer = match streaming_endpoint(&self.provider_type) {
+            Some((base_url, api_key)) => {
+                tokio::spawn(async move {
+                    if let Err(e) = stream_chat_completion(&base_url, &api_key, &model, &messages, &params, &tx).await {
+                        let _ = tx.send(Err(e)).await;
+                    }
+                })
+            }
+            None => {
+                let provider = self.provider.clone();
+                tokio::spawn(async move {
+                    let messages: Vec<ChatMessage> = messages.iter().map(to_chat_message).collect();
+                    let config = ChatConfig::for_model(&model)
+                        .with_max_tokens(params.max_tokens)
+                        .with_temperature(params.temperature);
+                    let chunk = provider.chat(&messages, &config).await
+                        .map(|r| r.content)
+                        .map_err(|e| format!("LLM error: {}", e));
+                    let _ = tx.send(chunk).await;
+                })
+            }
+        };
+
+        CompletionStream { rx, producer }
     }
     
     /// Chat completion with full message history
     pub async fn chat(&self, messages: Vec<ChatMessage>, max_tokens: usize) -> Result<ChatResponse, String> {
         let config = ChatConfig::for_model(&self.model)
             .with_max_tokens(max_tokens)
-            .with_temperature(0.7);
+            .with_temperature(self.params.temperature);
         
-        self.provider.chat(&messages, &config).await
-            .map_err(|e| format!("LLM error: {}", e))
+        self.chat_with_retry(&messages, &config).await
     }
     
-    /// Get embeddings for text
-    pub async fn embed(&self, text: &str) -> Result<Vec<f32>, String> {
-        self.provider.embed(text).await
-            .map_err(|e| format!("Embedding error: {}", e))
+    /// One chat turn with `tools` available. `messages` are OpenAI-format JSON so earlier
+    /// tool calls and results can be replayed. Errors for providers without an
+    /// OpenAI-compatible API (Azure, Anthropic).
+    pub async fn chat_with_tools(&self, messages: &[serde_json::Value], tools: &[ToolDefinition], params: &GenerationParams) -> Result<(ToolTurn, TokenUsage), String> {
+        let (base_url, api_key) = streaming_endpoint(&self.provider_type)
+            .ok_or_else(|| format!("Provider {} does not support tool calling", self.provider_name()))?;
+        let (turn, usage) = chat_completion_with_tools(&base_url, &api_key, &self.model, messages, tools, params).await?;
+        let usage = usage.unwrap_or_else(|| {
+            let tokenizer = tokenizer_for_model(&self.model);
+            let completion = match turn {
+                ToolTurn::Final(ref content) => tokenizer.count_tokens(content),
+                ToolTurn::Calls { ref message, .. } => tokenizer.count_tokens(&message.to_string()),
+            };
+            TokenUsage {
+                prompt_tokens: tokenizer.count_tokens(&serde_json::Value::from(messages.to_vec()).to_string()) as u64,
+                completion_tokens: completion as u64,
+                estimated: true,
+            }
+        });
+        Ok((turn, usage))
+    }
+    
+    /// Get embeddings for text from the provider's default embedding model
+    pub async fn embed(&self, text: &str) -> Result<Vec<f32>, ProviderError> {
+        let provider = &self.provider;
+        retry_with_backoff(&self.retry, self.provider_name(), || async move {
+            provider.embed(text).await
+                .map_err(|e| ProviderError::untyped(format!("Embedding error: {}", e)))
+        })
+        .await
+    }
+
+    /// Embeddings for text from `model`. Providers with an OpenAI-compatible API are asked
+    /// for that model by name; the others only offer their default embedding model.
+    pub async fn embed_with_model(&self, text: &str, model: &str) -> Result<Vec<f32>, ProviderError> {
+        let (base_url, api_key) = match streaming_endpoint(&self.provider_type) {
+            Some(endpoint) => endpoint,
+            None => return self.embed(text).await,
+        };
+        let (base_url, api_key) = (&base_url, &api_key);
+        retry_with_backoff(&self.retry, self.provider_name(), || async move {
+            embedding_request(base_url, api_key, model, text).await
+        })
+        .await
+    }
+
+    async fn chat_with_retry(&self, messages: &[ChatMessage], config: &ChatConfig) -> Result<ChatResponse, String> {
+        let provider = &self.provider;
+        retry_with_backoff(&self.retry, self.provider_name(), || async move {
+            provider.chat(messages, config).await
+                .map_err(|e| ProviderError::untyped(format!("LLM error: {}", e)))
+        })
+        .await
+        .map_err(|e| e.to_string())
     }
     
     pub fn provider_name(&self) -> &str {
@@ -178,5 +461,120 @@ impl NafsLLMClient {
     }
 }
 
+/// POSTs `text` to an OpenAI-compatible `/embeddings` endpoint, naming `model`, and
+/// returns the vector
+pub async fn embedding_request(base_url: &str, api_key: &str, model: &str, text: &str) -> Result<Vec<f32>, ProviderError> {
+    let url = format!("{}/embeddings", base_url.trim_end_matches('/'));
+    let body = serde_json::json!({
+        "model": model,
+        "input": text,
+    });
+    let response = crate::http_client::shared()
+        .post(&url)
+        .bearer_auth(api_key)
+        .json(&body)
+        .timeout(crate::http_client::request_timeout())
+        .send()
+        .await
+        .map_err(|e| ProviderError::new(None, format!("Embedding request failed: {}", e)))?;
+    let status = response.status();
+    if !status.is_success() {
+        let detail = response.text().await.unwrap_or_default();
+        return Err(ProviderError::new(Some(status.as_u16()), format!("Embedding error {}: {}", status, detail.trim())));
+    }
+    let parsed: serde_json::Value = response.json().await
+        .map_err(|e| ProviderError::new(None, format!("Invalid embedding response: {}", e)))?;
+    parsed["data"][0]["embedding"].as_array()
+        .map(|values| values.iter().filter_map(|v| v.as_f64()).map(|v| v as f32).collect())
+        .ok_or_else(|| ProviderError::new(None, "Embedding response has no data[0].embedding".to_string()))
+}
+
+/// OpenAI-format chat message, for `chat_stream` and `stream_chat_completion`
+pub fn message_json(role: &str, content: &str) -> serde_json::Value {
+    serde_json::json!({"role": role, "content": content})
+}
+
+/// A `message_json` message for providers called through NAFS; unknown roles count as user
+fn to_chat_message(message: &serde_json::Value) -> ChatMessage {
+    let content = message["content"].as_str().unwrap_or("");
+    match message["role"].as_str() {
+        Some("system") => ChatMessage::system(content),
+        Some("assistant") => ChatMessage::assistant(content),
+        _ => ChatMessage::user(content),
+    }
+}
+
+/// POSTs a streaming chat completion of `messages` (see `message_json`) and forwards each
+/// SSE `delta.content` to `tx`
+pub async fn stream_chat_completion(
+    base_url: &str,
+    api_key: &str,
+    model: &str,
+    messages: &[serde_json::Value],
+    params: &GenerationParams,
+    tx: &mpsc::Sender<Result<String, String>>,
+) -> Result<(), String> {
+    let url = format!("{}/chat/completions", base_url.trim_end_matches('/'));
+    let body = serde_json::json!({
+        "model": model,
+        "messages": messages,
+        "max_tokens": params.max_tokens,
+        "temperature": params.temperature,
+        "stream": true
+    });
+
+    // No whole-request timeout, which would cut off long answers; instead the stream is
+    // abandoned when the provider goes quiet for `read_timeout`
+    let read_timeout = crate::http_client::read_timeout();
+    let stalled = || format!("LLM stream stalled: no data for {}s", read_timeout.as_secs());
+    let request = crate::http_client::shared()
+        .post(&url)
+        .bearer_auth(api_key)
+        .json(&body)
+        .send();
+    let response = tokio::time::timeout(read_timeout, request).await
+        .map_err(|_| stalled())?
+        .map_err(|e| format!("LLM stream request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("LLM stream error {}: {}", status, body));
+    }
+
+    let mut bytes = response.bytes_stream();
+    let mut buffer: Vec<u8> = Vec::new();
+
+    while let Some(chunk) = tokio::time::timeout(read_timeout, bytes.next()).await.map_err(|_| stalled())? {
+        let chunk = chunk.map_err(|e| format!("LLM stream read failed: {}", e))?;
+        buffer.extend_from_slice(&chunk);
+
+        // SSE events are newline-delimited; keep any trailing partial line in the buffer.
+        // Only whole lines are decoded, as a network chunk can end mid-character.
+        while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+            let data = match line.strip_prefix("data:") {
+                Some(d) => d.trim(),
+                None => continue,
+            };
+            if data == "[DONE]" {
+                return Ok(());
+            }
+            if let Ok(event) = serde_json::from_str::<serde_json::Value>(data) {
+                if let Some(text) = event["choices"][0]["delta"]["content"].as_str() {
+                    if !text.is_empty() && tx.send(Ok(text.to_string())).await.is_err() {
+                        // Receiver dropped, stop reading
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
 // Re-export for convenience
 pub use nafs_llm::{ChatMessage as NafsChatMessage, MessageRole as NafsMessageRole};
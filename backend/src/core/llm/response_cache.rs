@@ -0,0 +1,105 @@
+//! Opt-in cache for identical LLM prompts
+//!
+//! Enabled with `LLM_CACHE_TTL_SECS`. Entries are keyed on a hash of (provider, model,
+//! system prompt, prompt, temperature, max_tokens); `LLM_CACHE_PERSIST=true` also keeps them in
+//! `{DATA_PATH}/llm_cache.json` so they survive restarts.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CachedResponse {
+    content: String,
+    created_at: u64,
+}
+
+pub struct LlmResponseCache {
+    entries: Mutex<HashMap<String, CachedResponse>>,
+    ttl_secs: u64,
+    persist_path: Option<String>,
+    /// Held while the file is written, so a slower write of an older snapshot can't
+    /// overwrite a newer one
+    persist_lock: tokio::sync::Mutex<()>,
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+}
+
+impl LlmResponseCache {
+    pub fn new(ttl_secs: u64, persist_path: Option<String>) -> Self {
+        let mut entries = HashMap::new();
+        if let Some(ref path) = persist_path {
+            if let Ok(content) = std::fs::read_to_string(path) {
+                if let Ok(loaded) = serde_json::from_str::<HashMap<String, CachedResponse>>(&content) {
+                    entries = loaded;
+                }
+            }
+        }
+        Self { entries: Mutex::new(entries), ttl_secs, persist_path, persist_lock: tokio::sync::Mutex::new(()) }
+    }
+
+    fn from_env() -> Option<Self> {
+        let ttl_secs = std::env::var("LLM_CACHE_TTL_SECS").ok()?.parse::<u64>().ok()?;
+        if ttl_secs == 0 {
+            return None;
+        }
+        let persist = std::env::var("LLM_CACHE_PERSIST").map(|v| v == "true").unwrap_or(false);
+        let persist_path = persist.then(|| {
+            let data_path = std::env::var("DATA_PATH").unwrap_or_else(|_| "/data".to_string());
+            format!("{}/llm_cache.json", data_path)
+        });
+        Some(Self::new(ttl_secs, persist_path))
+    }
+
+    /// Process-wide cache, `None` unless enabled via env
+    pub fn global() -> Option<&'static LlmResponseCache> {
+        static CACHE: OnceLock<Option<LlmResponseCache>> = OnceLock::new();
+        CACHE.get_or_init(Self::from_env).as_ref()
+    }
+
+    /// Everything that shapes the answer is part of the key, so a short or differently
+    /// prompted answer is never served for another request. SHA-256 rather than
+    /// `DefaultHasher`, whose output may change between Rust releases, as keys are persisted.
+    pub fn key(provider: &str, model: &str, system: &str, prompt: &str, temperature: f32, max_tokens: usize) -> String {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        // Length-prefixed, so text can't move between fields and keep the same key
+        for field in [provider, model, system, prompt] {
+            hasher.update((field.len() as u64).to_le_bytes());
+            hasher.update(field.as_bytes());
+        }
+        hasher.update(temperature.to_bits().to_le_bytes());
+        hasher.update((max_tokens as u64).to_le_bytes());
+        hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(key)
+            .filter(|e| now_secs().saturating_sub(e.created_at) < self.ttl_secs)
+            .map(|e| e.content.clone())
+    }
+
+    pub async fn put(&self, key: String, content: String) {
+        {
+            let mut entries = self.entries.lock().unwrap();
+            let now = now_secs();
+            entries.retain(|_, e| now.saturating_sub(e.created_at) < self.ttl_secs);
+            entries.insert(key, CachedResponse { content, created_at: now });
+        }
+
+        if let Some(ref path) = self.persist_path {
+            let _writing = self.persist_lock.lock().await;
+            // Snapshot under the write lock, so the file ends up with the latest entries
+            let snapshot = serde_json::to_string(&*self.entries.lock().unwrap());
+            if let Ok(content) = snapshot {
+                if let Err(e) = tokio::fs::write(path, content).await {
+                    println!("WARN: Failed to persist LLM cache: {}", e);
+                }
+            }
+        }
+    }
+}
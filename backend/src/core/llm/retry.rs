@@ -0,0 +1,120 @@
+//! Retry with exponential backoff shared by all LLM and embedding provider wrappers
+
+use std::env;
+use std::future::Future;
+use tokio::time::Duration;
+
+/// Provider call failure, carrying the HTTP status when one is known
+#[derive(Debug, Clone)]
+pub struct ProviderError {
+    pub status: Option<u16>,
+    pub message: String,
+}
+
+impl ProviderError {
+    pub fn new(status: Option<u16>, message: impl Into<String>) -> Self {
+        Self { status, message: message.into() }
+    }
+
+    /// Failure from a provider that only surfaces error text (the NAFS clients). It carries
+    /// no status, so it is not retried here.
+    pub fn untyped(message: impl Into<String>) -> Self {
+        Self { status: None, message: message.into() }
+    }
+}
+
+impl std::fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Lets `?` hand a provider failure to code that still reports errors as `String`
+impl From<ProviderError> for String {
+    fn from(e: ProviderError) -> Self {
+        e.message
+    }
+}
+
+/// 429 (rate limited) and 5xx (upstream trouble) are worth retrying
+pub fn is_retriable_status(status: u16) -> bool {
+    status == 429 || (500..600).contains(&status)
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub jitter_ms: u64,
+    pub is_retriable: fn(u16) -> bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay_ms: 2000,
+            max_delay_ms: 30_000,
+            jitter_ms: 250,
+            is_retriable: is_retriable_status,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Defaults overridden by `LLM_RETRY_MAX`, `LLM_RETRY_BASE_MS`, and `LLM_RETRY_JITTER_MS`
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let read = |key: &str, default: u64| env::var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+        Self {
+            max_retries: read("LLM_RETRY_MAX", defaults.max_retries as u64) as u32,
+            base_delay_ms: read("LLM_RETRY_BASE_MS", defaults.base_delay_ms),
+            jitter_ms: read("LLM_RETRY_JITTER_MS", defaults.jitter_ms),
+            ..defaults
+        }
+    }
+
+    /// Exponential delay for the given 0-based retry attempt, capped, plus random jitter
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        let exp = self.base_delay_ms.saturating_mul(1u64 << attempt.min(16));
+        let jitter = if self.jitter_ms > 0 {
+            (uuid::Uuid::new_v4().as_u128() % (self.jitter_ms as u128 + 1)) as u64
+        } else {
+            0
+        };
+        Duration::from_millis(exp.min(self.max_delay_ms) + jitter)
+    }
+
+    fn should_retry(&self, error: &ProviderError) -> bool {
+        error.status.map(self.is_retriable).unwrap_or(false)
+    }
+}
+
+/// Run `op` until it succeeds, fails with a non-retriable error, or retries are exhausted
+pub async fn retry_with_backoff<T, F, Fut>(policy: &RetryPolicy, label: &str, mut op: F) -> Result<T, ProviderError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ProviderError>>,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < policy.max_retries && policy.should_retry(&e) => {
+                let delay = policy.delay_for(attempt);
+                println!(
+                    "WARN: {} returned {}. Retrying in {}ms ({}/{})...",
+                    label,
+                    e.status.unwrap_or_default(),
+                    delay.as_millis(),
+                    attempt + 1,
+                    policy.max_retries
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Token counting and truncation
+//!
+//! Context assembly, cost accounting, and truncation all go through a [`Tokenizer`]
+//! selected per model. With the `tiktoken` feature the BPE encoding for the model is used;
+//! otherwise (or for unknown models when `TOKENIZER=heuristic`) a character/word estimate.
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex, OnceLock};
+
+pub trait Tokenizer: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn count_tokens(&self, text: &str) -> usize;
+
+    /// Truncate `text` to at most `max_tokens` tokens
+    fn truncate(&self, text: &str, max_tokens: usize) -> String;
+}
+
+/// Approximation used when no BPE tokenizer is available (~4 chars or ~0.75 words per token)
+pub struct HeuristicTokenizer;
+
+impl Tokenizer for HeuristicTokenizer {
+    fn name(&self) -> &str {
+        "heuristic"
+    }
+
+    fn count_tokens(&self, text: &str) -> usize {
+        let chars = text.chars().count();
+        let words = text.split_whitespace().count();
+        let by_chars = (chars + 3) / 4;
+        let by_words = (words * 4 + 2) / 3;
+        by_chars.max(by_words)
+    }
+
+    fn truncate(&self, text: &str, max_tokens: usize) -> String {
+        if self.count_tokens(text) <= max_tokens {
+            return text.to_string();
+        }
+        // Longest prefix within the budget; the estimate never shrinks as a prefix grows
+        let ends: Vec<usize> = text.char_indices().map(|(i, _)| i).skip(1).chain(std::iter::once(text.len())).collect();
+        let prefix = match ends.partition_point(|&end| self.count_tokens(&text[..end]) <= max_tokens) {
+            0 => "",
+            fitting => &text[..ends[fitting - 1]],
+        };
+        // Back off to the last word boundary rather than ending mid-word
+        let mid_word = !prefix.ends_with(char::is_whitespace)
+            && !text[prefix.len()..].starts_with(char::is_whitespace);
+        match prefix.rfind(char::is_whitespace) {
+            Some(boundary) if mid_word => prefix[..boundary].trim_end().to_string(),
+            _ => prefix.trim_end().to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "tiktoken")]
+pub struct TiktokenTokenizer {
+    name: String,
+    bpe: tiktoken_rs::CoreBPE,
+}
+
+#[cfg(feature = "tiktoken")]
+impl TiktokenTokenizer {
+    /// Encoding for the model, or `cl100k_base` for models tiktoken doesn't know (Claude, Llama, ...)
+    pub fn for_model(model: &str) -> Option<Self> {
+        match tiktoken_rs::get_bpe_from_model(model) {
+            Ok(bpe) => Some(Self { name: format!("tiktoken:{}", model), bpe }),
+            Err(_) => tiktoken_rs::cl100k_base()
+                .ok()
+                .map(|bpe| Self { name: "tiktoken:cl100k_base".to_string(), bpe }),
+        }
+    }
+}
+
+#[cfg(feature = "tiktoken")]
+impl Tokenizer for TiktokenTokenizer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn count_tokens(&self, text: &str) -> usize {
+        self.bpe.encode_with_special_tokens(text).len()
+    }
+
+    fn truncate(&self, text: &str, max_tokens: usize) -> String {
+        let tokens = self.bpe.encode_with_special_tokens(text);
+        if tokens.len() <= max_tokens {
+            return text.to_string();
+        }
+        for kept in (0..=max_tokens).rev() {
+            // A prefix can split a character, and re-encoding the decoded text can merge
+            // into more tokens than were kept, so shorten until it fits
+            if let Ok(cut) = self.bpe.decode(tokens[..kept].to_vec()) {
+                if self.count_tokens(&cut) <= max_tokens {
+                    return cut;
+                }
+            }
+        }
+        String::new()
+    }
+}
+
+#[cfg(feature = "tiktoken")]
+fn bpe_tokenizer(model: &str) -> Option<Arc<dyn Tokenizer>> {
+    TiktokenTokenizer::for_model(model).map(|t| Arc::new(t) as Arc<dyn Tokenizer>)
+}
+
+#[cfg(not(feature = "tiktoken"))]
+fn bpe_tokenizer(_model: &str) -> Option<Arc<dyn Tokenizer>> {
+    None
+}
+
+/// Tokenizer for a model. Instances are cached since BPE tables are costly to build.
+pub fn tokenizer_for_model(model: &str) -> Arc<dyn Tokenizer> {
+    if env::var("TOKENIZER").map(|t| t == "heuristic").unwrap_or(false) {
+        return Arc::new(HeuristicTokenizer);
+    }
+
+    static CACHE: OnceLock<Mutex<HashMap<String, Arc<dyn Tokenizer>>>> = OnceLock::new();
+    let cache = CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut cache = cache.lock().unwrap();
+
+    cache
+        .entry(model.to_string())
+        .or_insert_with(|| bpe_tokenizer(model).unwrap_or_else(|| Arc::new(HeuristicTokenizer)))
+        .clone()
+}
+
+/// Split text on word boundaries into pieces of at most ~`max_tokens` tokens each
+pub fn chunk_by_tokens(tokenizer: &dyn Tokenizer, text: &str, max_tokens: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_tokens = 0;
+
+    for word in text.split_inclusive(char::is_whitespace) {
+        let word_tokens = tokenizer.count_tokens(word);
+        if current_tokens + word_tokens > max_tokens && !current.is_empty() {
+            chunks.push(std::mem::take(&mut current));
+            current_tokens = 0;
+        }
+        current.push_str(word);
+        current_tokens += word_tokens;
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Tokenizer for the configured default model
+pub fn default_tokenizer() -> Arc<dyn Tokenizer> {
+    tokenizer_for_model(&crate::core::llm::nafs_provider::get_default_model())
+}
@@ -0,0 +1,137 @@
+//! Function calling over the OpenAI-compatible chat completions API
+//!
+//! Messages are kept as raw JSON so assistant turns carrying `tool_calls` can be sent
+//! back to the provider unchanged alongside the `tool` results.
+
+use crate::core::llm::nafs_provider::GenerationParams;
+use crate::core::llm::usage::TokenUsage;
+use serde_json::{json, Value};
+
+/// A tool the model may call, described by a JSON Schema for its arguments
+#[derive(Debug, Clone)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    pub parameters: Value,
+}
+
+impl ToolDefinition {
+    pub fn to_openai(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": self.name,
+                "description": self.description,
+                "parameters": self.parameters,
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    /// Parsed arguments; `Value::Null` when the model sent invalid JSON
+    pub arguments: Value,
+}
+
+/// One model turn: either a final answer or a request to run tools
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolTurn {
+    Final(String),
+    /// `message` is the assistant turn to append to the history before the tool results
+    Calls { message: Value, calls: Vec<ToolCall> },
+}
+
+/// Parse a (non-streaming) chat completion response body. Every entry of `tool_calls`
+/// becomes a `ToolCall`, even one without a usable name, so each id in the assistant
+/// turn gets a result; entries missing an id are given one in `message` as well.
+pub fn parse_tool_response(body: &Value) -> Result<(ToolTurn, Option<TokenUsage>), String> {
+    let mut message = body["choices"][0]["message"].clone();
+    if message.is_null() {
+        return Err(format!("Chat completion response has no message: {}", body));
+    }
+
+    let usage = body.get("usage").filter(|u| u.is_object()).map(|u| TokenUsage {
+        prompt_tokens: u["prompt_tokens"].as_u64().unwrap_or(0),
+        completion_tokens: u["completion_tokens"].as_u64().unwrap_or(0),
+        estimated: false,
+    });
+
+    let calls: Vec<ToolCall> = match message["tool_calls"].as_array_mut() {
+        Some(entries) => entries.iter_mut().enumerate().map(|(i, c)| {
+            let id = match c["id"].as_str().filter(|id| !id.is_empty()) {
+                Some(id) => id.to_string(),
+                None => {
+                    let id = format!("call_{}", i);
+                    c["id"] = Value::from(id.clone());
+                    id
+                }
+            };
+            let name = c["function"]["name"].as_str().unwrap_or_default().to_string();
+            // Arguments arrive as a JSON-encoded string
+            let arguments = c["function"]["arguments"].as_str()
+                .and_then(|a| serde_json::from_str(a).ok())
+                .unwrap_or(Value::Null);
+            ToolCall { id, name, arguments }
+        }).collect(),
+        None => Vec::new(),
+    };
+
+    if calls.is_empty() {
+        let content = message["content"].as_str().unwrap_or_default().to_string();
+        return Ok((ToolTurn::Final(content), usage));
+    }
+    Ok((ToolTurn::Calls { message, calls }, usage))
+}
+
+/// Message reporting a tool's output back to the model
+pub fn tool_result_message(call_id: &str, content: &str) -> Value {
+    json!({ "role": "tool", "tool_call_id": call_id, "content": content })
+}
+
+/// Sampling temperature for tool-calling turns unless the agent sets its own; kept low so
+/// tool arguments stay well-formed
+pub const TOOL_TEMPERATURE: f32 = 0.2;
+
+/// POSTs one chat completion with `tools` available to the model
+pub async fn chat_completion_with_tools(
+    base_url: &str,
+    api_key: &str,
+    model: &str,
+    messages: &[Value],
+    tools: &[ToolDefinition],
+    params: &GenerationParams,
+) -> Result<(ToolTurn, Option<TokenUsage>), String> {
+    let url = format!("{}/chat/completions", base_url.trim_end_matches('/'));
+    let mut body = json!({
+        "model": model,
+        "messages": messages,
+        "max_tokens": params.max_tokens,
+        "temperature": params.temperature,
+    });
+    // Providers reject an empty tool list, so omit it to force a plain answer
+    if !tools.is_empty() {
+        body["tools"] = Value::from(tools.iter().map(|t| t.to_openai()).collect::<Vec<Value>>());
+        body["tool_choice"] = json!("auto");
+    }
+
+    let response = crate::http_client::shared()
+        .post(&url)
+        .bearer_auth(api_key)
+        .json(&body)
+        .timeout(crate::http_client::request_timeout())
+        .send()
+        .await
+        .map_err(|e| format!("LLM tool request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("LLM tool error {}: {}", status, body));
+    }
+
+    let body: Value = response.json().await.map_err(|e| format!("LLM tool response unreadable: {}", e))?;
+    parse_tool_response(&body)
+}
@@ -1,6 +1,19 @@
 pub mod search_engine;
+pub mod search_cache;
 pub mod graph_manager;
 pub mod agent_orchestrator;
 pub mod rbac;
 pub mod llm;
 pub mod audit_manager;
+pub mod abbreviations;
+pub mod output_filter;
+pub mod citations;
+pub mod graph_export;
+pub mod text_analysis;
+pub mod sparse_vector;
+pub mod query_syntax;
+pub mod ingest_queue;
+pub mod search_history;
+pub mod feedback;
+pub mod redaction;
+pub mod result_processor;
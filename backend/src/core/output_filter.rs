@@ -0,0 +1,92 @@
+use regex::Regex;
+
+/// Whole first lines that only acknowledge the request or announce what follows
+const LEADING_PATTERNS: &[&str] = &[
+    // Bare acknowledgements: "Sure!", "Certainly."
+    r"(?i)^(?:sure|certainly|of course|absolutely|great question)[,!.]*$",
+    // Announcements ending in a colon: "Here is the summary you asked for:", "Sure, here's the report:"
+    r"(?i)^(?:(?:sure|certainly|of course|absolutely)[,!.]?\s+)?here(?:'s| is| are)\b.*:$",
+];
+
+const DEFAULT_PATTERNS: &[&str] = &[
+    // Trailing pleasantries and offers of further help
+    r"(?i)\n\s*(?:i hope this helps|hope this helps|let me know if|feel free to|please let me know)[^\n]*\s*$",
+];
+
+/// Strips boilerplate preambles and trailing disclaimers from LLM output.
+///
+/// A preamble is only ever the first line, and is only dropped when the answer follows on
+/// later lines. The remaining patterns are applied repeatedly until the text stops
+/// changing. Extra patterns can be supplied as a JSON array of regexes in `OUTPUT_STRIP_PATTERNS`; set
+/// `AGENT_STRIP_PREAMBLE=false` to disable filtering entirely.
+#[derive(Clone)]
+pub struct OutputFilter {
+    leading: Vec<Regex>,
+    patterns: Vec<Regex>,
+    enabled: bool,
+}
+
+impl Default for OutputFilter {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+impl OutputFilter {
+    pub fn new(extra_patterns: &[String]) -> Self {
+        let patterns = DEFAULT_PATTERNS.iter()
+            .map(|p| p.to_string())
+            .chain(extra_patterns.iter().cloned())
+            .filter_map(|p| match Regex::new(&p) {
+                Ok(re) => Some(re),
+                Err(e) => {
+                    println!("WARN: Ignoring invalid output pattern {}: {}", p, e);
+                    None
+                }
+            })
+            .collect();
+        let leading = LEADING_PATTERNS.iter()
+            .map(|p| Regex::new(p).expect("built-in preamble pattern is valid"))
+            .collect();
+        Self { leading, patterns, enabled: true }
+    }
+
+    pub fn from_env() -> Self {
+        let extra: Vec<String> = std::env::var("OUTPUT_STRIP_PATTERNS")
+            .ok()
+            .and_then(|v| serde_json::from_str(&v).ok())
+            .unwrap_or_default();
+        let mut filter = Self::new(&extra);
+        filter.enabled = std::env::var("AGENT_STRIP_PREAMBLE").map(|v| v != "false").unwrap_or(true);
+        filter
+    }
+
+    pub fn clean(&self, text: &str) -> String {
+        if !self.enabled {
+            return text.to_string();
+        }
+
+        let mut cleaned = text.trim().to_string();
+        if let Some((first, rest)) = cleaned.split_once('\n') {
+            if self.leading.iter().any(|p| p.is_match(first.trim())) {
+                cleaned = rest.trim().to_string();
+            }
+        }
+        loop {
+            let before = cleaned.len();
+            for pattern in &self.patterns {
+                cleaned = pattern.replace(&cleaned, "").trim().to_string();
+            }
+            if cleaned.len() == before {
+                break;
+            }
+        }
+
+        // Never hand back nothing if the whole response looked like boilerplate
+        if cleaned.is_empty() {
+            text.trim().to_string()
+        } else {
+            cleaned
+        }
+    }
+}
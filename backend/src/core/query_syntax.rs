@@ -0,0 +1,113 @@
+/// Document fields a query clause can be scoped to with `field:value`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueryField {
+    /// First non-empty line of the content
+    Title,
+    Content,
+    /// Substring of the document id
+    DocId,
+    Language,
+}
+
+impl QueryField {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "title" => Some(Self::Title),
+            "content" | "body" => Some(Self::Content),
+            "id" | "doc_id" => Some(Self::DocId),
+            "lang" | "language" => Some(Self::Language),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldClause {
+    pub field: QueryField,
+    pub value: String,
+}
+
+/// A search query split into free terms, quoted phrases that must match contiguously,
+/// and field-scoped clauses that must all hold
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedQuery {
+    pub terms: Vec<String>,
+    pub phrases: Vec<String>,
+    pub fields: Vec<FieldClause>,
+}
+
+impl ParsedQuery {
+    /// The query's words without syntax, for scoring and embedding. Document id and
+    /// language clauses are filters only and are left out.
+    pub fn text(&self) -> String {
+        let scoped = self.fields.iter()
+            .filter(|c| matches!(c.field, QueryField::Title | QueryField::Content))
+            .map(|c| c.value.as_str());
+        self.terms.iter().map(String::as_str)
+            .chain(self.phrases.iter().map(String::as_str))
+            .chain(scoped)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// True when some clause restricts which documents may match
+    pub fn has_constraints(&self) -> bool {
+        !self.phrases.is_empty() || !self.fields.is_empty()
+    }
+}
+
+/// Parses `"exact phrase"` and `field:value` / `field:"a phrase"` clauses out of `query`.
+/// Unknown field names and an unterminated quote are read as plain text.
+pub fn parse_query(query: &str) -> ParsedQuery {
+    let mut parsed = ParsedQuery::default();
+    let mut rest = query.trim_start();
+    while !rest.is_empty() {
+        if let Some(after_quote) = rest.strip_prefix('"') {
+            let (phrase, remainder) = take_quoted(after_quote);
+            // A phrase without words (`"--"`) would constrain nothing
+            if phrase.chars().any(char::is_alphanumeric) {
+                parsed.phrases.push(phrase.trim().to_string());
+            }
+            rest = remainder.trim_start();
+            continue;
+        }
+
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let word = &rest[..end];
+        let field = word.split_once(':').and_then(|(name, _)| QueryField::parse(name).map(|f| (f, name.len())));
+        match field {
+            Some((field, name_len)) => {
+                let after_colon = &rest[name_len + 1..];
+                let (value, remainder) = match after_colon.strip_prefix('"') {
+                    Some(quoted) => take_quoted(quoted),
+                    None => {
+                        let end = after_colon.find(char::is_whitespace).unwrap_or(after_colon.len());
+                        (&after_colon[..end], &after_colon[end..])
+                    }
+                };
+                if !value.trim().is_empty() {
+                    parsed.fields.push(FieldClause { field, value: value.trim().to_string() });
+                }
+                rest = remainder.trim_start();
+            }
+            None => {
+                parsed.terms.push(word.to_string());
+                rest = rest[end..].trim_start();
+            }
+        }
+    }
+    parsed
+}
+
+/// Text up to the closing quote and what follows it; everything when the quote is unterminated
+fn take_quoted(text: &str) -> (&str, &str) {
+    match text.find('"') {
+        Some(end) => (&text[..end], &text[end + 1..]),
+        None => (text, ""),
+    }
+}
+
+/// True when `needle` occurs as a contiguous run in `haystack`
+pub fn contains_sequence(haystack: &[String], needle: &[String]) -> bool {
+    needle.is_empty() || haystack.windows(needle.len()).any(|window| window == needle)
+}
@@ -1,5 +1,7 @@
 use crate::core::search_engine::SearchResults;
-use crate::core::graph_manager::ContextGraph;
+use crate::core::graph_manager::{ContextGraph, Entity};
+use crate::db::barq_vector::DEFAULT_COLLECTION;
+use crate::error::{BrainVaultError, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -11,70 +13,419 @@ pub enum Role {
     Viewer,
 }
 
+impl Role {
+    /// Higher is more privileged; a user in several groups gets the highest role among them
+    fn rank(&self) -> u8 {
+        match self {
+            Role::Admin => 3,
+            Role::DataOwner => 2,
+            Role::Agent => 1,
+            Role::Viewer => 0,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Permission {
+    /// User id, or the group id for a grant added with `add_group_permission`
     pub user_id: String,
     pub role: Role,
     pub accessible_entities: Vec<String>,    // Graph node IDs they can access
     pub accessible_collections: Vec<String>, // Document collections
+    /// Documents and graph nodes denied regardless of role, listing or collection
+    #[serde(default)]
+    pub excluded_entities: Vec<String>,
+    /// Unix time (seconds) after which the grant no longer applies; never expires when unset
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+}
+
+impl Permission {
+    pub fn is_expired_at(&self, now: u64) -> bool {
+        self.expires_at.map_or(false, |expires_at| now >= expires_at)
+    }
+
+    /// Whether this permission covers `entity_id`, listed by id or filed under one of
+    /// `accessible_collections` (`collection` is `None` for entities outside any collection).
+    /// `excluded_entities` overrides every grant.
+    pub fn allows(&self, entity_id: &str, collection: Option<&str>) -> bool {
+        if self.excludes(entity_id) {
+            return false;
+        }
+        self.role == Role::Admin
+            || self.accessible_entities.iter().any(|id| id == entity_id)
+            || collection.map_or(false, |c| self.accessible_collections.iter().any(|allowed| allowed == c))
+    }
+
+    /// Whether this permission may add or replace `doc_id` in `collection`: Admins anywhere,
+    /// Data Owners in their `accessible_collections`, Agents and Viewers never.
+    /// `excluded_entities` overrides the role.
+    pub fn can_write(&self, doc_id: &str, collection: &str) -> bool {
+        if self.excludes(doc_id) {
+            return false;
+        }
+        match self.role {
+            Role::Admin => true,
+            Role::DataOwner => self.accessible_collections.iter().any(|c| c == collection),
+            Role::Agent | Role::Viewer => false,
+        }
+    }
+
+    /// Whether `entity_id` is explicitly denied
+    pub fn excludes(&self, entity_id: &str) -> bool {
+        self.excluded_entities.iter().any(|excluded| excluded == entity_id)
+    }
+}
+
+/// Collection a graph entity belongs to, from its `collection` property
+pub fn entity_collection(entity: &Entity) -> Option<&str> {
+    entity.properties.get("collection").map(String::as_str)
+}
+
+/// Why `RBAC::explain_access` allowed or denied an entity
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessReason {
+    /// Admins see everything not excluded
+    Admin,
+    /// The entity is in a grant's `accessible_entities`
+    Listed,
+    /// The entity's collection is in a grant's `accessible_collections`
+    CollectionGranted,
+    /// A grant's `excluded_entities` names the entity
+    Excluded,
+    /// A grant that would have covered the entity has expired
+    Expired,
+    /// The entity's collection isn't among the user's collections, nor is the entity listed
+    CollectionDenied,
+    /// The entity is in no collection and isn't listed
+    NotInAllowList,
+    /// The user has no grant at all, directly or through a group
+    UnknownUser,
+}
+
+/// Access decision for one user and entity, with the grant that decided it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccessExplanation {
+    pub user_id: String,
+    pub entity: String,
+    pub collection: Option<String>,
+    pub allowed: bool,
+    pub reason: AccessReason,
+    /// `user` for the user's own grant, `group:{id}` for a group's
+    pub grant: Option<String>,
+    /// Effective role, when the user has an unexpired grant
+    pub role: Option<Role>,
+    pub groups: Vec<String>,
+    pub detail: String,
+}
+
+/// Named set of users that share the grants given to the group
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Group {
+    pub id: String,
+    pub members: Vec<String>,
+}
+
+/// On-disk form of the RBAC configuration
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RbacState {
+    #[serde(default)]
+    pub permissions: Vec<Permission>,
+    #[serde(default)]
+    pub groups: Vec<Group>,
+    /// Grants whose `user_id` is a group id
+    #[serde(default)]
+    pub group_permissions: Vec<Permission>,
 }
 
 pub struct RBAC {
     pub permissions: HashMap<String, Permission>,
+    pub groups: HashMap<String, Group>,
+    pub group_permissions: HashMap<String, Permission>,
 }
 
 impl RBAC {
     pub fn new() -> Self {
         Self {
             permissions: HashMap::new(),
+            groups: HashMap::new(),
+            group_permissions: HashMap::new(),
         }
     }
+
+    /// Users, groups and group grants from `{data_path}/rbac.json`, without any that have
+    /// expired; empty when the file is absent
+    pub fn at_path(data_path: &str) -> Self {
+        let path = format!("{}/rbac.json", data_path);
+        let mut rbac = Self::new();
+        if let Ok(content) = std::fs::read_to_string(&path) {
+            match serde_json::from_str::<RbacState>(&content) {
+                Ok(state) => {
+                    println!("INFO: Loaded {} permissions and {} groups from {}", state.permissions.len(), state.groups.len(), path);
+                    state.permissions.into_iter().for_each(|p| rbac.add_permission(p));
+                    state.groups.into_iter().for_each(|g| rbac.add_group(g));
+                    state.group_permissions.into_iter().for_each(|p| rbac.add_group_permission(p));
+                }
+                Err(e) => println!("WARN: Invalid RBAC file {}: {}", path, e),
+            }
+        }
+        // Expired grants are dropped from the file too, rather than carried forward
+        let reaped = rbac.reap_expired();
+        if reaped > 0 {
+            println!("INFO: Dropped {} expired grants from {}", reaped, path);
+            if let Err(e) = rbac.save(data_path) {
+                println!("WARN: Could not rewrite {} without expired grants: {}", path, e);
+            }
+        }
+        rbac
+    }
+
+    /// Write users, groups and group grants to `{data_path}/rbac.json`
+    pub fn save(&self, data_path: &str) -> Result<()> {
+        let sorted = |map: &HashMap<String, Permission>| {
+            let mut perms: Vec<Permission> = map.values().cloned().collect();
+            perms.sort_by(|a, b| a.user_id.cmp(&b.user_id));
+            perms
+        };
+        let mut groups: Vec<Group> = self.groups.values().cloned().collect();
+        groups.sort_by(|a, b| a.id.cmp(&b.id));
+        let state = RbacState {
+            permissions: sorted(&self.permissions),
+            groups,
+            group_permissions: sorted(&self.group_permissions),
+        };
+        let content = serde_json::to_string_pretty(&state)
+            .map_err(|e| BrainVaultError::Internal(e.to_string()))?;
+        std::fs::write(format!("{}/rbac.json", data_path), content)?;
+        Ok(())
+    }
     
     pub fn add_permission(&mut self, perm: Permission) {
         self.permissions.insert(perm.user_id.clone(), perm);
     }
-    
-    pub async fn get_permission(&self, user_id: &str) -> Result<&Permission, String> {
-        self.permissions.get(user_id).ok_or_else(|| "User not found".to_string())
+
+    /// Drop expired user and group grants; returns how many were removed
+    pub fn reap_expired(&mut self) -> usize {
+        let now = now_secs();
+        let before = self.permissions.len() + self.group_permissions.len();
+        self.permissions.retain(|_, p| !p.is_expired_at(now));
+        self.group_permissions.retain(|_, p| !p.is_expired_at(now));
+        before - self.permissions.len() - self.group_permissions.len()
+    }
+
+    pub fn has_permission(&self, user_id: &str) -> bool {
+        self.permissions.contains_key(user_id)
+    }
+
+    pub fn add_group(&mut self, group: Group) {
+        self.groups.insert(group.id.clone(), group);
+    }
+
+    /// Grant `perm` to every member of the group named by `perm.user_id`
+    pub fn add_group_permission(&mut self, perm: Permission) {
+        self.group_permissions.insert(perm.user_id.clone(), perm);
+    }
+
+    /// Ids of the groups `user_id` belongs to, sorted
+    pub fn groups_of(&self, user_id: &str) -> Vec<&str> {
+        let mut ids: Vec<&str> = self.groups.values()
+            .filter(|g| g.members.iter().any(|m| m == user_id))
+            .map(|g| g.id.as_str())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// The user's effective permission: their direct grant unioned with the grants of
+    /// every group they belong to, with the highest role and all exclusions kept.
+    /// Expired grants are ignored; unknown when no unexpired grant is left.
+    pub async fn get_permission(&self, user_id: &str) -> Result<Permission> {
+        let now = now_secs();
+        let grants: Vec<&Permission> = self.permissions.get(user_id).into_iter()
+            .chain(self.groups_of(user_id).into_iter().filter_map(|g| self.group_permissions.get(g)))
+            .filter(|grant| !grant.is_expired_at(now))
+            .collect();
+        let (first, rest) = grants.split_first()
+            .ok_or_else(|| BrainVaultError::Unauthorized(format!("Unknown user '{}'", user_id)))?;
+
+        let merge = |into: &mut Vec<String>, from: &[String]| {
+            for id in from {
+                if !into.contains(id) {
+                    into.push(id.clone());
+                }
+            }
+        };
+        let mut effective = Permission { user_id: user_id.to_string(), ..(*first).clone() };
+        // The union lasts only as long as its earliest-expiring grant
+        effective.expires_at = grants.iter().filter_map(|g| g.expires_at).min();
+        for grant in rest {
+            if grant.role.rank() > effective.role.rank() {
+                effective.role = grant.role.clone();
+            }
+            merge(&mut effective.accessible_entities, &grant.accessible_entities);
+            merge(&mut effective.accessible_collections, &grant.accessible_collections);
+            merge(&mut effective.excluded_entities, &grant.excluded_entities);
+        }
+        Ok(effective)
+    }
+
+    /// Every grant that applies to `user_id`, expired or not, labelled `user` or `group:{id}`
+    fn grants_of(&self, user_id: &str) -> Vec<(String, &Permission)> {
+        self.permissions.get(user_id).map(|p| ("user".to_string(), p)).into_iter()
+            .chain(self.groups_of(user_id).into_iter()
+                .filter_map(|g| self.group_permissions.get(g).map(|p| (format!("group:{}", g), p))))
+            .collect()
+    }
+
+    /// The same decision as `check_access_in`, with the reason and the grant behind it
+    pub async fn explain_access(&self, user_id: &str, entity_id: &str, collection: Option<&str>) -> AccessExplanation {
+        let now = now_secs();
+        let grants = self.grants_of(user_id);
+        let (live, expired): (Vec<_>, Vec<_>) = grants.iter().partition(|(_, g)| !g.is_expired_at(now));
+        let effective = self.get_permission(user_id).await.ok();
+
+        let explain = |allowed: bool, reason: AccessReason, grant: Option<&String>, detail: String| AccessExplanation {
+            user_id: user_id.to_string(),
+            entity: entity_id.to_string(),
+            collection: collection.map(str::to_string),
+            allowed,
+            reason,
+            grant: grant.cloned(),
+            role: effective.as_ref().map(|p| p.role.clone()),
+            groups: self.groups_of(user_id).into_iter().map(str::to_string).collect(),
+            detail,
+        };
+
+        if let Some((source, _)) = live.iter().find(|(_, g)| g.excludes(entity_id)) {
+            return explain(false, AccessReason::Excluded, Some(source), format!("'{}' is in the excluded entities of the {} grant", entity_id, source));
+        }
+        if let Some((source, _)) = live.iter().find(|(_, g)| g.role == Role::Admin) {
+            return explain(true, AccessReason::Admin, Some(source), format!("The {} grant has the Admin role", source));
+        }
+        if let Some((source, _)) = live.iter().find(|(_, g)| g.accessible_entities.iter().any(|id| id == entity_id)) {
+            return explain(true, AccessReason::Listed, Some(source), format!("'{}' is listed in the {} grant", entity_id, source));
+        }
+        if let Some(c) = collection {
+            if let Some((source, _)) = live.iter().find(|(_, g)| g.accessible_collections.iter().any(|allowed| allowed == c)) {
+                return explain(true, AccessReason::CollectionGranted, Some(source), format!("Collection '{}' is granted by the {} grant", c, source));
+            }
+        }
+        if let Some((source, grant)) = expired.iter().find(|(_, g)| Permission { expires_at: None, ..(*g).clone() }.allows(entity_id, collection)) {
+            return explain(false, AccessReason::Expired, Some(source), format!("The {} grant covered it but expired at {}", source, grant.expires_at.unwrap_or_default()));
+        }
+        if grants.is_empty() {
+            return explain(false, AccessReason::UnknownUser, None, format!("'{}' has no grant, directly or through a group", user_id));
+        }
+        if live.is_empty() {
+            return explain(false, AccessReason::Expired, None, format!("Every grant of '{}' has expired", user_id));
+        }
+        match collection {
+            Some(c) => {
+                let allowed = effective.as_ref().map(|p| p.accessible_collections.clone()).unwrap_or_default();
+                explain(false, AccessReason::CollectionDenied, None, format!("'{}' isn't listed and collection '{}' isn't among {:?}", entity_id, c, allowed))
+            }
+            None => explain(false, AccessReason::NotInAllowList, None, format!("'{}' isn't listed in any grant and belongs to no collection", entity_id)),
+        }
+    }
+
+    /// Whether `user_id` currently holds the Admin role, directly or through a group
+    pub async fn is_admin(&self, user_id: &str) -> bool {
+        matches!(self.get_permission(user_id).await, Ok(p) if p.role == Role::Admin)
     }
 
-    pub async fn check_access(&self, user_id: &str, entity_id: &str) -> Result<bool, String> {
+    pub async fn check_access(&self, user_id: &str, entity_id: &str) -> Result<bool> {
+        self.check_access_in(user_id, entity_id, None).await
+    }
+
+    /// Like `check_access`, also granting access through the collection `entity_id` is filed under
+    pub async fn check_access_in(&self, user_id: &str, entity_id: &str, collection: Option<&str>) -> Result<bool> {
+        let perm = self.get_permission(user_id).await?;
+        Ok(perm.allows(entity_id, collection))
+    }
+
+    /// Whether `user_id` may search within `collection`
+    pub async fn check_collection_access(&self, user_id: &str, collection: &str) -> Result<bool> {
         let perm = self.get_permission(user_id).await?;
         if perm.role == Role::Admin {
             return Ok(true);
         }
-        Ok(perm.accessible_entities.contains(&entity_id.to_string()))
+        Ok(perm.accessible_collections.iter().any(|c| c == collection))
     }
-    
+
+    /// Whether `user_id` may write `doc_id` into `collection`; see `Permission::can_write`
+    pub async fn check_collection_write(&self, user_id: &str, doc_id: &str, collection: &str) -> Result<bool> {
+        let perm = self.get_permission(user_id).await?;
+        Ok(perm.can_write(doc_id, collection))
+    }
+
     pub async fn get_permitted_search_results(&self, user_id: &str, results: SearchResults) -> SearchResults {
         let perm_result = self.get_permission(user_id).await;
         if let Ok(perm) = perm_result {
-             if perm.role == Role::Admin {
-                 return results;
-             }
-             
              let filtered = results.hits.into_iter()
-                .filter(|hit| perm.accessible_entities.contains(&hit.doc_id))
+                // Historical revisions carry their document's id, so they follow its access
+                .filter(|hit| perm.allows(&hit.doc_id, None))
                 .collect();
              return SearchResults { hits: filtered };
         }
         
         SearchResults { hits: vec![] }
     }
-    
-    pub async fn filter_context(&self, user_id: &str, context: ContextGraph) -> Result<ContextGraph, String> {
+
+    /// Like `get_permitted_search_results`, also keeping hits from permitted collections.
+    /// `collections` maps document ids to their collection; documents not in it are in
+    /// `DEFAULT_COLLECTION`.
+    pub async fn get_permitted_search_results_in(
+        &self,
+        user_id: &str,
+        results: SearchResults,
+        collections: &HashMap<String, String>,
+    ) -> SearchResults {
+        let perm = match self.get_permission(user_id).await {
+            Ok(perm) => perm,
+            Err(_) => return SearchResults { hits: vec![] },
+        };
+        let hits = results.hits.into_iter()
+            .filter(|hit| {
+                let collection = collections.get(&hit.doc_id).map_or(DEFAULT_COLLECTION, String::as_str);
+                perm.allows(&hit.doc_id, Some(collection))
+            })
+            .collect();
+        SearchResults { hits }
+    }
+
+    /// The part of `context` `user_id` may see. Relationships go with their endpoints: an
+    /// edge is kept only when the caller may see both ends, so hidden ids don't leak
+    /// through `from_id`/`to_id`.
+    pub async fn filter_context(&self, user_id: &str, context: ContextGraph) -> Result<ContextGraph> {
          let perm = self.get_permission(user_id).await?;
-         if perm.role == Role::Admin {
+         if perm.role == Role::Admin && perm.excluded_entities.is_empty() {
              return Ok(context);
          }
-         
+
+         let collections: HashMap<String, Option<String>> = context.entities.iter()
+             .map(|e| (e.id.clone(), entity_collection(e).map(str::to_string)))
+             .collect();
+         let visible = |id: &str| perm.allows(id, collections.get(id).and_then(|c| c.as_deref()));
+
          let entities: Vec<_> = context.entities.into_iter()
-             .filter(|e| perm.accessible_entities.contains(&e.id))
+             .filter(|e| visible(&e.id))
+             .collect();
+         let depths = context.depths.into_iter()
+             .filter(|(id, _)| visible(id))
+             .collect();
+         let relationships = context.relationships.into_iter()
+             .filter(|rel| visible(&rel.from_id) && visible(&rel.to_id))
              .collect();
-             
          Ok(ContextGraph {
              entities,
-             relationships: context.relationships, 
+             relationships,
+             depths,
+             next_cursor: context.next_cursor,
          })
     }
 }
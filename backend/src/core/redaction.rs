@@ -0,0 +1,137 @@
+//! Masking of personal data in document content
+//!
+//! With `REDACTION_ENABLED=true`, emails, SSNs and phone numbers (or the subset named in
+//! `REDACTION_TYPES`) are replaced with `[REDACTED:EMAIL]`-style markers before a document
+//! is embedded, cached or handed to an agent, so the original values never reach the
+//! vector store or an external LLM. `REDACTION_PATTERNS` adds deployment-specific rules as
+//! a JSON object of name -> regex. `REDACTION_ON_QUERY=true` also masks content returned
+//! by search and document lookups, covering documents ingested before redaction was on.
+
+use regex::Regex;
+use std::collections::BTreeMap;
+
+/// Built-in rules, in the order they're applied: SSNs go before phone numbers so a
+/// `123-45-6789` is never half-matched as a phone number. A phone number needs a leading
+/// `+` country code, a parenthesized area code or separators between its groups, so bare
+/// digit runs such as timestamps and order ids are left alone.
+const BUILTIN_RULES: &[(&str, &str)] = &[
+    ("email", r"(?i)\b[A-Z0-9._%+-]+@[A-Z0-9.-]+\.[A-Z]{2,}\b"),
+    ("ssn", r"\b\d{3}-\d{2}-\d{4}\b"),
+    ("phone", r"(?:\+\d{1,3}[ .-]?(?:\(\d{3}\)|\d{3})[ .-]?\d{3}[ .-]?\d{4}|(?:\(\d{3}\) ?|\b\d{3}[ .-])\d{3}[ .-]\d{4})\b"),
+];
+
+/// Content after redaction, with the number of matches masked per rule
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Redaction {
+    pub text: String,
+    pub counts: BTreeMap<String, usize>,
+}
+
+impl Redaction {
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// `email:2, phone:1`, for logs and audit details; never includes the masked values
+    pub fn summary(&self) -> String {
+        self.counts.iter().map(|(name, n)| format!("{}:{}", name, n)).collect::<Vec<_>>().join(", ")
+    }
+}
+
+#[derive(Clone)]
+pub struct Redactor {
+    rules: Vec<(String, Regex)>,
+    /// Mask content before it is indexed
+    pub on_ingest: bool,
+    /// Mask content returned by search and document lookups
+    pub on_query: bool,
+}
+
+impl Default for Redactor {
+    /// All built-in rules, switched off
+    fn default() -> Self {
+        let types: Vec<String> = BUILTIN_RULES.iter().map(|(name, _)| name.to_string()).collect();
+        Self::new(&types, &BTreeMap::new())
+    }
+}
+
+impl Redactor {
+    /// The built-in rules named in `types`, followed by `extra` (name -> regex)
+    pub fn new(types: &[String], extra: &BTreeMap<String, String>) -> Self {
+        for name in types {
+            if !BUILTIN_RULES.iter().any(|(builtin, _)| builtin == name) {
+                println!("WARN: Ignoring unknown redaction type {}", name);
+            }
+        }
+        let rules = BUILTIN_RULES.iter()
+            .filter(|(name, _)| types.iter().any(|t| t == name))
+            .map(|(name, pattern)| (name.to_string(), pattern.to_string()))
+            .chain(extra.iter().map(|(name, pattern)| (name.clone(), pattern.clone())))
+            .filter_map(|(name, pattern)| match Regex::new(&pattern) {
+                Ok(re) => Some((name, re)),
+                Err(e) => {
+                    println!("WARN: Ignoring invalid redaction pattern {}: {}", name, e);
+                    None
+                }
+            })
+            .collect();
+        Self { rules, on_ingest: false, on_query: false }
+    }
+
+    pub fn from_env() -> Self {
+        let types: Vec<String> = match std::env::var("REDACTION_TYPES") {
+            Ok(v) => v.split(',').map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty()).collect(),
+            Err(_) => BUILTIN_RULES.iter().map(|(name, _)| name.to_string()).collect(),
+        };
+        let extra: BTreeMap<String, String> = std::env::var("REDACTION_PATTERNS")
+            .ok()
+            .and_then(|v| serde_json::from_str(&v).ok())
+            .unwrap_or_default();
+        Self::new(&types, &extra)
+            .with_ingest(std::env::var("REDACTION_ENABLED").map(|v| v == "true").unwrap_or(false))
+            .with_query(std::env::var("REDACTION_ON_QUERY").map(|v| v == "true").unwrap_or(false))
+    }
+
+    pub fn with_ingest(mut self, enabled: bool) -> Self {
+        self.on_ingest = enabled;
+        self
+    }
+
+    pub fn with_query(mut self, enabled: bool) -> Self {
+        self.on_query = enabled;
+        self
+    }
+
+    /// Applies every rule, whether or not redaction is switched on. Markers match none
+    /// of the built-in rules, so redacting twice changes nothing.
+    pub fn redact(&self, text: &str) -> Redaction {
+        let mut redaction = Redaction { text: text.to_string(), counts: BTreeMap::new() };
+        for (name, pattern) in &self.rules {
+            let found = pattern.find_iter(&redaction.text).count();
+            if found > 0 {
+                let marker = format!("[REDACTED:{}]", name.to_uppercase());
+                redaction.text = pattern.replace_all(&redaction.text, regex::NoExpand(&marker)).into_owned();
+                redaction.counts.insert(name.clone(), found);
+            }
+        }
+        redaction
+    }
+
+    /// `text` as it should be indexed: redacted when ingest redaction is on
+    pub fn redact_ingest(&self, text: &str) -> Redaction {
+        if self.on_ingest {
+            self.redact(text)
+        } else {
+            Redaction { text: text.to_string(), counts: BTreeMap::new() }
+        }
+    }
+
+    /// `text` as it should be returned to a caller: redacted when query redaction is on
+    pub fn redact_output(&self, text: &str) -> String {
+        if self.on_query {
+            self.redact(text).text
+        } else {
+            text.to_string()
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Post-processing hooks for agent results
+//!
+//! Every processor registered with `AgentOrchestrator::with_result_processor` sees a task's
+//! result in `complete_task`, before it is stored, and may return it changed or reject it,
+//! which fails the task. Two are built in and configured from the environment:
+//! `AGENT_RESULT_MAX_CHARS` caps result length and `AGENT_RESULT_SCHEMA` names a JSON
+//! Schema file that results of `json` tasks must satisfy.
+
+use crate::core::agent_orchestrator::{ResponseFormat, Task};
+use crate::core::search_engine::truncate_content;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// A step between an agent producing a result and the result being stored
+pub trait ResultProcessor: Send + Sync {
+    /// Shown in the task's audit trail and in rejection reasons
+    fn name(&self) -> &str;
+
+    /// The result to store, possibly modified, or `Err(reason)` to reject it. `task` is
+    /// the task as it stands before completion.
+    fn process(&self, task: &Task, result: Value) -> Result<Value, String>;
+}
+
+/// Caps results at `max_chars` characters. Text is cut short with `…`; a structured
+/// result can't be cut without breaking it, so one over the cap is rejected instead.
+pub struct LengthCap {
+    pub max_chars: usize,
+}
+
+impl ResultProcessor for LengthCap {
+    fn name(&self) -> &str {
+        "length_cap"
+    }
+
+    fn process(&self, _task: &Task, result: Value) -> Result<Value, String> {
+        match result {
+            Value::String(text) => Ok(Value::String(truncate_content(&text, self.max_chars))),
+            other => {
+                let length = other.to_string().chars().count();
+                if length > self.max_chars {
+                    return Err(format!("result is {} characters, over the {}-character cap", length, self.max_chars));
+                }
+                Ok(other)
+            }
+        }
+    }
+}
+
+/// Validates the results of `json` tasks against a JSON Schema. Supports the common
+/// keywords: `type`, `enum`, `required`, `properties`, `additionalProperties: false`,
+/// `items`, `minLength`/`maxLength`, `minimum`/`maximum` and `minItems`/`maxItems`.
+/// Other keywords are ignored.
+pub struct JsonSchemaValidator {
+    pub schema: Value,
+}
+
+impl JsonSchemaValidator {
+    /// Validator for the schema in the file at `path`
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let schema = serde_json::from_str(&content).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(Self { schema })
+    }
+}
+
+impl ResultProcessor for JsonSchemaValidator {
+    fn name(&self) -> &str {
+        "json_schema"
+    }
+
+    fn process(&self, task: &Task, result: Value) -> Result<Value, String> {
+        if task.response_format != ResponseFormat::Json {
+            return Ok(result);
+        }
+        let mut problems = Vec::new();
+        check_schema(&self.schema, &result, "", &mut problems);
+        if problems.is_empty() {
+            Ok(result)
+        } else {
+            Err(format!("result does not match the schema: {}", problems.join("; ")))
+        }
+    }
+}
+
+fn type_matches(expected: &str, value: &Value) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().map_or(false, |n| n.fract() == 0.0),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+/// Appends every way `value` (at JSON pointer `path`) fails `schema` to `problems`
+fn check_schema(schema: &Value, value: &Value, path: &str, problems: &mut Vec<String>) {
+    let at = if path.is_empty() { "/" } else { path };
+
+    let types: Vec<&str> = match schema.get("type") {
+        Some(Value::String(t)) => vec![t.as_str()],
+        Some(Value::Array(ts)) => ts.iter().filter_map(Value::as_str).collect(),
+        _ => vec![],
+    };
+    if !types.is_empty() && !types.iter().any(|t| type_matches(t, value)) {
+        problems.push(format!("{} should be {}", at, types.join(" or ")));
+        return;
+    }
+    if let Some(Value::Array(allowed)) = schema.get("enum") {
+        if !allowed.contains(value) {
+            problems.push(format!("{} is not one of the allowed values", at));
+        }
+    }
+
+    let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+    match value {
+        Value::String(text) => {
+            let length = text.chars().count() as f64;
+            if bound("minLength").map_or(false, |min| length < min) {
+                problems.push(format!("{} is shorter than {} characters", at, bound("minLength").unwrap_or_default()));
+            }
+            if bound("maxLength").map_or(false, |max| length > max) {
+                problems.push(format!("{} is longer than {} characters", at, bound("maxLength").unwrap_or_default()));
+            }
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or_default();
+            if bound("minimum").map_or(false, |min| n < min) {
+                problems.push(format!("{} is below the minimum {}", at, bound("minimum").unwrap_or_default()));
+            }
+            if bound("maximum").map_or(false, |max| n > max) {
+                problems.push(format!("{} is above the maximum {}", at, bound("maximum").unwrap_or_default()));
+            }
+        }
+        Value::Array(items) => {
+            let count = items.len() as f64;
+            if bound("minItems").map_or(false, |min| count < min) {
+                problems.push(format!("{} has fewer than {} items", at, bound("minItems").unwrap_or_default()));
+            }
+            if bound("maxItems").map_or(false, |max| count > max) {
+                problems.push(format!("{} has more than {} items", at, bound("maxItems").unwrap_or_default()));
+            }
+            if let Some(item_schema) = schema.get("items").filter(|s| s.is_object()) {
+                for (i, item) in items.iter().enumerate() {
+                    check_schema(item_schema, item, &format!("{}/{}", path, i), problems);
+                }
+            }
+        }
+        Value::Object(fields) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for name in required.iter().filter_map(Value::as_str) {
+                    if !fields.contains_key(name) {
+                        problems.push(format!("{} is missing required property '{}'", at, name));
+                    }
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (name, field) in fields {
+                match properties.and_then(|p| p.get(name)) {
+                    Some(field_schema) => check_schema(field_schema, field, &format!("{}/{}", path, name), problems),
+                    None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                        problems.push(format!("{} has unexpected property '{}'", at, name));
+                    }
+                    None => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The built-in processors switched on by `AGENT_RESULT_MAX_CHARS` and `AGENT_RESULT_SCHEMA`
+pub fn processors_from_env() -> Vec<Arc<dyn ResultProcessor>> {
+    let mut processors: Vec<Arc<dyn ResultProcessor>> = Vec::new();
+    if let Ok(path) = std::env::var("AGENT_RESULT_SCHEMA") {
+        match JsonSchemaValidator::from_file(&path) {
+            Ok(validator) => processors.push(Arc::new(validator)),
+            Err(e) => println!("WARN: Ignoring AGENT_RESULT_SCHEMA: {}", e),
+        }
+    }
+    let max_chars = std::env::var("AGENT_RESULT_MAX_CHARS")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    if max_chars > 0 {
+        processors.push(Arc::new(LengthCap { max_chars }));
+    }
+    processors
+}
@@ -0,0 +1,110 @@
+use crate::core::search_engine::SearchResults;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+/// Tag of searches that read every collection, evicted by a write to any of them
+pub const ALL_COLLECTIONS: &str = "*";
+
+struct CacheEntry {
+    results: SearchResults,
+    collections: HashSet<String>,
+    doc_ids: HashSet<String>,
+    inserted_at: Instant,
+}
+
+/// Search response cache whose entries are tagged with the collections they read and the
+/// documents they returned, so writes only evict entries they could have changed.
+#[derive(Clone)]
+pub struct SearchCache {
+    entries: Arc<RwLock<HashMap<String, CacheEntry>>>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+impl SearchCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            entries: Arc::new(RwLock::new(HashMap::new())),
+            ttl,
+            capacity,
+        }
+    }
+
+    /// Enabled when `SEARCH_CACHE_TTL_SECS` is set to a non-zero value
+    pub fn from_env() -> Option<Self> {
+        let ttl = std::env::var("SEARCH_CACHE_TTL_SECS").ok()?.parse::<u64>().ok()?;
+        if ttl == 0 {
+            return None;
+        }
+        let capacity = std::env::var("SEARCH_CACHE_CAPACITY")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1000);
+        Some(Self::new(Duration::from_secs(ttl), capacity))
+    }
+
+    /// Hash of the collection, query (case and surrounding space aside), `top_k` and the
+    /// search `options` that change its results. Each field is length-prefixed, so no query
+    /// text can pass for another query's options.
+    pub fn key(collection: &str, query: &str, top_k: usize, options: &[String]) -> String {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        let query = query.trim().to_lowercase();
+        for field in [collection, query.as_str()].into_iter().chain(options.iter().map(String::as_str)) {
+            hasher.update((field.len() as u64).to_le_bytes());
+            hasher.update(field.as_bytes());
+        }
+        hasher.update((options.len() as u64).to_le_bytes());
+        hasher.update((top_k as u64).to_le_bytes());
+        hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    pub async fn get(&self, key: &str) -> Option<SearchResults> {
+        let entries = self.entries.read().await;
+        entries.get(key)
+            .filter(|e| e.inserted_at.elapsed() < self.ttl)
+            .map(|e| e.results.clone())
+    }
+
+    pub async fn put(&self, key: String, collections: &[&str], results: SearchResults) {
+        let mut entries = self.entries.write().await;
+
+        entries.retain(|_, e| e.inserted_at.elapsed() < self.ttl);
+        if entries.len() >= self.capacity {
+            // Evict the oldest entry
+            if let Some(oldest) = entries.iter().min_by_key(|(_, e)| e.inserted_at).map(|(k, _)| k.clone()) {
+                entries.remove(&oldest);
+            }
+        }
+
+        let doc_ids = results.hits.iter().map(|h| h.doc_id.clone()).collect();
+        entries.insert(key, CacheEntry {
+            results,
+            collections: collections.iter().map(|c| c.to_string()).collect(),
+            doc_ids,
+            inserted_at: Instant::now(),
+        });
+    }
+
+    /// Evict entries that read from `collection` (including those tagged `ALL_COLLECTIONS`)
+    /// or returned any of `doc_ids`
+    pub async fn invalidate(&self, collection: &str, doc_ids: &[&str]) {
+        let mut entries = self.entries.write().await;
+        entries.retain(|_, e| {
+            !e.collections.contains(collection)
+                && !e.collections.contains(ALL_COLLECTIONS)
+                && !doc_ids.iter().any(|id| e.doc_ids.contains(*id))
+        });
+    }
+
+    pub async fn len(&self) -> usize {
+        self.entries.read().await.len()
+    }
+
+    pub async fn clear(&self) {
+        self.entries.write().await.clear();
+    }
+}
@@ -1,95 +1,843 @@
-use crate::db::barq_vector::{BarqVectorClient, SearchHit as DbHit};
+use crate::core::search_cache::{SearchCache, ALL_COLLECTIONS};
+use crate::core::feedback::FeedbackStore;
+use crate::core::redaction::{Redaction, Redactor};
+use crate::core::text_analysis::normalize_language;
+use crate::core::query_syntax::parse_query;
+use crate::error::{BrainVaultError, Result};
+use crate::db::barq_vector::{cosine_similarity, BarqVectorClient, CorpusStats, DocumentVersion, IndexOutcome, LexicalOptions, ReindexGuard, ReindexReport, SearchHit as DbHit, DEFAULT_COLLECTION};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use utoipa::ToSchema;
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SearchWeights {
     pub vector_weight: f32,
     pub bm25_weight: f32,
 }
 
+impl SearchWeights {
+    /// Weights with the given overrides applied; each must be a non-negative number and
+    /// at least one of the results must be positive
+    pub fn with_overrides(&self, vector_weight: Option<f32>, bm25_weight: Option<f32>) -> Result<Self> {
+        for (name, value) in [("vector_weight", vector_weight), ("bm25_weight", bm25_weight)] {
+            if let Some(v) = value {
+                if !v.is_finite() || v < 0.0 {
+                    return Err(BrainVaultError::BadRequest(format!("{} must be a non-negative number, got {}", name, v)));
+                }
+            }
+        }
+        let weights = Self {
+            vector_weight: vector_weight.unwrap_or(self.vector_weight),
+            bm25_weight: bm25_weight.unwrap_or(self.bm25_weight),
+        };
+        if weights.vector_weight == 0.0 && weights.bm25_weight == 0.0 {
+            return Err(BrainVaultError::BadRequest("vector_weight and bm25_weight cannot both be 0".to_string()));
+        }
+        Ok(weights)
+    }
+}
+
+/// Result of `HybridSearchEngine::ingest_with_report`
+pub struct IngestReport {
+    pub outcome: IndexOutcome,
+    /// The content as indexed, with counts of what was masked
+    pub redaction: Redaction,
+}
+
 #[derive(Clone)]
 pub struct HybridSearchEngine {
     pub vector_db: BarqVectorClient,
     pub lexical_weights: SearchWeights,
+    /// MMR trade-off in [0, 1]: 0 ranks purely by score, higher values penalize redundant hits
+    pub diversity: f32,
+    /// Leading hits sent to the LLM when a search asks for reranking
+    pub rerank_top_n: usize,
+    /// Characters of content returned per hit unless a request asks otherwise; 0 keeps it whole
+    pub max_content_len: usize,
+    cache: Option<SearchCache>,
+    /// Relevance feedback that nudges fused scores up or down
+    pub feedback: Option<Arc<FeedbackStore>>,
+    /// Personal data masking for ingested and returned content
+    pub redactor: Redactor,
+    /// Refuse searches with 503 until the vector client's warm-up is done
+    pub require_ready: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct SearchHit {
     pub doc_id: String,
     pub score: f32,
     pub content: Option<String>,
+    /// Query-matching excerpts with terms wrapped in `<em>` tags
+    #[serde(default)]
+    pub highlights: Vec<String>,
+    /// Set on matches from a superseded or deleted revision of the document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResults {
     pub hits: Vec<SearchHit>,
 }
 
+/// Candidates retrieved when paging, which also bounds the reported total
+pub const MAX_RESULT_WINDOW: usize = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SearchPage {
+    pub hits: Vec<SearchHit>,
+    /// Matches across all pages (capped at `MAX_RESULT_WINDOW`)
+    pub total: usize,
+    pub offset: usize,
+    pub has_more: bool,
+    /// Extra terms searched when query expansion was requested
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expansions: Vec<String>,
+    /// Parts of the search that failed; the hits are whatever the rest produced
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Appended to content that `truncate_content` cut short
+pub const TRUNCATION_MARKER: &str = "…";
+
+/// `text` cut to at most `max_chars` characters plus `TRUNCATION_MARKER` when anything
+/// was removed. A `max_chars` of 0 keeps the text whole.
+pub fn truncate_content(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((cut, _)) if max_chars > 0 => format!("{}{}", &text[..cut], TRUNCATION_MARKER),
+        _ => text.to_string(),
+    }
+}
+
+/// Rejects collection names that are empty or not made of letters, digits, `-`, `_` and `.`
+pub fn validate_collection(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(BrainVaultError::BadRequest(format!("Invalid collection name '{}'", name)))
+    }
+}
+
+/// Top hits considered for diversity reranking; the tail keeps its score order
+const MMR_CANDIDATES: usize = 100;
+
+/// Characters of each candidate shown to the LLM when reranking
+const RERANK_PASSAGE_CHARS: usize = 600;
+
+/// Scores from `<n>: <score>` lines (brackets and other separators tolerated), indexed
+/// by passage. Passages without a parsable score in [0, 10] are `None`.
+pub fn parse_rerank_scores(response: &str, passages: usize) -> Vec<Option<f32>> {
+    let line_re = Regex::new(r"^\W*(\d+)\W+(\d+(?:\.\d+)?)").unwrap();
+    let mut scores = vec![None; passages];
+    for line in response.lines() {
+        if let Some(caps) = line_re.captures(line.trim()) {
+            let index = caps[1].parse::<usize>().ok().filter(|i| (1..=passages).contains(i));
+            let score = caps[2].parse::<f32>().ok().filter(|s| (0.0..=10.0).contains(s));
+            if let (Some(i), Some(score)) = (index, score) {
+                scores[i - 1].get_or_insert(score);
+            }
+        }
+    }
+    scores
+}
+
+/// Jaccard similarity over lowercase alphanumeric term sets
+pub fn jaccard_similarity(a: &str, b: &str) -> f32 {
+    let terms = |s: &str| -> HashSet<String> {
+        s.split(|c: char| !c.is_alphanumeric())
+            .filter(|t| !t.is_empty())
+            .map(|t| t.to_lowercase())
+            .collect()
+    };
+    let (ta, tb) = (terms(a), terms(b));
+    let union = ta.union(&tb).count();
+    if union == 0 {
+        return 0.0;
+    }
+    ta.intersection(&tb).count() as f32 / union as f32
+}
+
+/// Upper bound on LLM-suggested terms searched alongside the original query
+pub const MAX_EXPANSIONS: usize = 5;
+/// Score multiplier for hits found only through an expansion term
+const EXPANSION_WEIGHT: f32 = 0.8;
+
+/// Parses an LLM expansion reply (one term per line or comma-separated), dropping
+/// list markers, duplicates and the original query itself.
+pub fn parse_expansion_terms(response: &str, query: &str) -> Vec<String> {
+    let original = query.trim().to_lowercase();
+    let list_marker = Regex::new(r"^\s*(?:[-*•]|\d+[.)])\s*").expect("valid list marker pattern");
+    let mut seen = HashSet::new();
+    response
+        .split(|c| c == '\n' || c == ',')
+        .map(|t| list_marker.replace(t, "").trim().trim_matches('"').trim().to_string())
+        .filter(|t| !t.is_empty() && t.len() <= 64)
+        .filter(|t| {
+            let key = t.to_lowercase();
+            key != original && seen.insert(key)
+        })
+        .take(MAX_EXPANSIONS)
+        .collect()
+}
+
+/// Hits whose score is a finite number. NaN (e.g. from a zero-norm or corrupt embedding)
+/// or infinite scores would otherwise land anywhere in the ranking, so they are dropped
+/// and logged under `source`.
+pub fn finite_hits(hits: Vec<DbHit>, source: &str) -> Vec<DbHit> {
+    let before = hits.len();
+    let kept: Vec<DbHit> = hits.into_iter().filter(|hit| hit.score.is_finite()).collect();
+    if kept.len() < before {
+        println!("WARN: Dropped {} {} hit(s) with a non-finite score", before - kept.len(), source);
+    }
+    kept
+}
+
+/// Orders hits by score, best first, then by doc id so ties are deterministic
+pub fn compare_hits(a: &SearchHit, b: &SearchHit) -> std::cmp::Ordering {
+    b.score.partial_cmp(&a.score)
+        .unwrap_or(std::cmp::Ordering::Equal)
+        .then_with(|| a.doc_id.cmp(&b.doc_id))
+        .then_with(|| a.version.cmp(&b.version))
+}
+
+/// Fuses result sets by keeping each document's best weighted score
+pub fn fuse_results(result_sets: Vec<(SearchResults, f32)>) -> SearchResults {
+    let mut best: HashMap<String, SearchHit> = HashMap::new();
+    for (results, weight) in result_sets {
+        for mut hit in results.hits {
+            hit.score *= weight;
+            if !hit.score.is_finite() {
+                println!("WARN: Dropped fused hit {} with a non-finite score", hit.doc_id);
+                continue;
+            }
+            match best.get(&hit.doc_id) {
+                Some(existing) if existing.score >= hit.score => {}
+                _ => {
+                    best.insert(hit.doc_id.clone(), hit);
+                }
+            }
+        }
+    }
+    let mut hits: Vec<SearchHit> = best.into_values().collect();
+    hits.sort_by(compare_hits);
+    SearchResults { hits }
+}
+
+/// Highlighted snippets returned per hit
+pub const MAX_SNIPPETS: usize = 3;
+/// Characters of context kept around a match in long sentences
+const SNIPPET_WINDOW: usize = 120;
+
+/// Marks a query's terms in document text. Built once per query and reused for every hit.
+pub struct Highlighter {
+    pattern: Regex,
+}
+
+impl Highlighter {
+    /// `None` when `query` has no term of two or more characters
+    pub fn new(query: &str) -> Option<Self> {
+        let terms: Vec<String> = query
+            .split_whitespace()
+            .map(|t| t.trim_matches(|c: char| !c.is_alphanumeric()))
+            .filter(|t| t.chars().count() >= 2)
+            .map(regex::escape)
+            .collect();
+        if terms.is_empty() {
+            return None;
+        }
+        Regex::new(&format!(r"(?i)\b({})", terms.join("|"))).ok().map(|pattern| Self { pattern })
+    }
+
+    /// Up to `max_snippets` sentences from `content` that mention a query term. The text
+    /// is HTML-escaped and each occurrence wrapped in `<em>` tags, so the snippets are safe
+    /// to render as markup.
+    pub fn snippets(&self, content: &str, max_snippets: usize) -> Vec<String> {
+        let mut snippets = Vec::new();
+        if max_snippets == 0 {
+            return snippets;
+        }
+        let mut start = 0;
+        for (idx, c) in content.char_indices() {
+            if matches!(c, '.' | '!' | '?' | '\n') || idx + c.len_utf8() == content.len() {
+                let end = idx + c.len_utf8();
+                let sentence = content[start..end].trim();
+                start = end;
+                if sentence.is_empty() {
+                    continue;
+                }
+                if let Some(m) = self.pattern.find(sentence) {
+                    snippets.push(self.mark(window_around(sentence, m.start(), SNIPPET_WINDOW)));
+                    if snippets.len() >= max_snippets {
+                        break;
+                    }
+                }
+            }
+        }
+        snippets
+    }
+
+    fn mark(&self, excerpt: &str) -> String {
+        let mut out = String::with_capacity(excerpt.len() + 16);
+        let mut last = 0;
+        for m in self.pattern.find_iter(excerpt) {
+            out.push_str(&html_escape(&excerpt[last..m.start()]));
+            out.push_str("<em>");
+            out.push_str(&html_escape(m.as_str()));
+            out.push_str("</em>");
+            last = m.end();
+        }
+        out.push_str(&html_escape(&excerpt[last..]));
+        out
+    }
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Extracts up to `max_snippets` sentences from `content` that mention a query
+/// term, HTML-escaped with each occurrence wrapped in `<em>` tags. Callers highlighting
+/// many documents for one query should build a `Highlighter` once instead.
+pub fn highlight_snippets(content: &str, query: &str, max_snippets: usize) -> Vec<String> {
+    match Highlighter::new(query) {
+        Some(highlighter) => highlighter.snippets(content, max_snippets),
+        None => vec![],
+    }
+}
+
+/// Slice of `text` of roughly `window` bytes around `at`, on char boundaries
+fn window_around(text: &str, at: usize, window: usize) -> &str {
+    if text.len() <= window {
+        return text;
+    }
+    let mut lo = at.saturating_sub(window / 2);
+    while !text.is_char_boundary(lo) {
+        lo -= 1;
+    }
+    let mut hi = (lo + window).min(text.len());
+    while !text.is_char_boundary(hi) {
+        hi += 1;
+    }
+    &text[lo..hi]
+}
+
+impl SearchPage {
+    /// Truncates each hit's content to `max_chars` (0 for no limit); the full text stays
+    /// available from `GET /api/documents/{doc_id}`
+    pub fn limit_content(&mut self, max_chars: usize) {
+        for hit in &mut self.hits {
+            if let Some(content) = hit.content.as_mut() {
+                *content = truncate_content(content, max_chars);
+            }
+        }
+    }
+
+    /// Masks personal data in each hit's content and highlights, when query redaction is on
+    pub fn redact(&mut self, redactor: &Redactor) {
+        redact_hits(&mut self.hits, redactor);
+    }
+}
+
+/// Masks personal data in the content and highlights of `hits`, when query redaction is on
+pub fn redact_hits(hits: &mut [SearchHit], redactor: &Redactor) {
+    if !redactor.on_query {
+        return;
+    }
+    for hit in hits {
+        if let Some(content) = hit.content.as_mut() {
+            *content = redactor.redact_output(content);
+        }
+        for highlight in &mut hit.highlights {
+            *highlight = redactor.redact_output(highlight);
+        }
+    }
+}
+
+impl SearchResults {
+    /// Masks personal data in each hit, as `SearchPage::redact` does
+    pub fn redact(&mut self, redactor: &Redactor) {
+        redact_hits(&mut self.hits, redactor);
+    }
+
+    pub fn paginate(self, offset: usize, page_size: usize) -> SearchPage {
+        let total = self.hits.len();
+        let hits: Vec<SearchHit> = self.hits.into_iter().skip(offset).take(page_size).collect();
+        SearchPage {
+            has_more: offset + hits.len() < total,
+            hits,
+            total,
+            offset,
+            expansions: vec![],
+            warnings: vec![],
+        }
+    }
+}
+
 impl HybridSearchEngine {
     pub async fn check_health(&self) -> bool {
         self.vector_db.health().await.unwrap_or(false)
     }
 
+    pub fn vector_circuit(&self) -> crate::db::circuit_breaker::CircuitStatus {
+        self.vector_db.circuit_status()
+    }
+
     pub fn new(vector_db: BarqVectorClient, weights: SearchWeights) -> Self {
         Self {
             vector_db,
             lexical_weights: weights,
+            diversity: std::env::var("SEARCH_DIVERSITY")
+                .ok()
+                .and_then(|v| v.parse::<f32>().ok())
+                .unwrap_or(0.0)
+                .clamp(0.0, 1.0),
+            rerank_top_n: std::env::var("SEARCH_RERANK_TOP_N")
+                .ok()
+                .and_then(|v| v.parse::<usize>().ok())
+                .unwrap_or(10),
+            max_content_len: std::env::var("SEARCH_MAX_CONTENT_LEN")
+                .ok()
+                .and_then(|v| v.parse::<usize>().ok())
+                .unwrap_or(2000),
+            cache: SearchCache::from_env(),
+            feedback: None,
+            redactor: Redactor::from_env(),
+            require_ready: std::env::var("SEARCH_REQUIRE_READY").map(|v| v == "true").unwrap_or(false),
+        }
+    }
+
+    pub fn with_diversity(mut self, diversity: f32) -> Self {
+        self.diversity = diversity.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_max_content_len(mut self, max_content_len: usize) -> Self {
+        self.max_content_len = max_content_len;
+        self
+    }
+
+    pub fn with_cache(mut self, cache: SearchCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn with_require_ready(mut self, require_ready: bool) -> Self {
+        self.require_ready = require_ready;
+        self
+    }
+
+    /// Whether the index is loaded and searchable; re-embedding may still be running
+    pub fn is_ready(&self) -> bool {
+        self.vector_db.is_ready()
+    }
+
+    /// 503 while the engine is warming up and `require_ready` is set, so callers retry
+    /// instead of reading an empty result as missing data
+    pub fn ensure_ready(&self) -> Result<()> {
+        if self.require_ready && !self.is_ready() {
+            return Err(BrainVaultError::Unavailable("Search is warming up; retry shortly".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    pub fn with_feedback(mut self, feedback: FeedbackStore) -> Self {
+        self.feedback = Some(Arc::new(feedback));
+        self
+    }
+
+    /// Records a relevance vote and drops cached results, whose ranking it may change.
+    /// `Unavailable` when feedback is not enabled.
+    pub async fn record_feedback(&self, user_id: &str, query: &str, doc_id: &str, relevant: bool) -> Result<()> {
+        let feedback = self.feedback.as_ref()
+            .ok_or_else(|| BrainVaultError::Unavailable("Relevance feedback is not enabled".to_string()))?;
+        feedback.record(user_id, query, doc_id, relevant).await;
+        if let Some(ref cache) = self.cache {
+            // Votes also count towards every other query that returns the document
+            let collection = self.vector_db.collection_of(doc_id).await.unwrap_or_else(|| DEFAULT_COLLECTION.to_string());
+            cache.invalidate(&collection, &[doc_id]).await;
         }
+        Ok(())
     }
 
-    pub async fn search(&self, query: &str, top_k: usize) -> Result<SearchResults, Box<dyn std::error::Error + Send + Sync>> {
-        let vector_results = self.vector_db.semantic_search(query, top_k).await
+    pub async fn search(&self, query: &str, top_k: usize) -> Result<SearchResults> {
+        self.search_weighted(query, top_k, None).await
+    }
+
+    /// Search fusing vector and BM25 scores with `weights` instead of the engine defaults
+    pub async fn search_weighted(&self, query: &str, top_k: usize, weights: Option<&SearchWeights>) -> Result<SearchResults> {
+        self.search_with(query, top_k, weights, &LexicalOptions::default()).await
+    }
+
+    /// Search with fusion weight overrides and lexical options such as the query language
+    pub async fn search_with(&self, query: &str, top_k: usize, weights: Option<&SearchWeights>, lexical: &LexicalOptions) -> Result<SearchResults> {
+        let weights = weights.filter(|w| **w != self.lexical_weights);
+        // Unscoped searches read every collection, so any write may change them
+        let collection = lexical.collection.as_deref().unwrap_or(ALL_COLLECTIONS);
+        let mut options = Vec::new();
+        if let Some(w) = weights {
+            options.push(format!("w={}/{}", w.vector_weight, w.bm25_weight));
+        }
+        if let Some(ref language) = lexical.language {
+            options.push(format!("lang={}", language));
+        }
+        if lexical.fuzzy {
+            options.push("fuzzy".to_string());
+        }
+        let cache_key = SearchCache::key(collection, query, top_k, &options);
+        if let Some(ref cache) = self.cache {
+            if let Some(cached) = cache.get(&cache_key).await {
+                return Ok(cached);
+            }
+        }
+
+        // Both halves run concurrently, so a search costs the slower of the two round trips.
+        // Dense and sparse scores are fused as they are; a half that fails, or a query that
+        // can't be embedded, simply contributes no hits
+        let (dense, sparse) = tokio::join!(
+            self.vector_db.dense_search_in(query, top_k, lexical.collection.as_deref()),
+            self.vector_db.sparse_search_with(query, top_k, lexical),
+        );
+        let vector_results = dense
+            .map(Option::unwrap_or_default)
             .unwrap_or_else(|e| {
                 println!("WARN: Semantic search failed: {}", e);
                 vec![]
             });
-        let lexical_results = self.vector_db.bm25_search(query, top_k).await
+        let lexical_results = sparse
             .unwrap_or_else(|e| {
-                println!("WARN: BM25 search failed: {}", e);
+                println!("WARN: Sparse search failed: {}", e);
                 vec![]
             });
         
-        let merged = self.merge_results(vector_results, lexical_results);
+        // Highlight the query's words, not its phrase and field syntax
+        let highlight_text = parse_query(query).text();
+        // Read up front so the diversified order never depends on a concurrent cache write
+        let embeddings = if self.diversity > 0.0 {
+            let ids: Vec<&str> = vector_results.iter().chain(&lexical_results).map(|h| h.doc_id.as_str()).collect();
+            self.vector_db.cached_embeddings(&ids).await
+        } else {
+            HashMap::new()
+        };
+        let merged = self.merge_with_embeddings(&highlight_text, vector_results, lexical_results, weights.unwrap_or(&self.lexical_weights), &embeddings);
+        if let Some(ref cache) = self.cache {
+            cache.put(cache_key, &[collection], merged.clone()).await;
+        }
         Ok(merged)
     }
     
-    fn merge_results(&self, vector_hits: Vec<DbHit>, bm25_hits: Vec<DbHit>) -> SearchResults {
+    /// One page of results starting at `offset`, with the total match count
+    pub async fn search_page(&self, query: &str, page_size: usize, offset: usize) -> Result<SearchPage> {
+        let results = self.search(query, MAX_RESULT_WINDOW).await?;
+        Ok(results.paginate(offset, page_size))
+    }
+    
+    /// Asks the configured LLM for synonyms and related terms; empty when no LLM is available
+    pub async fn expand_query(&self, query: &str) -> Vec<String> {
+        use crate::core::llm::nafs_provider::NafsLLMClient;
+
+        let client = match NafsLLMClient::new() {
+            Some(c) => c,
+            None => return vec![],
+        };
+        let prompt = format!(
+            "List up to {} synonyms or closely related search terms for the query below. \
+            Return one term per line with no numbering or explanations.\n\nQuery: {}",
+            MAX_EXPANSIONS, query
+        );
+        match client.generate(&prompt).await {
+            Ok(response) => parse_expansion_terms(&response, query),
+            Err(e) => {
+                println!("WARN: Query expansion failed: {}", e);
+                vec![]
+            }
+        }
+    }
+
+    /// Searches the query plus its LLM expansions and fuses the results.
+    /// Returns the expansion terms alongside the results.
+    pub async fn search_expanded(&self, query: &str, top_k: usize, weights: Option<&SearchWeights>, lexical: &LexicalOptions) -> Result<(SearchResults, Vec<String>)> {
+        let expansions = self.expand_query(query).await;
+        let original = self.search_with(query, top_k, weights, lexical).await?;
+        if expansions.is_empty() {
+            return Ok((original, expansions));
+        }
+
+        let expanded = futures::future::join_all(expansions.iter().map(|term| self.search_with(term, top_k, weights, lexical))).await;
+        let mut result_sets = vec![(original, 1.0)];
+        for (term, result) in expansions.iter().zip(expanded) {
+            match result {
+                Ok(results) => result_sets.push((results, EXPANSION_WEIGHT)),
+                Err(e) => println!("WARN: Expanded search for '{}' failed: {}", term, e),
+            }
+        }
+        Ok((fuse_results(result_sets), expansions))
+    }
+    
+    /// Reorders the leading `rerank_top_n` hits by an LLM-judged relevance score (0-10).
+    /// Hits the model doesn't score keep their place after the scored ones; the rest of
+    /// the list is untouched. Returns the input unchanged when no LLM is configured.
+    pub async fn rerank(&self, query: &str, mut results: SearchResults) -> SearchResults {
+        use crate::core::llm::nafs_provider::NafsLLMClient;
+
+        let n = self.rerank_top_n.min(results.hits.len());
+        if n < 2 {
+            return results;
+        }
+        let client = match NafsLLMClient::new() {
+            Some(c) => c.without_cache(),
+            None => return results,
+        };
+
+        let passages = results.hits[..n].iter().enumerate()
+            .map(|(i, hit)| {
+                let text = truncate_content(hit.content.as_deref().unwrap_or(&hit.doc_id), RERANK_PASSAGE_CHARS);
+                format!("[{}] {}", i + 1, text)
+            })
+            .collect::<Vec<String>>()
+            .join("\n\n");
+        let prompt = format!(
+            "Rate how relevant each passage is to the query on a scale from 0 (unrelated) to 10 (directly answers it).\n\
+            Reply with one line per passage in the form <number>: <score> and nothing else.\n\n\
+            Query: {}\n\nPassages:\n{}",
+            query, passages
+        );
+
+        let scores = match client.generate(&prompt).await {
+            Ok(response) => parse_rerank_scores(&response, n),
+            Err(e) => {
+                println!("WARN: Reranking failed: {}", e);
+                return results;
+            }
+        };
+
+        let mut head: Vec<(usize, SearchHit)> = results.hits.drain(..n).enumerate().collect();
+        // Stable sort keeps the fused order among equal or missing scores
+        head.sort_by(|(a, _), (b, _)| {
+            let score = |i: &usize| scores[*i].unwrap_or(f32::NEG_INFINITY);
+            score(b).total_cmp(&score(a))
+        });
+        let mut hits: Vec<SearchHit> = head.into_iter().map(|(_, hit)| hit).collect();
+        hits.append(&mut results.hits);
+        SearchResults { hits }
+    }
+    
+    pub fn merge_results(&self, query: &str, vector_hits: Vec<DbHit>, bm25_hits: Vec<DbHit>) -> SearchResults {
+        self.merge_results_weighted(query, vector_hits, bm25_hits, &self.lexical_weights)
+    }
+
+    /// Merges without embeddings, so diversification compares content overlap only
+    pub fn merge_results_weighted(&self, query: &str, vector_hits: Vec<DbHit>, bm25_hits: Vec<DbHit>, weights: &SearchWeights) -> SearchResults {
+        self.merge_with_embeddings(query, vector_hits, bm25_hits, weights, &HashMap::new())
+    }
+
+    /// `merge_results_weighted`, diversifying with a snapshot of the hits' cached embeddings
+    fn merge_with_embeddings(&self, query: &str, vector_hits: Vec<DbHit>, bm25_hits: Vec<DbHit>, weights: &SearchWeights, embeddings: &HashMap<String, Vec<f32>>) -> SearchResults {
         let mut scores: HashMap<String, f32> = HashMap::new();
         let mut content_map: HashMap<String, Option<String>> = HashMap::new();
         
-        for hit in vector_hits {
-            *scores.entry(hit.doc_id.clone()).or_insert(0.0) += hit.score * self.lexical_weights.vector_weight;
+        for hit in finite_hits(vector_hits, "vector") {
+            *scores.entry(hit.doc_id.clone()).or_insert(0.0) += hit.score * weights.vector_weight;
             content_map.entry(hit.doc_id).or_insert(hit.content);
         }
         
-        for hit in bm25_hits {
-             *scores.entry(hit.doc_id.clone()).or_insert(0.0) += hit.score * self.lexical_weights.bm25_weight;
+        for hit in finite_hits(bm25_hits, "lexical") {
+             *scores.entry(hit.doc_id.clone()).or_insert(0.0) += hit.score * weights.bm25_weight;
              content_map.entry(hit.doc_id).or_insert(hit.content);
         }
         
+        let highlighter = Highlighter::new(query);
         let mut hits: Vec<SearchHit> = scores.into_iter().map(|(id, score)| {
+            // Feedback only scales the fused score, so it can reorder close hits but
+            // not lift a weak match over a strong one
+            let score = match self.feedback {
+                Some(ref feedback) => score * feedback.score_factor(query, &id),
+                None => score,
+            };
+            let content = content_map.get(&id).cloned().flatten();
+            let highlights = match (content.as_deref(), highlighter.as_ref()) {
+                (Some(c), Some(h)) => h.snippets(c, MAX_SNIPPETS),
+                _ => vec![],
+            };
             SearchHit {
                 doc_id: id.clone(),
                 score,
-                content: content_map.get(&id).cloned().flatten(),
+                content,
+                highlights,
+                version: None,
             }
         }).collect();
         // Sort by score descending
-        hits.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        hits.sort_by(compare_hits);
+        if self.diversity > 0.0 {
+            hits = self.diversify(hits, embeddings);
+        }
         
         SearchResults { hits }
     }
+
+    /// Maximal marginal relevance reordering of score-sorted hits. Similarity uses the
+    /// embeddings when both hits have one, otherwise Jaccard overlap of their content.
+    fn diversify(&self, mut hits: Vec<SearchHit>, embeddings: &HashMap<String, Vec<f32>>) -> Vec<SearchHit> {
+        let tail = if hits.len() > MMR_CANDIDATES { hits.split_off(MMR_CANDIDATES) } else { vec![] };
+        let similarity = |a: &SearchHit, b: &SearchHit| -> f32 {
+            match (embeddings.get(&a.doc_id), embeddings.get(&b.doc_id)) {
+                (Some(ea), Some(eb)) => cosine_similarity(ea, eb),
+                _ => match (&a.content, &b.content) {
+                    (Some(ca), Some(cb)) => jaccard_similarity(ca, cb),
+                    _ => 0.0,
+                },
+            }
+        };
+
+        let max_score = hits.iter().map(|h| h.score).fold(0.0f32, f32::max);
+        let relevance = |h: &SearchHit| if max_score > 0.0 { h.score / max_score } else { 0.0 };
+
+        let mut remaining = hits;
+        let mut selected: Vec<SearchHit> = Vec::with_capacity(remaining.len() + tail.len());
+        // Highest similarity of each remaining hit to anything already selected
+        let mut redundancy = vec![0.0f32; remaining.len()];
+        while !remaining.is_empty() {
+            let best = (0..remaining.len())
+                .map(|i| (i, (1.0 - self.diversity) * relevance(&remaining[i]) - self.diversity * redundancy[i]))
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal).then(b.0.cmp(&a.0)))
+                .map(|(i, _)| i)
+                .unwrap_or(0);
+            let chosen = remaining.remove(best);
+            redundancy.remove(best);
+            for (i, hit) in remaining.iter().enumerate() {
+                redundancy[i] = redundancy[i].max(similarity(&chosen, hit));
+            }
+            selected.push(chosen);
+        }
+        selected.extend(tail);
+        selected
+    }
     
-    pub async fn ingest_document(&self, doc_id: &str, content: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        self.vector_db.index_document(doc_id, content).await
-            .map_err(|e| Box::new(std::io::Error::new(std::io::ErrorKind::Other, e)) as Box<dyn std::error::Error + Send + Sync>)?;
-        Ok(())
+    pub async fn ingest_document(&self, doc_id: &str, content: &str) -> Result<IndexOutcome> {
+        self.ingest_document_in(doc_id, content, None).await
+    }
+
+    /// Ingests a document analyzed as `language` by lexical search
+    pub async fn ingest_document_in(&self, doc_id: &str, content: &str, language: Option<&str>) -> Result<IndexOutcome> {
+        self.ingest_document_into(doc_id, content, language, None).await
+    }
+
+    /// Ingests a document into `collection` (the default collection when `None`), masking
+    /// personal data first when ingest redaction is on
+    pub async fn ingest_document_into(&self, doc_id: &str, content: &str, language: Option<&str>, collection: Option<&str>) -> Result<IndexOutcome> {
+        self.ingest_with_report(doc_id, content, language, collection).await.map(|report| report.outcome)
+    }
+
+    /// `ingest_document_into`, also returning what was masked. This is the one place ingested
+    /// content is redacted, so callers pass the raw text.
+    pub async fn ingest_with_report(&self, doc_id: &str, content: &str, language: Option<&str>, collection: Option<&str>) -> Result<IngestReport> {
+        if let Some(name) = language.filter(|name| normalize_language(name).is_none()) {
+            return Err(BrainVaultError::BadRequest(format!("Unsupported language '{}'", name)));
+        }
+        if let Some(name) = collection {
+            validate_collection(name)?;
+        }
+        if let Some(mismatch) = self.vector_db.model_mismatch(collection) {
+            return Err(BrainVaultError::Conflict(mismatch));
+        }
+        let redaction = self.redactor.redact_ingest(content);
+        if !redaction.is_empty() {
+            println!("INFO: Redacted {} from {}", redaction.summary(), doc_id);
+        }
+        let outcome = self.vector_db.index_document_into(doc_id, &redaction.text, language, collection).await
+            .map_err(BrainVaultError::Upstream)?;
+        if outcome == IndexOutcome::Indexed {
+            if let Some(ref cache) = self.cache {
+                cache.invalidate(collection.unwrap_or(DEFAULT_COLLECTION), &[doc_id]).await;
+            }
+        }
+        Ok(IngestReport { outcome, redaction })
+    }
+
+    /// Re-embeds the whole corpus with the configured model (see `BarqVectorClient::reindex`)
+    /// and drops cached results computed from the old vectors
+    pub async fn reindex(&self) -> Result<ReindexReport> {
+        let running = self.begin_reindex()?;
+        self.reindex_with(running).await
+    }
+
+    /// Claims the reindex slot; `Conflict` if a reindex is already running
+    pub fn begin_reindex(&self) -> Result<ReindexGuard> {
+        self.vector_db.begin_reindex().map_err(BrainVaultError::Conflict)
+    }
+
+    /// `reindex` for a slot already claimed with `begin_reindex`
+    pub async fn reindex_with(&self, running: ReindexGuard) -> Result<ReindexReport> {
+        let report = self.vector_db.reindex_with(running).await.map_err(BrainVaultError::Conflict)?;
+        if let Some(ref cache) = self.cache {
+            cache.clear().await;
+        }
+        Ok(report)
+    }
+
+    /// Soft-deletes a document, keeping its content in the version history
+    pub async fn delete_document(&self, doc_id: &str) -> Result<DocumentVersion> {
+        let collection = self.vector_db.collection_of(doc_id).await.unwrap_or_else(|| DEFAULT_COLLECTION.to_string());
+        let tombstone = self.vector_db.delete_document(doc_id).await
+            .map_err(BrainVaultError::NotFound)?;
+        if let Some(ref cache) = self.cache {
+            cache.invalidate(&collection, &[doc_id]).await;
+        }
+        Ok(tombstone)
+    }
+
+    pub async fn list_versions(&self, doc_id: &str) -> Vec<DocumentVersion> {
+        self.vector_db.list_versions(doc_id).await
+    }
+
+    pub async fn get_version(&self, doc_id: &str, version: u32) -> Option<String> {
+        self.vector_db.get_version(doc_id, version).await
+    }
+
+    /// Matches among superseded and deleted revisions. Revisions only match lexically, so
+    /// their scores are weighted like the lexical half of a live search with `weights`,
+    /// putting them on the same scale as the hits they are merged with.
+    pub async fn search_history(&self, query: &str, top_k: usize, weights: Option<&SearchWeights>, lexical: &LexicalOptions) -> Vec<SearchHit> {
+        let bm25_weight = weights.unwrap_or(&self.lexical_weights).bm25_weight;
+        let highlighter = Highlighter::new(&parse_query(query).text());
+        self.vector_db.history_search(query, top_k, lexical).await
+            .into_iter()
+            .map(|hit| SearchHit {
+                highlights: match (hit.content.as_deref(), highlighter.as_ref()) {
+                    (Some(c), Some(h)) => h.snippets(c, MAX_SNIPPETS),
+                    _ => vec![],
+                },
+                doc_id: hit.doc_id,
+                score: hit.score * bm25_weight,
+                content: hit.content,
+                version: Some(hit.version),
+            })
+            .collect()
     }
 
     pub async fn get_document_count(&self) -> usize {
         self.vector_db.get_document_count().await
     }
+
+    pub async fn corpus_stats(&self) -> CorpusStats {
+        self.vector_db.corpus_stats().await
+    }
     
     pub async fn get_all_documents(&self) -> Vec<serde_json::Value> {
         self.vector_db.get_all_documents().await
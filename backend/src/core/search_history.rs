@@ -0,0 +1,346 @@
+//! Per-user search history and saved searches
+//!
+//! Each user's recent queries and named searches live in
+//! `{DATA_PATH}/search_history/{user hash}.json`. Recording can be turned off for
+//! everyone with `SEARCH_HISTORY=false` or by a user for themselves; saved searches
+//! are kept either way. Queries are stored as the JSON request so they can be replayed.
+//!
+//! Changes are written out shortly after they're made, so a burst of searches costs one
+//! write per user rather than one per search.
+
+use crate::error::{BrainVaultError, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Queries kept per user when `SEARCH_HISTORY_LIMIT` is unset
+pub const DEFAULT_HISTORY_LIMIT: usize = 100;
+/// Upper bound on `SEARCH_HISTORY_LIMIT`
+pub const MAX_HISTORY_LIMIT: usize = 1000;
+/// Saved searches per user
+pub const MAX_SAVED_SEARCHES: usize = 100;
+/// Largest query, as JSON, that is recorded or saved
+pub const MAX_QUERY_BYTES: usize = 16 * 1024;
+const MAX_SAVED_NAME_LEN: usize = 100;
+/// Users kept in memory; the least recently used one with nothing left to write is
+/// dropped first and reloaded from disk when needed
+const MAX_CACHED_USERS: usize = 1000;
+/// How long changes wait to be written, collecting any that follow
+const FLUSH_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryEntry {
+    pub id: String,
+    /// The search request as sent
+    pub query: Value,
+    /// Hits visible to the user when the search ran
+    pub hits: usize,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedSearch {
+    pub name: String,
+    pub query: Value,
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UserSearches {
+    /// Newest first
+    #[serde(default)]
+    pub history: Vec<HistoryEntry>,
+    #[serde(default)]
+    pub saved: Vec<SavedSearch>,
+    /// The user turned history recording off
+    #[serde(default)]
+    pub history_disabled: bool,
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+}
+
+#[derive(Default)]
+struct Store {
+    /// Users loaded from disk so far, with when each was last used
+    users: HashMap<String, (UserSearches, u64)>,
+    /// Users with unwritten changes, each with the tick of its latest change
+    dirty: HashMap<String, u64>,
+    tick: u64,
+    flush_scheduled: bool,
+}
+
+impl Store {
+    fn touch(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    /// Drops the least recently used users that have nothing left to write, keeping `keep`
+    fn evict(&mut self, keep: &str) {
+        while self.users.len() >= MAX_CACHED_USERS {
+            let oldest = self.users.iter()
+                .filter(|(id, _)| id.as_str() != keep && !self.dirty.contains_key(*id))
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(id, _)| id.clone());
+            match oldest {
+                Some(id) => {
+                    self.users.remove(&id);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+struct Inner {
+    dir: String,
+    store: Mutex<Store>,
+    /// Serializes flushes so an older snapshot never overwrites a newer one
+    flush_lock: tokio::sync::Mutex<()>,
+}
+
+impl Inner {
+    fn lock(&self) -> MutexGuard<'_, Store> {
+        self.store.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// User ids come from a header, so the file name is a hash rather than the id itself
+    fn file_for(&self, user_id: &str) -> String {
+        let digest = Sha256::digest(user_id.as_bytes());
+        let name: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+        format!("{}/{}.json", self.dir, name)
+    }
+
+    async fn load(&self, user_id: &str) -> UserSearches {
+        tokio::fs::read_to_string(self.file_for(user_id)).await
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes every user changed since the last flush. A user stays pending if the write
+    /// fails or they changed again meanwhile.
+    async fn flush(&self) {
+        let _flushing = self.flush_lock.lock().await;
+        let pending: Vec<(String, u64, String)> = {
+            let mut store = self.lock();
+            store.flush_scheduled = false;
+            store.dirty.iter()
+                .filter_map(|(id, tick)| {
+                    let (searches, _) = store.users.get(id)?;
+                    serde_json::to_string(searches).ok().map(|content| (id.clone(), *tick, content))
+                })
+                .collect()
+        };
+        if pending.is_empty() {
+            return;
+        }
+        if let Err(e) = tokio::fs::create_dir_all(&self.dir).await {
+            println!("WARN: Failed to create search history directory {}: {}", self.dir, e);
+            return;
+        }
+        for (user_id, tick, content) in pending {
+            match tokio::fs::write(self.file_for(&user_id), content).await {
+                Ok(()) => {
+                    let mut store = self.lock();
+                    if store.dirty.get(&user_id) == Some(&tick) {
+                        store.dirty.remove(&user_id);
+                    }
+                }
+                Err(e) => println!("WARN: Failed to save search history: {}", e),
+            }
+        }
+    }
+}
+
+pub struct SearchHistory {
+    enabled: bool,
+    limit: usize,
+    inner: Arc<Inner>,
+}
+
+impl SearchHistory {
+    /// Store under `{data_path}/search_history`; `SEARCH_HISTORY` and
+    /// `SEARCH_HISTORY_LIMIT` control recording
+    pub fn at_path(data_path: &str) -> Self {
+        let enabled = std::env::var("SEARCH_HISTORY").map(|v| v != "false").unwrap_or(true);
+        let limit = std::env::var("SEARCH_HISTORY_LIMIT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_HISTORY_LIMIT);
+        Self {
+            enabled,
+            limit: limit.min(MAX_HISTORY_LIMIT),
+            inner: Arc::new(Inner {
+                dir: format!("{}/search_history", data_path),
+                store: Mutex::new(Store::default()),
+                flush_lock: tokio::sync::Mutex::new(()),
+            }),
+        }
+    }
+
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit.min(MAX_HISTORY_LIMIT);
+        self
+    }
+
+    /// Writes pending changes now instead of after the usual delay, e.g. at shutdown
+    pub async fn flush(&self) {
+        self.inner.flush().await;
+    }
+
+    fn schedule_flush(&self, store: &mut Store) {
+        if store.flush_scheduled {
+            return;
+        }
+        store.flush_scheduled = true;
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(FLUSH_DELAY).await;
+            inner.flush().await;
+        });
+    }
+
+    /// Apply `f` to the user's searches, saving them soon after when it returns true.
+    /// The store is only locked while `f` runs; loading from disk happens outside it.
+    async fn update<T>(&self, user_id: &str, f: impl FnOnce(&mut UserSearches) -> (T, bool)) -> T {
+        let mut loaded = None;
+        loop {
+            {
+                let mut store = self.inner.lock();
+                let now = store.touch();
+                if let Some(searches) = loaded.take() {
+                    store.evict(user_id);
+                    store.users.entry(user_id.to_string()).or_insert((searches, now));
+                }
+                if let Some((searches, used)) = store.users.get_mut(user_id) {
+                    *used = now;
+                    let (result, changed) = f(searches);
+                    if changed {
+                        store.dirty.insert(user_id.to_string(), now);
+                        self.schedule_flush(&mut store);
+                    }
+                    return result;
+                }
+            }
+            loaded = Some(self.inner.load(user_id).await);
+        }
+    }
+
+    /// Whether searches by `user_id` are currently recorded
+    pub async fn is_recording(&self, user_id: &str) -> bool {
+        self.enabled && self.limit > 0 && !self.update(user_id, |s| (s.history_disabled, false)).await
+    }
+
+    /// Remember a search; returns the new entry's id, or `None` when history is off
+    pub async fn record(&self, user_id: &str, query: Value, hits: usize) -> Option<String> {
+        if !self.enabled || self.limit == 0 {
+            return None;
+        }
+        if query.to_string().len() > MAX_QUERY_BYTES {
+            return None;
+        }
+        let limit = self.limit;
+        self.update(user_id, |s| {
+            if s.history_disabled {
+                return (None, false);
+            }
+            let id = uuid::Uuid::new_v4().to_string();
+            s.history.insert(0, HistoryEntry { id: id.clone(), query, hits, timestamp: now_secs() });
+            s.history.truncate(limit);
+            (Some(id), true)
+        }).await
+    }
+
+    /// Recorded searches, newest first
+    pub async fn history(&self, user_id: &str) -> Vec<HistoryEntry> {
+        self.update(user_id, |s| (s.history.clone(), false)).await
+    }
+
+    pub async fn history_entry(&self, user_id: &str, id: &str) -> Result<HistoryEntry> {
+        self.update(user_id, |s| (s.history.iter().find(|e| e.id == id).cloned(), false)).await
+            .ok_or_else(|| BrainVaultError::NotFound(format!("No search '{}' in history", id)))
+    }
+
+    pub async fn clear_history(&self, user_id: &str) {
+        self.update(user_id, |s| {
+            let changed = !s.history.is_empty();
+            s.history.clear();
+            ((), changed)
+        }).await
+    }
+
+    /// Turn recording on or off for one user; turning it off also forgets past searches
+    pub async fn set_history_enabled(&self, user_id: &str, enabled: bool) {
+        self.update(user_id, |s| {
+            s.history_disabled = !enabled;
+            if !enabled {
+                s.history.clear();
+            }
+            ((), true)
+        }).await
+    }
+
+    /// Save `query` under `name`, replacing any saved search of that name
+    pub async fn save(&self, user_id: &str, name: &str, query: Value) -> Result<SavedSearch> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > MAX_SAVED_NAME_LEN {
+            return Err(BrainVaultError::BadRequest(format!(
+                "Saved search name must be 1-{} characters", MAX_SAVED_NAME_LEN
+            )));
+        }
+        if query.to_string().len() > MAX_QUERY_BYTES {
+            return Err(BrainVaultError::BadRequest(format!(
+                "Saved search query must be at most {} bytes", MAX_QUERY_BYTES
+            )));
+        }
+        let saved = SavedSearch { name: name.to_string(), query, created_at: now_secs() };
+        let added = self.update(user_id, |s| {
+            s.saved.retain(|existing| existing.name != saved.name);
+            if s.saved.len() >= MAX_SAVED_SEARCHES {
+                return (false, false);
+            }
+            s.saved.push(saved.clone());
+            (true, true)
+        }).await;
+        if !added {
+            return Err(BrainVaultError::BadRequest(format!(
+                "At most {} saved searches are kept; delete one first", MAX_SAVED_SEARCHES
+            )));
+        }
+        Ok(saved)
+    }
+
+    pub async fn saved_searches(&self, user_id: &str) -> Vec<SavedSearch> {
+        self.update(user_id, |s| (s.saved.clone(), false)).await
+    }
+
+    pub async fn saved_search(&self, user_id: &str, name: &str) -> Result<SavedSearch> {
+        self.update(user_id, |s| (s.saved.iter().find(|saved| saved.name == name).cloned(), false)).await
+            .ok_or_else(|| BrainVaultError::NotFound(format!("No saved search '{}'", name)))
+    }
+
+    pub async fn delete_saved(&self, user_id: &str, name: &str) -> Result<()> {
+        let removed = self.update(user_id, |s| {
+            let before = s.saved.len();
+            s.saved.retain(|saved| saved.name != name);
+            let removed = s.saved.len() != before;
+            (removed, removed)
+        }).await;
+        if removed {
+            Ok(())
+        } else {
+            Err(BrainVaultError::NotFound(format!("No saved search '{}'", name)))
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Sparse term-weight vectors for the lexical half of hybrid search
+//!
+//! Each document is stored as the raw counts of its analyzed terms, with its length, so the
+//! searcher can credit term frequency against the average document length. Queries are
+//! weighted by inverse document frequency at search time, so rare terms count for more as
+//! the corpus grows, and their weights sum to one: a document's score is the weighted mean
+//! of its per-term credits, in [0, 1] like the dense embedding scores it is fused with.
+
+use std::collections::HashMap;
+
+/// Term -> weight
+pub type SparseVector = HashMap<String, f32>;
+
+/// `1 + ln w` for counts above one; fractional credits (expansions, fuzzy matches) pass through
+fn sublinear(weight: f32) -> f32 {
+    if weight > 1.0 {
+        1.0 + weight.ln()
+    } else {
+        weight
+    }
+}
+
+/// Occurrences of each term in `terms`
+pub fn term_counts(terms: &[String]) -> SparseVector {
+    let mut counts = SparseVector::new();
+    for term in terms {
+        *counts.entry(term.clone()).or_insert(0.0) += 1.0;
+    }
+    counts
+}
+
+/// Number of terms a count vector was built from
+fn length(vector: &SparseVector) -> f32 {
+    vector.values().sum()
+}
+
+/// Term counts per document with the document frequency of every term
+#[derive(Debug, Clone, Default)]
+pub struct SparseIndex {
+    vectors: HashMap<String, SparseVector>,
+    doc_freq: HashMap<String, usize>,
+    total_terms: f32,
+}
+
+impl SparseIndex {
+    /// Stores the term counts of `doc_id`, replacing its previous ones
+    pub fn insert(&mut self, doc_id: &str, counts: SparseVector) {
+        self.remove(doc_id);
+        for term in counts.keys() {
+            *self.doc_freq.entry(term.clone()).or_insert(0) += 1;
+        }
+        self.total_terms += length(&counts);
+        self.vectors.insert(doc_id.to_string(), counts);
+    }
+
+    pub fn remove(&mut self, doc_id: &str) {
+        if let Some(old) = self.vectors.remove(doc_id) {
+            self.total_terms = (self.total_terms - length(&old)).max(0.0);
+            for term in old.keys() {
+                if let Some(df) = self.doc_freq.get_mut(term) {
+                    *df -= 1;
+                    if *df == 0 {
+                        self.doc_freq.remove(term);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.vectors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vectors.is_empty()
+    }
+
+    pub fn contains(&self, doc_id: &str) -> bool {
+        self.vectors.contains_key(doc_id)
+    }
+
+    pub fn vector(&self, doc_id: &str) -> Option<&SparseVector> {
+        self.vectors.get(doc_id)
+    }
+
+    /// Each document with its term counts and length
+    pub fn documents(&self) -> impl Iterator<Item = (&String, &SparseVector, f32)> {
+        self.vectors.iter().map(|(id, counts)| (id, counts, length(counts)))
+    }
+
+    /// Mean number of terms per document
+    pub fn average_length(&self) -> f32 {
+        if self.vectors.is_empty() {
+            0.0
+        } else {
+            self.total_terms / self.vectors.len() as f32
+        }
+    }
+
+    /// Every term that occurs in at least one document
+    pub fn terms(&self) -> impl Iterator<Item = &String> {
+        self.doc_freq.keys()
+    }
+
+    pub fn contains_term(&self, term: &str) -> bool {
+        self.doc_freq.contains_key(term)
+    }
+
+    /// Smoothed inverse document frequency; 0 for terms no document contains
+    pub fn idf(&self, term: &str) -> f32 {
+        match self.doc_freq.get(term) {
+            Some(&df) => (1.0 + self.vectors.len() as f32 / df as f32).ln(),
+            None => 0.0,
+        }
+    }
+
+    /// Query weights summing to one from raw term weights (counts, or fractional credits
+    /// for expansions), each scaled by its IDF
+    pub fn query_vector(&self, term_weights: &SparseVector) -> SparseVector {
+        let mut vector: SparseVector = term_weights.iter()
+            .map(|(term, w)| (term.clone(), sublinear(*w) * self.idf(term)))
+            .filter(|(_, w)| *w > 0.0)
+            .collect();
+        let total: f32 = vector.values().sum();
+        if total > 0.0 {
+            vector.values_mut().for_each(|w| *w /= total);
+        }
+        vector
+    }
+}
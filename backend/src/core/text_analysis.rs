@@ -0,0 +1,197 @@
+use rust_stemmers::{Algorithm, Stemmer};
+
+/// Language used for documents and queries that don't name one
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+const STOPWORDS_EN: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "from", "has", "have",
+    "in", "into", "is", "it", "its", "of", "on", "or", "that", "the", "their", "this",
+    "to", "was", "were", "will", "with",
+];
+const STOPWORDS_FR: &[&str] = &[
+    "au", "aux", "avec", "ce", "ces", "dans", "de", "des", "du", "elle", "en", "est", "et",
+    "il", "la", "le", "les", "leur", "mais", "ou", "par", "pas", "pour", "qui", "que", "sa",
+    "se", "son", "sur", "un", "une",
+];
+const STOPWORDS_DE: &[&str] = &[
+    "als", "am", "auf", "aus", "bei", "das", "dem", "den", "der", "des", "die", "ein", "eine",
+    "einer", "es", "für", "im", "in", "ist", "mit", "nicht", "oder", "sich", "sie", "und",
+    "von", "zu", "zum", "zur",
+];
+const STOPWORDS_ES: &[&str] = &[
+    "a", "al", "como", "con", "de", "del", "el", "en", "es", "la", "las", "lo", "los", "no",
+    "o", "para", "por", "que", "se", "su", "sus", "un", "una", "y",
+];
+const STOPWORDS_IT: &[&str] = &[
+    "a", "al", "alla", "che", "con", "da", "del", "della", "di", "e", "gli", "il", "in", "la",
+    "le", "lo", "non", "per", "si", "su", "un", "una",
+];
+const STOPWORDS_PT: &[&str] = &[
+    "a", "ao", "as", "com", "da", "das", "de", "do", "dos", "e", "em", "na", "no", "o", "os",
+    "para", "por", "que", "se", "um", "uma",
+];
+const STOPWORDS_NL: &[&str] = &[
+    "de", "een", "en", "het", "in", "is", "met", "niet", "of", "op", "te", "van", "voor",
+    "zijn",
+];
+
+/// Canonical code for a supported language name or ISO 639-1 code (`"English"`, `"en-GB"`)
+pub fn normalize_language(language: &str) -> Option<&'static str> {
+    let lower = language.trim().to_lowercase();
+    let primary = lower.split(['-', '_']).next().unwrap_or("");
+    match primary {
+        "en" | "english" => Some("en"),
+        "fr" | "french" => Some("fr"),
+        "de" | "german" => Some("de"),
+        "es" | "spanish" => Some("es"),
+        "it" | "italian" => Some("it"),
+        "pt" | "portuguese" => Some("pt"),
+        "nl" | "dutch" => Some("nl"),
+        _ => None,
+    }
+}
+
+fn stopwords(language: &str) -> &'static [&'static str] {
+    match language {
+        "fr" => STOPWORDS_FR,
+        "de" => STOPWORDS_DE,
+        "es" => STOPWORDS_ES,
+        "it" => STOPWORDS_IT,
+        "pt" => STOPWORDS_PT,
+        "nl" => STOPWORDS_NL,
+        _ => STOPWORDS_EN,
+    }
+}
+
+fn snowball(language: &str) -> Algorithm {
+    match language {
+        "fr" => Algorithm::French,
+        "de" => Algorithm::German,
+        "es" => Algorithm::Spanish,
+        "it" => Algorithm::Italian,
+        "pt" => Algorithm::Portuguese,
+        "nl" => Algorithm::Dutch,
+        _ => Algorithm::English,
+    }
+}
+
+/// Turns text into the terms lexical search compares.
+///
+/// Text is lowercased and split on anything that isn't a letter or digit, with possessive
+/// `'s` dropped, so `"CEO's plan."` yields `ceo` and `plan`. Stopword removal and Snowball
+/// stemming follow the text's language and can each be turned off.
+#[derive(Debug, Clone)]
+pub struct TextAnalyzer {
+    pub remove_stopwords: bool,
+    pub stemming: bool,
+    /// Canonical code used when text has no language of its own
+    pub default_language: &'static str,
+}
+
+impl Default for TextAnalyzer {
+    fn default() -> Self {
+        Self { remove_stopwords: true, stemming: false, default_language: DEFAULT_LANGUAGE }
+    }
+}
+
+impl TextAnalyzer {
+    /// Reads `LEXICAL_STOPWORDS` (default true), `LEXICAL_STEMMING` (default false) and
+    /// `LEXICAL_LANGUAGE` (default `en`)
+    pub fn from_env() -> Self {
+        let flag = |name: &str, default: bool| std::env::var(name).ok()
+            .and_then(|v| v.parse::<bool>().ok())
+            .unwrap_or(default);
+        let defaults = Self::default();
+        let default_language = match std::env::var("LEXICAL_LANGUAGE") {
+            Ok(value) => normalize_language(&value).unwrap_or_else(|| {
+                println!("WARN: Unsupported LEXICAL_LANGUAGE '{}', using {}", value, DEFAULT_LANGUAGE);
+                DEFAULT_LANGUAGE
+            }),
+            Err(_) => DEFAULT_LANGUAGE,
+        };
+        Self {
+            remove_stopwords: flag("LEXICAL_STOPWORDS", defaults.remove_stopwords),
+            stemming: flag("LEXICAL_STEMMING", defaults.stemming),
+            default_language,
+        }
+    }
+
+    /// Analyzed terms of `text` in the default language
+    pub fn tokenize(&self, text: &str) -> Vec<String> {
+        self.tokenize_in(text, None)
+    }
+
+    /// Terms of a document for indexing: like `tokenize_in`, but stopwords are kept, so a
+    /// query made only of stopwords (which keeps its own) still finds the documents with them
+    pub fn index_terms_in(&self, text: &str, language: Option<&str>) -> Vec<String> {
+        TextAnalyzer { remove_stopwords: false, ..self.clone() }.tokenize_in(text, language)
+    }
+
+    /// Analyzed terms of `text`, in order, using `language`'s stopwords and stemmer.
+    /// Unsupported or missing languages fall back to the default. Text made only of
+    /// stopwords keeps them, so it still matches something.
+    pub fn tokenize_in(&self, text: &str, language: Option<&str>) -> Vec<String> {
+        let language = language.and_then(normalize_language).unwrap_or(self.default_language);
+        let words = split_words(text);
+        let kept: Vec<String> = if self.remove_stopwords {
+            let stop = stopwords(language);
+            words.iter().filter(|w| !stop.contains(&w.as_str())).cloned().collect()
+        } else {
+            words.clone()
+        };
+        let terms = if kept.is_empty() { words } else { kept };
+        if self.stemming {
+            let stemmer = Stemmer::create(snowball(language));
+            terms.iter().map(|t| stemmer.stem(t).into_owned()).collect()
+        } else {
+            terms
+        }
+    }
+}
+
+/// Lowercased words with punctuation, possessive `'s` and inner apostrophes removed
+fn split_words(text: &str) -> Vec<String> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '\'' || c == '\u{2019}'))
+        .map(|piece| {
+            let piece = piece.trim_matches(|c: char| c == '\'' || c == '\u{2019}').to_lowercase();
+            let piece = piece.strip_suffix("'s")
+                .or_else(|| piece.strip_suffix("\u{2019}s"))
+                .unwrap_or(piece.as_str())
+                .to_string();
+            piece.replace(['\'', '\u{2019}'], "")
+        })
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+/// Typos tolerated in a term of `len` characters: none up to 3, one up to 7, two beyond
+pub fn edit_budget(len: usize) -> usize {
+    match len {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Levenshtein distance between `a` and `b`, in characters
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// True when `candidate` is within the edit budget of `term`
+pub fn fuzzy_match(term: &str, candidate: &str) -> bool {
+    let budget = edit_budget(term.chars().count());
+    budget > 0
+        && term.chars().count().abs_diff(candidate.chars().count()) <= budget
+        && levenshtein(term, candidate) <= budget
+}
@@ -62,19 +62,32 @@ pub struct BarqGraphClient {
     client: Client,
     id_counter: std::sync::Arc<tokio::sync::RwLock<u64>>,
     name_to_id: std::sync::Arc<tokio::sync::RwLock<HashMap<String, u64>>>,
+    /// Nodes whose entity was merged away, and the name of the entity they now belong to
+    retired: std::sync::Arc<tokio::sync::RwLock<HashMap<u64, String>>>,
 }
 
 impl BarqGraphClient {
     pub fn new() -> Self {
         let base_url = env::var("GRAPH_DB_URL").unwrap_or_else(|_| "http://barq-graph:8080".to_string());
+        Self::connect(&base_url)
+    }
+
+    pub fn connect(base_url: &str) -> Self {
         Self {
-            base_url,
-            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: crate::http_client::shared(),
             id_counter: std::sync::Arc::new(tokio::sync::RwLock::new(1)),
             name_to_id: std::sync::Arc::new(tokio::sync::RwLock::new(HashMap::new())),
+            retired: std::sync::Arc::new(tokio::sync::RwLock::new(HashMap::new())),
         }
     }
 
+    /// Use `client` instead of the shared outbound client
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
     async fn get_next_id(&self) -> u64 {
         let mut counter = self.id_counter.write().await;
         let id = *counter;
@@ -84,7 +97,7 @@ impl BarqGraphClient {
 
     pub async fn health(&self) -> Result<bool, String> {
         let url = format!("{}/health", self.base_url);
-        match self.client.get(&url).send().await {
+        match self.client.get(&url).timeout(crate::http_client::request_timeout()).send().await {
             Ok(resp) => Ok(resp.status().is_success()),
             Err(e) => Err(format!("Health check failed: {}", e)),
         }
@@ -93,6 +106,7 @@ impl BarqGraphClient {
     pub async fn get_stats(&self) -> Result<StatsResponse, String> {
         let url = format!("{}/stats", self.base_url);
         let resp = self.client.get(&url)
+            .timeout(crate::http_client::request_timeout())
             .send()
             .await
             .map_err(|e| format!("Stats request failed: {}", e))?;
@@ -120,6 +134,7 @@ impl BarqGraphClient {
 
         let resp = self.client.post(&url)
             .json(&node)
+            .timeout(crate::http_client::request_timeout())
             .send()
             .await
             .map_err(|e| format!("Create node failed: {}", e))?;
@@ -145,6 +160,7 @@ impl BarqGraphClient {
 
         let resp = self.client.post(&url)
             .json(&edge)
+            .timeout(crate::http_client::request_timeout())
             .send()
             .await
             .map_err(|e| format!("Create edge failed: {}", e))?;
@@ -170,6 +186,7 @@ impl BarqGraphClient {
 
         let resp = self.client.post(&url)
             .json(&request)
+            .timeout(crate::http_client::request_timeout())
             .send()
             .await
             .map_err(|e| format!("Hybrid query failed: {}", e))?;
@@ -188,4 +205,63 @@ impl BarqGraphClient {
         let map = self.name_to_id.read().await;
         map.get(name).copied()
     }
+
+    /// Name a node was created under by this client, if any. A retired node answers to
+    /// the entity it was folded into.
+    pub async fn get_name_by_node_id(&self, id: u64) -> Option<String> {
+        if let Some(name) = self.retired.read().await.get(&id) {
+            return Some(name.clone());
+        }
+        let map = self.name_to_id.read().await;
+        map.iter().find(|(_, v)| **v == id).map(|(k, _)| k.clone())
+    }
+
+    /// Stop resolving `name` to its node and report that node as `into` from now on.
+    /// Barq has no node deletion, so this is how a merged-away entity leaves the index.
+    pub async fn retire_node(&self, name: &str, into: &str) {
+        let id = self.name_to_id.write().await.remove(name);
+        let mut retired = self.retired.write().await;
+        for owner in retired.values_mut().filter(|owner| owner.as_str() == name) {
+            *owner = into.to_string();
+        }
+        if let Some(id) = id {
+            retired.insert(id, into.to_string());
+        }
+    }
+
+    /// Every node in the store
+    pub async fn list_nodes(&self) -> Result<Vec<GraphNode>, String> {
+        let url = format!("{}/nodes", self.base_url);
+        let resp = self.client.get(&url)
+            .timeout(crate::http_client::request_timeout())
+            .send()
+            .await
+            .map_err(|e| format!("List nodes failed: {}", e))?;
+
+        if resp.status().is_success() {
+            resp.json::<Vec<GraphNode>>()
+                .await
+                .map_err(|e| format!("Parse nodes failed: {}", e))
+        } else {
+            Err(format!("List nodes failed: {}", resp.status()))
+        }
+    }
+
+    /// Every edge in the store
+    pub async fn list_edges(&self) -> Result<Vec<GraphEdge>, String> {
+        let url = format!("{}/edges", self.base_url);
+        let resp = self.client.get(&url)
+            .timeout(crate::http_client::request_timeout())
+            .send()
+            .await
+            .map_err(|e| format!("List edges failed: {}", e))?;
+
+        if resp.status().is_success() {
+            resp.json::<Vec<GraphEdge>>()
+                .await
+                .map_err(|e| format!("Parse edges failed: {}", e))
+        } else {
+            Err(format!("List edges failed: {}", resp.status()))
+        }
+    }
 }
@@ -1,9 +1,16 @@
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use std::collections::HashMap;
-use crate::core::llm::embeddings::AzureEmbeddingClient;
+use tokio::sync::{Mutex, RwLock};
+use std::collections::{BTreeMap, HashMap};
+use crate::core::llm::embeddings::{create_embedding_provider, EmbeddingProvider};
+use crate::core::abbreviations::AbbreviationMap;
+use crate::db::circuit_breaker::{CircuitBreaker, CircuitStatus};
+use crate::core::llm::retry::{retry_with_backoff, ProviderError, RetryPolicy};
+use crate::core::text_analysis::{fuzzy_match, normalize_language, TextAnalyzer};
+use crate::core::query_syntax::{contains_sequence, parse_query, ParsedQuery, QueryField};
+use crate::core::sparse_vector::{term_counts, SparseIndex, SparseVector};
+use std::collections::HashSet;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchHit {
@@ -12,6 +19,15 @@ pub struct SearchHit {
     pub content: Option<String>,
 }
 
+/// A match among archived revisions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryHit {
+    pub doc_id: String,
+    pub version: u32,
+    pub score: f32,
+    pub content: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct InsertRequest {
     id: String,
@@ -23,6 +39,8 @@ struct InsertRequest {
 struct SearchRequest {
     vector: Vec<f32>,
     top_k: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    filter: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,20 +55,375 @@ struct SearchResponse {
     results: Vec<SearchResultItem>,
 }
 
+/// Progress of the startup warm-up, for `/api/health`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct WarmupStatus {
+    /// `disabled`, `running` (loading the index), `reembedding` (ready, embedding cached
+    /// documents that have no vector in the background) or `done`
+    pub state: &'static str,
+    pub collection_ready: bool,
+    /// Cached documents that had no vector when the warm-up started
+    pub missing: usize,
+    pub embedded: usize,
+    pub failed: usize,
+}
+
+impl Default for WarmupStatus {
+    fn default() -> Self {
+        Self { state: "disabled", collection_ready: false, missing: 0, embedded: 0, failed: 0 }
+    }
+}
+
+/// Size of the indexed corpus for `/api/knowledge/stats`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CorpusStats {
+    /// Live documents
+    pub documents: usize,
+    /// UTF-8 bytes of live document content
+    pub content_bytes: usize,
+    /// Deleted documents whose history is still kept
+    pub deleted_documents: usize,
+    /// Document collections with at least one live document
+    pub collections: usize,
+}
+
+/// What `index_document` did with a document
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexOutcome {
+    Indexed,
+    /// Same content was already embedded under this doc_id; nothing was re-sent
+    Unchanged,
+}
+
+/// Hex SHA-256 of a document's content, recorded with each revision as `content_hash`
+pub fn content_hash(content: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// One stored revision of a document. Superseded and deleted revisions keep their content
+/// in the version archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentVersion {
+    pub version: u32,
+    pub content_hash: String,
+    /// Unix seconds when this revision was indexed
+    pub indexed_at: u64,
+    /// Set on the last revision when the document was soft-deleted
+    #[serde(default)]
+    pub deleted: bool,
+    /// Canonical language code the revision is analyzed with; the analyzer default when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Collection the revision belongs to; `DEFAULT_COLLECTION` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+}
+
+/// Collection of documents ingested without one
+pub const DEFAULT_COLLECTION: &str = "default";
+/// Barq collection behind `DEFAULT_COLLECTION` when `VECTOR_COLLECTION` is unset
+pub const DEFAULT_BARQ_COLLECTION: &str = "brainvault_docs";
+
+/// Embedding model whose vectors a Barq collection holds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmbeddingStamp {
+    pub model: String,
+    pub dimension: usize,
+}
+
+/// Contents of `{DATA_PATH}/collection_models.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CollectionModels {
+    /// Bumped by every reindex; Barq collections of generation n > 0 are suffixed `_g{n}`
+    #[serde(default)]
+    generation: u64,
+    /// Barq collection -> model of the vectors written to it
+    #[serde(default)]
+    collections: HashMap<String, EmbeddingStamp>,
+}
+
+/// The claim on the reindex slot from `BarqVectorClient::begin_reindex`
+pub struct ReindexGuard(tokio::sync::OwnedMutexGuard<()>);
+
+/// Outcome of `BarqVectorClient::reindex`
+#[derive(Debug, Clone, Serialize)]
+pub struct ReindexReport {
+    pub model: String,
+    pub dimension: usize,
+    pub generation: u64,
+    /// Barq collections now in use
+    pub collections: Vec<String>,
+    /// Collections of the previous generation, left in Barq until they are dropped by hand
+    pub previous_collections: Vec<String>,
+    pub reindexed: usize,
+    /// Documents that could not be embedded; they stay searchable lexically
+    pub failed: Vec<String>,
+}
+
+/// Progress of reindexing, as reported by `GET /api/knowledge/reindex`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReindexStatus {
+    pub running: bool,
+    /// Outcome of the last reindex that finished
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_report: Option<ReindexReport>,
+    /// Why the last reindex failed, if it did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// Per-query settings for lexical matching
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LexicalOptions {
+    /// Language to analyze the query in; by default each document's own language
+    pub language: Option<String>,
+    /// Credit query terms with no exact match that are within a few typos of a document term
+    pub fuzzy: bool,
+    /// Only match documents in this collection (semantic search honors it too)
+    pub collection: Option<String>,
+}
+
+/// True when `id` belongs to `wanted`, given the non-default entries of `collections`
+fn in_collection(collections: &HashMap<String, String>, id: &str, wanted: &str) -> bool {
+    collections.get(id).map_or(DEFAULT_COLLECTION, String::as_str) == wanted
+}
+
+/// Credit for a query term matched only within its edit budget (exact match = 1.0)
+const FUZZY_MATCH_WEIGHT: f32 = 0.7;
+/// Share of a literal match's credit earned by how often the term occurs, when
+/// `LEXICAL_TF_WEIGHT` is unset; the rest is earned by occurring at all
+pub const DEFAULT_TF_WEIGHT: f32 = 0.5;
+/// Occurrences at which a term earns half its frequency credit, in a document of average
+/// length, when `LEXICAL_TF_SATURATION` is unset (BM25's k1)
+pub const DEFAULT_TF_SATURATION: f32 = 1.2;
+/// How far document length scales the saturation point (BM25's b): longer documents need
+/// more mentions for the same credit
+const TF_LENGTH_NORMALIZATION: f32 = 0.75;
+
+/// Display label of a document revision (`policy@2`). Labels are never parsed back:
+/// document ids may contain `@` themselves.
+pub fn version_key(doc_id: &str, version: u32) -> String {
+    format!("{}@{}", doc_id, version)
+}
+
+/// A line of `document_versions.jsonl`: a revision as first stored, or as later marked
+/// deleted. The last line for a version wins.
+#[derive(Serialize, Deserialize)]
+struct VersionRecord {
+    doc_id: String,
+    #[serde(flatten)]
+    version: DocumentVersion,
+}
+
+/// A line of `version_archive.jsonl`: the content of a superseded or deleted revision
+#[derive(Serialize, Deserialize)]
+struct ArchiveRecord {
+    doc_id: String,
+    version: u32,
+    content: String,
+}
+
+/// A line of `embedding_log.jsonl`: a document's new vector, or `None` once it is deleted
+#[derive(Serialize, Deserialize)]
+struct EmbeddingRecord {
+    doc_id: String,
+    embedding: Option<Vec<f32>>,
+}
+
+/// Embedding log records kept before they are folded into `embedding_cache.json`, unless
+/// the cache holds more vectors than this
+const EMBEDDING_LOG_COMPACT_MIN: usize = 1000;
+
+/// Every parseable line of the JSON-lines file at `path`; a line torn by a crash is skipped
+fn read_log<T: serde::de::DeserializeOwned>(path: &str) -> Vec<T> {
+    std::fs::read_to_string(path)
+        .map(|content| content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+        .unwrap_or_default()
+}
+
+/// Appends `records` to the JSON-lines file at `path`, one per line
+fn append_log<T: Serialize>(path: &str, records: &[T]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let lines: String = records.iter()
+        .filter_map(|record| serde_json::to_string(record).ok())
+        .map(|line| line + "\n")
+        .collect();
+    std::fs::OpenOptions::new().create(true).append(true).open(path)?
+        .write_all(lines.as_bytes())
+}
+
+/// Revision history and archive from the append logs under `data_path`. Stores written
+/// before the logs existed (`document_versions.json`, and `version_archive.json` keyed by
+/// `{doc_id}@{version}`) are converted to logs on first load.
+fn load_versions(data_path: &str) -> (HashMap<String, Vec<DocumentVersion>>, HashMap<String, BTreeMap<u32, String>>) {
+    let versions_log = format!("{}/document_versions.jsonl", data_path);
+    let archive_log = format!("{}/version_archive.jsonl", data_path);
+    let legacy_versions = format!("{}/document_versions.json", data_path);
+    let legacy_archive = format!("{}/version_archive.json", data_path);
+
+    if let Some(legacy) = std::fs::read_to_string(&legacy_versions).ok()
+        .and_then(|content| serde_json::from_str::<HashMap<String, Vec<DocumentVersion>>>(&content).ok())
+    {
+        let records: Vec<VersionRecord> = legacy.into_iter()
+            .flat_map(|(doc_id, history)| history.into_iter().map(move |version| VersionRecord { doc_id: doc_id.clone(), version }))
+            .collect();
+        if append_log(&versions_log, &records).is_ok() {
+            let _ = std::fs::remove_file(&legacy_versions);
+        }
+    }
+    if let Some(legacy) = std::fs::read_to_string(&legacy_archive).ok()
+        .and_then(|content| serde_json::from_str::<HashMap<String, String>>(&content).ok())
+    {
+        // The keys were written by `version_key`, so the last `@` always precedes the version
+        let records: Vec<ArchiveRecord> = legacy.into_iter()
+            .filter_map(|(key, content)| {
+                let (doc_id, version) = key.rsplit_once('@')?;
+                Some(ArchiveRecord { doc_id: doc_id.to_string(), version: version.parse().ok()?, content })
+            })
+            .collect();
+        if append_log(&archive_log, &records).is_ok() {
+            let _ = std::fs::remove_file(&legacy_archive);
+        }
+    }
+
+    let mut versions: HashMap<String, Vec<DocumentVersion>> = HashMap::new();
+    for record in read_log::<VersionRecord>(&versions_log) {
+        let history = versions.entry(record.doc_id).or_default();
+        match history.iter_mut().find(|v| v.version == record.version.version) {
+            Some(existing) => *existing = record.version,
+            None => history.push(record.version),
+        }
+    }
+    for history in versions.values_mut() {
+        history.sort_by_key(|v| v.version);
+    }
+    let mut archive: HashMap<String, BTreeMap<u32, String>> = HashMap::new();
+    for record in read_log::<ArchiveRecord>(&archive_log) {
+        archive.entry(record.doc_id).or_default().insert(record.version, record.content);
+    }
+    (versions, archive)
+}
+
+/// Similarity measure of the vector collection, set with `VECTOR_DISTANCE`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DistanceMetric {
+    #[default]
+    Cosine,
+    DotProduct,
+    Euclidean,
+}
+
+impl DistanceMetric {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_lowercase().as_str() {
+            "cosine" => Ok(Self::Cosine),
+            "dot" | "dot_product" | "dotproduct" | "inner_product" => Ok(Self::DotProduct),
+            "euclidean" | "l2" => Ok(Self::Euclidean),
+            other => Err(format!("Unsupported VECTOR_DISTANCE '{}'; use cosine, dot_product or euclidean", other)),
+        }
+    }
+
+    /// Name sent to Barq when creating the collection
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Cosine => "cosine",
+            Self::DotProduct => "dot_product",
+            Self::Euclidean => "euclidean",
+        }
+    }
+
+    /// Higher-is-better score between two vectors. Euclidean distance d maps to 1 / (1 + d).
+    pub fn similarity(&self, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            Self::Cosine => cosine_similarity(a, b),
+            Self::DotProduct if a.len() == b.len() => a.iter().zip(b).map(|(x, y)| x * y).sum(),
+            Self::Euclidean if a.len() == b.len() && !a.is_empty() => {
+                let distance = a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum::<f32>().sqrt();
+                1.0 / (1.0 + distance)
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// Cosine similarity; 0.0 when either vector has zero norm or the lengths differ
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
 #[derive(Clone)]
 pub struct BarqVectorClient {
     base_url: String,
+    /// Barq collection for `DEFAULT_COLLECTION`; other collections get their own, named after it
     collection_name: String,
+    /// Barq collections created (or found to exist) so far, so each is set up only once
+    ready: Arc<std::sync::Mutex<HashSet<String>>>,
+    /// Reindex generation and the embedding model of each Barq collection
+    models: Arc<std::sync::RwLock<CollectionModels>>,
+    /// Held while a reindex runs
+    reindexing: Arc<Mutex<()>>,
+    /// Shared by writes to the corpus; a reindex takes it exclusively while it catches up
+    /// on those writes and switches generations
+    ingest_gate: Arc<RwLock<()>>,
+    /// Outcome of the last reindex
+    last_reindex: Arc<std::sync::Mutex<ReindexStatus>>,
     client: reqwest::Client,
     content_cache: Arc<RwLock<HashMap<String, String>>>,
+    embedding_cache: Arc<RwLock<HashMap<String, Vec<f32>>>>,
+    /// Records appended to `embedding_log.jsonl` since `embedding_cache.json` was written
+    embedding_log_len: Arc<std::sync::atomic::AtomicUsize>,
+    /// Term-weight vector of every live document, built from the content cache on first
+    /// use (vectors are cheap to recompute, so they are not persisted) and kept current by
+    /// indexing and deletes
+    sparse: Arc<RwLock<Option<SparseIndex>>>,
+    versions: Arc<RwLock<HashMap<String, Vec<DocumentVersion>>>>,
+    /// Content of superseded and deleted revisions, by document and version
+    archive: Arc<RwLock<HashMap<String, BTreeMap<u32, String>>>>,
+    data_path: String,
     dimension: usize,
+    /// `VECTOR_DISTANCE` as configured; validated when the collection is created
+    distance: String,
+    /// Skips Barq calls during an outage so requests go straight to the local fallback
+    breaker: Arc<CircuitBreaker>,
+    /// Backoff for collection setup when Barq answers 5xx
+    retry: RetryPolicy,
+    warmup: Arc<std::sync::Mutex<WarmupStatus>>,
+    abbreviations: Arc<AbbreviationMap>,
+    analyzer: Arc<TextAnalyzer>,
+    /// Share of query terms (0-1) a document must match to be a lexical hit
+    lexical_min_score: f32,
+    /// Share (0-1) of a matched term's credit that depends on its frequency
+    lexical_tf_weight: f32,
+    /// Occurrences giving half the frequency credit in an average-length document
+    lexical_tf_saturation: f32,
+    embedder: Option<Arc<dyn EmbeddingProvider>>,
 }
 
 impl BarqVectorClient {
     pub fn new() -> Self {
         let base_url = env::var("VECTOR_DB_URL").unwrap_or_else(|_| "http://barq-vector:8080".to_string());
         let data_path = env::var("DATA_PATH").unwrap_or_else(|_| "/data".to_string());
-        
+        Self::connect(&base_url, &data_path)
+    }
+
+    /// Client for the vector DB at `base_url`, persisting caches under `data_path`
+    pub fn connect(base_url: &str, data_path: &str) -> Self {
         let mut cache = HashMap::new();
         let cache_file = format!("{}/vector_cache.json", data_path);
         
@@ -61,96 +434,895 @@ impl BarqVectorClient {
             }
         }
 
+        let mut embeddings = HashMap::new();
+        let embedding_file = format!("{}/embedding_cache.json", data_path);
+        if let Ok(content) = std::fs::read_to_string(&embedding_file) {
+            if let Ok(loaded) = serde_json::from_str::<HashMap<String, Vec<f32>>>(&content) {
+                println!("INFO: Loaded {} embeddings from persistent cache", loaded.len());
+                embeddings = loaded;
+            }
+        }
+        // Vectors stored or dropped since that snapshot
+        let logged: Vec<EmbeddingRecord> = read_log(&format!("{}/embedding_log.jsonl", data_path));
+        let embedding_log_len = logged.len();
+        for record in logged {
+            match record.embedding {
+                Some(embedding) => embeddings.insert(record.doc_id, embedding),
+                None => embeddings.remove(&record.doc_id),
+            };
+        }
+
+        let (versions, archive) = load_versions(data_path);
+
+        let models = std::fs::read_to_string(format!("{}/collection_models.json", data_path)).ok()
+            .and_then(|content| serde_json::from_str::<CollectionModels>(&content).ok())
+            .unwrap_or_default();
+
+        let embedder = create_embedding_provider();
+        // Vector size follows the embedding model unless pinned with EMBEDDING_DIM
+        let dimension = env::var("EMBEDDING_DIM").ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .or_else(|| embedder.as_ref().map(|e| e.dimension()))
+            .unwrap_or(1536);
+
         Self {
-            base_url,
-            collection_name: "brainvault_docs".to_string(),
-            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            data_path: data_path.to_string(),
+            collection_name: env::var("VECTOR_COLLECTION").unwrap_or_else(|_| DEFAULT_BARQ_COLLECTION.to_string()),
+            ready: Arc::new(std::sync::Mutex::new(HashSet::new())),
+            models: Arc::new(std::sync::RwLock::new(models)),
+            reindexing: Arc::new(Mutex::new(())),
+            ingest_gate: Arc::new(RwLock::new(())),
+            last_reindex: Arc::new(std::sync::Mutex::new(ReindexStatus::default())),
+            client: crate::http_client::shared(),
             content_cache: Arc::new(RwLock::new(cache)),
-            dimension: 1536,
+            embedding_cache: Arc::new(RwLock::new(embeddings)),
+            embedding_log_len: Arc::new(std::sync::atomic::AtomicUsize::new(embedding_log_len)),
+            sparse: Arc::new(RwLock::new(None)),
+            versions: Arc::new(RwLock::new(versions)),
+            archive: Arc::new(RwLock::new(archive)),
+            dimension,
+            distance: env::var("VECTOR_DISTANCE").unwrap_or_else(|_| DistanceMetric::default().as_str().to_string()),
+            breaker: Arc::new(CircuitBreaker::from_env("VECTOR")),
+            retry: RetryPolicy { max_retries: 2, base_delay_ms: 500, max_delay_ms: 5_000, ..RetryPolicy::default() },
+            warmup: Arc::new(std::sync::Mutex::new(WarmupStatus::default())),
+            abbreviations: Arc::new(AbbreviationMap::from_env()),
+            analyzer: Arc::new(TextAnalyzer::from_env()),
+            lexical_min_score: env::var("LEXICAL_MIN_SCORE").ok()
+                .and_then(|v| v.parse::<f32>().ok())
+                .unwrap_or(0.3),
+            lexical_tf_weight: env::var("LEXICAL_TF_WEIGHT").ok()
+                .and_then(|v| v.parse::<f32>().ok())
+                .unwrap_or(DEFAULT_TF_WEIGHT)
+                .clamp(0.0, 1.0),
+            lexical_tf_saturation: env::var("LEXICAL_TF_SATURATION").ok()
+                .and_then(|v| v.parse::<f32>().ok())
+                .unwrap_or(DEFAULT_TF_SATURATION)
+                .max(0.0),
+            embedder,
         }
     }
 
+    pub fn with_embedder(mut self, embedder: Arc<dyn EmbeddingProvider>) -> Self {
+        self.dimension = embedder.dimension();
+        self.embedder = Some(embedder);
+        self
+    }
+
+    pub fn with_abbreviations(mut self, abbreviations: AbbreviationMap) -> Self {
+        self.abbreviations = Arc::new(abbreviations);
+        self
+    }
+
+    pub fn with_analyzer(mut self, analyzer: TextAnalyzer) -> Self {
+        self.analyzer = Arc::new(analyzer);
+        self
+    }
+
+    pub fn with_lexical_min_score(mut self, min_score: f32) -> Self {
+        self.lexical_min_score = min_score;
+        self
+    }
+
+    /// Term frequency weighting of lexical scores; a weight of 0 scores each term by
+    /// presence alone
+    pub fn with_term_frequency(mut self, weight: f32, saturation: f32) -> Self {
+        self.lexical_tf_weight = weight.clamp(0.0, 1.0);
+        self.lexical_tf_saturation = saturation.max(0.0);
+        self
+    }
+
+    /// Credit for a term occurring `tf` times in a document of `len` terms: saturates as
+    /// mentions add up, so keyword stuffing gains little, and is judged relative to
+    /// `avg_len` so long documents don't win by size alone
+    fn term_credit(&self, tf: usize, len: usize, avg_len: f32) -> f32 {
+        let tf = tf as f32;
+        let length = if avg_len > 0.0 { len as f32 / avg_len } else { 1.0 };
+        let norm = 1.0 - TF_LENGTH_NORMALIZATION + TF_LENGTH_NORMALIZATION * length;
+        let saturated = tf / (tf + self.lexical_tf_saturation * norm);
+        1.0 - self.lexical_tf_weight + self.lexical_tf_weight * saturated
+    }
+
+    pub fn with_dimension(mut self, dimension: usize) -> Self {
+        self.dimension = dimension;
+        self
+    }
+
+    /// Barq collection for `DEFAULT_COLLECTION`, and prefix of the others
+    pub fn with_collection_name(mut self, name: &str) -> Self {
+        self.collection_name = name.to_string();
+        self
+    }
+
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.breaker = Arc::new(breaker);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// State of the breaker guarding Barq calls
+    pub fn circuit_status(&self) -> CircuitStatus {
+        self.breaker.status()
+    }
+
+    pub fn warmup_status(&self) -> WarmupStatus {
+        self.warmup.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// False until the startup warm-up has prepared the collection and lexical index; searches
+    /// before then can miss documents. Re-embedding documents without a vector carries on
+    /// after that and doesn't hold readiness back. Persisted caches are loaded before the
+    /// client exists, so without a warm-up the client is ready immediately.
+    pub fn is_ready(&self) -> bool {
+        self.warmup_status().state != "running"
+    }
+
+    /// Marks the warm-up as running, then runs it in the background. Readiness turns false
+    /// right away rather than whenever the task first gets scheduled.
+    pub fn spawn_warm_up(&self, reembed: bool) -> tokio::task::JoinHandle<()> {
+        self.update_warmup(|w| w.state = "running");
+        let client = self.clone();
+        tokio::spawn(async move { client.warm_up(reembed).await })
+    }
+
+    fn update_warmup(&self, update: impl FnOnce(&mut WarmupStatus)) {
+        update(&mut self.warmup.lock().unwrap_or_else(|e| e.into_inner()));
+    }
+
+    /// Startup pass so the first search doesn't pay for setup: makes sure the collection
+    /// exists, builds the lexical index over the cached corpus and, with `reembed`, embeds
+    /// cached documents that have no vector (e.g. ones stored while the embedding provider
+    /// was down). Progress shows in `warmup_status`; the client is ready once the re-embedding
+    /// starts.
+    pub async fn warm_up(&self, reembed: bool) {
+        self.update_warmup(|w| w.state = "running");
+        let collection_ready = match self.ensure_collection().await {
+            Ok(()) => true,
+            Err(e) => {
+                println!("WARN: Warm-up could not prepare the collection: {}", e);
+                false
+            }
+        };
+        self.update_warmup(|w| w.collection_ready = collection_ready);
+        self.ensure_sparse_index().await;
+
+        let missing: Vec<String> = match (&self.embedder, reembed) {
+            (Some(_), true) => {
+                let embeddings = self.embedding_cache.read().await;
+                let mut ids: Vec<String> = self.content_cache.read().await.keys()
+                    .filter(|id| !embeddings.contains_key(*id))
+                    .cloned()
+                    .collect();
+                ids.sort();
+                ids
+            }
+            _ => Vec::new(),
+        };
+        // The index is loaded; what remains only improves dense recall
+        self.update_warmup(|w| {
+            w.missing = missing.len();
+            w.state = "reembedding";
+        });
+        if !missing.is_empty() {
+            println!("INFO: Warm-up embedding {} cached documents without vectors", missing.len());
+        }
+
+        for doc_id in missing {
+            let content = match self.content_cache.read().await.get(&doc_id).cloned() {
+                Some(content) => content,
+                None => continue,
+            };
+            match self.embed_cached(&doc_id, &content).await {
+                Ok(()) => self.update_warmup(|w| w.embedded += 1),
+                Err(e) => {
+                    println!("WARN: Warm-up could not embed '{}': {}", doc_id, e);
+                    self.update_warmup(|w| w.failed += 1);
+                }
+            }
+        }
+        self.save_embeddings().await;
+
+        let status = self.warmup_status();
+        println!(
+            "INFO: Vector warm-up done (collection ready: {}, embedded {}/{}, failed {})",
+            status.collection_ready, status.embedded, status.missing, status.failed
+        );
+        self.update_warmup(|w| w.state = "done");
+    }
+
+    /// Embeds an already stored document and pushes its vector, without a new version
+    async fn embed_cached(&self, doc_id: &str, content: &str) -> Result<(), String> {
+        let _gate = self.ingest_gate.read().await;
+        let embedder = self.embedder.as_ref().ok_or("No embedding client")?;
+        let embedding = embedder.embed(content).await?;
+        self.validate_embedding(&embedding)?;
+        let collection = self.document_collection(doc_id).await;
+        if let Some(mismatch) = self.model_mismatch(collection.as_deref()) {
+            return Err(mismatch);
+        }
+        self.stamp_collection(&self.barq_collection(collection.as_deref()));
+        self.embedding_cache.write().await.insert(doc_id.to_string(), embedding.clone());
+        self.upsert_remote(doc_id, content, collection.as_deref(), embedding).await;
+        Ok(())
+    }
+
+    /// Use `client` instead of the shared outbound client
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn with_distance(mut self, metric: DistanceMetric) -> Self {
+        self.distance = metric.as_str().to_string();
+        self
+    }
+
+    /// The configured distance metric, or an error naming the unsupported value
+    pub fn distance(&self) -> Result<DistanceMetric, String> {
+        DistanceMetric::parse(&self.distance)
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Barq silently drops vectors whose length differs from the collection's, so catch it here
+    pub fn validate_embedding(&self, embedding: &[f32]) -> Result<(), String> {
+        if embedding.len() != self.dimension {
+            return Err(format!(
+                "Embedding dimension mismatch: model returned {} values but collection '{}' expects {}. \
+                 Set EMBEDDING_DIM to match the embedding model.",
+                embedding.len(), self.collection_name, self.dimension
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn collection_name(&self) -> &str {
+        &self.collection_name
+    }
+
     pub async fn save_cache(&self) {
-        let data_path = env::var("DATA_PATH").unwrap_or_else(|_| "/data".to_string());
-        let cache_file = format!("{}/vector_cache.json", data_path);
+        let cache_file = format!("{}/vector_cache.json", self.data_path);
         let cache = self.content_cache.read().await;
         if let Ok(content) = serde_json::to_string(&*cache) {
             let _ = std::fs::write(cache_file, content);
         }
     }
 
+    /// Writes every vector to `embedding_cache.json` and empties the embedding log. For
+    /// bulk changes; single documents go through `log_embedding`.
+    async fn save_embeddings(&self) {
+        let embeddings = self.embedding_cache.read().await;
+        self.write_embeddings(&embeddings);
+    }
+
+    fn write_embeddings(&self, embeddings: &HashMap<String, Vec<f32>>) {
+        let embedding_file = format!("{}/embedding_cache.json", self.data_path);
+        let staged = format!("{}.tmp", embedding_file);
+        let written = serde_json::to_string(embeddings).ok()
+            .map_or(false, |content| std::fs::write(&staged, content).and_then(|_| std::fs::rename(&staged, &embedding_file)).is_ok());
+        // The log is only dropped once the snapshot holding its records is in place
+        if written {
+            let _ = std::fs::remove_file(format!("{}/embedding_log.jsonl", self.data_path));
+            self.embedding_log_len.store(0, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    /// Stores `doc_id`'s vector, or drops it for `None`, and appends the change to the
+    /// embedding log instead of rewriting the whole cache file. The log is folded into
+    /// the snapshot once it outgrows the cache.
+    async fn log_embedding(&self, doc_id: &str, embedding: Option<Vec<f32>>) {
+        // Held across the append so a snapshot can't drop a record it doesn't contain
+        let mut embeddings = self.embedding_cache.write().await;
+        let record = EmbeddingRecord { doc_id: doc_id.to_string(), embedding };
+        match record.embedding {
+            Some(ref embedding) => embeddings.insert(doc_id.to_string(), embedding.clone()),
+            None => embeddings.remove(doc_id),
+        };
+        let _ = append_log(&format!("{}/embedding_log.jsonl", self.data_path), &[record]);
+        let logged = self.embedding_log_len.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+        if logged > EMBEDDING_LOG_COMPACT_MIN.max(embeddings.len()) {
+            self.write_embeddings(&embeddings);
+        }
+    }
+
+    /// Appends changed revisions of `doc_id`, and the content of one it archived, to the
+    /// version logs. Revisions are only ever added or marked deleted, so nothing is rewritten.
+    fn log_versions(&self, doc_id: &str, changed: &[DocumentVersion], archived: Option<(u32, String)>) {
+        let records: Vec<VersionRecord> = changed.iter()
+            .map(|version| VersionRecord { doc_id: doc_id.to_string(), version: version.clone() })
+            .collect();
+        let _ = append_log(&format!("{}/document_versions.jsonl", self.data_path), &records);
+        if let Some((version, content)) = archived {
+            let _ = append_log(&format!("{}/version_archive.jsonl", self.data_path), &[ArchiveRecord { doc_id: doc_id.to_string(), version, content }]);
+        }
+    }
+
+    /// Makes `content` the current revision of `doc_id`, archiving the revision it replaces.
+    /// Content matching the current revision, in the same language and collection, adds no
+    /// revision. Returns whether one was added.
+    async fn store_content(&self, doc_id: &str, content: &str, hash: &str, language: Option<&str>, collection: Option<&str>) -> bool {
+        let sparse_vector = term_counts(&self.analyzer.index_terms_in(content, language));
+        let mut changed = Vec::new();
+        let mut archived = None;
+        {
+            let mut cache = self.content_cache.write().await;
+            let mut versions = self.versions.write().await;
+            let history = versions.entry(doc_id.to_string()).or_default();
+            if let Some(previous) = cache.get(doc_id) {
+                if history.is_empty() {
+                    // Indexed before versioning existed
+                    let first = DocumentVersion { version: 1, content_hash: content_hash(previous), indexed_at: 0, deleted: false, language: None, collection: None };
+                    changed.push(first.clone());
+                    history.push(first);
+                }
+                let current = history.last().expect("history is non-empty");
+                let unchanged = current.content_hash == hash
+                    && current.language.as_deref() == language
+                    && current.collection.as_deref() == collection;
+                if !unchanged {
+                    self.archive.write().await.entry(doc_id.to_string()).or_default().insert(current.version, previous.clone());
+                    archived = Some((current.version, previous.clone()));
+                }
+            }
+            if archived.is_some() || !cache.contains_key(doc_id) {
+                let version = DocumentVersion {
+                    version: history.last().map_or(1, |v| v.version + 1),
+                    content_hash: hash.to_string(),
+                    indexed_at: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs(),
+                    deleted: false,
+                    language: language.map(String::from),
+                    collection: collection.map(String::from),
+                };
+                changed.push(version.clone());
+                history.push(version);
+                cache.insert(doc_id.to_string(), content.to_string());
+                if let Some(ref mut index) = *self.sparse.write().await {
+                    index.insert(doc_id, sparse_vector);
+                }
+            }
+        }
+        if changed.is_empty() {
+            return false;
+        }
+        self.log_versions(doc_id, &changed, archived);
+        self.save_cache().await;
+        true
+    }
+
+    /// Soft-deletes a document: its content moves to the version archive, it stops appearing
+    /// in search, and its last revision is marked deleted. The Barq vector is removed on a
+    /// best-effort basis; remote hits for tombstoned ids are dropped either way.
+    pub async fn delete_document(&self, doc_id: &str) -> Result<DocumentVersion, String> {
+        let _gate = self.ingest_gate.read().await;
+        let tombstone = {
+            let mut cache = self.content_cache.write().await;
+            let content = cache.remove(doc_id).ok_or_else(|| format!("Document {} not found", doc_id))?;
+            let mut versions = self.versions.write().await;
+            let history = versions.entry(doc_id.to_string()).or_default();
+            if history.is_empty() {
+                // Indexed before versioning existed
+                history.push(DocumentVersion {
+                    version: 1,
+                    content_hash: content_hash(&content),
+                    indexed_at: 0,
+                    deleted: false,
+                    language: None,
+                    collection: None,
+                });
+            }
+            let last = history.last_mut().expect("history is non-empty");
+            last.deleted = true;
+            self.archive.write().await.entry(doc_id.to_string()).or_default().insert(last.version, content.clone());
+            self.log_versions(doc_id, std::slice::from_ref(last), Some((last.version, content)));
+            last.clone()
+        };
+        self.log_embedding(doc_id, None).await;
+        if let Some(ref mut index) = *self.sparse.write().await {
+            index.remove(doc_id);
+        }
+        self.save_cache().await;
+
+        self.delete_remote(doc_id, tombstone.collection.as_deref()).await;
+        Ok(tombstone)
+    }
+
+    /// Removes `doc_id`'s vector from the Barq collection of `collection`, best effort
+    async fn delete_remote(&self, doc_id: &str, collection: Option<&str>) {
+        let permit = match self.breaker.try_call() {
+            Some(permit) => permit,
+            None => {
+                println!("WARN: Barq circuit open; '{}' not removed from Barq", doc_id);
+                return;
+            }
+        };
+        let url = format!("{}/collections/{}/vectors/{}", self.base_url, self.barq_collection(collection), doc_id);
+        match self.client.delete(&url).timeout(crate::http_client::request_timeout()).send().await {
+            Ok(resp) if resp.status().is_server_error() => {
+                permit.failed();
+                println!("WARN: Barq delete returned {}", resp.status());
+            }
+            Ok(resp) => {
+                permit.succeeded();
+                if resp.status().is_success() {
+                    println!("INFO: Removed document '{}' from Barq", doc_id);
+                } else {
+                    println!("WARN: Barq delete returned {}", resp.status());
+                }
+            }
+            Err(e) => {
+                permit.failed();
+                println!("WARN: Barq delete failed: {}", e);
+            }
+        }
+    }
+
+    /// Revision history of `doc_id`, oldest first
+    pub async fn list_versions(&self, doc_id: &str) -> Vec<DocumentVersion> {
+        self.versions.read().await.get(doc_id).cloned().unwrap_or_default()
+    }
+
+    /// Content of one revision: the archive for superseded or deleted ones, otherwise the live document
+    pub async fn get_version(&self, doc_id: &str, version: u32) -> Option<String> {
+        if let Some(content) = self.archive.read().await.get(doc_id).and_then(|revisions| revisions.get(&version)) {
+            return Some(content.clone());
+        }
+        let current = self.versions.read().await.get(doc_id)
+            .and_then(|h| h.last())
+            .map_or(false, |v| v.version == version && !v.deleted);
+        if current {
+            return self.content_cache.read().await.get(doc_id).cloned();
+        }
+        None
+    }
+
+    /// True when the last revision of `doc_id` is a soft-delete tombstone
+    pub async fn is_deleted(&self, doc_id: &str) -> bool {
+        self.versions.read().await.get(doc_id)
+            .and_then(|h| h.last())
+            .map_or(false, |v| v.deleted)
+    }
+
+    /// Keyword search over archived revisions
+    pub async fn history_search(&self, query: &str, top_k: usize, options: &LexicalOptions) -> Vec<HistoryHit> {
+        // Revisions are searched under their labels, which are unique per document and
+        // version; `revisions` maps each label back so it never has to be parsed
+        let mut documents = HashMap::new();
+        let mut revisions = HashMap::new();
+        for (doc_id, archived) in self.archive.read().await.iter() {
+            for (version, content) in archived {
+                let key = version_key(doc_id, *version);
+                documents.insert(key.clone(), content.clone());
+                revisions.insert(key, (doc_id.clone(), *version));
+            }
+        }
+        let mut languages = HashMap::new();
+        let mut collections = HashMap::new();
+        for (doc_id, history) in self.versions.read().await.iter() {
+            for v in history {
+                let key = version_key(doc_id, v.version);
+                if let Some(ref language) = v.language {
+                    languages.insert(key.clone(), language.clone());
+                }
+                if let Some(ref collection) = v.collection {
+                    collections.insert(key, collection.clone());
+                }
+            }
+        }
+        let doc_ids: HashMap<String, String> = revisions.iter().map(|(key, (doc_id, _))| (key.clone(), doc_id.clone())).collect();
+        self.keyword_search(&documents, &doc_ids, &languages, &collections, query, top_k, options)
+            .into_iter()
+            .filter_map(|hit| {
+                let (doc_id, version) = revisions.remove(&hit.doc_id)?;
+                Some(HistoryHit { doc_id, version, score: hit.score, content: hit.content })
+            })
+            .collect()
+    }
+
     pub async fn health(&self) -> Result<bool, String> {
         let url = format!("{}/health", self.base_url);
-        match self.client.get(&url).send().await {
+        match self.client.get(&url).timeout(crate::http_client::request_timeout()).send().await {
             Ok(resp) => Ok(resp.status().is_success()),
             Err(e) => Err(format!("Health check failed: {}", e)),
         }
     }
 
+    /// Barq collection holding the vectors of `collection`: the configured one for
+    /// `DEFAULT_COLLECTION`, `{configured}__{collection}` for the rest, so collections never
+    /// share an index
+    pub fn barq_collection(&self, collection: Option<&str>) -> String {
+        let generation = self.models.read().unwrap_or_else(|e| e.into_inner()).generation;
+        self.barq_collection_in(generation, collection)
+    }
+
+    fn barq_collection_in(&self, generation: u64, collection: Option<&str>) -> String {
+        let base = match generation {
+            0 => self.collection_name.clone(),
+            n => format!("{}_g{}", self.collection_name, n),
+        };
+        match collection.filter(|c| *c != DEFAULT_COLLECTION) {
+            Some(collection) => format!("{}__{}", base, collection),
+            None => base,
+        }
+    }
+
+    /// Model and dimension new vectors are produced with
+    pub fn current_model(&self) -> Option<EmbeddingStamp> {
+        self.embedder.as_ref().map(|e| EmbeddingStamp { model: e.model().to_string(), dimension: self.dimension })
+    }
+
+    /// Model recorded for a Barq collection; `None` until a vector has been written to it
+    pub fn collection_model(&self, barq_collection: &str) -> Option<EmbeddingStamp> {
+        self.models.read().unwrap_or_else(|e| e.into_inner()).collections.get(barq_collection).cloned()
+    }
+
+    /// Why `collection` can't take vectors from the current model, if it holds another's.
+    /// With no collection, any collection in use counts.
+    pub fn model_mismatch(&self, collection: Option<&str>) -> Option<String> {
+        let current = self.current_model()?;
+        let models = self.models.read().unwrap_or_else(|e| e.into_inner());
+        let mut stamped: Vec<(&String, &EmbeddingStamp)> = match collection {
+            Some(_) => {
+                let name = self.barq_collection_in(models.generation, collection);
+                models.collections.get_key_value(&name).into_iter().collect()
+            }
+            None => models.collections.iter().collect(),
+        };
+        stamped.sort_by(|a, b| a.0.cmp(b.0));
+        stamped.into_iter().find(|(_, stamp)| **stamp != current).map(|(name, stamp)| format!(
+            "Collection '{}' holds embeddings from {} ({} dimensions) but the configured model is {} ({} dimensions). \
+             Run POST /api/knowledge/reindex to re-embed the corpus.",
+            name, stamp.model, stamp.dimension, current.model, current.dimension
+        ))
+    }
+
+    /// Records the current model for `barq_collection` the first time a vector goes into it
+    fn stamp_collection(&self, barq_collection: &str) {
+        let current = match self.current_model() {
+            Some(current) => current,
+            None => return,
+        };
+        let mut models = self.models.write().unwrap_or_else(|e| e.into_inner());
+        if models.collections.contains_key(barq_collection) {
+            return;
+        }
+        models.collections.insert(barq_collection.to_string(), current);
+        self.save_models(&models);
+    }
+
+    fn save_models(&self, models: &CollectionModels) {
+        if let Ok(content) = serde_json::to_string(models) {
+            if let Err(e) = std::fs::write(format!("{}/collection_models.json", self.data_path), content) {
+                println!("WARN: Failed to save collection models: {}", e);
+            }
+        }
+    }
+
+    /// Claims the reindex slot, or fails if a reindex is already running. The slot is
+    /// freed when the `reindex_with` given the guard finishes.
+    pub fn begin_reindex(&self) -> Result<ReindexGuard, String> {
+        self.reindexing.clone().try_lock_owned()
+            .map(ReindexGuard)
+            .map_err(|_| "A reindex is already running".to_string())
+    }
+
+    /// `begin_reindex` followed by `reindex_with`
+    pub async fn reindex(&self) -> Result<ReindexReport, String> {
+        let running = self.begin_reindex()?;
+        self.reindex_with(running).await
+    }
+
+    /// Whether a reindex is running, and how the last one ended
+    pub fn reindex_status(&self) -> ReindexStatus {
+        let mut status = self.last_reindex.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        status.running = self.reindexing.try_lock().is_err();
+        status
+    }
+
+    /// Runs the reindex claimed by `running` (see `run_reindex`) and keeps its outcome for
+    /// `reindex_status`
+    pub async fn reindex_with(&self, running: ReindexGuard) -> Result<ReindexReport, String> {
+        let result = self.run_reindex().await;
+        {
+            let mut last = self.last_reindex.lock().unwrap_or_else(|e| e.into_inner());
+            last.last_report = result.as_ref().ok().cloned();
+            last.last_error = result.as_ref().err().cloned();
+        }
+        drop(running);
+        result
+    }
+
+    /// Re-embeds every live document with the current model into a fresh generation of Barq
+    /// collections, then switches searches and ingestion over to it and replaces the cached
+    /// vectors. Until the switch, searches keep using the old vectors. Documents written or
+    /// deleted while the corpus is re-embedded are caught up on just before the switch, with
+    /// ingestion paused. The previous collections stay in Barq and are listed in the report.
+    async fn run_reindex(&self) -> Result<ReindexReport, String> {
+        let embedder = self.embedder.clone().ok_or("No embedding client configured")?;
+        let current = self.current_model().ok_or("No embedding client configured")?;
+
+        let generation = self.models.read().unwrap_or_else(|e| e.into_inner()).generation + 1;
+        let snapshot = self.corpus_snapshot().await;
+        println!("INFO: Reindexing {} documents with {} into generation {}", snapshot.len(), current.model, generation);
+
+        let mut embeddings = HashMap::new();
+        let mut used = HashSet::from([self.barq_collection_in(generation, None)]);
+        let mut failed = Vec::new();
+        let mut documents: Vec<&String> = snapshot.keys().collect();
+        documents.sort();
+        for doc_id in documents {
+            let (content, collection) = &snapshot[doc_id];
+            self.reembed(embedder.as_ref(), generation, doc_id, content, collection.as_deref(), &mut embeddings, &mut used, &mut failed).await;
+        }
+
+        // Catch up on writes made since the snapshot with ingestion paused, so nothing lands
+        // in the old generation between here and the switch
+        let _paused = self.ingest_gate.write().await;
+        let latest = self.corpus_snapshot().await;
+        embeddings.retain(|doc_id, _| latest.contains_key(doc_id));
+        failed.retain(|doc_id| latest.contains_key(doc_id));
+        let mut changed: Vec<&String> = latest.iter()
+            .filter(|(doc_id, document)| snapshot.get(*doc_id) != Some(*document))
+            .map(|(doc_id, _)| doc_id)
+            .collect();
+        changed.sort();
+        if !changed.is_empty() {
+            println!("INFO: Reindex catching up on {} documents changed while it ran", changed.len());
+        }
+        for doc_id in changed {
+            let (content, collection) = &latest[doc_id];
+            embeddings.remove(doc_id);
+            failed.retain(|id| id != doc_id);
+            self.reembed(embedder.as_ref(), generation, doc_id, content, collection.as_deref(), &mut embeddings, &mut used, &mut failed).await;
+        }
+
+        let previous_collections = {
+            let mut models = self.models.write().unwrap_or_else(|e| e.into_inner());
+            let mut previous: Vec<String> = models.collections.keys().cloned().collect();
+            previous.sort();
+            models.generation = generation;
+            models.collections = used.iter().map(|name| (name.clone(), current.clone())).collect();
+            self.save_models(&models);
+            previous
+        };
+        let reindexed = embeddings.len();
+        *self.embedding_cache.write().await = embeddings;
+        self.save_embeddings().await;
+
+        let mut collections: Vec<String> = used.into_iter().collect();
+        collections.sort();
+        println!("INFO: Reindex done: {} documents in {:?}, {} failed", reindexed, collections, failed.len());
+        Ok(ReindexReport {
+            model: current.model,
+            dimension: current.dimension,
+            generation,
+            collections,
+            previous_collections,
+            reindexed,
+            failed,
+        })
+    }
+
+    /// Content and collection of every live document
+    async fn corpus_snapshot(&self) -> HashMap<String, (String, Option<String>)> {
+        let collections = self.document_collections().await;
+        self.content_cache.read().await.iter()
+            .map(|(doc_id, content)| (doc_id.clone(), (content.clone(), collections.get(doc_id).cloned())))
+            .collect()
+    }
+
+    /// Embeds one document into `generation` for a reindex, recording the vector or the failure
+    #[allow(clippy::too_many_arguments)]
+    async fn reembed(
+        &self,
+        embedder: &dyn EmbeddingProvider,
+        generation: u64,
+        doc_id: &str,
+        content: &str,
+        collection: Option<&str>,
+        embeddings: &mut HashMap<String, Vec<f32>>,
+        used: &mut HashSet<String>,
+        failed: &mut Vec<String>,
+    ) {
+        let embedding = match embedder.embed(content).await.map_err(String::from).and_then(|e| self.validate_embedding(&e).map(|_| e)) {
+            Ok(embedding) => embedding,
+            Err(e) => {
+                println!("WARN: Reindex could not embed '{}': {}", doc_id, e);
+                failed.push(doc_id.to_string());
+                return;
+            }
+        };
+        let barq_collection = self.barq_collection_in(generation, collection);
+        self.upsert_remote_to(&barq_collection, doc_id, content, collection, embedding.clone()).await;
+        used.insert(barq_collection);
+        embeddings.insert(doc_id.to_string(), embedding);
+    }
+
+    /// Barq collections known to exist, sorted
+    pub fn ready_collections(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.ready.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Creates the Barq collection behind `DEFAULT_COLLECTION`
     pub async fn ensure_collection(&self) -> Result<(), String> {
+        self.create_collection(&self.barq_collection(None)).await
+    }
+
+    /// Creates `name` the first time a document or search needs it
+    async fn ensure_barq_collection(&self, name: &str) -> Result<(), String> {
+        if self.ready.lock().unwrap_or_else(|e| e.into_inner()).contains(name) {
+            return Ok(());
+        }
+        self.create_collection(name).await
+    }
+
+    /// Creates a Barq collection. A 409 means it already exists and counts as success; any
+    /// other 4xx (a dimension or metric Barq rejects, bad credentials, ...) is returned with
+    /// the response body, and 5xx responses are retried before giving up.
+    async fn create_collection(&self, name: &str) -> Result<(), String> {
+        let metric = self.distance()?;
         let url = format!("{}/collections", self.base_url);
         let body = serde_json::json!({
-            "name": self.collection_name,
+            "name": name,
             "dimension": self.dimension,
-            "distance_metric": "cosine"
+            "distance_metric": metric.as_str()
         });
-        
-        match self.client.post(&url).json(&body).send().await {
-            Ok(resp) => {
-                if resp.status().is_success() || resp.status().as_u16() == 409 {
-                    println!("INFO: Collection '{}' ready", self.collection_name);
-                    Ok(())
-                } else {
-                    println!("WARN: Collection creation returned {}", resp.status());
-                    Ok(()) // Continue anyway
-                }
-            }
-            Err(e) => {
-                println!("WARN: Could not create collection: {}", e);
-                Ok(()) // Continue with local fallback
+
+        let (url, body) = (&url, &body);
+        retry_with_backoff(&self.retry, "Barq collection setup", || async move {
+            let resp = self.client.post(url).json(body).timeout(crate::http_client::request_timeout()).send().await
+                .map_err(|e| ProviderError::new(None, format!("Could not create collection: {}", e)))?;
+            let status = resp.status();
+            if status.is_success() || status.as_u16() == 409 {
+                return Ok(());
             }
-        }
+            let detail = resp.text().await.unwrap_or_default();
+            Err(ProviderError::new(
+                Some(status.as_u16()),
+                format!("Collection '{}' creation returned {}: {}", name, status, detail.trim()),
+            ))
+        })
+        .await
+        .map_err(|e| e.message)?;
+
+        self.ready.lock().unwrap_or_else(|e| e.into_inner()).insert(name.to_string());
+        println!("INFO: Collection '{}' ready", name);
+        Ok(())
+    }
+
+    pub async fn index_document(&self, doc_id: &str, content: &str) -> Result<IndexOutcome, String> {
+        self.index_document_in(doc_id, content, None).await
     }
 
-    pub async fn index_document(&self, doc_id: &str, content: &str) -> Result<(), String> {
-        // Generate embedding using Azure OpenAI
-        let embedding = if let Some(embedding_client) = AzureEmbeddingClient::new() {
-            match embedding_client.get_embedding(content).await {
+    /// Indexes a document whose text is in `language` (a name or ISO 639-1 code), which
+    /// selects the stopwords and stemmer lexical search applies to it
+    pub async fn index_document_in(&self, doc_id: &str, content: &str, language: Option<&str>) -> Result<IndexOutcome, String> {
+        self.index_document_into(doc_id, content, language, None).await
+    }
+
+    /// Like `index_document_in`, filing the document under `collection` for scoped search
+    pub async fn index_document_into(&self, doc_id: &str, content: &str, language: Option<&str>, collection: Option<&str>) -> Result<IndexOutcome, String> {
+        let _gate = self.ingest_gate.read().await;
+        let language = match language {
+            Some(name) => Some(normalize_language(name).ok_or_else(|| format!("Unsupported language '{}'", name))?),
+            None => None,
+        };
+        let collection = collection.filter(|c| *c != DEFAULT_COLLECTION);
+        let hash = content_hash(content);
+        if self.is_unchanged(doc_id, &hash, language, collection).await {
+            println!("INFO: Document '{}' unchanged, skipping re-embedding", doc_id);
+            return Ok(IndexOutcome::Unchanged);
+        }
+        // Vectors from two models in one collection can't be compared
+        if let Some(mismatch) = self.model_mismatch(collection) {
+            return Err(mismatch);
+        }
+
+        // Generate embedding using the configured provider
+        let embedding = if let Some(ref embedder) = self.embedder {
+            match embedder.embed(content).await {
                 Ok(emb) => emb,
                 Err(e) => {
                     println!("WARN: Embedding failed: {}. Storing locally only.", e);
                     // Store locally without Barq
-                    {
-                        let mut cache = self.content_cache.write().await;
-                        cache.insert(doc_id.to_string(), content.to_string());
-                    }
-                    self.save_cache().await;
-                    return Ok(());
+                    self.store_content(doc_id, content, &hash, language, collection).await;
+                    return Ok(IndexOutcome::Indexed);
                 }
             }
         } else {
             println!("WARN: No embedding client. Storing locally only.");
-            {
-                let mut cache = self.content_cache.write().await;
-                cache.insert(doc_id.to_string(), content.to_string());
+            if !self.store_content(doc_id, content, &hash, language, collection).await {
+                return Ok(IndexOutcome::Unchanged);
             }
-            self.save_cache().await;
-            return Ok(());
+            return Ok(IndexOutcome::Indexed);
         };
 
-        // Ensure collection exists
-        let _ = self.ensure_collection().await;
+        self.validate_embedding(&embedding)?;
+        self.stamp_collection(&self.barq_collection(collection));
 
-        // Try to insert into Barq via REST
-        let url = format!("{}/collections/{}/vectors", self.base_url, self.collection_name);
+        // A document refiled under another collection leaves its old vector behind otherwise
+        if self.embedding_cache.read().await.contains_key(doc_id) {
+            let previous = self.document_collection(doc_id).await;
+            if previous.as_deref() != collection {
+                self.delete_remote(doc_id, previous.as_deref()).await;
+            }
+        }
+
+        // Keep the vector locally so semantic search still works when Barq is down
+        self.log_embedding(doc_id, Some(embedding.clone())).await;
+
+        self.upsert_remote(doc_id, content, collection, embedding).await;
+
+        // Always cache content locally
+        self.store_content(doc_id, content, &hash, language, collection).await;
+        
+        Ok(IndexOutcome::Indexed)
+    }
+
+    /// Inserts a vector into Barq via REST, unless it is known to be down. Failures are
+    /// logged; the local caches keep the document searchable.
+    async fn upsert_remote(&self, doc_id: &str, content: &str, collection: Option<&str>, embedding: Vec<f32>) {
+        let barq_collection = self.barq_collection(collection);
+        self.upsert_remote_to(&barq_collection, doc_id, content, collection, embedding).await
+    }
+
+    async fn upsert_remote_to(&self, barq_collection: &str, doc_id: &str, content: &str, collection: Option<&str>, embedding: Vec<f32>) {
+        let url = format!("{}/collections/{}/vectors", self.base_url, barq_collection);
         let body = InsertRequest {
             id: doc_id.to_string(),
             vector: embedding,
-            payload: serde_json::json!({"content": content, "doc_id": doc_id}),
+            payload: serde_json::json!({
+                "content": content,
+                "doc_id": doc_id,
+                "collection": collection.unwrap_or(DEFAULT_COLLECTION),
+            }),
         };
 
-        match self.client.post(&url).json(&body).send().await {
+        let permit = match self.breaker.try_call() {
+            Some(permit) => permit,
+            None => {
+                println!("WARN: Barq circuit open; '{}' stored locally only", doc_id);
+                return;
+            }
+        };
+        if let Err(e) = self.ensure_barq_collection(barq_collection).await {
+            println!("WARN: {}", e);
+        }
+        match self.client.post(&url).json(&body).timeout(crate::http_client::request_timeout()).send().await {
+            // A 5xx means Barq itself is failing; a 4xx is a problem with this request
+            Ok(resp) if resp.status().is_server_error() => {
+                permit.failed();
+                println!("WARN: Barq insert returned {}", resp.status());
+            }
             Ok(resp) => {
+                permit.succeeded();
                 if resp.status().is_success() {
                     println!("INFO: Indexed document '{}' to Barq", doc_id);
                 } else {
@@ -158,62 +1330,387 @@ impl BarqVectorClient {
                 }
             }
             Err(e) => {
+                permit.failed();
                 println!("WARN: Barq insert failed: {}", e);
             }
         }
+    }
 
-        // Always cache content locally
-        {
-            let mut cache = self.content_cache.write().await;
-            cache.insert(doc_id.to_string(), content.to_string());
+    /// True when `doc_id` was already embedded and its current revision carries this
+    /// content hash, language and collection. Documents stored locally without a vector
+    /// are never treated as unchanged, so a later ingest can still embed them; neither are
+    /// deleted ones or those indexed before revisions recorded a hash.
+    async fn is_unchanged(&self, doc_id: &str, hash: &str, language: Option<&str>, collection: Option<&str>) -> bool {
+        if !self.embedding_cache.read().await.contains_key(doc_id) {
+            return false;
+        }
+        let versions = self.versions.read().await;
+        match versions.get(doc_id).and_then(|h| h.last()) {
+            Some(current) => !current.deleted
+                && current.content_hash == hash
+                && current.language.as_deref() == language
+                && current.collection.as_deref() == collection,
+            None => false,
         }
-        self.save_cache().await;
-        
-        Ok(())
     }
 
+    /// Nearest documents to the query's embedding. Phrase and field clauses are embedded as
+    /// plain text and limit which documents are ranked.
     pub async fn semantic_search(&self, query: &str, top_k: usize) -> Result<Vec<SearchHit>, String> {
-        // Use local BM25-style search since embeddings may not be available
-        self.local_search(query, top_k).await
+        self.semantic_search_in(query, top_k, None).await
+    }
+
+    /// Semantic search limited to the documents of `collection` when one is given. Falls
+    /// back to keyword matching when the query can't be embedded.
+    pub async fn semantic_search_in(&self, query: &str, top_k: usize, collection: Option<&str>) -> Result<Vec<SearchHit>, String> {
+        match self.dense_search_in(query, top_k, collection).await? {
+            Some(hits) => Ok(hits),
+            None => {
+                let fallback = LexicalOptions { collection: collection.map(String::from), ..LexicalOptions::default() };
+                self.local_search(query, top_k, &fallback).await
+            }
+        }
+    }
+
+    /// Nearest documents by embedding only; `None` when the query can't be embedded (no
+    /// embedding client, a filter-only query, or a provider failure)
+    pub async fn dense_search_in(&self, query: &str, top_k: usize, collection: Option<&str>) -> Result<Option<Vec<SearchHit>>, String> {
+        let embedder = match self.embedder {
+            Some(ref embedder) => embedder,
+            None => return Ok(None),
+        };
+        let parsed = parse_query(query);
+        let text = parsed.text();
+        if text.trim().is_empty() {
+            // Only filters (`id:`, `lang:`), nothing to embed
+            return Ok(None);
+        }
+        if let Some(mismatch) = self.model_mismatch(collection) {
+            println!("WARN: Skipping semantic search: {}", mismatch);
+            return Ok(None);
+        }
+
+        let query_vector = match embedder.embed(&text).await {
+            Ok(v) => v,
+            Err(e) => {
+                println!("WARN: Query embedding failed: {}", e);
+                return Ok(None);
+            }
+        };
+
+        if parsed.has_constraints() {
+            // Barq can't apply phrase and field clauses, and filtering its top_k afterwards
+            // loses matches ranked below the cut, so the documents meeting them are ranked
+            // on their local vectors instead
+            let matching = self.documents_satisfying(&parsed).await;
+            return Ok(Some(self.rank_local_vectors(&query_vector, top_k, collection, Some(&matching)).await));
+        }
+        let hits = match self.breaker.try_call() {
+            None => self.local_vector_search_in(&query_vector, top_k, collection).await,
+            Some(permit) => match self.remote_search(&query_vector, top_k, collection).await {
+                Ok(hits) => {
+                    permit.succeeded();
+                    hits
+                }
+                Err(e) => {
+                    permit.failed();
+                    println!("WARN: {}. Using local cosine fallback.", e);
+                    self.local_vector_search_in(&query_vector, top_k, collection).await
+                }
+            },
+        };
+        Ok(Some(hits))
+    }
+
+    /// Ids of the live documents meeting every phrase and field clause of `parsed`
+    async fn documents_satisfying(&self, parsed: &ParsedQuery) -> HashSet<String> {
+        let languages = self.document_languages().await;
+        self.content_cache.read().await.iter()
+            .filter(|(id, content)| self.satisfies(parsed, id, content, languages.get(*id).map(String::as_str)))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Language of each live document that was ingested with one
+    async fn document_languages(&self) -> HashMap<String, String> {
+        self.versions.read().await.iter()
+            .filter_map(|(doc_id, history)| history.last()?.language.clone().map(|language| (doc_id.clone(), language)))
+            .collect()
+    }
+
+    /// Collection of each live document filed under one other than `DEFAULT_COLLECTION`
+    pub async fn document_collections(&self) -> HashMap<String, String> {
+        self.versions.read().await.iter()
+            .filter_map(|(doc_id, history)| history.last()?.collection.clone().map(|collection| (doc_id.clone(), collection)))
+            .collect()
+    }
+
+    /// Collection the current revision of `doc_id` is filed under, if not the default
+    async fn document_collection(&self, doc_id: &str) -> Option<String> {
+        self.versions.read().await.get(doc_id)?.last()?.collection.clone()
+    }
+
+    /// Collection of a stored document, `DEFAULT_COLLECTION` when it has none;
+    /// `None` for unknown documents
+    pub async fn collection_of(&self, doc_id: &str) -> Option<String> {
+        let versions = self.versions.read().await;
+        let current = versions.get(doc_id)?.last()?;
+        Some(current.collection.clone().unwrap_or_else(|| DEFAULT_COLLECTION.to_string()))
+    }
+
+    /// True when the document meets every phrase and field clause of `parsed`. Phrases keep
+    /// their stopwords, so "to be or not to be" only matches that exact run of words.
+    fn satisfies(&self, parsed: &ParsedQuery, doc_id: &str, content: &str, language: Option<&str>) -> bool {
+        let analyzer = TextAnalyzer { remove_stopwords: false, ..(*self.analyzer).clone() };
+        let content_terms = analyzer.tokenize_in(content, language);
+        let phrases_match = parsed.phrases.iter()
+            .all(|phrase| contains_sequence(&content_terms, &analyzer.tokenize_in(phrase, language)));
+        phrases_match && parsed.fields.iter().all(|clause| match clause.field {
+            QueryField::Content => contains_sequence(&content_terms, &analyzer.tokenize_in(&clause.value, language)),
+            QueryField::Title => {
+                let title = content.lines().find(|line| !line.trim().is_empty()).unwrap_or("");
+                contains_sequence(&analyzer.tokenize_in(title, language), &analyzer.tokenize_in(&clause.value, language))
+            }
+            QueryField::DocId => doc_id.to_lowercase().contains(&clause.value.to_lowercase()),
+            QueryField::Language => {
+                normalize_language(&clause.value) == Some(language.unwrap_or(self.analyzer.default_language))
+            }
+        })
     }
 
-    async fn local_search(&self, query: &str, top_k: usize) -> Result<Vec<SearchHit>, String> {
+    /// Searches the Barq collection of `collection`, or every collection with live documents
+    /// when none is given, and merges the hits by score
+    async fn remote_search(&self, vector: &[f32], top_k: usize, collection: Option<&str>) -> Result<Vec<SearchHit>, String> {
+        let mut targets: Vec<(String, Option<serde_json::Value>)> = match collection {
+            Some(wanted) if wanted != DEFAULT_COLLECTION => vec![
+                (self.barq_collection(Some(wanted)), None),
+                // Vectors filed before collections had their own index are still in the shared one
+                (self.collection_name.clone(), Some(serde_json::json!({"collection": wanted}))),
+            ],
+            Some(wanted) => vec![(self.collection_name.clone(), Some(serde_json::json!({"collection": wanted})))],
+            None => vec![(self.collection_name.clone(), None)],
+        };
+        if collection.is_none() {
+            let named: HashSet<String> = self.document_collections().await.into_values().collect();
+            let mut named: Vec<String> = named.into_iter().collect();
+            named.sort();
+            targets.extend(named.iter().map(|c| (self.barq_collection(Some(c)), None)));
+        }
+
+        let mut results: HashMap<String, SearchResultItem> = HashMap::new();
+        for (barq_collection, filter) in targets {
+            let url = format!("{}/collections/{}/search", self.base_url, barq_collection);
+            let body = SearchRequest { vector: vector.to_vec(), top_k, filter };
+            let resp = self.client.post(&url).json(&body).timeout(crate::http_client::request_timeout()).send().await
+                .map_err(|e| format!("Barq search failed: {}", e))?;
+            if resp.status().as_u16() == 404 {
+                // Nothing has been written to this collection yet
+                continue;
+            }
+            if !resp.status().is_success() {
+                return Err(format!("Barq search in '{}' returned {}", barq_collection, resp.status()));
+            }
+            let parsed: SearchResponse = resp.json().await
+                .map_err(|e| format!("Barq search parse failed: {}", e))?;
+            for item in parsed.results {
+                match results.get(&item.id) {
+                    Some(existing) if existing.score >= item.score => {}
+                    _ => {
+                        results.insert(item.id.clone(), item);
+                    }
+                }
+            }
+        }
+
         let cache = self.content_cache.read().await;
-        let query_lower = query.to_lowercase();
-        let query_terms: Vec<&str> = query_lower.split_whitespace().collect();
-        
-        // Require at least 30% of terms to match for relevance
-        let min_score_threshold = 0.3;
+        let versions = self.versions.read().await;
+        let tombstoned = |id: &str| versions.get(id).and_then(|h| h.last()).map_or(false, |v| v.deleted);
+        // Vectors upserted before collections existed carry no payload field for the filter,
+        // and refiled documents may linger in their old index, so membership is re-checked
+        // against the local version history
+        let in_scope = |id: &str| collection.map_or(true, |wanted| {
+            let current = versions.get(id).and_then(|h| h.last()).and_then(|v| v.collection.as_deref());
+            current.unwrap_or(DEFAULT_COLLECTION) == wanted
+        });
+        let mut hits: Vec<SearchHit> = results.into_values().filter(|r| !tombstoned(&r.id) && in_scope(&r.id)).map(|r| {
+            let content = r.payload.as_ref()
+                .and_then(|p| p["content"].as_str().map(String::from))
+                .or_else(|| cache.get(&r.id).cloned());
+            SearchHit { doc_id: r.id, score: r.score, content }
+        }).collect();
+        hits.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal).then_with(|| a.doc_id.cmp(&b.doc_id)));
+        hits.truncate(top_k);
+        Ok(hits)
+    }
+
+    /// Cached embeddings for `doc_ids`
+    pub async fn cached_embeddings(&self, doc_ids: &[&str]) -> HashMap<String, Vec<f32>> {
+        let embeddings = self.embedding_cache.read().await;
+        doc_ids.iter()
+            .filter_map(|id| embeddings.get(*id).map(|v| (id.to_string(), v.clone())))
+            .collect()
+    }
+
+    /// Brute-force similarity over locally cached embeddings, using the collection's metric
+    /// (cosine when it is misconfigured)
+    pub async fn local_vector_search(&self, query_vector: &[f32], top_k: usize) -> Vec<SearchHit> {
+        self.local_vector_search_in(query_vector, top_k, None).await
+    }
+
+    /// `local_vector_search` over the documents of `collection` only, when one is given
+    pub async fn local_vector_search_in(&self, query_vector: &[f32], top_k: usize, collection: Option<&str>) -> Vec<SearchHit> {
+        self.rank_local_vectors(query_vector, top_k, collection, None).await
+    }
+
+    /// Cached vectors closest to `query_vector`, among the ids in `only` when given
+    async fn rank_local_vectors(&self, query_vector: &[f32], top_k: usize, collection: Option<&str>, only: Option<&HashSet<String>>) -> Vec<SearchHit> {
+        let metric = self.distance().unwrap_or_default();
+        let collections = match collection {
+            Some(_) => self.document_collections().await,
+            None => HashMap::new(),
+        };
+        let embeddings = self.embedding_cache.read().await;
+        let cache = self.content_cache.read().await;
+
+        let mut scored: Vec<(String, f32)> = embeddings.iter()
+            .filter(|(id, _)| collection.map_or(true, |wanted| in_collection(&collections, id, wanted)))
+            .filter(|(id, _)| only.map_or(true, |ids| ids.contains(*id)))
+            .map(|(id, vector)| (id.clone(), metric.similarity(query_vector, vector)))
+            .filter(|(id, score)| {
+                if !score.is_finite() {
+                    println!("WARN: Skipping {} in local vector search: similarity is {}", id, score);
+                }
+                score.is_finite() && *score > 0.0
+            })
+            .collect();
+        // Ties fall back to doc id so equal scores always come back in the same order
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal).then_with(|| a.0.cmp(&b.0)));
+
+        scored.into_iter()
+            .take(top_k)
+            .map(|(id, score)| SearchHit {
+                content: cache.get(&id).cloned(),
+                doc_id: id,
+                score,
+            })
+            .collect()
+    }
+
+    async fn local_search(&self, query: &str, top_k: usize, options: &LexicalOptions) -> Result<Vec<SearchHit>, String> {
+        let languages = self.document_languages().await;
+        let collections = self.document_collections().await;
+        let cache = self.content_cache.read().await;
+        Ok(self.keyword_search(&cache, &HashMap::new(), &languages, &collections, query, top_k, options))
+    }
+
+    /// Query terms analyzed in `language`, each with the analyzed forms of its abbreviation
+    /// expansions. Abbreviations are keyed by surface form, so they're looked up before stemming.
+    fn analyze_query(&self, query: &str, language: Option<&str>) -> (Vec<String>, Vec<Vec<Vec<String>>>) {
+        let terms = self.analyzer.tokenize_in(query, language);
+        let expansions = TextAnalyzer { stemming: false, ..(*self.analyzer).clone() }
+            .tokenize_in(query, language)
+            .iter()
+            .map(|word| self.abbreviations.expansions(word).iter().map(|full| self.analyzer.tokenize_in(full, language)).collect())
+            .collect();
+        (terms, expansions)
+    }
+
+    /// Term-overlap scoring of `documents` against `query`. Documents keyed by something
+    /// other than their id have it in `doc_ids`, for `id:` clauses and id matches. Each
+    /// document is analyzed in its entry in `languages`, and so is the query unless
+    /// `options` fixes its language. Documents failing a phrase or field clause of the
+    /// query, or outside the collection `options` asks for (per `collections`), are
+    /// skipped. A document must match `lexical_min_score` of the query terms; among those
+    /// that do, terms mentioned more often (for the document's length) score higher.
+    fn keyword_search(
+        &self,
+        documents: &HashMap<String, String>,
+        doc_ids: &HashMap<String, String>,
+        languages: &HashMap<String, String>,
+        collections: &HashMap<String, String>,
+        query: &str,
+        top_k: usize,
+        options: &LexicalOptions,
+    ) -> Vec<SearchHit> {
+        let parsed = parse_query(query);
+        let text = parsed.text();
+        let mut analyzed_queries: HashMap<Option<&str>, (Vec<String>, Vec<Vec<Vec<String>>>)> = HashMap::new();
         
-        let mut scored: Vec<(String, f32, String)> = cache
+        // Term counts of every candidate, and their average length for normalization
+        let candidates: Vec<(&String, &String, HashMap<String, usize>, usize)> = documents
             .iter()
+            .filter(|(id, _)| options.collection.as_deref().map_or(true, |wanted| in_collection(collections, id, wanted)))
+            .filter(|(id, content)| {
+                let doc_id = doc_ids.get(*id).map_or(id.as_str(), String::as_str);
+                !parsed.has_constraints() || self.satisfies(&parsed, doc_id, content, languages.get(*id).map(String::as_str))
+            })
             .map(|(id, content)| {
-                let content_lower = content.to_lowercase();
-                let id_lower = id.to_lowercase();
+                let terms = self.analyzer.index_terms_in(content, languages.get(id).map(String::as_str));
+                let len = terms.len();
+                let mut counts: HashMap<String, usize> = HashMap::new();
+                for term in terms {
+                    *counts.entry(term).or_insert(0) += 1;
+                }
+                (id, content, counts, len)
+            })
+            .collect();
+        let avg_len = candidates.iter().map(|(_, _, _, len)| *len).sum::<usize>() as f32 / candidates.len().max(1) as f32;
+
+        let mut scored: Vec<(String, f32, String)> = candidates
+            .into_iter()
+            .filter_map(|(id, content, content_terms, len)| {
+                let doc_language = languages.get(id).map(String::as_str);
+                let query_language = options.language.as_deref().or(doc_language);
+                let (query_terms, expansions) = &*analyzed_queries.entry(query_language)
+                    .or_insert_with(|| self.analyze_query(&text, query_language));
+                if query_terms.is_empty() {
+                    // Filters alone (`id:`, `lang:`): every document passing them matches fully
+                    return Some((id.clone(), 1.0, content.clone()));
+                }
+                let id_lower = doc_ids.get(id).unwrap_or(id).to_lowercase();
+                let single_mention = self.term_credit(1, len, avg_len);
                 
-                // Count term matches in content
-                let content_matches: usize = query_terms.iter()
-                    .filter(|term| content_lower.contains(*term))
-                    .count();
+                // Credit per term: full for a literal match, down-weighted if only an expansion
+                // or (when enabled) a near-miss spelling matches. `matched` decides whether the
+                // document is a hit at all; `weighted` ranks literal matches by frequency.
+                let (matched, weighted) = query_terms.iter()
+                    .enumerate()
+                    .map(|(i, term)| {
+                        if let Some(&tf) = content_terms.get(term) {
+                            return (1.0, self.term_credit(tf, len, avg_len));
+                        }
+                        let expanded = expansions.get(i).map_or(false, |forms| {
+                            forms.iter().any(|form| !form.is_empty() && form.iter().all(|t| content_terms.contains_key(t)))
+                        });
+                        let mut credit: f32 = if expanded { self.abbreviations.expansion_weight } else { 0.0 };
+                        if options.fuzzy && credit < FUZZY_MATCH_WEIGHT && content_terms.keys().any(|t| fuzzy_match(term, t)) {
+                            credit = FUZZY_MATCH_WEIGHT;
+                        }
+                        (credit, credit * single_mention)
+                    })
+                    .fold((0.0f32, 0.0f32), |(m, w), (cm, cw)| (m + cm, w + cw));
                 
                 // Boost if query matches document ID
-                let id_match_boost: f32 = if query_terms.iter().any(|term| id_lower.contains(*term)) {
+                let id_match_boost: f32 = if query_terms.iter().any(|term| id_lower.contains(term.as_str())) {
                     0.3
                 } else {
                     0.0
                 };
+                if matched / query_terms.len() as f32 + id_match_boost < self.lexical_min_score {
+                    return None;
+                }
                 
-                let base_score = content_matches as f32 / query_terms.len().max(1) as f32;
+                let base_score = weighted / query_terms.len() as f32;
                 let score = (base_score + id_match_boost).min(1.0);
                 
-                (id.clone(), score, content.clone())
+                Some((id.clone(), score, content.clone()))
             })
-            .filter(|(_, score, _)| *score >= min_score_threshold)
             .collect();
 
-        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        // Ties fall back to doc id so equal scores always come back in the same order
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal).then_with(|| a.0.cmp(&b.0)));
 
-        let results: Vec<SearchHit> = scored
+        scored
             .into_iter()
             .take(top_k)
             .map(|(id, score, content)| SearchHit {
@@ -221,13 +1718,108 @@ impl BarqVectorClient {
                 score,
                 content: Some(content),
             })
+            .collect()
+    }
+
+    pub async fn bm25_search(&self, query: &str, top_k: usize) -> Result<Vec<SearchHit>, String> {
+        self.local_search(query, top_k, &LexicalOptions::default()).await
+    }
+
+    pub async fn bm25_search_with(&self, query: &str, top_k: usize, options: &LexicalOptions) -> Result<Vec<SearchHit>, String> {
+        self.local_search(query, top_k, options).await
+    }
+
+    /// Builds the sparse index from the content cache if this is its first use, and adds
+    /// vectors for any cached document it is missing
+    async fn ensure_sparse_index(&self) {
+        let languages = self.document_languages().await;
+        let cache = self.content_cache.read().await;
+        let mut sparse = self.sparse.write().await;
+        let index = sparse.get_or_insert_with(SparseIndex::default);
+        if index.len() == cache.len() {
+            return;
+        }
+        let missing: Vec<(&String, SparseVector)> = cache.iter()
+            .filter(|(id, _)| !index.contains(id))
+            .map(|(id, content)| (id, term_counts(&self.analyzer.index_terms_in(content, languages.get(id).map(String::as_str)))))
             .collect();
+        for (doc_id, vector) in missing {
+            index.insert(doc_id, vector);
+        }
+    }
 
-        Ok(results)
+    /// Raw term weights for `text` analyzed in `language`: one per occurrence, abbreviation
+    /// expansions at the expansion weight and, with `fuzzy`, indexed terms within a few
+    /// typos of an unknown query term at `FUZZY_MATCH_WEIGHT`
+    fn sparse_query_terms(&self, index: &SparseIndex, text: &str, language: Option<&str>, fuzzy: bool) -> SparseVector {
+        let (terms, expansions) = self.analyze_query(text, language);
+        let mut weights = SparseVector::new();
+        let credit = |weights: &mut SparseVector, term: &str, weight: f32| {
+            let entry = weights.entry(term.to_string()).or_insert(0.0);
+            *entry = entry.max(weight);
+        };
+        for (i, term) in terms.iter().enumerate() {
+            *weights.entry(term.clone()).or_insert(0.0) += 1.0;
+            for form in expansions.get(i).into_iter().flatten() {
+                for expanded in form {
+                    credit(&mut weights, expanded, self.abbreviations.expansion_weight);
+                }
+            }
+            if fuzzy && !index.contains_term(term) {
+                for near in index.terms().filter(|t| fuzzy_match(term, t)) {
+                    credit(&mut weights, near, FUZZY_MATCH_WEIGHT);
+                }
+            }
+        }
+        weights
     }
 
-    pub async fn bm25_search(&self, query: &str, top_k: usize) -> Result<Vec<SearchHit>, String> {
-        self.local_search(query, top_k).await
+    /// Lexical search over stored sparse vectors: each query term's IDF weight times the
+    /// document's `term_credit` for it, summed into a score in [0, 1]. Honors phrase and field clauses and the
+    /// collection, language and fuzzy options like `bm25_search_with`; queries made only
+    /// of filters are answered by keyword matching.
+    pub async fn sparse_search_with(&self, query: &str, top_k: usize, options: &LexicalOptions) -> Result<Vec<SearchHit>, String> {
+        let parsed = parse_query(query);
+        let text = parsed.text();
+        if self.analyzer.tokenize_in(&text, options.language.as_deref()).is_empty() {
+            return self.local_search(query, top_k, options).await;
+        }
+        self.ensure_sparse_index().await;
+
+        let languages = self.document_languages().await;
+        let collections = self.document_collections().await;
+        let cache = self.content_cache.read().await;
+        let sparse = self.sparse.read().await;
+        let index = match sparse.as_ref() {
+            Some(index) => index,
+            None => return Ok(vec![]),
+        };
+
+        let mut query_vectors: HashMap<Option<&str>, SparseVector> = HashMap::new();
+        let avg_len = index.average_length();
+        let mut scored: Vec<(String, f32)> = index.documents()
+            .filter(|(id, _, _)| options.collection.as_deref().map_or(true, |wanted| in_collection(&collections, id, wanted)))
+            .filter_map(|(id, counts, len)| {
+                let doc_language = languages.get(id).map(String::as_str);
+                let query_language = options.language.as_deref().or(doc_language);
+                let query_vector = query_vectors.entry(query_language)
+                    .or_insert_with(|| index.query_vector(&self.sparse_query_terms(index, &text, query_language, options.fuzzy)));
+                let score: f32 = query_vector.iter()
+                    .filter_map(|(term, weight)| counts.get(term).map(|&tf| weight * self.term_credit(tf as usize, len as usize, avg_len)))
+                    .sum();
+                (score > 0.0).then(|| (id.clone(), score))
+            })
+            .filter(|(id, _)| !parsed.has_constraints() || cache.get(id).map_or(false, |content| {
+                self.satisfies(&parsed, id, content, languages.get(id).map(String::as_str))
+            }))
+            .collect();
+        // Ties fall back to doc id so equal scores always come back in the same order
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal).then_with(|| a.0.cmp(&b.0)));
+
+        Ok(scored.into_iter()
+            .take(top_k)
+            .map(|(id, score)| SearchHit { content: cache.get(&id).cloned(), doc_id: id, score })
+            .collect())
     }
 
     pub async fn get_document(&self, doc_id: &str) -> Option<SearchHit> {
@@ -244,6 +1836,31 @@ impl BarqVectorClient {
         cache.len()
     }
 
+    /// Corpus size from the local caches, read in place without copying content
+    pub async fn corpus_stats(&self) -> CorpusStats {
+        // Same lock order as `store_content`: content before versions
+        let cache = self.content_cache.read().await;
+        let versions = self.versions.read().await;
+        let collections: HashSet<&str> = cache.keys()
+            .map(|id| versions.get(id).and_then(|h| h.last()).and_then(|v| v.collection.as_deref()).unwrap_or(DEFAULT_COLLECTION))
+            .collect();
+        CorpusStats {
+            documents: cache.len(),
+            content_bytes: cache.values().map(String::len).sum(),
+            deleted_documents: versions.values()
+                .filter(|history| history.last().map_or(false, |v| v.deleted))
+                .count(),
+            collections: collections.len(),
+        }
+    }
+
+    /// Ids of all live documents, sorted
+    pub async fn document_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.content_cache.read().await.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
     pub async fn list_all_documents(&self) -> Vec<SearchHit> {
         let cache = self.content_cache.read().await;
         cache.iter().map(|(id, content)| SearchHit {
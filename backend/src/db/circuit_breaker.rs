@@ -0,0 +1,143 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Closed,
+    /// Calls are skipped until the instant passes
+    Open(Instant),
+    /// One probe call is in flight; its outcome closes or reopens the circuit
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: State,
+    consecutive_failures: u32,
+}
+
+/// Snapshot of a breaker for `/api/health`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CircuitStatus {
+    /// `closed`, `open` or `half_open`
+    pub state: &'static str,
+    pub consecutive_failures: u32,
+    /// Seconds until an open circuit lets a probe through
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_in_secs: Option<u64>,
+}
+
+/// Stops calling a failing dependency for a while.
+///
+/// After `failure_threshold` consecutive failures the circuit opens and `try_call` returns
+/// `None` for `cooldown`, so callers go straight to their fallback. The first call after
+/// the cooldown is a probe: success closes the circuit, failure reopens it.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            inner: Mutex::new(Inner { state: State::Closed, consecutive_failures: 0 }),
+        }
+    }
+
+    /// Reads `{prefix}_CIRCUIT_FAILURES` (default 5) and `{prefix}_CIRCUIT_COOLDOWN_SECS` (default 30)
+    pub fn from_env(prefix: &str) -> Self {
+        let read = |name: String, default: u64| std::env::var(name).ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(default);
+        Self::new(
+            read(format!("{}_CIRCUIT_FAILURES", prefix), 5) as u32,
+            Duration::from_secs(read(format!("{}_CIRCUIT_COOLDOWN_SECS", prefix), 30)),
+        )
+    }
+
+    /// A permit to attempt the call now, or `None` to use the fallback. Once the cooldown
+    /// has passed, the first caller gets through as the probe and others keep using the
+    /// fallback until it reports back.
+    pub fn try_call(&self) -> Option<CallPermit<'_>> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let allowed = match inner.state {
+            State::Closed => true,
+            State::Open(until) if Instant::now() >= until => {
+                inner.state = State::HalfOpen;
+                true
+            }
+            State::Open(_) | State::HalfOpen => false,
+        };
+        allowed.then(|| CallPermit { breaker: self, settled: false })
+    }
+
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if inner.state != State::Closed {
+            println!("INFO: Circuit closed after successful probe");
+        }
+        inner.state = State::Closed;
+        inner.consecutive_failures = 0;
+    }
+
+    pub fn record_failure(&self) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.consecutive_failures += 1;
+        let trip = match inner.state {
+            State::HalfOpen => true,
+            State::Closed => inner.consecutive_failures >= self.failure_threshold,
+            State::Open(_) => false,
+        };
+        if trip {
+            println!(
+                "WARN: Circuit opened after {} consecutive failures; retrying in {}s",
+                inner.consecutive_failures, self.cooldown.as_secs()
+            );
+            inner.state = State::Open(Instant::now() + self.cooldown);
+        }
+    }
+
+    pub fn status(&self) -> CircuitStatus {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let (state, retry_in_secs) = match inner.state {
+            State::Closed => ("closed", None),
+            State::Open(until) => ("open", Some(until.saturating_duration_since(Instant::now()).as_secs())),
+            State::HalfOpen => ("half_open", None),
+        };
+        CircuitStatus { state, consecutive_failures: inner.consecutive_failures, retry_in_secs }
+    }
+}
+
+/// One call let through by `CircuitBreaker::try_call`. Report how it went with `succeeded`
+/// or `failed`; a permit dropped without either (the call was cancelled or panicked) counts
+/// as a failure, so an abandoned half-open probe can't leave the circuit stuck.
+#[must_use = "report the call's outcome with `succeeded` or `failed`"]
+pub struct CallPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    settled: bool,
+}
+
+impl CallPermit<'_> {
+    pub fn succeeded(mut self) {
+        self.settled = true;
+        self.breaker.record_success();
+    }
+
+    pub fn failed(mut self) {
+        self.settled = true;
+        self.breaker.record_failure();
+    }
+}
+
+impl Drop for CallPermit<'_> {
+    fn drop(&mut self) {
+        if !self.settled {
+            self.breaker.record_failure();
+        }
+    }
+}
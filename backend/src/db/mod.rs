@@ -1,2 +1,3 @@
 pub mod barq_vector;
 pub mod barq_graph;
+pub mod circuit_breaker;
@@ -0,0 +1,144 @@
+use crate::api::middleware::request_id::current_request_id;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde::Serialize;
+use std::fmt;
+use utoipa::ToSchema;
+
+/// Application error carrying the category needed to pick an HTTP status.
+///
+/// Handlers can return `Result<_, BrainVaultError>` directly; the status code and a
+/// JSON body of the form `{"error": "<kind>", "message": "..."}` are derived from the variant.
+/// Inside a request the body also carries its `request_id`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BrainVaultError {
+    /// The requested task, document, entity or user does not exist
+    NotFound(String),
+    /// The caller lacks permission for the resource
+    Unauthorized(String),
+    /// A dependency (vector DB, graph DB, LLM provider) failed or is unreachable
+    Upstream(String),
+    /// The request itself is invalid
+    BadRequest(String),
+    /// The request conflicts with the resource's current state
+    Conflict(String),
+    /// The request or a document in it exceeds a configured size limit
+    PayloadTooLarge(String),
+    /// The server is temporarily unable to take the request, e.g. a full queue
+    Unavailable(String),
+    /// The request ran out of its time budget before producing anything useful
+    Timeout(String),
+    Internal(String),
+}
+
+pub type Result<T> = std::result::Result<T, BrainVaultError>;
+
+impl BrainVaultError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BrainVaultError::NotFound(_) => "not_found",
+            BrainVaultError::Unauthorized(_) => "unauthorized",
+            BrainVaultError::Upstream(_) => "upstream",
+            BrainVaultError::BadRequest(_) => "bad_request",
+            BrainVaultError::Conflict(_) => "conflict",
+            BrainVaultError::PayloadTooLarge(_) => "payload_too_large",
+            BrainVaultError::Unavailable(_) => "unavailable",
+            BrainVaultError::Timeout(_) => "timeout",
+            BrainVaultError::Internal(_) => "internal",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            BrainVaultError::NotFound(m)
+            | BrainVaultError::Unauthorized(m)
+            | BrainVaultError::Upstream(m)
+            | BrainVaultError::BadRequest(m)
+            | BrainVaultError::Conflict(m)
+            | BrainVaultError::PayloadTooLarge(m)
+            | BrainVaultError::Unavailable(m)
+            | BrainVaultError::Timeout(m)
+            | BrainVaultError::Internal(m) => m,
+        }
+    }
+}
+
+impl fmt::Display for BrainVaultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BrainVaultError::NotFound(m) => write!(f, "Not found: {}", m),
+            BrainVaultError::Unauthorized(m) => write!(f, "Unauthorized: {}", m),
+            BrainVaultError::Upstream(m) => write!(f, "Upstream error: {}", m),
+            BrainVaultError::BadRequest(m) => write!(f, "Bad request: {}", m),
+            BrainVaultError::Conflict(m) => write!(f, "Conflict: {}", m),
+            BrainVaultError::PayloadTooLarge(m) => write!(f, "Payload too large: {}", m),
+            BrainVaultError::Unavailable(m) => write!(f, "Unavailable: {}", m),
+            BrainVaultError::Timeout(m) => write!(f, "Timed out: {}", m),
+            BrainVaultError::Internal(m) => write!(f, "Internal error: {}", m),
+        }
+    }
+}
+
+impl std::error::Error for BrainVaultError {}
+
+/// JSON body of every error response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    /// Error category, e.g. `not_found`
+    pub error: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl ResponseError for BrainVaultError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            BrainVaultError::NotFound(_) => StatusCode::NOT_FOUND,
+            BrainVaultError::Unauthorized(_) => StatusCode::FORBIDDEN,
+            BrainVaultError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            BrainVaultError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            BrainVaultError::Conflict(_) => StatusCode::CONFLICT,
+            BrainVaultError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            BrainVaultError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            BrainVaultError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            BrainVaultError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ErrorBody {
+            error: self.kind().to_string(),
+            message: self.message().to_string(),
+            // Lets a caller quote the id that ties this failure to the server logs
+            request_id: current_request_id(),
+        })
+    }
+}
+
+/// Untyped errors from modules still returning `String` are treated as internal
+impl From<String> for BrainVaultError {
+    fn from(message: String) -> Self {
+        BrainVaultError::Internal(message)
+    }
+}
+
+impl From<reqwest::Error> for BrainVaultError {
+    fn from(e: reqwest::Error) -> Self {
+        BrainVaultError::Upstream(e.to_string())
+    }
+}
+
+/// Serializing our own data is a server fault. Request bodies that don't parse are
+/// rejected by the JSON extractor, or mapped to `BadRequest` explicitly where parsed by hand.
+impl From<serde_json::Error> for BrainVaultError {
+    fn from(e: serde_json::Error) -> Self {
+        BrainVaultError::Internal(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for BrainVaultError {
+    fn from(e: std::io::Error) -> Self {
+        BrainVaultError::Internal(e.to_string())
+    }
+}
@@ -0,0 +1,95 @@
+//! The outbound HTTP client shared by the Barq, LLM and embedding clients.
+//!
+//! One pooled `reqwest::Client` is built on first use with a connect timeout. It has no
+//! overall timeout, which would also cut off long streamed completions: plain calls add
+//! `request_timeout()` per request, and streams give up after `read_timeout()` without
+//! data. Clients take it by default; `reqwest::Client` is reference-counted, so clones
+//! share the pool.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Timeouts and pool sizing for outbound requests
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpClientSettings {
+    /// Time allowed to establish a connection (`HTTP_CONNECT_TIMEOUT_SECS`, default 5)
+    pub connect_timeout: Duration,
+    /// Time allowed for a whole non-streaming request including the response body
+    /// (`HTTP_TIMEOUT_SECS`, default 120, long enough for slow LLM completions)
+    pub request_timeout: Duration,
+    /// Longest wait for the next chunk of a streamed response (`HTTP_READ_TIMEOUT_SECS`,
+    /// default 60)
+    pub read_timeout: Duration,
+    /// Idle keep-alive connections kept per host (`HTTP_POOL_MAX_IDLE_PER_HOST`, default 16)
+    pub pool_max_idle_per_host: usize,
+    /// How long an idle pooled connection is kept (`HTTP_POOL_IDLE_TIMEOUT_SECS`, default 90)
+    pub pool_idle_timeout: Duration,
+}
+
+impl Default for HttpClientSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(5),
+            request_timeout: Duration::from_secs(120),
+            read_timeout: Duration::from_secs(60),
+            pool_max_idle_per_host: 16,
+            pool_idle_timeout: Duration::from_secs(90),
+        }
+    }
+}
+
+impl HttpClientSettings {
+    pub fn from_env() -> Self {
+        let secs = |name: &str, default: Duration| std::env::var(name).ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|s| *s > 0)
+            .map(Duration::from_secs)
+            .unwrap_or(default);
+        let defaults = Self::default();
+        Self {
+            connect_timeout: secs("HTTP_CONNECT_TIMEOUT_SECS", defaults.connect_timeout),
+            request_timeout: secs("HTTP_TIMEOUT_SECS", defaults.request_timeout),
+            read_timeout: secs("HTTP_READ_TIMEOUT_SECS", defaults.read_timeout),
+            pool_max_idle_per_host: std::env::var("HTTP_POOL_MAX_IDLE_PER_HOST").ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.pool_max_idle_per_host),
+            pool_idle_timeout: secs("HTTP_POOL_IDLE_TIMEOUT_SECS", defaults.pool_idle_timeout),
+        }
+    }
+
+    pub fn build(&self) -> reqwest::Client {
+        reqwest::Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .tcp_keepalive(Duration::from_secs(60))
+            .build()
+            .unwrap_or_else(|e| {
+                println!("WARN: Could not build HTTP client ({}); using defaults without timeouts", e);
+                reqwest::Client::new()
+            })
+    }
+}
+
+static SETTINGS: OnceLock<HttpClientSettings> = OnceLock::new();
+static SHARED: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// Settings from the environment, read on first use
+pub fn settings() -> &'static HttpClientSettings {
+    SETTINGS.get_or_init(HttpClientSettings::from_env)
+}
+
+/// The process-wide client, built from `settings()` on first use
+pub fn shared() -> reqwest::Client {
+    SHARED.get_or_init(|| settings().build()).clone()
+}
+
+/// Whole-request timeout for non-streaming calls through the shared client
+pub fn request_timeout() -> Duration {
+    settings().request_timeout
+}
+
+/// Longest gap between chunks before a streamed response is abandoned
+pub fn read_timeout() -> Duration {
+    settings().read_timeout
+}
@@ -1,3 +1,6 @@
 pub mod api;
+pub mod config;
 pub mod core;
 pub mod db;
+pub mod error;
+pub mod http_client;
@@ -1,43 +1,70 @@
 use actix_web::{web, App, HttpServer};
+use brainvault_backend::config::AppConfig;
+use brainvault_backend::api::middleware::rate_limit::{rate_limit, RateLimiter};
+use brainvault_backend::api::middleware::request_id::request_id;
 use brainvault_backend::api::handlers::{knowledge, agents, security};
+use brainvault_backend::api::openapi::{ApiDoc, OPENAPI_PATH};
 use brainvault_backend::core::audit_manager::AuditManager;
 use brainvault_backend::core::search_engine::{HybridSearchEngine, SearchWeights};
 use brainvault_backend::core::graph_manager::KnowledgeGraphManager;
 use brainvault_backend::core::rbac::{RBAC, Role, Permission};
 use brainvault_backend::core::agent_orchestrator::{AgentOrchestrator, AgentProfile, AgentType};
+use brainvault_backend::core::ingest_queue::IngestQueue;
+use brainvault_backend::core::search_history::SearchHistory;
+use brainvault_backend::core::feedback::FeedbackStore;
 use brainvault_backend::db::barq_vector::BarqVectorClient;
 use brainvault_backend::db::barq_graph::BarqGraphClient;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    println!("Starting BrainVault API on 0.0.0.0:8080");
+    // Load and validate configuration before touching anything else
+    let config = match AppConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            std::process::exit(1);
+        }
+    };
+    println!("Starting BrainVault API on {}", config.bind_address);
+    println!("INFO: LLM provider '{}' with model '{}'", config.llm_provider, config.llm_model);
 
     // Initialize dependencies
-    let vector_client = BarqVectorClient::new();
-    let graph_client = BarqGraphClient::new();
+    let vector_client = BarqVectorClient::connect(&config.vector_db_url, &config.data_path);
+    let graph_client = BarqGraphClient::connect(&config.graph_db_url);
     
     let search_engine = HybridSearchEngine::new(
         vector_client, 
         SearchWeights { vector_weight: 0.7, bm25_weight: 0.3 }
-    );
+    ).with_feedback(FeedbackStore::at_path(&config.data_path));
     
     let graph_manager = KnowledgeGraphManager::new(graph_client);
     
-    // Initialize RBAC with default admin
-    let mut rbac = RBAC::new();
-    rbac.add_permission(Permission {
-        user_id: "admin".to_string(),
-        role: Role::Admin,
-        accessible_entities: vec![],
-        accessible_collections: vec![],
-    });
+    // Initialize RBAC from DATA_PATH/rbac.json (users, groups and group grants),
+    // with a default admin
+    let mut rbac = RBAC::at_path(&config.data_path);
+    if !rbac.has_permission("admin") {
+        rbac.add_permission(Permission {
+            user_id: "admin".to_string(),
+            role: Role::Admin,
+            accessible_entities: vec![],
+            accessible_collections: vec![],
+            excluded_entities: vec![],
+            expires_at: None,
+        });
+    }
     // Add a default viewer for testing
-    rbac.add_permission(Permission {
-        user_id: "viewer".to_string(),
-        role: Role::Viewer,
-        accessible_entities: vec!["doc-001".to_string(), "quantum-comp".to_string()], 
-        accessible_collections: vec![],
-    });
+    if !rbac.has_permission("viewer") {
+        rbac.add_permission(Permission {
+            user_id: "viewer".to_string(),
+            role: Role::Viewer,
+            accessible_entities: vec!["doc-001".to_string(), "quantum-comp".to_string()], 
+            accessible_collections: vec![],
+            excluded_entities: vec![],
+            expires_at: None,
+        });
+    }
 
     // Initialize Agent Orchestrator with tools
     // We wrap search_engine and graph_manager in Arc for orchestrator
@@ -49,9 +76,25 @@ async fn main() -> std::io::Result<()> {
     // Or wrap in Arc first.
     
     let search_arc = std::sync::Arc::new(search_engine);
+
+    // Optional warm-up so the first search after a deploy doesn't pay for setup
+    if config.vector_warmup {
+        search_arc.vector_db.spawn_warm_up(config.vector_warmup_reembed);
+    }
     let graph_arc = std::sync::Arc::new(graph_manager);
     
-    let orchestrator = AgentOrchestrator::new(Some(search_arc.clone()), Some(graph_arc.clone()));
+    // Initialize Audit Manager (shared with the orchestrator so task events are audited)
+    let audit_manager = AuditManager::at_path(&config.data_path);
+
+    // Background worker for ingestion requests sent with "background": true
+    let ingest_queue = IngestQueue::from_env(search_arc.clone());
+
+    // Shared with the orchestrator so agent tools only return what the submitter may read
+    let rbac_arc = std::sync::Arc::new(rbac);
+
+    let orchestrator = AgentOrchestrator::new(Some(search_arc.clone()), Some(graph_arc.clone()))
+        .with_audit(audit_manager.clone())
+        .with_rbac(rbac_arc.clone());
     
     // Register a default agent
     // Register Agent Swarm
@@ -69,6 +112,10 @@ async fn main() -> std::io::Result<()> {
             name: name.to_string(),
             agent_type: atype,
             capabilities: vec!["general".to_string()],
+            system_prompt: None,
+            max_tokens: None,
+            temperature: None,
+            research: Default::default(),
         }).await;
     }
     
@@ -83,43 +130,92 @@ async fn main() -> std::io::Result<()> {
     // web::Data::from(search_arc) works if we want to share the Arc.
     let search_data = web::Data::from(search_arc);
     let graph_data = web::Data::from(graph_arc);
-    let rbac_data = web::Data::new(rbac);
+    let rbac_data = web::Data::from(rbac_arc);
     let orch_data = web::Data::new(orchestrator);
 
-    // Initialize Audit Manager
-    let audit_manager = AuditManager::new();
     let audit_data = web::Data::new(audit_manager);
+    let ingest_queue_data = web::Data::new(ingest_queue);
+    let history_data = web::Data::new(SearchHistory::at_path(&config.data_path));
+    let pending_history = history_data.clone();
+    let bind_address = config.bind_address;
+    let config_data = web::Data::new(config);
+    let limiter_data = web::Data::new(RateLimiter::from_env());
 
     HttpServer::new(move || {
-        let cors = actix_cors::Cors::permissive(); // For dev phase only
-
         App::new()
-            .wrap(cors)
-            .app_data(web::JsonConfig::default().limit(52428800)) // 50MB limit
+            .wrap(actix_web::middleware::from_fn(rate_limit))
+            .wrap(actix_web::middleware::from_fn(request_id))
+            .wrap(config_data.cors())
+            .app_data(config_data.json_config())
+            .app_data(config_data.clone())
+            .app_data(limiter_data.clone())
             .app_data(search_data.clone())
             .app_data(graph_data.clone())
             .app_data(rbac_data.clone())
             .app_data(orch_data.clone())
             .app_data(audit_data.clone())
+            .app_data(ingest_queue_data.clone())
+            .app_data(history_data.clone())
             .service(knowledge::health_check)
+            .service(knowledge::readiness_check)
             .service(knowledge::ingest_knowledge)
+            .service(knowledge::get_ingest_job)
             .service(knowledge::hybrid_search)
+            .service(knowledge::search_feedback)
+            .service(knowledge::get_search_history)
+            .service(knowledge::clear_search_history)
+            .service(knowledge::update_search_history_settings)
+            .service(knowledge::replay_search)
+            .service(knowledge::list_saved_searches)
+            .service(knowledge::save_search)
+            .service(knowledge::delete_saved_search)
+            .service(knowledge::run_saved_search)
             .service(knowledge::get_context)
+            .service(knowledge::find_graph_path)
+            .service(knowledge::export_graph)
+            .service(knowledge::suggest_duplicates)
+            .service(knowledge::merge_entities)
+            .service(knowledge::update_entity)
+            .service(knowledge::list_document_versions)
+            .service(knowledge::delete_document)
+            .service(knowledge::export_documents)
+            .service(knowledge::import_documents)
+            .service(knowledge::reindex_corpus)
+            .service(knowledge::get_reindex_status)
             .service(knowledge::seed_test_data)
             .service(knowledge::chat_with_knowledge)
+            .service(knowledge::ask_question)
             .service(knowledge::get_knowledge_stats)
             .service(knowledge::list_documents)
             .service(knowledge::get_graph_data)
             .service(knowledge::get_document)
             .service(knowledge::list_all_documents)
+            // After the fixed /api/knowledge/* paths so they aren't read as document ids
+            .service(knowledge::get_knowledge_document)
             .service(agents::submit_task)
             .service(agents::get_task_status)
+            .service(agents::get_task_result)
+            .service(agents::get_task_audit)
+            .service(agents::cancel_task)
             .service(agents::get_stats)
             .service(agents::get_all_tasks)
             .service(agents::register_agent)
+            .service(agents::list_agents)
+            .service(agents::deregister_agent)
+            .service(agents::get_usage)
+            .service(agents::agent_chat)
             .service(security::get_security_logs)
+            .service(security::verify_security_logs)
+            .service(security::get_denial_risk)
+            .service(security::check_entity_access)
+            .service(security::explain_entity_access)
+            .service(SwaggerUi::new("/api/docs/{_:.*}").url(OPENAPI_PATH, ApiDoc::openapi()))
     })
-    .bind(("0.0.0.0", 8080))?
+    .bind(bind_address)?
     .run()
-    .await
+    .await?;
+
+    // Searches made just before shutdown are still waiting to be written
+    pending_history.flush().await;
+    Ok(())
 }
@@ -0,0 +1,18 @@
+use brainvault_backend::core::abbreviations::AbbreviationMap;
+use brainvault_backend::db::barq_vector::BarqVectorClient;
+use std::collections::HashMap;
+
+#[tokio::test]
+async fn test_acronym_query_matches_expanded_form() {
+    let abbreviations = AbbreviationMap::new(HashMap::from([
+        ("K8s".to_string(), vec!["Kubernetes".to_string()]),
+    ]));
+    let client = BarqVectorClient::new().with_abbreviations(abbreviations);
+    client.index_document("abbr-doc-orchestration", "Kubernetes orchestrates containerized workloads.").await.unwrap();
+
+    let hits = client.bm25_search("k8s", 5).await.unwrap();
+    let hit = hits.iter().find(|h| h.doc_id == "abbr-doc-orchestration").expect("expanded form should match");
+
+    // Expansion matches count for less than a literal match
+    assert!(hit.score < 1.0);
+}
@@ -0,0 +1,155 @@
+use brainvault_backend::core::audit_manager::{AuditManager, AuditQuery, EventKind, Severity};
+use std::collections::HashMap;
+
+fn temp_data_path() -> String {
+    let dir = std::env::temp_dir().join(format!("brainvault-audit-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.to_string_lossy().to_string()
+}
+
+#[tokio::test]
+async fn test_query_logs_filters_by_user_and_pages() {
+    let audit = AuditManager::at_path(&temp_data_path());
+    for i in 0..5 {
+        audit.record(EventKind::Query, Severity::Low, "Search", "alice", &format!("q{}", i), HashMap::new()).await;
+    }
+    audit.record(EventKind::Ingest, Severity::Medium, "Ingest", "bob", "ok", HashMap::new()).await;
+
+    let query = AuditQuery { user: Some("alice".to_string()), limit: Some(2), offset: 1, ..Default::default() };
+    let page = audit.query_logs(&query).await;
+
+    assert_eq!(page.len(), 2);
+    assert!(page.iter().all(|l| l.user == "alice"));
+    assert_eq!(page[0].status, "q3");
+}
+
+#[tokio::test]
+async fn test_overflow_is_archived_and_still_queryable() {
+    let data_path = temp_data_path();
+    let audit = AuditManager::at_path(&data_path).with_max_entries(2);
+    for i in 0..4 {
+        audit.record(EventKind::Query, Severity::Low, "Search", "carol", &format!("q{}", i), HashMap::new()).await;
+    }
+
+    assert_eq!(audit.get_logs(None, None).await.len(), 2);
+    assert!(std::fs::read_dir(format!("{}/audit_archive", data_path)).unwrap().count() > 0);
+
+    let all = audit.query_logs(&AuditQuery { since: Some(0), ..Default::default() }).await;
+    assert_eq!(all.len(), 4);
+    assert_eq!(all[0].status, "q3");
+}
+
+#[tokio::test]
+async fn test_verify_chain_detects_tampering() {
+    let data_path = temp_data_path();
+    let audit = AuditManager::at_path(&data_path).with_max_entries(2);
+    for i in 0..4 {
+        audit.record(EventKind::Ingest, Severity::Medium, "Ingest", "dave", &format!("doc{}", i), HashMap::new()).await;
+    }
+    let verification = audit.verify_chain().await;
+    assert!(verification.valid);
+    assert_eq!(verification.entries_checked, 4);
+
+    // Edit an entry on disk and reload
+    let log_file = format!("{}/audit_logs.json", data_path);
+    let tampered = std::fs::read_to_string(&log_file).unwrap().replacen("\"dave\"", "\"mallory\"", 1);
+    std::fs::write(&log_file, tampered).unwrap();
+
+    let reloaded = AuditManager::at_path(&data_path);
+    let verification = reloaded.verify_chain().await;
+    assert!(!verification.valid);
+    assert!(verification.broken_at.is_some());
+}
+
+#[tokio::test]
+async fn test_repeated_denials_escalate_risk() {
+    let audit = AuditManager::at_path(&temp_data_path());
+    let mut severities = vec![];
+    for i in 0..10 {
+        let doc = format!("doc{}", i);
+        severities.push(audit.record_denial(Severity::Medium, "Document Read Denied", "mallory", &doc, HashMap::new()).await);
+    }
+    audit.record_denial(Severity::Medium, "Document Read Denied", "bob", "doc1", HashMap::new()).await;
+
+    assert_eq!(severities[0], Severity::Medium);
+    assert_eq!(severities[2], Severity::High);
+    assert_eq!(severities[9], Severity::Critical);
+
+    let denied = audit.get_logs(Some(Severity::Critical), Some(EventKind::AccessDenied)).await;
+    assert_eq!(denied.len(), 1);
+    assert_eq!(denied[0].details.get("resource").map(String::as_str), Some("doc9"));
+    assert_eq!(denied[0].details.get("denials_in_window").map(String::as_str), Some("10"));
+
+    let summary = audit.denial_summary().await;
+    assert_eq!(summary[0].user, "mallory");
+    assert_eq!(summary[0].risk, Severity::Critical);
+    assert_eq!(summary[1].user, "bob");
+    assert_eq!(summary[1].denials, 1);
+}
+
+#[actix_web::test]
+async fn test_denial_risk_report_is_admin_only() {
+    use actix_web::{test, web, App};
+    use brainvault_backend::api::handlers::security::get_denial_risk;
+    use brainvault_backend::core::rbac::{Permission, Role, RBAC};
+
+    let audit = AuditManager::at_path(&temp_data_path());
+    audit.record_denial(Severity::Medium, "Document Read Denied", "mallory", "doc1", HashMap::new()).await;
+    let mut rbac = RBAC::new();
+    for (user, role) in [("root", Role::Admin), ("mallory", Role::Viewer)] {
+        rbac.add_permission(Permission {
+            user_id: user.to_string(),
+            role,
+            accessible_entities: vec![],
+            accessible_collections: vec![],
+            excluded_entities: vec![],
+            expires_at: None,
+        });
+    }
+    let app = test::init_service(App::new()
+        .app_data(web::Data::new(audit))
+        .app_data(web::Data::new(rbac))
+        .service(get_denial_risk)).await;
+
+    let req = test::TestRequest::get().uri("/api/security/risk").insert_header(("X-User-ID", "mallory")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+
+    let req = test::TestRequest::get().uri("/api/security/risk").insert_header(("X-User-ID", "root")).to_request();
+    let summary: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(summary[0]["user"], "mallory");
+    assert_eq!(summary[0]["denials"], 2);
+}
+
+#[actix_web::test]
+async fn test_security_logs_are_admin_only() {
+    use actix_web::{test, web, App};
+    use brainvault_backend::api::handlers::security::get_security_logs;
+    use brainvault_backend::core::rbac::{Permission, Role, RBAC};
+
+    let audit = AuditManager::at_path(&temp_data_path());
+    audit.record(EventKind::Query, Severity::Low, "Ask Question", "alice", "Processing", HashMap::from([
+        ("question".to_string(), "What is the merger price?".to_string()),
+    ])).await;
+    let mut rbac = RBAC::new();
+    for (user, role) in [("root", Role::Admin), ("mallory", Role::Viewer)] {
+        rbac.add_permission(Permission {
+            user_id: user.to_string(),
+            role,
+            accessible_entities: vec![],
+            accessible_collections: vec![],
+            excluded_entities: vec![],
+            expires_at: None,
+        });
+    }
+    let app = test::init_service(App::new()
+        .app_data(web::Data::new(audit))
+        .app_data(web::Data::new(rbac))
+        .service(get_security_logs)).await;
+
+    let req = test::TestRequest::get().uri("/api/security/logs").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+    let req = test::TestRequest::get().uri("/api/security/logs").insert_header(("X-User-ID", "mallory")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+    let req = test::TestRequest::get().uri("/api/security/logs").insert_header(("X-User-ID", "root")).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+}
@@ -0,0 +1,37 @@
+use brainvault_backend::db::circuit_breaker::CircuitBreaker;
+use std::time::Duration;
+
+#[test]
+fn test_breaker_opens_after_threshold_and_probes_after_cooldown() {
+    let breaker = CircuitBreaker::new(2, Duration::from_millis(20));
+    breaker.try_call().expect("closed circuit lets calls through").failed();
+    assert_eq!(breaker.status().state, "closed");
+    breaker.try_call().unwrap().failed();
+    assert_eq!(breaker.status().state, "open");
+    assert!(breaker.try_call().is_none());
+
+    std::thread::sleep(Duration::from_millis(30));
+    let probe = breaker.try_call().expect("first call after the cooldown is the probe");
+    assert!(breaker.try_call().is_none(), "only one probe at a time");
+    probe.failed();
+    assert_eq!(breaker.status().state, "open");
+
+    std::thread::sleep(Duration::from_millis(30));
+    breaker.try_call().unwrap().succeeded();
+    assert_eq!(breaker.status().state, "closed");
+    assert_eq!(breaker.status().consecutive_failures, 0);
+}
+
+#[test]
+fn test_abandoned_probe_counts_as_a_failure() {
+    let breaker = CircuitBreaker::new(1, Duration::from_millis(20));
+    breaker.try_call().unwrap().failed();
+    std::thread::sleep(Duration::from_millis(30));
+
+    // A probe whose call is cancelled before reporting back must not leave the circuit half-open
+    drop(breaker.try_call().expect("probe"));
+    assert_eq!(breaker.status().state, "open");
+
+    std::thread::sleep(Duration::from_millis(30));
+    assert!(breaker.try_call().is_some(), "the next cooldown lets a new probe through");
+}
@@ -0,0 +1,20 @@
+use brainvault_backend::core::citations::{extract_citations, Citation};
+
+#[test]
+fn test_extract_citations_maps_markers_to_doc_ids() {
+    let sources = vec!["doc-001".to_string(), "doc-003".to_string(), "doc-007".to_string()];
+    let answer = "Qubits hold superposed states [1]. Zero trust limits exposure [3, 1]; see also [9].";
+
+    let citations = extract_citations(answer, &sources);
+
+    assert_eq!(citations, vec![
+        Citation { index: 1, doc_id: "doc-001".to_string() },
+        Citation { index: 3, doc_id: "doc-007".to_string() },
+    ]);
+}
+
+#[test]
+fn test_extract_citations_empty_when_answer_is_uncited() {
+    let sources = vec!["doc-001".to_string()];
+    assert!(extract_citations("Quantum computers are fast.", &sources).is_empty());
+}
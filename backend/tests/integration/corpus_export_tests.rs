@@ -0,0 +1,150 @@
+use actix_web::{test, web, App};
+use brainvault_backend::api::handlers::knowledge::{export_documents, import_documents, DocumentRecord};
+use brainvault_backend::config::AppConfig;
+use brainvault_backend::core::audit_manager::AuditManager;
+use brainvault_backend::core::rbac::{Permission, Role, RBAC};
+use brainvault_backend::core::search_engine::{HybridSearchEngine, SearchWeights};
+use brainvault_backend::db::barq_vector::BarqVectorClient;
+
+fn engine(name: &str) -> HybridSearchEngine {
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", &format!("/nonexistent/brainvault-{}", name));
+    HybridSearchEngine::new(client, SearchWeights { vector_weight: 0.0, bm25_weight: 1.0 })
+}
+
+fn rbac() -> RBAC {
+    let mut rbac = RBAC::new();
+    for (user_id, role) in [("root", Role::Admin), ("reader", Role::Viewer)] {
+        rbac.add_permission(Permission {
+            user_id: user_id.to_string(),
+            role,
+            accessible_entities: vec![],
+            accessible_collections: vec![],
+            excluded_entities: vec![],
+            expires_at: None,
+        });
+    }
+    rbac
+}
+
+fn audit() -> AuditManager {
+    let dir = std::env::temp_dir().join(format!("brainvault-corpus-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    AuditManager::at_path(&dir.to_string_lossy())
+}
+
+fn config() -> AppConfig {
+    AppConfig {
+        bind_address: "127.0.0.1:0".parse().unwrap(),
+        cors_allowed_origins: vec![],
+        data_path: "/tmp".to_string(),
+        vector_db_url: "http://127.0.0.1:9".to_string(),
+        graph_db_url: "http://127.0.0.1:9".to_string(),
+        llm_provider: "openai".to_string(),
+        llm_model: "gpt-4o".to_string(),
+        max_body_bytes: 1_048_576,
+        max_document_bytes: 1024,
+        ask_timeout_ms: 0,
+        vector_warmup: false,
+        vector_warmup_reembed: false,
+        ingest_concurrency: 4,
+    }
+}
+
+#[actix_web::test]
+async fn test_export_then_import_restores_the_corpus() {
+    let source = engine("corpus-export-source");
+    source.ingest_document("corpus-handbook", "Remote work needs manager approval").await.unwrap();
+    source.ingest_document_into("corpus-budget", "Quarterly budget review", Some("en"), Some("finance")).await.unwrap();
+    let source_app = test::init_service(App::new()
+        .app_data(web::Data::new(source))
+        .app_data(web::Data::new(rbac()))
+        .app_data(web::Data::new(audit()))
+        .app_data(web::Data::new(config()))
+        .service(export_documents)
+        .service(import_documents)).await;
+
+    let req = test::TestRequest::get().uri("/api/knowledge/export").insert_header(("X-User-ID", "root")).to_request();
+    let dump = test::call_and_read_body(&source_app, req).await;
+    let records: Vec<DocumentRecord> = String::from_utf8_lossy(&dump).lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(records.len(), 2);
+
+    let target = engine("corpus-export-target");
+    let vector_db = target.vector_db.clone();
+    let target_app = test::init_service(App::new()
+        .app_data(web::Data::new(target))
+        .app_data(web::Data::new(rbac()))
+        .app_data(web::Data::new(audit()))
+        .app_data(web::Data::new(config()))
+        .service(export_documents)
+        .service(import_documents)).await;
+    let import = |body: web::Bytes| test::TestRequest::post()
+        .uri("/api/knowledge/import")
+        .insert_header(("X-User-ID", "root"))
+        .set_payload(body)
+        .to_request();
+
+    let summary: serde_json::Value = test::call_and_read_body_json(&target_app, import(dump.clone())).await;
+    assert_eq!((summary["imported"].as_u64(), summary["failed"].as_u64()), (Some(2), Some(0)));
+    let restored = vector_db.get_document("corpus-budget").await.and_then(|d| d.content);
+    assert_eq!(restored.as_deref(), Some("Quarterly budget review"));
+    assert_eq!(vector_db.collection_of("corpus-budget").await.as_deref(), Some("finance"));
+
+    // Importing the same dump again changes nothing
+    let summary: serde_json::Value = test::call_and_read_body_json(&target_app, import(dump)).await;
+    assert_eq!((summary["imported"].as_u64(), summary["unchanged"].as_u64()), (Some(0), Some(2)));
+}
+
+#[actix_web::test]
+async fn test_import_reports_bad_lines_and_keeps_the_rest() {
+    let target = engine("corpus-import-errors");
+    let vector_db = target.vector_db.clone();
+    let app = test::init_service(App::new()
+        .app_data(web::Data::new(target))
+        .app_data(web::Data::new(rbac()))
+        .app_data(web::Data::new(audit()))
+        .app_data(web::Data::new(config()))
+        .service(export_documents)
+        .service(import_documents)).await;
+
+    let body = [
+        r#"{"doc_id": "corpus-good", "content": "Onboarding checklist"}"#.to_string(),
+        "not json".to_string(),
+        r#"{"doc_id": " ", "content": "no id"}"#.to_string(),
+        r#"{"doc_id": "corpus-tampered", "content": "edited", "content_hash": "0000"}"#.to_string(),
+        format!(r#"{{"doc_id": "corpus-huge", "content": "{}"}}"#, "x".repeat(2048)),
+    ].join("\n");
+    let req = test::TestRequest::post()
+        .uri("/api/knowledge/import")
+        .insert_header(("X-User-ID", "root"))
+        .set_payload(body)
+        .to_request();
+    let summary: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!((summary["imported"].as_u64(), summary["failed"].as_u64()), (Some(1), Some(4)));
+    let errors: Vec<&str> = summary["errors"].as_array().unwrap().iter().map(|e| e.as_str().unwrap()).collect();
+    assert!(errors[0].starts_with("line 2:"));
+    assert!(errors[2].contains("content_hash mismatch"));
+    assert!(vector_db.get_document("corpus-good").await.is_some());
+    assert!(vector_db.get_document("corpus-tampered").await.is_none());
+}
+
+#[actix_web::test]
+async fn test_export_and_import_require_admin() {
+    let app = test::init_service(App::new()
+        .app_data(web::Data::new(engine("corpus-non-admin")))
+        .app_data(web::Data::new(rbac()))
+        .app_data(web::Data::new(audit()))
+        .app_data(web::Data::new(config()))
+        .service(export_documents)
+        .service(import_documents)).await;
+
+    let req = test::TestRequest::get().uri("/api/knowledge/export").insert_header(("X-User-ID", "reader")).to_request();
+    assert!(test::call_service(&app, req).await.status().is_client_error());
+    let req = test::TestRequest::post()
+        .uri("/api/knowledge/import")
+        .insert_header(("X-User-ID", "reader"))
+        .set_payload(r#"{"doc_id": "corpus-sneaky", "content": "x"}"#)
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_client_error());
+}
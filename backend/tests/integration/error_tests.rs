@@ -0,0 +1,82 @@
+use actix_web::http::StatusCode;
+use actix_web::ResponseError;
+use brainvault_backend::error::BrainVaultError;
+
+#[test]
+fn test_error_variants_map_to_http_status() {
+    assert_eq!(BrainVaultError::NotFound("task".into()).status_code(), StatusCode::NOT_FOUND);
+    assert_eq!(BrainVaultError::Unauthorized("viewer".into()).status_code(), StatusCode::FORBIDDEN);
+    assert_eq!(BrainVaultError::Upstream("barq".into()).status_code(), StatusCode::BAD_GATEWAY);
+    assert_eq!(BrainVaultError::BadRequest("doc_id".into()).status_code(), StatusCode::BAD_REQUEST);
+    assert_eq!(BrainVaultError::Conflict("busy".into()).status_code(), StatusCode::CONFLICT);
+    assert_eq!(BrainVaultError::Unavailable("queue".into()).status_code(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(BrainVaultError::Timeout("ask".into()).status_code(), StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(BrainVaultError::Internal("oops".into()).status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[test]
+fn test_json_errors_are_internal() {
+    let err = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+    assert_eq!(BrainVaultError::from(err).status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[tokio::test]
+async fn test_ask_budget_cuts_off_slow_stages() {
+    use brainvault_backend::api::handlers::knowledge::{ask_deadline, before_deadline, MAX_ASK_TIMEOUT_MS};
+    use std::time::Duration;
+
+    assert!(ask_deadline(None, 0).unwrap().is_none());
+    assert!(ask_deadline(Some(0), 1000).is_err());
+    assert!(ask_deadline(Some(MAX_ASK_TIMEOUT_MS + 1), 1000).is_err());
+
+    let deadline = ask_deadline(Some(50), 0).unwrap();
+    assert_eq!(before_deadline(deadline, async { 7 }).await, Some(7));
+    let slow = before_deadline(deadline, tokio::time::sleep(Duration::from_secs(5))).await;
+    assert!(slow.is_none());
+    // Once spent, the budget stays spent for later stages
+    assert_eq!(before_deadline(deadline, tokio::time::sleep(Duration::from_millis(1))).await, None);
+    assert_eq!(before_deadline(None, async { "done" }).await, Some("done"));
+}
+
+#[tokio::test]
+async fn test_unknown_user_is_unauthorized() {
+    use brainvault_backend::core::rbac::RBAC;
+
+    let rbac = RBAC::new();
+    let err = rbac.check_access("ghost", "doc_1").await.unwrap_err();
+    assert_eq!(err.status_code(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn test_incoming_request_id_is_honored_only_when_safe() {
+    use brainvault_backend::api::middleware::request_id::resolve_request_id;
+
+    assert_eq!(resolve_request_id(Some("trace-42.a:b")), "trace-42.a:b");
+    let generated = resolve_request_id(Some("bad id\nwith newline"));
+    assert_ne!(generated, "bad id\nwith newline");
+    assert_eq!(generated.len(), 36);
+    assert_eq!(resolve_request_id(None).len(), 36);
+    assert_eq!(resolve_request_id(Some(&"x".repeat(200))).len(), 36);
+}
+
+#[actix_web::test]
+async fn test_error_body_and_header_carry_request_id() {
+    use actix_web::{test, web, App, HttpResponse};
+    use brainvault_backend::api::middleware::request_id::request_id;
+
+    let app = test::init_service(
+        App::new()
+            .wrap(actix_web::middleware::from_fn(request_id))
+            .route("/missing", web::get().to(|| async {
+                Err::<HttpResponse, _>(BrainVaultError::NotFound("doc-9".into()))
+            })),
+    ).await;
+
+    let req = test::TestRequest::get().uri("/missing").insert_header(("X-Request-ID", "req-123")).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(resp.headers().get("x-request-id").unwrap(), "req-123");
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["request_id"], "req-123");
+    assert_eq!(body["error"], "not_found");
+}
@@ -0,0 +1,48 @@
+use brainvault_backend::core::graph_export::{to_cypher, to_graphml, ExportFormat};
+use brainvault_backend::core::graph_manager::{ContextGraph, Entity, Relationship};
+use std::collections::HashMap;
+
+fn sample() -> ContextGraph {
+    ContextGraph {
+        entities: vec![Entity {
+            id: "policy-1".to_string(),
+            label: "Policy".to_string(),
+            properties: HashMap::from([("name".to_string(), "Travel & Expenses".to_string())]),
+        }],
+        relationships: vec![Relationship {
+            from_id: "policy-1".to_string(),
+            to_id: "finance".to_string(),
+            rel_type: "OWNED_BY".to_string(),
+            weight: 0.5,
+            properties: HashMap::new(),
+            direction: None,
+        }],
+        depths: HashMap::new(),
+        next_cursor: None,
+    }
+}
+
+#[test]
+fn test_export_format_parse() {
+    assert_eq!(ExportFormat::parse("GraphML"), Some(ExportFormat::GraphMl));
+    assert_eq!(ExportFormat::parse("cypher"), Some(ExportFormat::Cypher));
+    assert_eq!(ExportFormat::parse("csv"), None);
+}
+
+#[test]
+fn test_graphml_declares_every_edge_endpoint() {
+    let xml = to_graphml(&sample());
+    assert!(xml.contains("<node id=\"policy-1\">"));
+    assert!(xml.contains("<node id=\"finance\">"));
+    assert!(xml.contains("Travel &amp; Expenses"));
+    assert!(xml.contains("<edge id=\"e0\" source=\"policy-1\" target=\"finance\">"));
+    assert!(xml.contains("<data key=\"weight\">0.5</data>"));
+}
+
+#[test]
+fn test_cypher_statements() {
+    let cypher = to_cypher(&sample());
+    assert!(cypher.contains("CREATE (:`Policy` {`id`: 'policy-1', `name`: 'Travel & Expenses'});"));
+    assert!(cypher.contains("CREATE (:`Entity` {`id`: 'finance'});"));
+    assert!(cypher.contains("MATCH (a {id: 'policy-1'}), (b {id: 'finance'}) CREATE (a)-[:`OWNED_BY` {`weight`: 0.5}]->(b);"));
+}
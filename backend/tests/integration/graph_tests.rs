@@ -19,7 +19,9 @@ async fn test_graph_manager_operations() {
         from_id: "e1".to_string(),
         to_id: "e2".to_string(),
         rel_type: "manages".to_string(),
+        weight: 1.0,
         properties: HashMap::new(),
+        direction: None,
     };
     
     assert!(manager.add_relationship(rel).await.is_ok());
@@ -27,3 +29,337 @@ async fn test_graph_manager_operations() {
     let context = manager.find_related_context("e1", 2).await;
     assert!(context.is_ok());
 }
+
+#[test]
+fn test_normalize_entity_name() {
+    use brainvault_backend::core::graph_manager::normalize_entity_name;
+
+    assert_eq!(normalize_entity_name("Machine-Learning"), "machine learning");
+    assert_eq!(normalize_entity_name("  machine   learning. "), "machine learning");
+    assert_eq!(normalize_entity_name("---"), "");
+    assert_eq!(normalize_entity_name("C++"), "c++");
+    assert_eq!(normalize_entity_name("C#"), "c#");
+    assert_ne!(normalize_entity_name("C++"), normalize_entity_name("C"));
+}
+
+#[test]
+fn test_ingest_requests_extract_entities_by_default() {
+    use brainvault_backend::api::handlers::knowledge::IngestRequest;
+
+    let req: IngestRequest = serde_json::from_str(r#"{"doc_id": "d", "content": "text"}"#).unwrap();
+    assert!(req.auto_extract);
+    let req: IngestRequest = serde_json::from_str(r#"{"doc_id": "d", "content": "text", "auto_extract": false}"#).unwrap();
+    assert!(!req.auto_extract);
+}
+
+#[test]
+fn test_parse_extraction_skips_malformed_lines() {
+    use brainvault_backend::core::agent_orchestrator::parse_extraction;
+
+    let response = "ENTITY|ml|Technology|Machine Learning\nnoise\nREL|ml|ai|SUBSET_OF\nENTITY||Technology|Nameless";
+    let (entities, relationships) = parse_extraction(response);
+    assert_eq!(entities.len(), 1);
+    assert_eq!(entities[0].properties.get("name").map(String::as_str), Some("Machine Learning"));
+    assert_eq!(relationships.len(), 1);
+    assert_eq!(relationships[0].rel_type, "SUBSET_OF");
+}
+
+#[tokio::test]
+async fn test_find_path_between_entities() {
+    let manager = KnowledgeGraphManager::new(BarqGraphClient::connect("http://127.0.0.1:9"));
+    let link = |from: &str, to: &str| Relationship {
+        from_id: from.to_string(),
+        to_id: to.to_string(),
+        rel_type: "GOVERNS".to_string(),
+        weight: 1.0,
+        properties: HashMap::new(),
+        direction: None,
+    };
+    manager.add_relationship(link("path-policy", "path-team")).await.unwrap();
+    manager.add_relationship(link("path-dept", "path-team")).await.unwrap();
+    manager.add_relationship(link("path-dept", "path-budget")).await.unwrap();
+
+    let path = manager.find_path("path-policy", "path-budget", 5).await.unwrap();
+    let hops: Vec<(&str, &str)> = path.relationships.iter().map(|r| (r.from_id.as_str(), r.to_id.as_str())).collect();
+    assert_eq!(hops, vec![("path-policy", "path-team"), ("path-dept", "path-team"), ("path-dept", "path-budget")]);
+
+    assert!(manager.find_path("path-policy", "path-budget", 2).await.is_err());
+    assert!(manager.find_path("path-policy", "path-missing", 5).await.is_err());
+}
+
+#[test]
+fn test_name_similarity_matches_abbreviated_words() {
+    use brainvault_backend::core::graph_manager::name_similarity;
+
+    assert_eq!(name_similarity("Acme Corp", "Acme Corporation"), 1.0);
+    assert_eq!(name_similarity("ACME-corp.", "acme corp"), 1.0);
+    assert!(name_similarity("Acme Corp", "Globex Corporation") < 0.8);
+    assert_eq!(name_similarity("", "Acme"), 0.0);
+}
+
+#[tokio::test]
+async fn test_merge_entities_repoints_relationships() {
+    let dir = std::env::temp_dir().join(format!("brainvault-merge-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let manager = KnowledgeGraphManager::at_path(BarqGraphClient::connect("http://127.0.0.1:9"), &dir.to_string_lossy());
+    let org = |id: &str, name: &str| Entity {
+        id: id.to_string(),
+        label: "Organization".to_string(),
+        properties: HashMap::from([("name".to_string(), name.to_string())]),
+    };
+    let link = |from: &str, to: &str| Relationship {
+        from_id: from.to_string(),
+        to_id: to.to_string(),
+        rel_type: "PARTNERS_WITH".to_string(),
+        weight: 1.0,
+        properties: HashMap::new(),
+        direction: None,
+    };
+    manager.add_entity(org("merge-acme", "Merge Acme Corp")).await.unwrap();
+    manager.add_entity(org("merge-acme-2", "Merge Acme Corporation")).await.unwrap();
+    manager.add_relationship(link("merge-acme-2", "merge-globex")).await.unwrap();
+    manager.add_relationship(link("merge-acme", "merge-acme-2")).await.unwrap();
+
+    let suggestions = manager.suggest_duplicates(0.9, 100).await;
+    assert!(suggestions.iter().any(|d| {
+        [d.keep_id.as_str(), d.merge_id.as_str()].contains(&"merge-acme") && [d.keep_id.as_str(), d.merge_id.as_str()].contains(&"merge-acme-2")
+    }));
+
+    let kept = manager.merge_entities("merge-acme", "merge-acme-2").await.unwrap();
+    assert_eq!(kept.properties.get("aliases").map(String::as_str), Some("Merge Acme Corporation"));
+
+    let context = manager.find_related_context("merge-acme", 1).await.unwrap();
+    assert_eq!(context.relationships.len(), 1);
+    assert_eq!(context.relationships[0].to_id, "merge-globex");
+    assert!(manager.merge_entities("merge-acme", "merge-acme-2").await.is_err());
+
+    // The merge is on disk
+    let reloaded = KnowledgeGraphManager::at_path(BarqGraphClient::connect("http://127.0.0.1:9"), &dir.to_string_lossy());
+    assert!(reloaded.get_entity("merge-acme-2").await.is_none());
+    assert_eq!(reloaded.find_related_context("merge-acme", 1).await.unwrap().relationships.len(), 1);
+}
+
+#[tokio::test]
+async fn test_failed_merge_leaves_graph_untouched() {
+    let manager = KnowledgeGraphManager::at_path(BarqGraphClient::connect("http://127.0.0.1:9"), "/nonexistent/brainvault-merge");
+    let org = |id: &str, name: &str| Entity {
+        id: id.to_string(),
+        label: "Organization".to_string(),
+        properties: HashMap::from([("name".to_string(), name.to_string())]),
+    };
+    manager.add_entity(org("rollback-initech", "Initech")).await.unwrap();
+    manager.add_entity(org("rollback-initech-2", "Initech Inc")).await.unwrap();
+    manager.add_relationship(Relationship {
+        from_id: "rollback-initech-2".to_string(),
+        to_id: "rollback-office".to_string(),
+        rel_type: "LOCATED_IN".to_string(),
+        weight: 1.0,
+        properties: HashMap::new(),
+        direction: None,
+    }).await.unwrap();
+
+    assert!(manager.merge_entities("rollback-initech", "rollback-initech-2").await.is_err());
+    assert!(manager.get_entity("rollback-initech-2").await.is_some());
+    assert!(manager.get_entity("rollback-initech").await.unwrap().properties.get("aliases").is_none());
+    let context = manager.find_related_context("rollback-initech-2", 1).await.unwrap();
+    assert_eq!(context.relationships.len(), 1);
+}
+
+#[tokio::test]
+async fn test_suggest_duplicates_only_compares_names_sharing_a_word() {
+    let manager = KnowledgeGraphManager::new(BarqGraphClient::connect("http://127.0.0.1:9"));
+    for (id, name) in [("dup-umbrella", "Umbrella Pharma"), ("dup-umbrella-2", "Umbrella Pharmaceuticals"), ("dup-hooli", "Hooli")] {
+        manager.add_entity(Entity {
+            id: id.to_string(),
+            label: "Organization".to_string(),
+            properties: HashMap::from([("name".to_string(), name.to_string())]),
+        }).await.unwrap();
+    }
+
+    // Even at threshold zero, names with no word in common are never paired
+    let suggestions = manager.suggest_duplicates(0.0, 1000).await;
+    assert!(suggestions.iter().any(|d| d.keep_id.starts_with("dup-umbrella") && d.merge_id.starts_with("dup-umbrella")));
+    assert!(!suggestions.iter().any(|d| d.keep_id == "dup-hooli" || d.merge_id == "dup-hooli"));
+}
+
+#[tokio::test]
+async fn test_update_entity_merges_properties() {
+    let manager = KnowledgeGraphManager::new(BarqGraphClient::connect("http://127.0.0.1:9"));
+    manager.add_entity(Entity {
+        id: "update-policy".to_string(),
+        label: "Policy".to_string(),
+        properties: HashMap::from([("name".to_string(), "Remote Work".to_string()), ("owner".to_string(), "HR".to_string())]),
+    }).await.unwrap();
+
+    let updated = manager.update_entity("update-policy", HashMap::from([("owner".to_string(), "People Ops".to_string()), ("version".to_string(), "2".to_string())])).await.unwrap();
+    assert_eq!(updated.properties.get("name").map(String::as_str), Some("Remote Work"));
+    assert_eq!(updated.properties.get("owner").map(String::as_str), Some("People Ops"));
+    assert_eq!(updated.properties.get("version").map(String::as_str), Some("2"));
+
+    assert!(manager.update_entity("update-missing", HashMap::new()).await.is_err());
+}
+
+#[tokio::test]
+async fn test_weighted_context_orders_and_prunes() {
+    let manager = KnowledgeGraphManager::new(BarqGraphClient::connect("http://127.0.0.1:9"));
+    let link = |to: &str, weight: f32| Relationship {
+        from_id: "weight-hub".to_string(),
+        to_id: to.to_string(),
+        rel_type: "DEPENDS_ON".to_string(),
+        weight,
+        properties: HashMap::new(),
+        direction: None,
+    };
+    manager.add_relationship(link("weight-weak", 0.2)).await.unwrap();
+    manager.add_relationship(link("weight-strong", 0.9)).await.unwrap();
+    manager.add_relationship(link("weight-medium", 0.5)).await.unwrap();
+
+    let context = manager.find_related_context("weight-hub", 1).await.unwrap();
+    let order: Vec<&str> = context.relationships.iter().map(|r| r.to_id.as_str()).collect();
+    assert_eq!(order, vec!["weight-strong", "weight-medium", "weight-weak"]);
+
+    let pruned = manager.find_weighted_context("weight-hub", 1, Some(0.4)).await.unwrap();
+    assert_eq!(pruned.relationships.len(), 2);
+}
+
+#[test]
+fn test_relationship_weight_defaults_to_one() {
+    let rel: Relationship = serde_json::from_str(r#"{"from_id": "a", "to_id": "b", "rel_type": "LINKS"}"#).unwrap();
+    assert_eq!(rel.weight, 1.0);
+}
+
+#[tokio::test]
+async fn test_context_reports_direction_and_depth() {
+    use brainvault_backend::core::graph_manager::EdgeDirection;
+
+    let manager = KnowledgeGraphManager::new(BarqGraphClient::connect("http://127.0.0.1:9"));
+    let link = |from: &str, to: &str| Relationship {
+        from_id: from.to_string(),
+        to_id: to.to_string(),
+        rel_type: "REPORTS_TO".to_string(),
+        weight: 1.0,
+        properties: HashMap::new(),
+        direction: None,
+    };
+    manager.add_relationship(link("dir-team", "dir-lead")).await.unwrap();
+    manager.add_relationship(link("dir-intern", "dir-team")).await.unwrap();
+    manager.add_relationship(link("dir-lead", "dir-cto")).await.unwrap();
+
+    let context = manager.find_related_context("dir-team", 2).await.unwrap();
+    let edges: Vec<(&str, Option<EdgeDirection>)> = context.relationships.iter()
+        .map(|r| (r.from_id.as_str(), r.direction))
+        .collect();
+    assert_eq!(edges, vec![
+        ("dir-team", Some(EdgeDirection::Outgoing)),
+        ("dir-intern", Some(EdgeDirection::Incoming)),
+        ("dir-lead", Some(EdgeDirection::Outgoing)),
+    ]);
+    assert_eq!(context.depths.get("dir-team"), Some(&0));
+    assert_eq!(context.depths.get("dir-intern"), Some(&1));
+    assert_eq!(context.depths.get("dir-cto"), Some(&2));
+
+    let one_hop = manager.find_related_context("dir-team", 1).await.unwrap();
+    assert_eq!(one_hop.relationships.len(), 2);
+    assert!(!one_hop.depths.contains_key("dir-cto"));
+}
+
+#[tokio::test]
+async fn test_context_pages_with_cursor() {
+    let manager = KnowledgeGraphManager::new(BarqGraphClient::connect("http://127.0.0.1:9"));
+    for i in 0..5 {
+        manager.add_relationship(Relationship {
+            from_id: "page-hub".to_string(),
+            to_id: format!("page-spoke-{}", i),
+            rel_type: "LINKS".to_string(),
+            weight: 1.0,
+            properties: HashMap::new(),
+            direction: None,
+        }).await.unwrap();
+    }
+
+    let mut nodes = Vec::new();
+    let mut edges = 0;
+    let mut cursor: Option<String> = None;
+    loop {
+        let page = manager.find_context_page("page-hub", 1, None, 2, cursor.as_deref()).await.unwrap();
+        assert!(page.depths.len() <= 2);
+        nodes.extend(page.depths.keys().cloned());
+        edges += page.relationships.len();
+        match page.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    nodes.sort();
+    nodes.dedup();
+    assert_eq!(nodes.len(), 6);
+    assert_eq!(edges, 5);
+    assert!(manager.find_context_page("page-hub", 1, None, 2, Some("not-a-cursor")).await.is_err());
+}
+
+#[test]
+fn test_context_summary_names_entities_and_edges() {
+    use brainvault_backend::core::graph_manager::ContextGraph;
+
+    let entity = |id: &str, name: &str| Entity {
+        id: id.to_string(),
+        label: "Team".to_string(),
+        properties: HashMap::from([("name".to_string(), name.to_string())]),
+    };
+    let link = |from: &str, to: &str| Relationship {
+        from_id: from.to_string(),
+        to_id: to.to_string(),
+        rel_type: "REPORTS_TO".to_string(),
+        weight: 1.0,
+        properties: HashMap::new(),
+        direction: None,
+    };
+    let context = ContextGraph {
+        entities: vec![entity("sum-team", "Platform"), entity("sum-lead", "Engineering")],
+        relationships: vec![link("sum-team", "sum-lead"), link("sum-intern", "sum-team")],
+        depths: HashMap::new(),
+        next_cursor: None,
+    };
+
+    assert_eq!(
+        context.summarize("sum-team"),
+        "Entity: Platform (Team) [sum-team]\n  - Platform [sum-team] -REPORTS_TO-> Engineering [sum-lead]\n  - [sum-intern] -REPORTS_TO-> Platform [sum-team]\n"
+    );
+}
+
+#[tokio::test]
+async fn test_context_summary_names_both_ends_of_distant_edges() {
+    use brainvault_backend::core::graph_manager::MAX_SUMMARY_CHARS;
+
+    let manager = KnowledgeGraphManager::new(BarqGraphClient::connect("http://127.0.0.1:9"));
+    for (id, name) in [("hop-team", "Platform"), ("hop-lead", "Engineering"), ("hop-cto", "Office of the CTO")] {
+        manager.add_entity(Entity {
+            id: id.to_string(),
+            label: "Team".to_string(),
+            properties: HashMap::from([("name".to_string(), name.to_string())]),
+        }).await.unwrap();
+    }
+    let link = |from: &str, to: &str| Relationship {
+        from_id: from.to_string(),
+        to_id: to.to_string(),
+        rel_type: "REPORTS_TO".to_string(),
+        weight: 1.0,
+        properties: HashMap::new(),
+        direction: None,
+    };
+    manager.add_relationship(link("hop-team", "hop-lead")).await.unwrap();
+    manager.add_relationship(link("hop-lead", "hop-cto")).await.unwrap();
+
+    let context = manager.find_related_context("hop-team", 2).await.unwrap();
+    assert_eq!(
+        context.summarize("hop-team"),
+        "Entity: Platform (Team) [hop-team]\n  - Platform [hop-team] -REPORTS_TO-> Engineering [hop-lead]\n  - Engineering [hop-lead] -REPORTS_TO-> Office of the CTO [hop-cto]\n"
+    );
+
+    for i in 0..200 {
+        manager.add_relationship(link(&format!("hop-member-{}", i), "hop-team")).await.unwrap();
+    }
+    let crowded = manager.find_related_context("hop-team", 2).await.unwrap().summarize("hop-team");
+    assert!(crowded.len() <= MAX_SUMMARY_CHARS + 64);
+    assert!(crowded.ends_with("more relationships\n"));
+}
@@ -0,0 +1,62 @@
+use async_trait::async_trait;
+use brainvault_backend::core::ingest_queue::{IngestJob, IngestJobState, IngestQueue};
+use brainvault_backend::core::llm::embeddings::EmbeddingProvider;
+use brainvault_backend::core::llm::retry::ProviderError;
+use brainvault_backend::core::search_engine::{HybridSearchEngine, SearchWeights};
+use brainvault_backend::db::barq_vector::{BarqVectorClient, IndexOutcome};
+use std::sync::Arc;
+
+struct FixedEmbedder;
+
+#[async_trait]
+impl EmbeddingProvider for FixedEmbedder {
+    fn name(&self) -> &str { "test" }
+    fn model(&self) -> &str { "test-embedding" }
+    fn dimension(&self) -> usize { 3 }
+    async fn embed(&self, _text: &str) -> Result<Vec<f32>, ProviderError> {
+        Ok(vec![0.1, 0.2, 0.3])
+    }
+}
+
+fn queue() -> (IngestQueue, Arc<HybridSearchEngine>) {
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-queue-test")
+        .with_embedder(Arc::new(FixedEmbedder))
+        .with_dimension(3);
+    let engine = Arc::new(HybridSearchEngine::new(client, SearchWeights { vector_weight: 0.5, bm25_weight: 0.5 }));
+    (IngestQueue::start(engine.clone(), 8), engine)
+}
+
+async fn wait_for(queue: &IngestQueue, job_id: &str) -> IngestJob {
+    for _ in 0..100 {
+        let job = queue.get_job(job_id).await.expect("job is tracked");
+        if matches!(job.state, IngestJobState::Completed | IngestJobState::Failed) {
+            return job;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    panic!("job {} did not finish", job_id);
+}
+
+#[tokio::test]
+async fn test_background_ingestion_completes() {
+    let (queue, engine) = queue();
+    let job_id = queue.enqueue("queued-doc", "zero trust architecture review", None, None).await.unwrap();
+
+    let job = wait_for(&queue, &job_id).await;
+    assert_eq!(job.state, IngestJobState::Completed);
+    assert_eq!(job.outcome, Some(IndexOutcome::Indexed));
+    assert!(job.finished_at.is_some());
+    assert_eq!(queue.pending().await, 0);
+    assert!(engine.vector_db.get_document("queued-doc").await.is_some());
+}
+
+#[tokio::test]
+async fn test_background_ingestion_reports_failure() {
+    let (queue, _) = queue();
+    let job_id = queue.enqueue("klingon-doc", "Qapla'", Some("klingon"), None).await.unwrap();
+
+    let job = wait_for(&queue, &job_id).await;
+    assert_eq!(job.state, IngestJobState::Failed);
+    assert!(job.error.unwrap().contains("klingon"));
+    assert!(queue.get_job("no-such-job").await.is_none());
+}
@@ -0,0 +1,145 @@
+use brainvault_backend::api::handlers::knowledge::{IngestRequest, IngestStages};
+use brainvault_backend::core::graph_manager::{Entity, Relationship};
+use std::collections::HashMap;
+
+fn entity(id: &str) -> Entity {
+    Entity { id: id.to_string(), label: "Technology".to_string(), properties: HashMap::new() }
+}
+
+fn relationship(from: &str, to: &str) -> Relationship {
+    Relationship { from_id: from.to_string(), to_id: to.to_string(), rel_type: "RELATED_TO".to_string(), weight: 1.0, properties: HashMap::new(), direction: None }
+}
+
+#[test]
+fn test_valid_ingest_request_has_no_problems() {
+    let req = IngestRequest {
+        doc_id: "doc-100".to_string(),
+        content: "Qubits enable superposition.".to_string(),
+        entities: vec![entity("qubit"), entity("superposition")],
+        relationships: vec![relationship("qubit", "superposition")],
+        auto_extract: false,
+        language: None,
+        background: false,
+        collection: None,
+    };
+    assert!(req.validate().is_empty());
+}
+
+#[test]
+fn test_ingest_validation_lists_every_problem() {
+    let req = IngestRequest {
+        doc_id: "   ".to_string(),
+        content: String::new(),
+        entities: vec![entity("qubit")],
+        relationships: vec![relationship("qubit", "entanglement")],
+        auto_extract: false,
+        language: None,
+        background: false,
+        collection: None,
+    };
+    let fields: Vec<String> = req.validate().into_iter().map(|p| p.field).collect();
+    assert_eq!(fields, vec!["doc_id", "content", "relationships[0].to_id"]);
+}
+
+#[test]
+fn test_stage_failures_become_prefixed_warnings() {
+    let mut stages = IngestStages::default();
+    assert!(stages.warnings().is_empty());
+
+    stages.entities.succeeded = 2;
+    stages.vector.fail("Barq unavailable");
+    stages.relationships.fail("a -USES-> b: graph timeout");
+
+    assert_eq!(stages.succeeded(), 2);
+    assert_eq!(stages.warnings(), vec![
+        "vector: Barq unavailable".to_string(),
+        "relationships: a -USES-> b: graph timeout".to_string(),
+    ]);
+    let body = serde_json::to_value(&stages).unwrap();
+    assert_eq!(body["vector"]["failed"], 1);
+    assert!(body["entities"].get("errors").is_none());
+}
+
+#[test]
+fn test_oversized_content_is_rejected_with_413() {
+    use actix_web::http::StatusCode;
+    use actix_web::ResponseError;
+
+    let req = IngestRequest {
+        doc_id: "handbook".to_string(),
+        content: "x".repeat(2048),
+        entities: vec![],
+        relationships: vec![],
+        auto_extract: false,
+        language: None,
+        background: false,
+        collection: None,
+    };
+    assert!(req.check_size(2048).is_ok());
+    let err = req.check_size(1024).unwrap_err();
+    assert_eq!(err.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(err.message().contains("handbook-part-1"));
+}
+
+#[actix_web::test]
+async fn test_body_over_the_json_limit_is_refused_with_413() {
+    use actix_web::{test, web, App, HttpResponse};
+    use brainvault_backend::config::AppConfig;
+
+    let config = AppConfig {
+        bind_address: "127.0.0.1:0".parse().unwrap(),
+        cors_allowed_origins: vec![],
+        data_path: "/tmp".to_string(),
+        vector_db_url: "http://127.0.0.1:9".to_string(),
+        graph_db_url: "http://127.0.0.1:9".to_string(),
+        llm_provider: "openai".to_string(),
+        llm_model: "gpt-4o".to_string(),
+        max_body_bytes: 256,
+        max_document_bytes: 128,
+        ask_timeout_ms: 0,
+        vector_warmup: false,
+        vector_warmup_reembed: false,
+        ingest_concurrency: 4,
+    };
+    let app = test::init_service(
+        App::new()
+            .app_data(config.json_config())
+            .route("/echo", web::post().to(|body: web::Json<serde_json::Value>| async move { HttpResponse::Ok().json(body.into_inner()) })),
+    ).await;
+
+    let small = test::TestRequest::post().uri("/echo").set_json(serde_json::json!({"content": "ok"})).to_request();
+    assert_eq!(test::call_service(&app, small).await.status(), 200);
+
+    let big = test::TestRequest::post().uri("/echo").set_json(serde_json::json!({"content": "x".repeat(1000)})).to_request();
+    let resp = test::call_service(&app, big).await;
+    assert_eq!(resp.status(), 413);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "payload_too_large");
+}
+
+#[test]
+fn test_body_limit_follows_the_document_limit() {
+    use brainvault_backend::config::AppConfig;
+
+    let mut config = AppConfig {
+        bind_address: "127.0.0.1:0".parse().unwrap(),
+        cors_allowed_origins: vec![],
+        data_path: "/tmp".to_string(),
+        vector_db_url: "http://127.0.0.1:9".to_string(),
+        graph_db_url: "http://127.0.0.1:9".to_string(),
+        llm_provider: "openai".to_string(),
+        llm_model: "gpt-4o".to_string(),
+        max_body_bytes: 52_428_800,
+        max_document_bytes: 1_048_576,
+        ask_timeout_ms: 0,
+        vector_warmup: false,
+        vector_warmup_reembed: false,
+        ingest_concurrency: 4,
+    };
+    // A 1MB document leaves room for escaping and the envelope, far below MAX_BODY_BYTES
+    let limit = config.body_limit();
+    assert!(limit > config.max_document_bytes && limit < 4 * 1_048_576);
+
+    config.max_body_bytes = 2_000_000;
+    assert_eq!(config.body_limit(), 2_000_000);
+}
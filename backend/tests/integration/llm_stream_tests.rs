@@ -0,0 +1,75 @@
+use brainvault_backend::core::llm::nafs_provider::{message_json, stream_chat_completion, GenerationParams};
+
+#[tokio::test]
+async fn test_stream_keeps_characters_split_across_chunks() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        socket.set_nodelay(true).unwrap();
+        let mut request = [0u8; 8192];
+        let _ = socket.read(&mut request).await;
+        let body = "data: {\"choices\":[{\"delta\":{\"content\":\"caf\u{e9} \u{1F600}\"}}]}\n\ndata: [DONE]\n\n".as_bytes();
+        socket.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n").await.unwrap();
+        // Cut inside the two-byte `é`, then inside the four-byte emoji
+        let cuts = [body.iter().position(|&b| b == 0xC3).unwrap() + 1, body.iter().position(|&b| b == 0xF0).unwrap() + 2];
+        let mut start = 0;
+        for end in cuts.into_iter().chain([body.len()]) {
+            socket.write_all(&body[start..end]).await.unwrap();
+            socket.flush().await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(30)).await;
+            start = end;
+        }
+    });
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(8);
+    let messages = [message_json("user", "hi")];
+    stream_chat_completion(&format!("http://{}/v1", addr), "key", "gpt-4o", &messages, &GenerationParams::default(), &tx).await.unwrap();
+    drop(tx);
+    let mut text = String::new();
+    while let Some(delta) = rx.recv().await {
+        text.push_str(&delta.unwrap());
+    }
+    assert_eq!(text, "caf\u{e9} \u{1F600}");
+}
+
+#[tokio::test]
+async fn test_stream_sends_the_whole_conversation() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 8192];
+        // Headers and the small JSON body arrive together or in a few reads
+        while !String::from_utf8_lossy(&request).contains("\"stream\":true") {
+            let n = socket.read(&mut buf).await.unwrap();
+            if n == 0 {
+                break;
+            }
+            request.extend_from_slice(&buf[..n]);
+        }
+        let body = "data: {\"choices\":[{\"delta\":{\"content\":\"Paris\"}}]}\n\ndata: [DONE]\n\n";
+        socket.write_all(format!("HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n{}", body).as_bytes()).await.unwrap();
+        String::from_utf8_lossy(&request).to_string()
+    });
+
+    let messages = [
+        message_json("system", "Be brief."),
+        message_json("user", "What is the capital of France?"),
+        message_json("assistant", "Paris."),
+        message_json("user", "And its population?"),
+    ];
+    let (tx, mut rx) = tokio::sync::mpsc::channel(8);
+    stream_chat_completion(&format!("http://{}/v1", addr), "key", "gpt-4o", &messages, &GenerationParams::default(), &tx).await.unwrap();
+    assert_eq!(rx.recv().await.unwrap().unwrap(), "Paris");
+
+    let request = server.await.unwrap();
+    assert!(request.contains("What is the capital of France?"));
+    assert!(request.contains("\"role\":\"assistant\""));
+    assert!(request.contains("And its population?"));
+}
@@ -2,3 +2,26 @@ pub mod search_engine_tests;
 pub mod graph_tests;
 pub mod rbac_tests;
 pub mod orchestrator_tests;
+pub mod tokenizer_tests;
+pub mod search_cache_tests;
+pub mod abbreviation_tests;
+pub mod output_filter_tests;
+pub mod redaction_tests;
+pub mod vector_client_tests;
+pub mod citation_tests;
+pub mod audit_tests;
+pub mod error_tests;
+pub mod ingest_validation_tests;
+pub mod rate_limit_tests;
+pub mod tool_calling_tests;
+pub mod graph_export_tests;
+pub mod text_analysis_tests;
+pub mod query_syntax_tests;
+pub mod circuit_breaker_tests;
+pub mod ingest_queue_tests;
+pub mod search_history_tests;
+pub mod openapi_tests;
+pub mod response_cache_tests;
+pub mod llm_stream_tests;
+pub mod corpus_export_tests;
+pub mod retry_tests;
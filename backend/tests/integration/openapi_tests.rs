@@ -0,0 +1,19 @@
+use brainvault_backend::api::openapi::ApiDoc;
+use utoipa::OpenApi;
+
+#[test]
+fn test_spec_covers_core_endpoints_and_schemas() {
+    let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+    for path in ["/api/knowledge/ingest", "/api/search", "/api/ask", "/api/graph/{entity_id}/context", "/api/agents/task", "/api/agents/task/{task_id}"] {
+        assert!(spec["paths"].get(path).is_some(), "missing path {}", path);
+    }
+    assert!(spec["paths"]["/api/search"].get("post").is_some());
+    assert!(spec["paths"]["/api/knowledge/ingest"]["post"]["responses"].get("207").is_some());
+
+    let schemas = &spec["components"]["schemas"];
+    for schema in ["IngestRequest", "IngestResponse", "SearchQuery", "SearchPage", "ContextGraph", "TaskRequest", "TaskResponse", "ErrorBody"] {
+        assert!(schemas.get(schema).is_some(), "missing schema {}", schema);
+    }
+    assert_eq!(spec["components"]["securitySchemes"]["user_id"]["name"], "X-User-ID");
+}
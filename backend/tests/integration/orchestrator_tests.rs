@@ -1,4 +1,4 @@
-use brainvault_backend::core::agent_orchestrator::{AgentOrchestrator, AgentProfile, AgentType, TaskStatus};
+use brainvault_backend::core::agent_orchestrator::{AgentOrchestrator, AgentProfile, AgentType, TaskFilter, TaskStatus};
 
 #[tokio::test]
 async fn test_orchestrator_flow() {
@@ -10,6 +10,10 @@ async fn test_orchestrator_flow() {
         name: "Bond".to_string(),
         agent_type: AgentType::Researcher,
         capabilities: vec!["search".to_string(), "deduction".to_string()],
+        system_prompt: None,
+        max_tokens: None,
+        temperature: None,
+        research: Default::default(),
     };
     orchestrator.register_agent(agent).await;
     
@@ -50,6 +54,10 @@ async fn test_agent_execution_loop() {
         name: "Sherlock".to_string(),
         agent_type: AgentType::Researcher,
         capabilities: vec![],
+        system_prompt: None,
+        max_tokens: None,
+        temperature: None,
+        research: Default::default(),
     }).await;
     
     // Spawn Loop
@@ -71,10 +79,617 @@ async fn test_agent_execution_loop() {
         tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
         let task = orchestrator.get_task(&task_id).await.unwrap();
         if let TaskStatus::Completed = task.status {
-            assert!(task.result.unwrap().contains("Found 0 docs")); // 0 because mock engine returns empty vec
+            assert!(task.result_text().unwrap().contains("Found 0 docs")); // 0 because mock engine returns empty vec
             return;
         }
     }
     
     panic!("Task did not complete in time");
 }
+
+#[test]
+fn test_low_relevance_sources_excluded_from_prompt() {
+    use brainvault_backend::core::agent_orchestrator::build_source_context;
+    use brainvault_backend::core::search_engine::SearchHit;
+
+    let hits = vec![
+        SearchHit { doc_id: "doc_strong".to_string(), score: 0.9, content: Some("Quantum error correction".to_string()), highlights: vec![], version: None },
+        SearchHit { doc_id: "doc_weak".to_string(), score: 0.1, content: Some("Office lunch menu".to_string()), highlights: vec![], version: None },
+    ];
+
+    let context = build_source_context(&hits, 0.3);
+    assert!(context.contains("doc_strong"));
+    assert!(!context.contains("doc_weak"));
+    assert!(!context.contains("Office lunch menu"));
+
+    // Nothing qualifies -> empty context so the agent reports no relevant sources
+    assert!(build_source_context(&hits, 0.95).is_empty());
+}
+
+#[test]
+fn test_search_query_strips_imperative_prefixes() {
+    use brainvault_backend::core::agent_orchestrator::extract_search_query;
+
+    assert_eq!(extract_search_query("Find Goldfinger"), "Goldfinger");
+    assert_eq!(extract_search_query("LOOK UP kubernetes autoscaling please"), "kubernetes autoscaling");
+    assert_eq!(extract_search_query("Search for: quarterly revenue 2024?"), "quarterly revenue 2024");
+    assert_eq!(extract_search_query("Can you please find everything about Project Atlas"), "Project Atlas");
+    assert_eq!(extract_search_query("Tell me about vector databases."), "vector databases");
+    assert_eq!(extract_search_query("Find research papers on lattice cryptography"), "research papers on lattice cryptography");
+
+    // Only a leading instruction is removed; subjects that merely start like a verb survive
+    assert_eq!(extract_search_query("Where did we find the leak"), "Where did we find the leak");
+    assert_eq!(extract_search_query("Searching algorithms overview"), "Searching algorithms overview");
+    assert_eq!(extract_search_query("Research"), "Research");
+}
+
+#[tokio::test]
+async fn test_research_context_respects_chunk_and_token_budgets() {
+    use brainvault_backend::core::agent_orchestrator::{build_budgeted_context, ResearchLimits, TaskOptions};
+    use brainvault_backend::core::llm::tokenizer::{HeuristicTokenizer, Tokenizer};
+    use brainvault_backend::core::search_engine::SearchHit;
+
+    let hit = |id: &str, score: f32| SearchHit {
+        doc_id: id.to_string(),
+        score,
+        content: Some("lattice cryptography ".repeat(50)),
+        highlights: vec![],
+        version: None,
+    };
+    let hits = vec![hit("doc_a", 0.9), hit("doc_b", 0.8), hit("doc_c", 0.7), hit("doc_low", 0.1)];
+
+    // Each source cut to 40 characters; all three relevant ones fit a generous budget
+    let context = build_budgeted_context(&hits, 0.3, 40, 10_000, &HeuristicTokenizer);
+    assert_eq!(context.lines().count(), 3);
+    assert!(context.lines().all(|l| l.chars().count() < 80));
+    assert!(!context.contains("doc_low"));
+
+    // Whole chunks against a tight budget: the best source survives, the rest are dropped
+    let context = build_budgeted_context(&hits, 0.3, 0, 300, &HeuristicTokenizer);
+    assert!(context.contains("doc_a"));
+    assert!(!context.contains("doc_c"));
+    assert!(HeuristicTokenizer.count_tokens(&context) <= 300);
+
+    // Task limits win over the agent's, which win over the defaults
+    let orchestrator = AgentOrchestrator::new(None, None).with_research_limits(5, 2000);
+    let mut profile: AgentProfile = serde_json::from_str(
+        r#"{"id": "deep", "name": "Deep", "agent_type": "Researcher", "capabilities": [], "research": {"chunks": 20}}"#,
+    ).unwrap();
+    let plain = orchestrator.submit_task("Survey post-quantum schemes".to_string()).await;
+    assert_eq!(orchestrator.research_limits(&profile, &plain).await, (20, 2000));
+
+    let options = TaskOptions { research: ResearchLimits { chunks: Some(40), chunk_chars: Some(500) }, ..Default::default() };
+    let deep = orchestrator.submit_task_with(None, "Exhaustive survey".to_string(), Some(AgentType::Researcher), options).await;
+    assert_eq!(orchestrator.research_limits(&profile, &deep).await, (40, 500));
+
+    profile.research.chunks = Some(0);
+    assert!(profile.validate().is_err());
+}
+
+#[tokio::test]
+async fn test_graph_enrichment_includes_mentioned_entity_neighbors() {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use brainvault_backend::core::graph_manager::{KnowledgeGraphManager, Entity, Relationship};
+    use brainvault_backend::db::barq_graph::BarqGraphClient;
+
+    let graph = KnowledgeGraphManager::new(BarqGraphClient::new());
+    graph.add_entity(Entity {
+        id: "acme-corp".to_string(),
+        label: "Company".to_string(),
+        properties: HashMap::from([("name".to_string(), "Acme Corp".to_string())]),
+    }).await.unwrap();
+    graph.add_entity(Entity {
+        id: "acme-finance".to_string(),
+        label: "Department".to_string(),
+        properties: HashMap::from([("name".to_string(), "Acme Finance".to_string())]),
+    }).await.unwrap();
+    graph.add_relationship(Relationship {
+        from_id: "acme-corp".to_string(),
+        to_id: "acme-finance".to_string(),
+        rel_type: "HAS_DEPARTMENT".to_string(),
+        weight: 1.0,
+        properties: HashMap::new(),
+        direction: None,
+    }).await.unwrap();
+
+    let orchestrator = AgentOrchestrator::new(None, Some(Arc::new(graph)))
+        .with_graph_enrichment(vec![AgentType::Analyst]);
+
+    let context = orchestrator.build_graph_context("Analyze spending trends at Acme Corp").await;
+    assert!(context.contains("HAS_DEPARTMENT"));
+    assert!(context.contains("Acme Finance"));
+}
+
+#[tokio::test]
+async fn test_session_id_is_kept_on_task() {
+    let orchestrator = AgentOrchestrator::new(None, None);
+    let task_id = orchestrator
+        .submit_task_in_session(Some("alice".to_string()), Some("s-1".to_string()), "find X".to_string(), None)
+        .await;
+
+    let task = orchestrator.get_task(&task_id).await.unwrap();
+    assert_eq!(task.session_id.as_deref(), Some("s-1"));
+    assert!(orchestrator.get_session("alice", "s-1").await.is_none());
+}
+
+#[test]
+fn test_session_memory_prompt() {
+    use brainvault_backend::core::agent_orchestrator::{SessionMemory, SessionTurn};
+
+    assert!(SessionMemory::default().to_prompt().is_empty());
+
+    let memory = SessionMemory {
+        summary: "User asked about vector stores".to_string(),
+        turns: vec![SessionTurn { request: "find X".to_string(), response: "X1 and X2".to_string() }],
+        ..Default::default()
+    };
+    let prompt = memory.to_prompt();
+    assert!(prompt.contains("Summary of earlier turns: User asked about vector stores"));
+    assert!(prompt.contains("User: find X\nAgent: X1 and X2"));
+}
+
+#[test]
+fn test_parse_json_reply() {
+    use brainvault_backend::core::agent_orchestrator::parse_json_reply;
+
+    assert_eq!(parse_json_reply("{\"a\": 1}").unwrap()["a"], 1);
+    assert_eq!(parse_json_reply("```json\n[1, 2]\n```").unwrap()[1], 2);
+    assert_eq!(parse_json_reply("Here you go: {\"ok\": true} Hope it helps.").unwrap()["ok"], true);
+    assert!(parse_json_reply("LLM Output Mock").is_err());
+}
+
+#[tokio::test]
+async fn test_invalid_json_is_retried_once_then_fails() {
+    use brainvault_backend::core::agent_orchestrator::{format_result, ResponseFormat};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let retries = AtomicUsize::new(0);
+    let retry = |reply: &'static str| {
+        let retries = &retries;
+        move |error: String| async move {
+            assert!(!error.is_empty());
+            retries.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, String>(reply.to_string())
+        }
+    };
+
+    // Valid on the first try: no retry
+    let value = format_result(ResponseFormat::Json, "```json\n{\"score\": 3}\n```".to_string(), retry("{}")).await.unwrap();
+    assert_eq!(value["score"].as_i64(), Some(3));
+    assert_eq!(retries.load(Ordering::SeqCst), 0);
+
+    // Invalid, then valid on the retry
+    let value = format_result(ResponseFormat::Json, "LLM Output Mock".to_string(), retry("{\"score\": 4}")).await.unwrap();
+    assert_eq!(value["score"].as_i64(), Some(4));
+    assert_eq!(retries.load(Ordering::SeqCst), 1);
+
+    // Invalid twice: the task fails instead of completing with wrapped text
+    let err = format_result(ResponseFormat::Json, "LLM Output Mock".to_string(), retry("still not JSON")).await.unwrap_err();
+    assert!(err.contains("valid JSON"));
+    assert_eq!(retries.load(Ordering::SeqCst), 2);
+
+    let value = format_result(ResponseFormat::Markdown, "# Report".to_string(), retry("{}")).await.unwrap();
+    assert_eq!(value.as_str(), Some("# Report"));
+    assert_eq!(retries.load(Ordering::SeqCst), 2);
+    assert!(ResponseFormat::Json.prompt_instruction().contains("JSON"));
+}
+
+#[test]
+fn test_agent_system_prompt_falls_back_to_type_default() {
+    let mut profile = AgentProfile {
+        id: "legal_1".to_string(),
+        name: "Counsel".to_string(),
+        agent_type: AgentType::Analyst,
+        capabilities: vec![],
+        system_prompt: None,
+        max_tokens: None,
+        temperature: None,
+        research: Default::default(),
+    };
+    assert_eq!(profile.effective_system_prompt(), AgentType::Analyst.default_system_prompt());
+
+    profile.system_prompt = Some("You are a legal analyst specialising in contract risk.".to_string());
+    assert_eq!(profile.effective_system_prompt(), "You are a legal analyst specialising in contract risk.");
+
+    let parsed: AgentProfile = serde_json::from_str(r#"{"id": "r", "name": "R", "agent_type": "Reviewer", "capabilities": []}"#).unwrap();
+    assert!(parsed.system_prompt.is_none());
+}
+
+#[tokio::test]
+async fn test_deregister_refuses_agent_with_in_flight_tasks() {
+    let orchestrator = AgentOrchestrator::new(None, None);
+    orchestrator.register_agent(AgentProfile {
+        id: "roster-analyst".to_string(),
+        name: "Roster".to_string(),
+        agent_type: AgentType::Analyst,
+        capabilities: vec![],
+        system_prompt: None,
+        max_tokens: None,
+        temperature: None,
+        research: Default::default(),
+    }).await;
+
+    let task_id = orchestrator.submit_task("Summarize Q3".to_string(), Some(AgentType::Analyst)).await;
+    orchestrator.assign_task(&task_id).await.unwrap();
+
+    let roster = orchestrator.list_agents().await;
+    assert_eq!(roster.len(), 1);
+    assert_eq!(roster[0].active_tasks, 1);
+    assert!(orchestrator.deregister_agent("roster-analyst").await.is_err());
+
+    orchestrator.complete_task(&task_id, "Done".to_string()).await.unwrap();
+    assert_eq!(orchestrator.list_agents().await[0].completed_tasks, 1);
+    assert!(orchestrator.deregister_agent("roster-analyst").await.is_ok());
+    assert!(orchestrator.list_agents().await.is_empty());
+    assert!(orchestrator.deregister_agent("roster-analyst").await.is_err());
+}
+
+#[tokio::test]
+async fn test_list_tasks_filters_and_paginates() {
+    let orchestrator = AgentOrchestrator::new(None, None);
+    orchestrator.register_agent(AgentProfile {
+        id: "queue-researcher".to_string(),
+        name: "Queue".to_string(),
+        agent_type: AgentType::Researcher,
+        capabilities: vec![],
+        system_prompt: None,
+        max_tokens: None,
+        temperature: None,
+        research: Default::default(),
+    }).await;
+
+    let mut ids = Vec::new();
+    for i in 0..3 {
+        ids.push(orchestrator.submit_task(format!("Task {}", i), Some(AgentType::Researcher)).await);
+        tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+    }
+    orchestrator.assign_task(&ids[0]).await.unwrap();
+
+    let in_progress = orchestrator.list_tasks(&TaskFilter { status: Some(TaskStatus::InProgress), ..Default::default() }).await;
+    assert_eq!(in_progress.total, 1);
+    assert_eq!(in_progress.tasks[0].id, ids[0]);
+
+    let by_agent = orchestrator.list_tasks(&TaskFilter { agent_id: Some("queue-researcher".to_string()), ..Default::default() }).await;
+    assert_eq!(by_agent.total, 1);
+
+    let newest = orchestrator.list_tasks(&TaskFilter { limit: Some(2), ..Default::default() }).await;
+    assert_eq!(newest.total, 3);
+    assert_eq!(newest.tasks.iter().map(|t| t.id.clone()).collect::<Vec<_>>(), vec![ids[2].clone(), ids[1].clone()]);
+
+    let oldest = orchestrator.list_tasks(&TaskFilter { oldest_first: true, offset: 1, ..Default::default() }).await;
+    assert_eq!(oldest.tasks.len(), 2);
+    assert_eq!(oldest.tasks[0].id, ids[1]);
+}
+
+#[test]
+fn test_agent_generation_params_are_validated() {
+    use brainvault_backend::core::llm::nafs_provider::GenerationParams;
+
+    let mut profile: AgentProfile = serde_json::from_str(
+        r#"{"id": "a", "name": "A", "agent_type": "Analyst", "capabilities": [], "temperature": 0.1}"#,
+    ).unwrap();
+    assert!(profile.validate().is_ok());
+    let params = profile.generation_params(GenerationParams::default());
+    assert_eq!(params, GenerationParams { max_tokens: 2000, temperature: 0.1 });
+
+    profile.temperature = Some(2.5);
+    assert!(profile.validate().is_err());
+    profile.temperature = None;
+    profile.max_tokens = Some(0);
+    assert!(profile.validate().is_err());
+}
+
+#[tokio::test]
+async fn test_task_result_can_be_structured() {
+    use brainvault_backend::core::agent_orchestrator::Task;
+
+    let orchestrator = AgentOrchestrator::new(None, None);
+    let task_id = orchestrator.submit_task("List findings".to_string(), None).await;
+    let findings = serde_json::json!({"findings": [{"id": 1, "severity": "high"}]});
+    orchestrator.complete_task(&task_id, findings.clone()).await.unwrap();
+
+    let task = orchestrator.get_task(&task_id).await.unwrap();
+    assert_eq!(task.result, Some(findings.clone()));
+    assert_eq!(task.result_text().unwrap(), findings.to_string());
+
+    // Tasks serialized with a plain string result still load, as text
+    let mut stored = serde_json::to_value(&task).unwrap();
+    stored["result"] = serde_json::Value::String("Found him in Miami".to_string());
+    let legacy: Task = serde_json::from_value(stored).unwrap();
+    assert_eq!(legacy.result_text().unwrap(), "Found him in Miami");
+}
+
+#[tokio::test]
+async fn test_assignment_wakes_idle_agent_loop() {
+    use std::sync::Arc;
+
+    let orchestrator = Arc::new(
+        AgentOrchestrator::new(None, None).with_loop_interval(std::time::Duration::from_secs(60))
+    );
+    orchestrator.register_agent(AgentProfile {
+        id: "wakeup-agent".to_string(),
+        name: "Watson".to_string(),
+        agent_type: AgentType::Reviewer,
+        capabilities: vec![],
+        system_prompt: None,
+        max_tokens: None,
+        temperature: None,
+        research: Default::default(),
+    }).await;
+
+    let orch_clone = orchestrator.clone();
+    tokio::spawn(async move {
+        orch_clone.run_agent_loop().await;
+    });
+
+    let task_id = orchestrator.submit_task("Draft a memo".to_string(), None).await;
+    orchestrator.assign_task(&task_id).await.unwrap();
+
+    for _ in 0..20 {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let task = orchestrator.get_task(&task_id).await.unwrap();
+        if !matches!(task.status, TaskStatus::InProgress) {
+            return;
+        }
+    }
+    panic!("Agent loop did not pick up the task before its idle interval");
+}
+
+#[tokio::test]
+async fn test_dispatch_starts_each_task_once() {
+    let orchestrator = AgentOrchestrator::new(None, None);
+    orchestrator.register_agent(AgentProfile {
+        id: "dispatch-agent".to_string(),
+        name: "Poirot".to_string(),
+        agent_type: AgentType::Reviewer,
+        capabilities: vec![],
+        system_prompt: None,
+        max_tokens: None,
+        temperature: None,
+        research: Default::default(),
+    }).await;
+
+    let task_id = orchestrator.submit_task("Review the memo".to_string(), None).await;
+    orchestrator.assign_task(&task_id).await.unwrap();
+
+    assert_eq!(orchestrator.dispatch_assigned_tasks().await, vec![task_id.clone()]);
+    assert!(orchestrator.dispatch_assigned_tasks().await.is_empty());
+
+    for _ in 0..40 {
+        if !orchestrator.is_in_flight(&task_id) {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    panic!("Task stayed in flight after its run");
+}
+
+#[actix_web::test]
+async fn test_task_audit_endpoint_shows_the_agent_trail() {
+    use actix_web::{test, web, App};
+    use brainvault_backend::api::handlers::agents::get_task_audit;
+
+    let orchestrator = AgentOrchestrator::new(None, None);
+    orchestrator.register_agent(AgentProfile {
+        id: "audit-researcher".to_string(),
+        name: "Auditor".to_string(),
+        agent_type: AgentType::Researcher,
+        capabilities: vec![],
+        system_prompt: None,
+        max_tokens: None,
+        temperature: None,
+        research: Default::default(),
+    }).await;
+    let task_id = orchestrator.submit_task_as(Some("auditor".to_string()), "Trace the supply chain".to_string(), None).await;
+    orchestrator.assign_task(&task_id).await.unwrap();
+    orchestrator.complete_task(&task_id, "Traced".to_string()).await.unwrap();
+
+    let app = test::init_service(App::new()
+        .app_data(web::Data::new(orchestrator))
+        .app_data(web::Data::new(brainvault_backend::core::rbac::RBAC::new()))
+        .service(get_task_audit)).await;
+
+    let req = test::TestRequest::get().uri(&format!("/api/agents/task/{}/audit", task_id)).insert_header(("X-User-ID", "auditor")).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["status"], "Completed");
+    assert_eq!(body["assigned_agent_id"], "audit-researcher");
+    let actions: Vec<&str> = body["entries"].as_array().unwrap().iter().map(|e| e["action"].as_str().unwrap()).collect();
+    assert_eq!(actions, vec!["SUBMITTED", "ASSIGNED", "COMPLETED"]);
+    assert!(body["finished_at"].as_u64().unwrap() >= body["submitted_at"].as_u64().unwrap());
+
+    let req = test::TestRequest::get().uri(&format!("/api/agents/task/{}/audit?action=assigned", task_id)).insert_header(("X-User-ID", "auditor")).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["entries"].as_array().unwrap().len(), 1);
+
+    let req = test::TestRequest::get().uri("/api/agents/task/no-such-task/audit").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), actix_web::http::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_cancelled_task_stays_cancelled() {
+    use brainvault_backend::error::BrainVaultError;
+
+    let orchestrator = AgentOrchestrator::new(None, None);
+    orchestrator.register_agent(AgentProfile {
+        id: "cancel-researcher".to_string(),
+        name: "Quitter".to_string(),
+        agent_type: AgentType::Researcher,
+        capabilities: vec![],
+        system_prompt: None,
+        max_tokens: None,
+        temperature: None,
+        research: Default::default(),
+    }).await;
+    let task_id = orchestrator.submit_task("Long survey".to_string()).await;
+    orchestrator.assign_task(&task_id).await.unwrap();
+
+    let task = orchestrator.cancel_task(&task_id, "Cancelled by tester").await.unwrap();
+    assert_eq!(task.status, TaskStatus::Cancelled);
+    assert_eq!(task.audit_log.last().unwrap().action, "CANCELLED");
+
+    // Never dispatched, and a late result doesn't revive it
+    assert!(orchestrator.dispatch_assigned_tasks().await.is_empty());
+    orchestrator.complete_task(&task_id, "Too late".to_string()).await.unwrap();
+    let task = orchestrator.get_task(&task_id).await.unwrap();
+    assert_eq!(task.status, TaskStatus::Cancelled);
+    assert!(task.result.is_none());
+
+    assert!(matches!(orchestrator.cancel_task(&task_id, "again").await, Err(BrainVaultError::Conflict(_))));
+    let summary = orchestrator.list_agents().await.into_iter().find(|a| a.profile.id == "cancel-researcher").unwrap();
+    assert_eq!((summary.active_tasks, summary.cancelled_tasks), (0, 1));
+}
+
+#[tokio::test]
+async fn test_result_processors_modify_or_reject_results() {
+    use brainvault_backend::core::agent_orchestrator::{ResponseFormat, TaskOptions};
+    use brainvault_backend::core::result_processor::{JsonSchemaValidator, LengthCap};
+    use brainvault_backend::error::BrainVaultError;
+
+    let schema = serde_json::json!({
+        "type": "object",
+        "required": ["summary", "confidence"],
+        "properties": {
+            "summary": {"type": "string", "minLength": 1},
+            "confidence": {"type": "number", "minimum": 0, "maximum": 1}
+        }
+    });
+    let orchestrator = AgentOrchestrator::new(None, None)
+        .with_result_processor(JsonSchemaValidator { schema })
+        .with_result_processor(LengthCap { max_chars: 10 });
+
+    // Text results are cut to the cap and the original kept; the schema doesn't apply to them
+    let text = orchestrator.submit_task_with(None, "Summarize".to_string(), None, TaskOptions::default()).await;
+    orchestrator.complete_task(&text, "A rather long summary".to_string()).await.unwrap();
+    let task = orchestrator.get_task(&text).await.unwrap();
+    assert_eq!(task.status, TaskStatus::Completed);
+    assert_eq!(task.result.unwrap().as_str().unwrap().chars().count(), 10);
+    assert_eq!(task.raw_result.as_deref(), Some("A rather long summary"));
+    assert!(task.audit_log.iter().any(|e| e.action == "POSTPROCESSED" && e.details.contains("length_cap")));
+
+    // JSON results that miss the schema fail the task
+    let options = TaskOptions { response_format: ResponseFormat::Json, ..Default::default() };
+    let json = orchestrator.submit_task_with(None, "Rate it".to_string(), None, options).await;
+    let err = orchestrator.complete_task(&json, serde_json::json!({"summary": "", "confidence": 2})).await.unwrap_err();
+    match err {
+        BrainVaultError::BadRequest(reason) => {
+            assert!(reason.contains("json_schema"));
+            assert!(reason.contains("/summary") && reason.contains("/confidence"));
+        }
+        other => panic!("expected BadRequest, got {:?}", other),
+    }
+    let task = orchestrator.get_task(&json).await.unwrap();
+    assert_eq!(task.status, TaskStatus::Failed);
+    assert!(task.result.is_none());
+}
+
+#[tokio::test]
+async fn test_sessions_are_private_to_their_user_and_evicted() {
+    use brainvault_backend::core::search_engine::{HybridSearchEngine, SearchWeights};
+    use brainvault_backend::db::barq_vector::BarqVectorClient;
+    use brainvault_backend::error::BrainVaultError;
+    use std::time::Duration;
+
+    let engine = HybridSearchEngine::new(BarqVectorClient::new(), SearchWeights { vector_weight: 0.5, bm25_weight: 0.5 });
+    let orchestrator = AgentOrchestrator::new(Some(std::sync::Arc::new(engine)), None)
+        .with_session_limits(2, Duration::from_secs(3600));
+    orchestrator.register_agent(AgentProfile {
+        id: "session-researcher".to_string(),
+        name: "Keeper".to_string(),
+        agent_type: AgentType::Researcher,
+        capabilities: vec![],
+        system_prompt: None,
+        max_tokens: None,
+        temperature: None,
+        research: Default::default(),
+    }).await;
+
+    let run = |user: &'static str, session: &'static str| {
+        let orchestrator = orchestrator.clone();
+        async move {
+            let task_id = orchestrator
+                .submit_task_in_session(Some(user.to_string()), Some(session.to_string()), "find X".to_string(), None)
+                .await;
+            orchestrator.assign_task(&task_id).await.unwrap();
+            orchestrator.dispatch_assigned_tasks().await;
+            for _ in 0..50 {
+                if !orchestrator.is_in_flight(&task_id) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    };
+
+    run("alice", "shared-id").await;
+    assert_eq!(orchestrator.get_session("alice", "shared-id").await.unwrap().turns.len(), 1);
+    assert!(orchestrator.get_session("mallory", "shared-id").await.is_none());
+    assert!(orchestrator.check_session_owner("alice", "shared-id").await.is_ok());
+    assert!(matches!(orchestrator.check_session_owner("mallory", "shared-id").await, Err(BrainVaultError::Unauthorized(_))));
+
+    // At the cap of two, the least recently used session goes first
+    run("bob", "s-b").await;
+    run("carol", "s-c").await;
+    assert!(orchestrator.get_session("alice", "shared-id").await.is_none());
+    assert!(orchestrator.get_session("bob", "s-b").await.is_some());
+    assert!(orchestrator.get_session("carol", "s-c").await.is_some());
+}
+
+#[actix_web::test]
+async fn test_only_submitter_or_admin_can_cancel_or_audit_a_task() {
+    use actix_web::{http::StatusCode, test, web, App};
+    use brainvault_backend::api::handlers::agents::{cancel_task, get_task_audit};
+    use brainvault_backend::core::rbac::{Permission, Role, RBAC};
+
+    let orchestrator = AgentOrchestrator::new(None, None);
+    let mut rbac = RBAC::new();
+    rbac.add_permission(Permission {
+        user_id: "root".to_string(),
+        role: Role::Admin,
+        accessible_entities: vec![],
+        accessible_collections: vec![],
+        excluded_entities: vec![],
+        expires_at: None,
+    });
+    let alice_task = orchestrator.submit_task_as(Some("alice".to_string()), "Audit vendors".to_string(), None).await;
+    let other_task = orchestrator.submit_task_as(Some("bob".to_string()), "Audit payroll".to_string(), None).await;
+
+    let app = test::init_service(App::new()
+        .app_data(web::Data::new(orchestrator.clone()))
+        .app_data(web::Data::new(rbac))
+        .service(get_task_audit)
+        .service(cancel_task)).await;
+    let cancel = |task: &str, user: &str| test::TestRequest::post()
+        .uri(&format!("/api/agents/task/{}/cancel", task))
+        .insert_header(("X-User-ID", user))
+        .to_request();
+    let audit = |task: &str, user: &str| test::TestRequest::get()
+        .uri(&format!("/api/agents/task/{}/audit", task))
+        .insert_header(("X-User-ID", user))
+        .to_request();
+
+    for user in ["mallory", "anonymous"] {
+        assert_eq!(test::call_service(&app, cancel(&alice_task, user)).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(test::call_service(&app, audit(&alice_task, user)).await.status(), StatusCode::FORBIDDEN);
+    }
+    assert_eq!(orchestrator.get_task(&alice_task).await.unwrap().status, TaskStatus::Pending);
+
+    assert_eq!(test::call_service(&app, audit(&alice_task, "alice")).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, cancel(&alice_task, "alice")).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, cancel(&other_task, "root")).await.status(), StatusCode::OK);
+    assert_eq!(orchestrator.get_task(&other_task).await.unwrap().status, TaskStatus::Cancelled);
+}
+
+#[tokio::test]
+async fn test_usage_outside_tasks_counts_toward_the_user() {
+    use brainvault_backend::core::llm::usage::TokenUsage;
+
+    let orchestrator = AgentOrchestrator::new(None, None);
+    let usage = TokenUsage { prompt_tokens: 120, completion_tokens: 30, estimated: false };
+    orchestrator.record_user_usage("alice", &usage).await;
+    orchestrator.record_user_usage("alice", &usage).await;
+
+    let report = orchestrator.get_usage_report().await;
+    assert_eq!(report.by_user["alice"].total_tokens(), 300);
+    assert_eq!(report.total.total_tokens(), 300);
+}
@@ -0,0 +1,37 @@
+use brainvault_backend::core::output_filter::OutputFilter;
+
+#[test]
+fn test_preamble_removed_content_preserved() {
+    let filter = OutputFilter::default();
+    let raw = "Sure, here's the summary you asked for:\n\nThe retention policy requires 7 years of storage.\n\nI hope this helps! Let me know if you need anything else.";
+
+    let cleaned = filter.clean(raw);
+    assert_eq!(cleaned, "The retention policy requires 7 years of storage.");
+}
+
+#[test]
+fn test_plain_output_untouched() {
+    let filter = OutputFilter::default();
+    let raw = "Key findings:\n- Revenue grew 12%";
+    assert_eq!(filter.clean(raw), raw);
+}
+
+#[test]
+fn test_answers_starting_with_sure_or_here_are_kept() {
+    let filter = OutputFilter::default();
+    for raw in [
+        "Here are the results: revenue grew 12%.\nCosts fell 3%.",
+        "Sure-fire ways to cut costs:\n- Renegotiate vendor contracts",
+        "Certainly the biggest risk is vendor lock-in.\nThe second is cost.",
+        "Here is the summary:",
+    ] {
+        assert_eq!(filter.clean(raw), raw);
+    }
+}
+
+#[test]
+fn test_only_the_first_line_is_a_preamble() {
+    let filter = OutputFilter::default();
+    let raw = "Sure!\nHere is the breakdown:\n- Storage: 7 years";
+    assert_eq!(filter.clean(raw), "Here is the breakdown:\n- Storage: 7 years");
+}
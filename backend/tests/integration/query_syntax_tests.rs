@@ -0,0 +1,76 @@
+use brainvault_backend::core::query_syntax::{parse_query, FieldClause, QueryField};
+use brainvault_backend::core::text_analysis::TextAnalyzer;
+use brainvault_backend::db::barq_vector::BarqVectorClient;
+
+#[test]
+fn test_parse_phrases_and_field_clauses() {
+    let parsed = parse_query(r#"retention "data retention policy" title:"Remote Work" id:hr- http://intranet"#);
+    assert_eq!(parsed.terms, vec!["retention", "http://intranet"]);
+    assert_eq!(parsed.phrases, vec!["data retention policy"]);
+    assert_eq!(parsed.fields, vec![
+        FieldClause { field: QueryField::Title, value: "Remote Work".to_string() },
+        FieldClause { field: QueryField::DocId, value: "hr-".to_string() },
+    ]);
+    assert_eq!(parsed.text(), "retention http://intranet data retention policy Remote Work");
+
+    // Unterminated quotes run to the end of the query
+    assert_eq!(parse_query(r#"policy "open ended"#).phrases, vec!["open ended"]);
+}
+
+#[tokio::test]
+async fn test_phrase_and_title_clauses_filter_local_search() {
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-syntax-test")
+        .with_analyzer(TextAnalyzer::default());
+    client.index_document("syntax-policy", "Data Retention\nOur data retention policy keeps records for seven years").await.unwrap();
+    client.index_document("syntax-memo", "Memo\nRetention of policy data is reviewed by legal").await.unwrap();
+
+    let ids = |hits: Vec<brainvault_backend::db::barq_vector::SearchHit>| hits.into_iter().map(|h| h.doc_id).collect::<Vec<_>>();
+    assert_eq!(ids(client.bm25_search(r#""data retention policy""#, 5).await.unwrap()), vec!["syntax-policy"]);
+    assert_eq!(ids(client.bm25_search("title:memo retention", 5).await.unwrap()), vec!["syntax-memo"]);
+    assert_eq!(ids(client.bm25_search("id:syntax-policy", 5).await.unwrap()), vec!["syntax-policy"]);
+}
+
+#[test]
+fn test_phrases_without_words_are_ignored() {
+    let parsed = parse_query(r#"retention "--" "  ""#);
+    assert!(parsed.phrases.is_empty());
+    assert!(!parsed.has_constraints());
+}
+
+#[tokio::test]
+async fn test_phrases_made_of_stopwords_match_exactly() {
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-stopword-phrase-test")
+        .with_analyzer(TextAnalyzer::default());
+    client.index_document("soliloquy", "To be, or not to be, that is the question").await.unwrap();
+    client.index_document("schedule", "Reviews will be done or not, as needed").await.unwrap();
+
+    let hits = client.bm25_search(r#"question "to be or not to be""#, 5).await.unwrap();
+    assert_eq!(hits.into_iter().map(|h| h.doc_id).collect::<Vec<_>>(), vec!["soliloquy"]);
+}
+
+/// Gives every text the same vector, so dense ranking falls back to doc id order
+struct FlatEmbedder;
+
+#[async_trait::async_trait]
+impl brainvault_backend::core::llm::embeddings::EmbeddingProvider for FlatEmbedder {
+    fn name(&self) -> &str { "test" }
+    fn model(&self) -> &str { "test-embedding" }
+    fn dimension(&self) -> usize { 3 }
+    async fn embed(&self, _text: &str) -> Result<Vec<f32>, brainvault_backend::core::llm::retry::ProviderError> {
+        Ok(vec![0.1, 0.2, 0.3])
+    }
+}
+
+#[tokio::test]
+async fn test_dense_search_filters_before_taking_top_k() {
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-dense-phrase-test")
+        .with_embedder(std::sync::Arc::new(FlatEmbedder))
+        .with_dimension(3);
+    client.index_document("a-minutes", "Retention was discussed at length").await.unwrap();
+    client.index_document("b-minutes", "Retention schedules were approved").await.unwrap();
+    client.index_document("z-policy", "Our data retention policy keeps records for seven years").await.unwrap();
+
+    // The only match ranks last, below a top_k of 1
+    let hits = client.dense_search_in(r#""data retention policy""#, 1, None).await.unwrap().expect("query is embeddable");
+    assert_eq!(hits.into_iter().map(|h| h.doc_id).collect::<Vec<_>>(), vec!["z-policy"]);
+}
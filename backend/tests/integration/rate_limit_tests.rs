@@ -0,0 +1,25 @@
+use brainvault_backend::api::middleware::rate_limit::{RateLimiter, ANONYMOUS_BUCKET};
+
+#[test]
+fn test_bucket_rejects_after_burst_with_retry_after() {
+    let limiter = RateLimiter::new(2, 1);
+
+    assert!(limiter.check("alice", true).is_ok());
+    assert!(limiter.check("alice", true).is_ok());
+    let retry_after = limiter.check("alice", true).unwrap_err();
+    assert!(retry_after.as_secs_f64() > 0.0 && retry_after.as_secs_f64() <= 30.0);
+
+    // Other users have their own bucket
+    assert!(limiter.check("bob", true).is_ok());
+}
+
+#[test]
+fn test_anonymous_traffic_shares_stricter_bucket() {
+    let limiter = RateLimiter::new(100, 1);
+
+    assert!(limiter.check(ANONYMOUS_BUCKET, false).is_ok());
+    assert!(limiter.check(ANONYMOUS_BUCKET, false).is_err());
+    assert!(limiter.applies_to("/api/search"));
+    assert!(limiter.applies_to("/api/agents/chat"));
+    assert!(!limiter.applies_to("/api/health"));
+}
@@ -10,12 +10,14 @@ async fn test_rbac_filtering() {
         role: Role::Viewer,
         accessible_entities: vec!["doc_1".to_string(), "doc_2".to_string()],
         accessible_collections: vec![],
+        excluded_entities: vec![],
+        expires_at: None,
     });
     
     let results = SearchResults {
         hits: vec![
-            SearchHit { doc_id: "doc_1".to_string(), score: 1.0, content: None },
-            SearchHit { doc_id: "doc_3".to_string(), score: 0.9, content: None },
+            SearchHit { doc_id: "doc_1".to_string(), score: 1.0, content: None, highlights: vec![], version: None },
+            SearchHit { doc_id: "doc_3".to_string(), score: 0.9, content: None, highlights: vec![], version: None },
         ],
     };
     
@@ -32,8 +34,447 @@ async fn test_admin_access() {
         role: Role::Admin,
         accessible_entities: vec![],
         accessible_collections: vec![],
+        excluded_entities: vec![],
+        expires_at: None,
     });
     
     let checks = rbac.check_access("admin", "any_doc").await;
     assert!(checks.unwrap());
 }
+
+#[tokio::test]
+async fn test_collection_access() {
+    let mut rbac = RBAC::new();
+    rbac.add_permission(Permission {
+        user_id: "team_a".to_string(),
+        role: Role::Viewer,
+        accessible_entities: vec![],
+        accessible_collections: vec!["team-a".to_string()],
+        excluded_entities: vec![],
+        expires_at: None,
+    });
+
+    assert!(rbac.check_collection_access("team_a", "team-a").await.unwrap());
+    assert!(!rbac.check_collection_access("team_a", "team-b").await.unwrap());
+    assert!(rbac.check_collection_access("stranger", "team-a").await.is_err());
+}
+
+#[tokio::test]
+async fn test_collection_membership_grants_entity_access() {
+    use brainvault_backend::core::graph_manager::{ContextGraph, Entity};
+    use std::collections::HashMap;
+
+    let mut rbac = RBAC::new();
+    rbac.add_permission(Permission {
+        user_id: "team_a".to_string(),
+        role: Role::Viewer,
+        accessible_entities: vec!["doc_listed".to_string()],
+        accessible_collections: vec!["team-a".to_string()],
+        excluded_entities: vec![],
+        expires_at: None,
+    });
+
+    assert!(rbac.check_access_in("team_a", "doc_x", Some("team-a")).await.unwrap());
+    assert!(!rbac.check_access_in("team_a", "doc_x", Some("team-b")).await.unwrap());
+    assert!(!rbac.check_access("team_a", "doc_x").await.unwrap());
+    assert!(rbac.check_access("team_a", "doc_listed").await.unwrap());
+
+    let hit = |id: &str| SearchHit { doc_id: id.to_string(), score: 1.0, content: None, highlights: vec![], version: None };
+    let results = SearchResults { hits: vec![hit("doc_a1"), hit("doc_b1@2"), hit("doc_listed"), hit("doc_default")] };
+    let collections = HashMap::from([
+        ("doc_a1".to_string(), "team-a".to_string()),
+        ("doc_b1".to_string(), "team-b".to_string()),
+    ]);
+    let permitted = rbac.get_permitted_search_results_in("team_a", results, &collections).await;
+    let ids: Vec<&str> = permitted.hits.iter().map(|h| h.doc_id.as_str()).collect();
+    assert_eq!(ids, vec!["doc_a1", "doc_listed"]);
+
+    let entity = |id: &str, collection: Option<&str>| Entity {
+        id: id.to_string(),
+        label: "Team".to_string(),
+        properties: collection.map(|c| HashMap::from([("collection".to_string(), c.to_string())])).unwrap_or_default(),
+    };
+    let context = ContextGraph {
+        entities: vec![entity("ent_a", Some("team-a")), entity("ent_b", Some("team-b")), entity("ent_none", None)],
+        relationships: vec![],
+        depths: HashMap::new(),
+        next_cursor: None,
+    };
+    let filtered = rbac.filter_context("team_a", context).await.unwrap();
+    let kept: Vec<&str> = filtered.entities.iter().map(|e| e.id.as_str()).collect();
+    assert_eq!(kept, vec!["ent_a"]);
+}
+
+#[tokio::test]
+async fn test_exclusions_override_grants() {
+    use brainvault_backend::core::graph_manager::{ContextGraph, Entity};
+    use std::collections::HashMap;
+
+    let mut rbac = RBAC::new();
+    rbac.add_permission(Permission {
+        user_id: "hr_viewer".to_string(),
+        role: Role::Viewer,
+        accessible_entities: vec!["salaries".to_string()],
+        accessible_collections: vec!["hr".to_string()],
+        excluded_entities: vec!["salaries".to_string(), "grievance-17".to_string()],
+        expires_at: None,
+    });
+    rbac.add_permission(Permission {
+        user_id: "admin".to_string(),
+        role: Role::Admin,
+        accessible_entities: vec![],
+        accessible_collections: vec![],
+        excluded_entities: vec!["grievance-17".to_string()],
+        expires_at: None,
+    });
+
+    assert!(rbac.check_access_in("hr_viewer", "handbook", Some("hr")).await.unwrap());
+    assert!(!rbac.check_access_in("hr_viewer", "grievance-17", Some("hr")).await.unwrap());
+    assert!(!rbac.check_access("hr_viewer", "salaries").await.unwrap());
+    assert!(!rbac.check_access("admin", "grievance-17").await.unwrap());
+
+    let hit = |id: &str| SearchHit { doc_id: id.to_string(), score: 1.0, content: None, highlights: vec![], version: None };
+    let collections = HashMap::from([
+        ("handbook".to_string(), "hr".to_string()),
+        ("grievance-17".to_string(), "hr".to_string()),
+    ]);
+    let results = SearchResults { hits: vec![hit("handbook"), hit("grievance-17@3"), hit("salaries")] };
+    let permitted = rbac.get_permitted_search_results_in("hr_viewer", results.clone(), &collections).await;
+    let ids: Vec<&str> = permitted.hits.iter().map(|h| h.doc_id.as_str()).collect();
+    assert_eq!(ids, vec!["handbook"]);
+    assert_eq!(rbac.get_permitted_search_results("admin", results).await.hits.len(), 2);
+
+    let entity = |id: &str| Entity { id: id.to_string(), label: "Doc".to_string(), properties: HashMap::new() };
+    let context = ContextGraph {
+        entities: vec![entity("policy"), entity("grievance-17")],
+        relationships: vec![],
+        depths: HashMap::new(),
+        next_cursor: None,
+    };
+    let filtered = rbac.filter_context("admin", context).await.unwrap();
+    let kept: Vec<&str> = filtered.entities.iter().map(|e| e.id.as_str()).collect();
+    assert_eq!(kept, vec!["policy"]);
+}
+
+#[tokio::test]
+async fn test_group_grants_union_with_direct_grants_and_persist() {
+    use brainvault_backend::core::rbac::Group;
+
+    let grant = |id: &str, role: Role, entities: &[&str], collections: &[&str]| Permission {
+        user_id: id.to_string(),
+        role,
+        accessible_entities: entities.iter().map(|e| e.to_string()).collect(),
+        accessible_collections: collections.iter().map(|c| c.to_string()).collect(),
+        excluded_entities: vec![],
+        expires_at: None,
+    };
+    let mut rbac = RBAC::new();
+    rbac.add_permission(grant("dana", Role::Viewer, &["memo-1"], &[]));
+    rbac.add_group(Group { id: "finance".to_string(), members: vec!["dana".to_string(), "eli".to_string()] });
+    rbac.add_group(Group { id: "owners".to_string(), members: vec!["dana".to_string()] });
+    rbac.add_group_permission(grant("finance", Role::Viewer, &[], &["finance"]));
+    rbac.add_group_permission(grant("owners", Role::DataOwner, &["budget-2024"], &[]));
+
+    let dana = rbac.get_permission("dana").await.unwrap();
+    assert_eq!(dana.role, Role::DataOwner);
+    assert!(rbac.check_access("dana", "memo-1").await.unwrap());
+    assert!(rbac.check_access("dana", "budget-2024").await.unwrap());
+    assert!(rbac.check_collection_access("dana", "finance").await.unwrap());
+
+    // Group membership alone makes a user known
+    assert!(rbac.check_collection_access("eli", "finance").await.unwrap());
+    assert!(!rbac.check_access("eli", "memo-1").await.unwrap());
+    assert!(rbac.get_permission("mallory").await.is_err());
+    assert_eq!(rbac.groups_of("dana"), vec!["finance", "owners"]);
+
+    let dir = std::env::temp_dir().join(format!("brainvault-rbac-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let data_path = dir.to_string_lossy().to_string();
+    rbac.save(&data_path).unwrap();
+    let reloaded = RBAC::at_path(&data_path);
+    assert_eq!(reloaded.get_permission("dana").await.unwrap().role, Role::DataOwner);
+    assert!(reloaded.check_collection_access("eli", "finance").await.unwrap());
+}
+
+#[tokio::test]
+async fn test_grant_stops_working_after_expiry() {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    let grant = |id: &str, expires_at: Option<u64>| Permission {
+        user_id: id.to_string(),
+        role: Role::Viewer,
+        accessible_entities: vec!["ledger".to_string()],
+        accessible_collections: vec![],
+        excluded_entities: vec![],
+        expires_at,
+    };
+    let mut rbac = RBAC::new();
+    rbac.add_permission(grant("auditor", Some(now + 1)));
+    rbac.add_permission(grant("contractor", Some(now - 10)));
+    rbac.add_permission(grant("employee", None));
+
+    assert!(rbac.check_access("auditor", "ledger").await.unwrap());
+    assert!(rbac.get_permission("contractor").await.is_err());
+
+    tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+    assert!(rbac.check_access("auditor", "ledger").await.is_err());
+    assert!(rbac.check_access("employee", "ledger").await.unwrap());
+
+    assert_eq!(rbac.reap_expired(), 2);
+    assert!(rbac.has_permission("employee"));
+    assert!(!rbac.has_permission("auditor"));
+}
+
+#[tokio::test]
+async fn test_expired_grants_are_reaped_on_load() {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    let grant = |id: &str, expires_at: Option<u64>| Permission {
+        user_id: id.to_string(),
+        role: Role::Viewer,
+        accessible_entities: vec!["ledger".to_string()],
+        accessible_collections: vec![],
+        excluded_entities: vec![],
+        expires_at,
+    };
+    let mut rbac = RBAC::new();
+    rbac.add_permission(grant("contractor", Some(now - 10)));
+    rbac.add_permission(grant("employee", None));
+    let dir = std::env::temp_dir().join(format!("brainvault-rbac-reap-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let data_path = dir.to_string_lossy().to_string();
+    rbac.save(&data_path).unwrap();
+
+    let reloaded = RBAC::at_path(&data_path);
+    assert!(!reloaded.has_permission("contractor"));
+    assert!(reloaded.has_permission("employee"));
+    let saved = std::fs::read_to_string(dir.join("rbac.json")).unwrap();
+    assert!(!saved.contains("contractor"));
+}
+
+#[tokio::test]
+async fn test_explain_access_names_the_deciding_rule() {
+    use brainvault_backend::core::rbac::{AccessReason, Group};
+
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    let mut rbac = RBAC::new();
+    rbac.add_permission(Permission {
+        user_id: "analyst".to_string(),
+        role: Role::Viewer,
+        accessible_entities: vec!["roadmap".to_string()],
+        accessible_collections: vec![],
+        excluded_entities: vec!["layoffs".to_string()],
+        expires_at: None,
+    });
+    rbac.add_group(Group { id: "finance".to_string(), members: vec!["analyst".to_string()] });
+    rbac.add_group_permission(Permission {
+        user_id: "finance".to_string(),
+        role: Role::Viewer,
+        accessible_entities: vec!["forecast".to_string()],
+        accessible_collections: vec!["finance".to_string()],
+        excluded_entities: vec![],
+        expires_at: Some(now - 60),
+    });
+
+    let reason = |e: &brainvault_backend::core::rbac::AccessExplanation| (e.allowed, e.reason.clone(), e.grant.clone());
+    assert_eq!(reason(&rbac.explain_access("analyst", "roadmap", None).await), (true, AccessReason::Listed, Some("user".to_string())));
+    assert_eq!(reason(&rbac.explain_access("analyst", "layoffs@2", Some("hr")).await), (false, AccessReason::Excluded, Some("user".to_string())));
+    assert_eq!(reason(&rbac.explain_access("analyst", "q3-budget", Some("finance")).await), (false, AccessReason::Expired, Some("group:finance".to_string())));
+    assert_eq!(reason(&rbac.explain_access("analyst", "handbook", Some("hr")).await), (false, AccessReason::CollectionDenied, None));
+    assert_eq!(reason(&rbac.explain_access("analyst", "memo", None).await), (false, AccessReason::NotInAllowList, None));
+    assert_eq!(reason(&rbac.explain_access("stranger", "memo", None).await), (false, AccessReason::UnknownUser, None));
+
+    // Agrees with the decision search filtering uses
+    for (entity, collection) in [("roadmap", None), ("layoffs", None), ("q3-budget", Some("finance"))] {
+        let explained = rbac.explain_access("analyst", entity, collection).await.allowed;
+        assert_eq!(explained, rbac.check_access_in("analyst", entity, collection).await.unwrap());
+    }
+}
+
+#[tokio::test]
+async fn test_filter_context_drops_edges_to_hidden_path_nodes() {
+    use brainvault_backend::core::graph_manager::{KnowledgeGraphManager, Relationship};
+    use brainvault_backend::db::barq_graph::BarqGraphClient;
+    use std::collections::HashMap;
+
+    let manager = KnowledgeGraphManager::new(BarqGraphClient::connect("http://127.0.0.1:9"));
+    let link = |from: &str, to: &str| Relationship {
+        from_id: from.to_string(),
+        to_id: to.to_string(),
+        rel_type: "FUNDS".to_string(),
+        weight: 1.0,
+        properties: HashMap::new(),
+        direction: None,
+    };
+    manager.add_relationship(link("hide-program", "hide-secret-vendor")).await.unwrap();
+    manager.add_relationship(link("hide-secret-vendor", "hide-budget")).await.unwrap();
+
+    let mut rbac = RBAC::new();
+    rbac.add_permission(Permission {
+        user_id: "path_viewer".to_string(),
+        role: Role::Viewer,
+        accessible_entities: vec!["hide-program".to_string(), "hide-budget".to_string()],
+        accessible_collections: vec![],
+        excluded_entities: vec![],
+        expires_at: None,
+    });
+
+    let path = manager.find_path("hide-program", "hide-budget", 5).await.unwrap();
+    assert_eq!(path.relationships.len(), 2);
+    let filtered = rbac.filter_context("path_viewer", path).await.unwrap();
+
+    assert!(filtered.relationships.is_empty());
+    assert!(!filtered.depths.contains_key("hide-secret-vendor"));
+    let leaked = serde_json::to_string(&filtered).unwrap();
+    assert!(!leaked.contains("hide-secret-vendor"));
+}
+
+#[tokio::test]
+async fn test_collection_writes_need_an_owning_role() {
+    let grant = |id: &str, role: Role, excluded: &[&str]| Permission {
+        user_id: id.to_string(),
+        role,
+        accessible_entities: vec![],
+        accessible_collections: vec!["legal".to_string()],
+        excluded_entities: excluded.iter().map(|e| e.to_string()).collect(),
+        expires_at: None,
+    };
+    let mut rbac = RBAC::new();
+    rbac.add_permission(grant("owner", Role::DataOwner, &["sealed-contract"]));
+    rbac.add_permission(grant("reader", Role::Viewer, &[]));
+    rbac.add_permission(grant("admin", Role::Admin, &[]));
+
+    assert!(rbac.check_collection_write("owner", "nda-1", "legal").await.unwrap());
+    assert!(!rbac.check_collection_write("owner", "nda-1", "finance").await.unwrap());
+    assert!(!rbac.check_collection_write("owner", "sealed-contract", "legal").await.unwrap());
+    assert!(!rbac.check_collection_write("reader", "nda-1", "legal").await.unwrap());
+    assert!(rbac.check_collection_write("admin", "anything", "finance").await.unwrap());
+    assert!(rbac.check_collection_write("stranger", "nda-1", "legal").await.is_err());
+}
+
+#[actix_web::test]
+async fn test_access_check_ignores_a_caller_supplied_collection() {
+    use actix_web::{test, web, App};
+    use brainvault_backend::api::handlers::security::check_entity_access;
+    use brainvault_backend::core::graph_manager::{Entity, KnowledgeGraphManager};
+    use brainvault_backend::core::search_engine::{HybridSearchEngine, SearchWeights};
+    use brainvault_backend::db::barq_graph::BarqGraphClient;
+    use brainvault_backend::db::barq_vector::BarqVectorClient;
+
+    let graph = KnowledgeGraphManager::new(BarqGraphClient::connect("http://127.0.0.1:9"));
+    graph.add_entity(Entity {
+        id: "spoof-merger-plan".to_string(),
+        label: "Project".to_string(),
+        properties: std::collections::HashMap::from([("collection".to_string(), "finance".to_string())]),
+    }).await.unwrap();
+    let engine = HybridSearchEngine::new(
+        BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-spoof-test"),
+        SearchWeights { vector_weight: 0.0, bm25_weight: 1.0 },
+    );
+    let mut rbac = RBAC::new();
+    rbac.add_permission(Permission {
+        user_id: "intern".to_string(),
+        role: Role::Viewer,
+        accessible_entities: vec![],
+        accessible_collections: vec!["public".to_string()],
+        excluded_entities: vec![],
+        expires_at: None,
+    });
+
+    let app = test::init_service(App::new()
+        .app_data(web::Data::new(engine))
+        .app_data(web::Data::new(graph))
+        .app_data(web::Data::new(rbac))
+        .service(check_entity_access)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/rbac/check?entity=spoof-merger-plan&collection=public")
+        .insert_header(("X-User-ID", "intern"))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["collection"], "finance");
+    assert_eq!(body["allowed"], false);
+}
+
+#[actix_web::test]
+async fn test_excluded_documents_cannot_be_deleted() {
+    use actix_web::http::StatusCode;
+    use actix_web::{test, web, App};
+    use brainvault_backend::api::handlers::knowledge::delete_document;
+    use brainvault_backend::core::audit_manager::AuditManager;
+    use brainvault_backend::core::search_engine::{HybridSearchEngine, SearchWeights};
+    use brainvault_backend::db::barq_vector::BarqVectorClient;
+
+    let engine = HybridSearchEngine::new(
+        BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-excluded-delete-test"),
+        SearchWeights { vector_weight: 0.0, bm25_weight: 1.0 },
+    );
+    for doc_id in ["nda-1", "sealed-contract"] {
+        engine.ingest_document_into(doc_id, "Confidential terms between the parties", None, Some("legal")).await.unwrap();
+    }
+    let mut rbac = RBAC::new();
+    rbac.add_permission(Permission {
+        user_id: "owner".to_string(),
+        role: Role::DataOwner,
+        accessible_entities: vec!["sealed-contract".to_string()],
+        accessible_collections: vec!["legal".to_string()],
+        excluded_entities: vec!["sealed-contract".to_string()],
+        expires_at: None,
+    });
+
+    let app = test::init_service(App::new()
+        .app_data(web::Data::new(engine))
+        .app_data(web::Data::new(rbac))
+        .app_data(web::Data::new(AuditManager::new()))
+        .service(delete_document)).await;
+    let delete = |doc_id: &str| test::TestRequest::delete()
+        .uri(&format!("/api/knowledge/{}", doc_id))
+        .insert_header(("X-User-ID", "owner"))
+        .to_request();
+
+    // Listing the id directly doesn't override the deny list
+    assert_eq!(test::call_service(&app, delete("sealed-contract")).await.status(), StatusCode::FORBIDDEN);
+    // Granted through the collection alone
+    assert_eq!(test::call_service(&app, delete("nda-1")).await.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn test_document_routes_only_return_readable_documents() {
+    use actix_web::http::StatusCode;
+    use actix_web::{test, web, App};
+    use brainvault_backend::api::handlers::knowledge::{get_document, list_all_documents, list_documents};
+    use brainvault_backend::core::search_engine::{HybridSearchEngine, SearchWeights};
+    use brainvault_backend::db::barq_vector::BarqVectorClient;
+
+    let engine = HybridSearchEngine::new(
+        BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-document-routes-test"),
+        SearchWeights { vector_weight: 0.0, bm25_weight: 1.0 },
+    );
+    engine.ingest_document_into("handbook", "Employee handbook", None, Some("public")).await.unwrap();
+    engine.ingest_document_into("salaries", "Salary bands by level", None, Some("hr")).await.unwrap();
+    let mut rbac = RBAC::new();
+    rbac.add_permission(Permission {
+        user_id: "intern".to_string(),
+        role: Role::Viewer,
+        accessible_entities: vec![],
+        accessible_collections: vec!["public".to_string()],
+        excluded_entities: vec![],
+        expires_at: None,
+    });
+
+    let app = test::init_service(App::new()
+        .app_data(web::Data::new(engine))
+        .app_data(web::Data::new(rbac))
+        .service(list_documents)
+        .service(get_document)
+        .service(list_all_documents)).await;
+    let get = |uri: &str, user: &str| test::TestRequest::get().uri(uri).insert_header(("X-User-ID", user)).to_request();
+
+    for uri in ["/api/documents", "/api/knowledge/documents"] {
+        let body: serde_json::Value = test::call_and_read_body_json(&app, get(uri, "intern")).await;
+        let ids: Vec<&str> = body["documents"].as_array().unwrap().iter().map(|d| d["doc_id"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["handbook"]);
+        let body: serde_json::Value = test::call_and_read_body_json(&app, get(uri, "stranger")).await;
+        assert!(body["documents"].as_array().unwrap().is_empty());
+    }
+    assert_eq!(test::call_service(&app, get("/api/documents/handbook", "intern")).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, get("/api/documents/salaries", "intern")).await.status(), StatusCode::FORBIDDEN);
+}
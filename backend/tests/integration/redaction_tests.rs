@@ -0,0 +1,170 @@
+use brainvault_backend::core::redaction::Redactor;
+use brainvault_backend::core::search_engine::{HybridSearchEngine, SearchWeights};
+use brainvault_backend::db::barq_vector::BarqVectorClient;
+use std::collections::BTreeMap;
+
+#[test]
+fn test_redacts_builtin_and_custom_patterns() {
+    let extra = BTreeMap::from([("employee_id".to_string(), r"\bEMP-\d{6}\b".to_string())]);
+    let redactor = Redactor::new(&["email".to_string(), "ssn".to_string(), "phone".to_string()], &extra);
+    let redaction = redactor.redact(
+        "Contact jane.doe@example.com or (555) 123-4567, SSN 123-45-6789, badge EMP-004211. Mail ops@example.org.",
+    );
+    assert_eq!(
+        redaction.text,
+        "Contact [REDACTED:EMAIL] or [REDACTED:PHONE], SSN [REDACTED:SSN], badge [REDACTED:EMPLOYEE_ID]. Mail [REDACTED:EMAIL].",
+    );
+    assert_eq!(redaction.summary(), "email:2, employee_id:1, phone:1, ssn:1");
+    assert_eq!(redactor.redact(&redaction.text).text, redaction.text);
+
+    // Switched off unless enabled, and only the requested types apply
+    assert!(redactor.redact_ingest("jane.doe@example.com").is_empty());
+    let emails_only = Redactor::new(&["email".to_string()], &BTreeMap::new()).with_ingest(true);
+    assert_eq!(emails_only.redact_ingest("jane@example.com 123-45-6789").text, "[REDACTED:EMAIL] 123-45-6789");
+}
+
+#[tokio::test]
+async fn test_ingest_stores_redacted_content() {
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-redaction-test");
+    let engine = HybridSearchEngine::new(client, SearchWeights { vector_weight: 0.0, bm25_weight: 1.0 })
+        .with_redactor(Redactor::default().with_ingest(true));
+    engine.ingest_document("redaction-hr", "Escalations go to hr.lead@example.com, phone 555-867-5309").await.unwrap();
+
+    let stored = engine.vector_db.get_document("redaction-hr").await.and_then(|d| d.content).unwrap();
+    assert_eq!(stored, "Escalations go to [REDACTED:EMAIL], phone [REDACTED:PHONE]");
+    let hits = engine.vector_db.bm25_search("example.com", 5).await.unwrap();
+    assert!(hits.iter().all(|h| h.doc_id != "redaction-hr"));
+}
+
+#[tokio::test]
+async fn test_ingest_report_carries_what_was_masked() {
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-redaction-report-test");
+    let engine = HybridSearchEngine::new(client, SearchWeights { vector_weight: 0.0, bm25_weight: 1.0 })
+        .with_redactor(Redactor::default().with_ingest(true));
+    let report = engine.ingest_with_report("redaction-report", "Mail hr.lead@example.com", None, None).await.unwrap();
+
+    assert_eq!(report.redaction.summary(), "email:1");
+    assert_eq!(report.redaction.text, "Mail [REDACTED:EMAIL]");
+    let stored = engine.vector_db.get_document("redaction-report").await.and_then(|d| d.content).unwrap();
+    assert_eq!(stored, report.redaction.text);
+}
+
+/// Engine holding one unredacted document, with query redaction on
+async fn engine_with_unredacted_document(doc_id: &str) -> HybridSearchEngine {
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-redaction-test");
+    let engine = HybridSearchEngine::new(client, SearchWeights { vector_weight: 0.0, bm25_weight: 1.0 })
+        .with_redactor(Redactor::default());
+    engine.ingest_document(doc_id, "Payroll questions go to pay.desk@example.com").await.unwrap();
+    engine.with_redactor(Redactor::default().with_query(true))
+}
+
+fn admin_rbac() -> brainvault_backend::core::rbac::RBAC {
+    use brainvault_backend::core::rbac::{Permission, Role, RBAC};
+    let mut rbac = RBAC::new();
+    rbac.add_permission(Permission {
+        user_id: "root".to_string(),
+        role: Role::Admin,
+        accessible_entities: vec![],
+        accessible_collections: vec![],
+        excluded_entities: vec![],
+        expires_at: None,
+    });
+    rbac
+}
+
+fn audit() -> brainvault_backend::core::audit_manager::AuditManager {
+    let dir = std::env::temp_dir().join(format!("brainvault-redaction-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    brainvault_backend::core::audit_manager::AuditManager::at_path(&dir.to_string_lossy())
+}
+
+#[actix_web::test]
+async fn test_document_read_is_redacted() {
+    use actix_web::{test, web, App};
+    use brainvault_backend::api::handlers::knowledge::get_knowledge_document;
+
+    let engine = engine_with_unredacted_document("redact-read").await;
+    let app = test::init_service(App::new()
+        .app_data(web::Data::new(engine))
+        .app_data(web::Data::new(admin_rbac()))
+        .app_data(web::Data::new(audit()))
+        .service(get_knowledge_document)).await;
+
+    let req = test::TestRequest::get().uri("/api/knowledge/redact-read").insert_header(("X-User-ID", "root")).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["content"], "Payroll questions go to [REDACTED:EMAIL]");
+}
+
+#[actix_web::test]
+async fn test_document_versions_are_redacted() {
+    use actix_web::{test, web, App};
+    use brainvault_backend::api::handlers::knowledge::list_document_versions;
+
+    let engine = engine_with_unredacted_document("redact-versions").await;
+    let app = test::init_service(App::new()
+        .app_data(web::Data::new(engine))
+        .app_data(web::Data::new(admin_rbac()))
+        .service(list_document_versions)).await;
+
+    let req = test::TestRequest::get().uri("/api/knowledge/redact-versions/versions").insert_header(("X-User-ID", "root")).to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body[0]["content"], "Payroll questions go to [REDACTED:EMAIL]");
+}
+
+#[actix_web::test]
+async fn test_corpus_export_is_redacted() {
+    use actix_web::{test, web, App};
+    use brainvault_backend::api::handlers::knowledge::{export_documents, DocumentRecord};
+    use brainvault_backend::db::barq_vector::content_hash;
+
+    let engine = engine_with_unredacted_document("redact-export").await;
+    let app = test::init_service(App::new()
+        .app_data(web::Data::new(engine))
+        .app_data(web::Data::new(admin_rbac()))
+        .app_data(web::Data::new(audit()))
+        .service(export_documents)).await;
+
+    let req = test::TestRequest::get().uri("/api/knowledge/export").insert_header(("X-User-ID", "root")).to_request();
+    let body = test::call_and_read_body(&app, req).await;
+    let records: Vec<DocumentRecord> = String::from_utf8_lossy(&body).lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let record = records.iter().find(|r| r.doc_id == "redact-export").unwrap();
+    assert_eq!(record.content, "Payroll questions go to [REDACTED:EMAIL]");
+    assert_eq!(record.content_hash.as_deref(), Some(content_hash(&record.content).as_str()));
+}
+
+#[tokio::test]
+async fn test_ask_sources_are_redacted_before_the_prompt() {
+    use brainvault_backend::core::citations::build_cited_context;
+    use brainvault_backend::core::llm::tokenizer::default_tokenizer;
+
+    // /api/ask masks the permitted hits before building the numbered context from them
+    let engine = engine_with_unredacted_document("redact-ask").await;
+    let mut results = engine.search("payroll questions", 5).await.unwrap();
+    results.redact(&engine.redactor);
+    let context = build_cited_context(&results.hits, default_tokenizer().as_ref(), 500);
+    assert!(context.contains("[REDACTED:EMAIL]"));
+    assert!(!context.contains("pay.desk@example.com"));
+}
+
+#[test]
+fn test_phone_rule_needs_phone_formatting() {
+    let redactor = Redactor::new(&["phone".to_string()], &BTreeMap::new());
+    for phone in ["(555) 123-4567", "555-867-5309", "555.867.5309", "+1 555 123 4567", "+15551234567"] {
+        assert_eq!(redactor.redact(phone).text, "[REDACTED:PHONE]", "{}", phone);
+    }
+
+    // Bare digit runs are timestamps, order ids and keys, not phone numbers
+    for text in [
+        "indexed_at 1700000000",
+        "order 4815162342 shipped",
+        "unix ms 1700000000123",
+        "api key 12345678901234",
+        "account 5551234567",
+    ] {
+        let redaction = redactor.redact(text);
+        assert!(redaction.is_empty(), "{}", text);
+        assert_eq!(redaction.text, text);
+    }
+}
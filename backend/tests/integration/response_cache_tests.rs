@@ -0,0 +1,32 @@
+use brainvault_backend::core::llm::response_cache::LlmResponseCache;
+
+#[test]
+fn test_cache_key_covers_length_and_system_prompt() {
+    let key = |system: &str, max_tokens: usize| LlmResponseCache::key("openai", "gpt-4o", system, "Summarize the handbook", 0.7, max_tokens);
+
+    assert_eq!(key("You are helpful.", 2000), key("You are helpful.", 2000));
+    // A short answer must not be served for a long-form request, or for another persona
+    assert_ne!(key("You are helpful.", 200), key("You are helpful.", 2000));
+    assert_ne!(key("You are terse.", 2000), key("You are helpful.", 2000));
+}
+
+#[test]
+fn test_cache_key_is_stable_across_builds() {
+    // Keys are persisted, so they must not depend on the standard library's hasher
+    let key = LlmResponseCache::key("openai", "gpt-4o", "You are helpful.", "Summarize the handbook", 0.7, 2000);
+    assert_eq!(key, "f44a2dd31d2071583ae013d3e7a7c86a10d174ee7e72d3c13387583e84f18f1e");
+}
+
+#[tokio::test]
+async fn test_persisted_responses_survive_a_restart() {
+    let dir = std::env::temp_dir().join(format!("brainvault-llm-cache-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("llm_cache.json").to_str().unwrap().to_string();
+
+    let key = LlmResponseCache::key("openai", "gpt-4o", "You are helpful.", "Summarize the handbook", 0.7, 2000);
+    LlmResponseCache::new(60, Some(path.clone())).put(key.clone(), "The handbook covers leave.".to_string()).await;
+
+    let reloaded = LlmResponseCache::new(60, Some(path));
+    assert_eq!(reloaded.get(&key).as_deref(), Some("The handbook covers leave."));
+    std::fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,62 @@
+use brainvault_backend::core::llm::retry::{retry_with_backoff, ProviderError, RetryPolicy};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+fn fast() -> RetryPolicy {
+    RetryPolicy { max_retries: 2, base_delay_ms: 1, jitter_ms: 0, ..RetryPolicy::default() }
+}
+
+/// Runs `retry_with_backoff` over an operation failing with `status` for the first
+/// `failures` calls; returns whether it succeeded and how many calls were made
+async fn run(status: Option<u16>, failures: usize) -> (bool, usize) {
+    let calls = AtomicUsize::new(0);
+    let result = retry_with_backoff(&fast(), "test", || async {
+        if calls.fetch_add(1, Ordering::SeqCst) < failures {
+            Err(ProviderError::new(status, "failed"))
+        } else {
+            Ok(())
+        }
+    }).await;
+    (result.is_ok(), calls.load(Ordering::SeqCst))
+}
+
+#[tokio::test]
+async fn test_retries_follow_the_typed_status() {
+    assert_eq!(run(Some(429), 1).await, (true, 2));
+    assert_eq!(run(Some(503), 2).await, (true, 3));
+    // Out of retries
+    assert_eq!(run(Some(500), 5).await, (false, 3));
+    // Client errors and failures without a status are final
+    assert_eq!(run(Some(400), 1).await, (false, 1));
+    assert_eq!(run(None, 1).await, (false, 1));
+}
+
+#[tokio::test]
+async fn test_status_digits_in_the_message_do_not_trigger_retries() {
+    let calls = AtomicUsize::new(0);
+    let result: Result<(), _> = retry_with_backoff(&fast(), "test", || async {
+        calls.fetch_add(1, Ordering::SeqCst);
+        Err(ProviderError::untyped("LLM error: model gpt-503 rejected prompt 429"))
+    }).await;
+    assert!(result.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_embedding_request_reports_the_http_status() {
+    use brainvault_backend::core::llm::nafs_provider::embedding_request;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut request = [0u8; 8192];
+        let _ = socket.read(&mut request).await;
+        let body = "slow down";
+        let response = format!("HTTP/1.1 429 Too Many Requests\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+        let _ = socket.write_all(response.as_bytes()).await;
+    });
+
+    let err = embedding_request(&format!("http://{}", addr), "key", "text-embedding-3-small", "hello").await.unwrap_err();
+    assert_eq!(err.status, Some(429));
+}
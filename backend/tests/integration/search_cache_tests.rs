@@ -0,0 +1,70 @@
+use brainvault_backend::core::search_cache::SearchCache;
+use brainvault_backend::core::search_engine::{SearchHit, SearchResults};
+use std::time::Duration;
+
+fn results(doc_id: &str) -> SearchResults {
+    SearchResults {
+        hits: vec![SearchHit { doc_id: doc_id.to_string(), score: 1.0, content: None, highlights: vec![], version: None }],
+    }
+}
+
+#[tokio::test]
+async fn test_write_to_one_collection_keeps_other_collection_cached() {
+    let cache = SearchCache::new(Duration::from_secs(60), 100);
+    let key_a = SearchCache::key("team_a", "policy", 5, &[]);
+    let key_b = SearchCache::key("team_b", "policy", 5, &[]);
+
+    cache.put(key_a.clone(), &["team_a"], results("a-doc")).await;
+    cache.put(key_b.clone(), &["team_b"], results("b-doc")).await;
+
+    cache.invalidate("team_a", &["a-new-doc"]).await;
+
+    assert!(cache.get(&key_a).await.is_none());
+    assert_eq!(cache.get(&key_b).await.unwrap().hits[0].doc_id, "b-doc");
+}
+
+#[tokio::test]
+async fn test_doc_update_evicts_entries_that_returned_it() {
+    let cache = SearchCache::new(Duration::from_secs(60), 100);
+    let key = SearchCache::key("team_b", "policy", 5, &[]);
+    cache.put(key.clone(), &["team_b"], results("shared-doc")).await;
+
+    cache.invalidate("team_a", &["shared-doc"]).await;
+
+    assert!(cache.get(&key).await.is_none());
+}
+
+#[test]
+fn test_query_text_cannot_pose_as_search_options() {
+    let fuzzy = vec!["fuzzy".to_string()];
+    assert_ne!(SearchCache::key("docs", "policy [fuzzy]", 5, &[]), SearchCache::key("docs", "policy", 5, &fuzzy));
+    assert_ne!(SearchCache::key("docs", "policy|5", 5, &[]), SearchCache::key("docs|5", "policy", 5, &[]));
+    assert_eq!(SearchCache::key("docs", "  Policy ", 5, &fuzzy), SearchCache::key("docs", "policy", 5, &fuzzy));
+}
+
+#[tokio::test]
+async fn test_engine_ingest_only_evicts_searches_of_its_collection() {
+    use brainvault_backend::core::search_cache::ALL_COLLECTIONS;
+    use brainvault_backend::core::search_engine::{HybridSearchEngine, SearchWeights};
+    use brainvault_backend::db::barq_vector::{BarqVectorClient, LexicalOptions};
+
+    let cache = SearchCache::new(Duration::from_secs(60), 100);
+    let engine = HybridSearchEngine::new(
+        BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-engine-cache-test"),
+        SearchWeights { vector_weight: 0.0, bm25_weight: 1.0 },
+    ).with_cache(cache.clone());
+    engine.ingest_document_into("a-policy", "Travel policy for team A", None, Some("team_a")).await.unwrap();
+    engine.ingest_document_into("b-policy", "Travel policy for team B", None, Some("team_b")).await.unwrap();
+
+    let scoped = |collection: &str| LexicalOptions { collection: Some(collection.to_string()), ..Default::default() };
+    engine.search_with("policy", 5, None, &scoped("team_a")).await.unwrap();
+    engine.search_with("policy", 5, None, &scoped("team_b")).await.unwrap();
+    engine.search_with("policy", 5, None, &LexicalOptions::default()).await.unwrap();
+    assert_eq!(cache.len().await, 3);
+
+    engine.ingest_document_into("a-expenses", "Expense policy for team A", None, Some("team_a")).await.unwrap();
+
+    assert!(cache.get(&SearchCache::key("team_b", "policy", 5, &[])).await.is_some());
+    assert!(cache.get(&SearchCache::key("team_a", "policy", 5, &[])).await.is_none());
+    assert!(cache.get(&SearchCache::key(ALL_COLLECTIONS, "policy", 5, &[])).await.is_none());
+}
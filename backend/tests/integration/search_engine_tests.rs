@@ -12,3 +12,235 @@ async fn test_hybrid_search_instantiation() {
     let hits = result.unwrap().hits;
     assert_eq!(hits.len(), 0);
 }
+
+#[test]
+fn test_paginate_reports_total_and_more_pages() {
+    use brainvault_backend::core::search_engine::{SearchHit, SearchResults};
+
+    let results = SearchResults {
+        hits: (0..25).map(|i| SearchHit { doc_id: format!("doc-{}", i), score: 1.0, content: None, highlights: vec![], version: None }).collect(),
+    };
+
+    let page = results.clone().paginate(10, 10);
+    assert_eq!(page.total, 25);
+    assert_eq!(page.hits[0].doc_id, "doc-10");
+    assert!(page.has_more);
+
+    let last = results.paginate(20, 10);
+    assert_eq!(last.hits.len(), 5);
+    assert!(!last.has_more);
+}
+
+#[test]
+fn test_highlight_snippets_mark_query_terms() {
+    use brainvault_backend::core::search_engine::highlight_snippets;
+
+    let content = "Quantum computers use qubits. Classical machines use bits. Qubits enable superposition.";
+    let snippets = highlight_snippets(content, "qubits", 3);
+
+    assert_eq!(snippets, vec![
+        "Quantum computers use <em>qubits</em>.".to_string(),
+        "<em>Qubits</em> enable superposition.".to_string(),
+    ]);
+}
+
+#[test]
+fn test_diversity_demotes_near_duplicate_hits() {
+    use brainvault_backend::db::barq_vector::SearchHit as DbHit;
+
+    let hit = |id: &str, score: f32, content: &str| DbHit { doc_id: id.to_string(), score, content: Some(content.to_string()) };
+    let bm25_hits = vec![
+        hit("mmr-policy", 1.0, "Zero trust network access policy for contractors"),
+        hit("mmr-policy-copy", 0.95, "Zero trust network access policy for contractors"),
+        hit("mmr-rotation", 0.8, "Encryption key rotation schedule"),
+    ];
+    let weights = SearchWeights { vector_weight: 0.0, bm25_weight: 1.0 };
+
+    let plain = HybridSearchEngine::new(BarqVectorClient::new(), weights.clone());
+    let ranked: Vec<String> = plain.merge_results("zero trust", vec![], bm25_hits.clone()).hits.into_iter().map(|h| h.doc_id).collect();
+    assert_eq!(ranked, vec!["mmr-policy", "mmr-policy-copy", "mmr-rotation"]);
+
+    let diverse = HybridSearchEngine::new(BarqVectorClient::new(), weights).with_diversity(0.5);
+    let reranked: Vec<String> = diverse.merge_results("zero trust", vec![], bm25_hits).hits.into_iter().map(|h| h.doc_id).collect();
+    assert_eq!(reranked, vec!["mmr-policy", "mmr-rotation", "mmr-policy-copy"]);
+}
+
+#[test]
+fn test_equal_scores_are_ordered_by_doc_id() {
+    use brainvault_backend::db::barq_vector::SearchHit as DbHit;
+
+    let hit = |id: &str| DbHit { doc_id: id.to_string(), score: 0.5, content: None };
+    let bm25_hits = vec![hit("tie-c"), hit("tie-a"), hit("tie-d"), hit("tie-b")];
+    let engine = HybridSearchEngine::new(BarqVectorClient::new(), SearchWeights { vector_weight: 0.0, bm25_weight: 1.0 });
+    for _ in 0..5 {
+        let ranked: Vec<String> = engine.merge_results("tie", vec![], bm25_hits.clone()).hits.into_iter().map(|h| h.doc_id).collect();
+        assert_eq!(ranked, vec!["tie-a", "tie-b", "tie-c", "tie-d"]);
+    }
+}
+
+#[test]
+fn test_nan_scores_never_rank_first() {
+    use brainvault_backend::core::search_engine::{fuse_results, SearchHit, SearchResults};
+    use brainvault_backend::db::barq_vector::SearchHit as DbHit;
+
+    let hit = |id: &str, score: f32| DbHit { doc_id: id.to_string(), score, content: None };
+    let engine = HybridSearchEngine::new(BarqVectorClient::new(), SearchWeights { vector_weight: 0.5, bm25_weight: 0.5 });
+    let merged = engine.merge_results(
+        "nan",
+        vec![hit("nan-poisoned", f32::NAN), hit("nan-good", 0.9), hit("nan-inf", f32::INFINITY)],
+        vec![hit("nan-good", 0.4), hit("nan-ok", 0.3)],
+    );
+    let ranked: Vec<&str> = merged.hits.iter().map(|h| h.doc_id.as_str()).collect();
+    assert_eq!(ranked, vec!["nan-good", "nan-ok"]);
+
+    let fused = fuse_results(vec![(SearchResults { hits: vec![
+        SearchHit { doc_id: "nan-poisoned".to_string(), score: f32::NAN, content: None, highlights: vec![], version: None },
+        SearchHit { doc_id: "nan-good".to_string(), score: 0.2, content: None, highlights: vec![], version: None },
+    ] }, 1.0)]);
+    assert_eq!(fused.hits.len(), 1);
+    assert_eq!(fused.hits[0].doc_id, "nan-good");
+}
+
+#[test]
+fn test_truncate_content_marks_cut_text() {
+    use brainvault_backend::core::search_engine::truncate_content;
+
+    assert_eq!(truncate_content("zero trust network", 9), "zero trus…");
+    assert_eq!(truncate_content("zero trust", 10), "zero trust");
+    assert_eq!(truncate_content("zero trust", 0), "zero trust");
+    assert_eq!(truncate_content("Größe über", 4), "Größ…");
+}
+
+#[test]
+fn test_parse_expansion_terms_cleans_llm_output() {
+    use brainvault_backend::core::search_engine::parse_expansion_terms;
+
+    let response = "1. container orchestration\n- K8s\n* Kubernetes\n5G networking, k8s";
+    let terms = parse_expansion_terms(response, "kubernetes");
+
+    assert_eq!(terms, vec!["container orchestration", "K8s", "5G networking"]);
+}
+
+#[test]
+fn test_parse_rerank_scores() {
+    use brainvault_backend::core::search_engine::parse_rerank_scores;
+
+    let response = "1: 3\n[2] - 9.5\n3: 42\nnot a score\n4: 7\n2: 1";
+    let scores = parse_rerank_scores(response, 4);
+    assert_eq!(scores, vec![Some(3.0), Some(9.5), None, Some(7.0)]);
+    assert_eq!(parse_rerank_scores("5: 8", 3), vec![None, None, None]);
+}
+
+#[test]
+fn test_search_weight_overrides() {
+    use brainvault_backend::core::search_engine::SearchWeights;
+
+    let defaults = SearchWeights { vector_weight: 0.7, bm25_weight: 0.3 };
+    let lexical = defaults.with_overrides(Some(0.0), Some(1.0)).unwrap();
+    assert_eq!(lexical, SearchWeights { vector_weight: 0.0, bm25_weight: 1.0 });
+    assert_eq!(defaults.with_overrides(None, Some(0.5)).unwrap().vector_weight, 0.7);
+
+    assert!(defaults.with_overrides(Some(-0.1), None).is_err());
+    assert!(defaults.with_overrides(Some(f32::NAN), None).is_err());
+    assert!(defaults.with_overrides(Some(0.0), Some(0.0)).is_err());
+}
+
+#[tokio::test]
+async fn test_feedback_reorders_close_hits_within_bound() {
+    use brainvault_backend::core::feedback::FeedbackStore;
+    use brainvault_backend::db::barq_vector::SearchHit as DbHit;
+
+    let hit = |id: &str, score: f32| DbHit { doc_id: id.to_string(), score, content: None };
+    let bm25_hits = vec![hit("fb-strong", 1.0), hit("fb-close-a", 0.52), hit("fb-close-b", 0.5)];
+    let feedback = FeedbackStore::new(0.1);
+    for user in ["u1", "u2", "u3", "u4", "u5", "u6"] {
+        feedback.record(user, "Travel  Policy", "fb-close-b", true).await;
+        feedback.record(user, "travel policy", "fb-strong", false).await;
+    }
+    // Repeated votes from one user count once
+    feedback.record("u1", "travel policy", "fb-close-b", true).await;
+    let (for_query, _) = feedback.tally("travel policy", "fb-close-b");
+    assert_eq!(for_query.relevant, 6);
+
+    let factor = feedback.score_factor("travel policy", "fb-close-b");
+    assert!(factor > 1.0 && factor <= 1.1);
+
+    let engine = HybridSearchEngine::new(BarqVectorClient::new(), SearchWeights { vector_weight: 0.0, bm25_weight: 1.0 })
+        .with_feedback(feedback);
+    let ranked: Vec<String> = engine.merge_results("travel policy", vec![], bm25_hits).hits.into_iter().map(|h| h.doc_id).collect();
+    assert_eq!(ranked, vec!["fb-strong", "fb-close-b", "fb-close-a"]);
+}
+
+#[tokio::test]
+async fn test_feedback_drops_cached_rankings() {
+    use brainvault_backend::core::feedback::FeedbackStore;
+    use brainvault_backend::core::search_cache::SearchCache;
+    use brainvault_backend::core::search_engine::{SearchHit, SearchResults};
+
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-feedback-cache-test");
+    let cache = SearchCache::new(std::time::Duration::from_secs(60), 100);
+    let collection = client.collection_name().to_string();
+    let engine = HybridSearchEngine::new(client, SearchWeights { vector_weight: 0.0, bm25_weight: 1.0 })
+        .with_cache(cache.clone())
+        .with_feedback(FeedbackStore::new(0.1));
+    let hit = SearchHit { doc_id: "fb-cached".to_string(), score: 1.0, content: None, highlights: vec![], version: None };
+    cache.put(SearchCache::key(&collection, "travel policy", 5, &[]), &[&collection], SearchResults { hits: vec![hit] }).await;
+    assert_eq!(cache.len().await, 1);
+
+    engine.record_feedback("u1", "travel policy", "fb-cached", false).await.unwrap();
+    assert_eq!(cache.len().await, 0);
+
+    let disabled = HybridSearchEngine::new(BarqVectorClient::new(), SearchWeights { vector_weight: 0.0, bm25_weight: 1.0 });
+    assert!(disabled.record_feedback("u1", "travel policy", "fb-cached", true).await.is_err());
+}
+
+#[tokio::test]
+async fn test_search_waits_for_warm_up_when_readiness_is_required() {
+    use brainvault_backend::error::BrainVaultError;
+
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-readiness-test");
+    let weights = SearchWeights { vector_weight: 0.0, bm25_weight: 1.0 };
+    let lenient = HybridSearchEngine::new(client.clone(), weights.clone()).with_require_ready(false);
+    let strict = HybridSearchEngine::new(client.clone(), weights).with_require_ready(true);
+    assert!(strict.is_ready() && strict.ensure_ready().is_ok());
+
+    let warm_up = client.spawn_warm_up(false);
+    assert!(!strict.is_ready());
+    assert!(matches!(strict.ensure_ready(), Err(BrainVaultError::Unavailable(_))));
+    assert!(lenient.ensure_ready().is_ok());
+
+    warm_up.await.unwrap();
+    assert_eq!(client.warmup_status().state, "done");
+    assert!(strict.ensure_ready().is_ok());
+}
+
+#[test]
+fn test_highlight_snippets_escape_document_markup() {
+    use brainvault_backend::core::search_engine::highlight_snippets;
+
+    let content = "Use <script>alert('qubits')</script> & \"qubits\" carefully.";
+    let snippets = highlight_snippets(content, "qubits", 3);
+
+    assert_eq!(snippets, vec![
+        "Use &lt;script&gt;alert(&#39;<em>qubits</em>&#39;)&lt;/script&gt; &amp; &quot;<em>qubits</em>&quot; carefully.".to_string(),
+    ]);
+}
+
+#[tokio::test]
+async fn test_history_hits_are_scored_like_lexical_matches() {
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-history-score-test");
+    client.index_document("charter", "quarterly budget review").await.unwrap();
+    client.index_document("charter", "annual planning cycle").await.unwrap();
+    let engine = HybridSearchEngine::new(client.clone(), SearchWeights { vector_weight: 0.6, bm25_weight: 0.4 });
+
+    let raw = client.history_search("budget review", 10, &Default::default()).await;
+    let hits = engine.search_history("budget review", 10, None, &Default::default()).await;
+    assert_eq!(hits.len(), 1);
+    assert_eq!((hits[0].doc_id.as_str(), hits[0].version), ("charter", Some(1)));
+    assert!((hits[0].score - raw[0].score * 0.4).abs() < 1e-6);
+
+    // Per-request weights apply to history hits too
+    let overridden = SearchWeights { vector_weight: 0.0, bm25_weight: 1.0 };
+    let hits = engine.search_history("budget review", 10, Some(&overridden), &Default::default()).await;
+    assert!((hits[0].score - raw[0].score).abs() < 1e-6);
+}
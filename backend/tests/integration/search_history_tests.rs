@@ -0,0 +1,83 @@
+use brainvault_backend::core::search_history::SearchHistory;
+use serde_json::json;
+
+fn temp_data_path() -> String {
+    let dir = std::env::temp_dir().join(format!("brainvault-history-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.to_string_lossy().to_string()
+}
+
+#[tokio::test]
+async fn test_history_is_per_user_bounded_and_persisted() {
+    let data_path = temp_data_path();
+    let history = SearchHistory::at_path(&data_path).with_enabled(true).with_limit(2);
+    for q in ["first", "second", "third"] {
+        history.record("alice", json!({"q": q, "top_k": 5}), 1).await;
+    }
+    history.record("bob", json!({"q": "other", "top_k": 5}), 0).await;
+    history.flush().await;
+
+    let reloaded = SearchHistory::at_path(&data_path).with_enabled(true);
+    let entries = reloaded.history("alice").await;
+    let queries: Vec<&str> = entries.iter().map(|e| e.query["q"].as_str().unwrap()).collect();
+    assert_eq!(queries, vec!["third", "second"]);
+    assert_eq!(reloaded.history_entry("alice", &entries[1].id).await.unwrap().query["q"], "second");
+    assert!(reloaded.history_entry("bob", &entries[1].id).await.is_err());
+}
+
+#[tokio::test]
+async fn test_opting_out_forgets_and_stops_recording() {
+    let history = SearchHistory::at_path(&temp_data_path()).with_enabled(true);
+    history.record("alice", json!({"q": "salary bands"}), 3).await;
+
+    history.set_history_enabled("alice", false).await;
+    assert!(history.history("alice").await.is_empty());
+    assert!(history.record("alice", json!({"q": "again"}), 3).await.is_none());
+    assert!(!history.is_recording("alice").await);
+
+    let disabled = SearchHistory::at_path(&temp_data_path()).with_enabled(false);
+    assert!(disabled.record("bob", json!({"q": "x"}), 1).await.is_none());
+}
+
+#[tokio::test]
+async fn test_saved_searches_replace_by_name() {
+    let history = SearchHistory::at_path(&temp_data_path()).with_enabled(false);
+    history.save("alice", "budgets", json!({"q": "budget 2023"})).await.unwrap();
+    history.save("alice", "budgets", json!({"q": "budget 2024"})).await.unwrap();
+    assert!(history.save("alice", "  ", json!({"q": "x"})).await.is_err());
+
+    let saved = history.saved_searches("alice").await;
+    assert_eq!(saved.len(), 1);
+    assert_eq!(history.saved_search("alice", "budgets").await.unwrap().query["q"], "budget 2024");
+
+    history.delete_saved("alice", "budgets").await.unwrap();
+    assert!(history.delete_saved("alice", "budgets").await.is_err());
+}
+
+#[tokio::test]
+async fn test_saved_searches_and_queries_are_capped() {
+    use brainvault_backend::core::search_history::{MAX_QUERY_BYTES, MAX_SAVED_SEARCHES};
+
+    let history = SearchHistory::at_path(&temp_data_path()).with_enabled(true);
+    for i in 0..MAX_SAVED_SEARCHES {
+        history.save("alice", &format!("search {}", i), json!({"q": "x"})).await.unwrap();
+    }
+    assert!(history.save("alice", "one more", json!({"q": "x"})).await.is_err());
+    // Replacing an existing name is still allowed
+    history.save("alice", "search 0", json!({"q": "y"})).await.unwrap();
+
+    let huge = json!({"q": "a".repeat(MAX_QUERY_BYTES)});
+    assert!(history.save("bob", "huge", huge.clone()).await.is_err());
+    assert!(history.record("bob", huge, 1).await.is_none());
+}
+
+#[tokio::test]
+async fn test_changes_are_written_after_a_delay() {
+    let data_path = temp_data_path();
+    let history = SearchHistory::at_path(&data_path).with_enabled(true);
+    history.record("alice", json!({"q": "pending"}), 1).await;
+    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+
+    let reloaded = SearchHistory::at_path(&data_path).with_enabled(true);
+    assert_eq!(reloaded.history("alice").await.len(), 1);
+}
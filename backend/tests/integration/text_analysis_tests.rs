@@ -0,0 +1,125 @@
+use brainvault_backend::core::text_analysis::{normalize_language, TextAnalyzer};
+use brainvault_backend::db::barq_vector::BarqVectorClient;
+
+#[test]
+fn test_tokenize_strips_punctuation_and_possessives() {
+    let analyzer = TextAnalyzer::default();
+    assert_eq!(analyzer.tokenize("CEO's plan."), vec!["ceo", "plan"]);
+    assert_eq!(analyzer.tokenize("The policy of the department"), vec!["policy", "department"]);
+    // Nothing but stopwords still leaves something to match
+    assert_eq!(analyzer.tokenize("of the"), vec!["of", "the"]);
+}
+
+#[test]
+fn test_stemming_is_optional() {
+    let stemmed = TextAnalyzer { stemming: true, ..TextAnalyzer::default() };
+    assert_eq!(stemmed.tokenize("Policies governing teams"), stemmed.tokenize("policy govern team"));
+    assert_ne!(TextAnalyzer::default().tokenize("teams"), TextAnalyzer::default().tokenize("team"));
+}
+
+#[test]
+fn test_language_selects_stopwords_and_stemmer() {
+    let analyzer = TextAnalyzer { stemming: true, ..TextAnalyzer::default() };
+    assert_eq!(analyzer.tokenize_in("la politique du département", Some("fr")), analyzer.tokenize_in("politiques départements", Some("fr")));
+    // "die" is only a stopword in German
+    assert_eq!(analyzer.tokenize_in("die Richtlinie", Some("de")).len(), 1);
+    assert_eq!(analyzer.tokenize_in("die policy", Some("en")).len(), 2);
+
+    assert_eq!(normalize_language("en-GB"), Some("en"));
+    assert_eq!(normalize_language("French"), Some("fr"));
+    assert_eq!(normalize_language("klingon"), None);
+}
+
+#[tokio::test]
+async fn test_trailing_punctuation_still_matches() {
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-analysis-test")
+        .with_analyzer(TextAnalyzer::default())
+        .with_lexical_min_score(0.5);
+    client.index_document("analysis-ceo", "The CEO presented the plan for next year").await.unwrap();
+
+    let hits = client.bm25_search("CEO's plan.", 5).await.unwrap();
+    assert_eq!(hits.first().map(|h| h.doc_id.as_str()), Some("analysis-ceo"));
+    assert!(client.bm25_search("budget forecast", 5).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_queries_made_of_stopwords_still_match() {
+    use brainvault_backend::db::barq_vector::LexicalOptions;
+
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-stopword-query-test")
+        .with_analyzer(TextAnalyzer::default());
+    client.index_document("stopword-band", "The Who played their final show").await.unwrap();
+    client.index_document("stopword-budget", "Quarterly budget review").await.unwrap();
+
+    let ids = |hits: Vec<brainvault_backend::db::barq_vector::SearchHit>| hits.into_iter().map(|h| h.doc_id).collect::<Vec<_>>();
+    assert_eq!(ids(client.bm25_search("the who", 5).await.unwrap()), vec!["stopword-band"]);
+    assert_eq!(ids(client.sparse_search_with("the who", 5, &LexicalOptions::default()).await.unwrap()), vec!["stopword-band"]);
+}
+
+#[tokio::test]
+async fn test_documents_are_scored_with_their_language() {
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-language-test")
+        .with_analyzer(TextAnalyzer { stemming: true, ..TextAnalyzer::default() })
+        .with_lexical_min_score(1.0);
+    client.index_document_in("language-fr", "Les politiques de télétravail", Some("fr")).await.unwrap();
+
+    let hits = client.bm25_search("politique télétravail", 5).await.unwrap();
+    assert_eq!(hits.first().map(|h| h.doc_id.as_str()), Some("language-fr"));
+    assert!(client.index_document_in("language-xx", "text", Some("klingon")).await.is_err());
+}
+
+#[test]
+fn test_edit_budget_scales_with_term_length() {
+    use brainvault_backend::core::text_analysis::{fuzzy_match, levenshtein};
+
+    assert_eq!(levenshtein("departmnt", "department"), 1);
+    assert_eq!(levenshtein("kitten", "sitting"), 3);
+    assert!(fuzzy_match("departmnt", "department"));
+    assert!(!fuzzy_match("cat", "car"));
+    assert!(!fuzzy_match("policy", "polite"));
+}
+
+#[tokio::test]
+async fn test_fuzzy_flag_tolerates_typos() {
+    use brainvault_backend::db::barq_vector::LexicalOptions;
+
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-fuzzy-test")
+        .with_analyzer(TextAnalyzer::default());
+    client.index_document("fuzzy-hr", "Each department reviews its budget quarterly").await.unwrap();
+
+    assert!(client.bm25_search("departmnt", 5).await.unwrap().is_empty());
+    let fuzzy = LexicalOptions { fuzzy: true, ..LexicalOptions::default() };
+    let hits = client.bm25_search_with("departmnt", 5, &fuzzy).await.unwrap();
+    assert_eq!(hits.first().map(|h| h.doc_id.as_str()), Some("fuzzy-hr"));
+    assert!(hits[0].score < 1.0);
+}
+
+#[tokio::test]
+async fn test_lexical_scores_count_mentions_with_saturation() {
+    let docs = [
+        ("tf-once", "Kubernetes cluster upgrade notes for the platform team this quarter".to_string()),
+        ("tf-often", "Kubernetes upgrade: drain Kubernetes nodes, then upgrade Kubernetes control plane".to_string()),
+        ("tf-stuffed", vec!["kubernetes"; 20].join(" ")),
+    ];
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-tf-test")
+        .with_analyzer(TextAnalyzer::default());
+    for (id, content) in &docs {
+        client.index_document(id, content).await.unwrap();
+    }
+
+    let hits = client.bm25_search("kubernetes", 5).await.unwrap();
+    let score = |id: &str| hits.iter().find(|h| h.doc_id == id).unwrap().score;
+    assert!(score("tf-often") > score("tf-once"));
+    // Twenty mentions earn less over three than three did over one
+    assert!(score("tf-stuffed") < 1.0);
+    assert!(score("tf-stuffed") - score("tf-often") < score("tf-often") - score("tf-once"));
+
+    // Presence-only scoring ties them
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-tf-test")
+        .with_analyzer(TextAnalyzer::default())
+        .with_term_frequency(0.0, 1.2);
+    for (id, content) in &docs {
+        client.index_document(id, content).await.unwrap();
+    }
+    assert!(client.bm25_search("kubernetes", 5).await.unwrap().iter().all(|h| h.score == 1.0));
+}
@@ -0,0 +1,40 @@
+use brainvault_backend::core::llm::tokenizer::{chunk_by_tokens, tokenizer_for_model, HeuristicTokenizer, Tokenizer};
+
+#[cfg(feature = "tiktoken")]
+#[test]
+fn test_default_tokenizer_known_counts() {
+    let tokenizer = tokenizer_for_model("gpt-4");
+    assert_eq!(tokenizer.count_tokens(""), 0);
+    assert_eq!(tokenizer.count_tokens("hello world"), 2);
+    assert_eq!(tokenizer.count_tokens("tiktoken is great!"), 6);
+
+    let truncated = tokenizer.truncate("tiktoken is great!", 2);
+    assert_eq!(tokenizer.count_tokens(&truncated), 2);
+}
+
+#[test]
+fn test_heuristic_tokenizer_fallback() {
+    let tokenizer = HeuristicTokenizer;
+    assert_eq!(tokenizer.count_tokens(""), 0);
+    assert_eq!(tokenizer.count_tokens("abcdefgh"), 2);
+    assert!(tokenizer.truncate(&"word ".repeat(100), 10).chars().count() <= 40);
+
+    let chunks = chunk_by_tokens(&tokenizer, &"lorem ipsum ".repeat(50), 20);
+    assert!(chunks.len() > 1);
+    assert!(chunks.iter().all(|c| tokenizer.count_tokens(c) <= 20));
+}
+
+#[test]
+fn test_heuristic_truncate_stays_within_budget() {
+    let tokenizer = HeuristicTokenizer;
+    // Short words make the word estimate dominate, so a 4-chars-per-token cut overshoots
+    let text = "a b c d e f g h i j k l m n o p q r s t u v w x y z ".repeat(4);
+    for max_tokens in [1, 7, 10, 33] {
+        let cut = tokenizer.truncate(&text, max_tokens);
+        assert!(tokenizer.count_tokens(&cut) <= max_tokens, "{} tokens for a budget of {}", tokenizer.count_tokens(&cut), max_tokens);
+        assert!(text.starts_with(&cut));
+    }
+
+    let cut = tokenizer.truncate("retention policy requires seven years", 5);
+    assert_eq!(cut, "retention policy");
+}
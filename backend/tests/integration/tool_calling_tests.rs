@@ -0,0 +1,113 @@
+use brainvault_backend::core::agent_orchestrator::AgentOrchestrator;
+use brainvault_backend::core::llm::tools::{parse_tool_response, ToolTurn};
+use serde_json::json;
+
+#[test]
+fn test_parse_tool_calls() {
+    let body = json!({
+        "choices": [{"message": {
+            "role": "assistant",
+            "content": null,
+            "tool_calls": [{
+                "id": "call_1",
+                "type": "function",
+                "function": {"name": "search", "arguments": "{\"query\": \"vector stores\", \"top_k\": 3}"}
+            }]
+        }}],
+        "usage": {"prompt_tokens": 40, "completion_tokens": 12}
+    });
+
+    let (turn, usage) = parse_tool_response(&body).unwrap();
+    match turn {
+        ToolTurn::Calls { calls, message } => {
+            assert_eq!(calls.len(), 1);
+            assert_eq!(calls[0].id, "call_1");
+            assert_eq!(calls[0].name, "search");
+            assert_eq!(calls[0].arguments["query"], "vector stores");
+            assert_eq!(message["role"], "assistant");
+        }
+        other => panic!("expected tool calls, got {:?}", other),
+    }
+    let usage = usage.unwrap();
+    assert_eq!(usage.prompt_tokens, 40);
+    assert!(!usage.estimated);
+}
+
+#[test]
+fn test_parse_final_answer() {
+    let body = json!({"choices": [{"message": {"role": "assistant", "content": "Done [doc-1]"}}]});
+    let (turn, usage) = parse_tool_response(&body).unwrap();
+    assert_eq!(turn, ToolTurn::Final("Done [doc-1]".to_string()));
+    assert!(usage.is_none());
+
+    assert!(parse_tool_response(&json!({"error": "bad"})).is_err());
+}
+
+#[test]
+fn test_tools_follow_configured_backends() {
+    let orchestrator = AgentOrchestrator::new(None, None);
+    assert!(orchestrator.agent_tools().is_empty());
+}
+
+#[test]
+fn test_every_tool_call_id_is_kept() {
+    let body = json!({
+        "choices": [{"message": {
+            "role": "assistant",
+            "content": null,
+            "tool_calls": [
+                {"type": "function", "function": {"name": "search", "arguments": "{\"query\": \"leave\"}"}},
+                {"id": "call_nameless", "type": "function", "function": {"arguments": "{}"}}
+            ]
+        }}]
+    });
+
+    let (turn, _) = parse_tool_response(&body).unwrap();
+    match turn {
+        ToolTurn::Calls { calls, message } => {
+            let ids: Vec<&str> = calls.iter().map(|c| c.id.as_str()).collect();
+            assert_eq!(ids, vec!["call_0", "call_nameless"]);
+            assert_eq!(calls[1].name, "");
+            // The assistant turn sent back carries the same ids the results answer
+            assert_eq!(message["tool_calls"][0]["id"], "call_0");
+        }
+        other => panic!("expected tool calls, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_search_tool_only_returns_documents_the_submitter_may_read() {
+    use brainvault_backend::core::agent_orchestrator::TaskOptions;
+    use brainvault_backend::core::llm::tools::ToolCall;
+    use brainvault_backend::core::rbac::{Permission, Role, RBAC};
+    use brainvault_backend::core::search_engine::{HybridSearchEngine, SearchWeights};
+    use brainvault_backend::db::barq_vector::BarqVectorClient;
+    use std::sync::Arc;
+
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-tool-rbac");
+    let engine = HybridSearchEngine::new(client, SearchWeights { vector_weight: 0.0, bm25_weight: 1.0 });
+    engine.ingest_document("tool-public-budget", "Travel budget guidelines").await.unwrap();
+    engine.ingest_document("tool-secret-budget", "Executive budget and salaries").await.unwrap();
+
+    let mut rbac = RBAC::new();
+    rbac.add_permission(Permission {
+        user_id: "tool-viewer".to_string(),
+        role: Role::Viewer,
+        accessible_entities: vec!["tool-public-budget".to_string()],
+        accessible_collections: vec![],
+        excluded_entities: vec![],
+        expires_at: None,
+    });
+    let orchestrator = AgentOrchestrator::new(Some(Arc::new(engine)), None)
+        .with_rbac(Arc::new(rbac))
+        .with_min_relevance(0.0);
+    let task_id = orchestrator.submit_task_with(Some("tool-viewer".to_string()), "Budget rules".to_string(), None, TaskOptions::default()).await;
+
+    let call = ToolCall { id: "call_1".to_string(), name: "search".to_string(), arguments: json!({"query": "budget"}) };
+    let output = orchestrator.execute_tool(&task_id, &call).await;
+    assert!(output.contains("tool-public-budget"));
+    assert!(!output.contains("tool-secret-budget"));
+
+    let nameless = ToolCall { id: "call_2".to_string(), name: String::new(), arguments: json!({}) };
+    assert!(orchestrator.execute_tool(&task_id, &nameless).await.starts_with("Error:"));
+}
@@ -0,0 +1,676 @@
+use async_trait::async_trait;
+use brainvault_backend::core::llm::embeddings::EmbeddingProvider;
+use brainvault_backend::core::llm::retry::ProviderError;
+use brainvault_backend::db::barq_vector::BarqVectorClient;
+use std::sync::Arc;
+
+/// Claims one dimension but returns vectors of another length
+struct MisconfiguredEmbedder;
+
+#[async_trait]
+impl EmbeddingProvider for MisconfiguredEmbedder {
+    fn name(&self) -> &str { "test" }
+    fn model(&self) -> &str { "test-embedding" }
+    fn dimension(&self) -> usize { 1536 }
+    async fn embed(&self, _text: &str) -> Result<Vec<f32>, ProviderError> {
+        Ok(vec![0.1, 0.2, 0.3])
+    }
+}
+
+#[tokio::test]
+async fn test_out_of_spec_embedding_rejected() {
+    let client = BarqVectorClient::new().with_embedder(Arc::new(MisconfiguredEmbedder));
+
+    let result = client.index_document("dim-mismatch-doc", "content").await;
+    let err = result.expect_err("mismatched vector must not be indexed");
+    assert!(err.contains("dimension mismatch"));
+    assert!(client.get_document("dim-mismatch-doc").await.is_none());
+}
+
+#[test]
+fn test_validate_embedding_matches_configured_dimension() {
+    let client = BarqVectorClient::new().with_dimension(4);
+    assert!(client.validate_embedding(&[0.0; 4]).is_ok());
+    assert!(client.validate_embedding(&[0.0; 768]).is_err());
+}
+
+/// Counts embedding calls; vectors match the configured dimension
+struct CountingEmbedder(std::sync::atomic::AtomicUsize);
+
+#[async_trait]
+impl EmbeddingProvider for CountingEmbedder {
+    fn name(&self) -> &str { "test" }
+    fn model(&self) -> &str { "test-embedding" }
+    fn dimension(&self) -> usize { 3 }
+    async fn embed(&self, _text: &str) -> Result<Vec<f32>, ProviderError> {
+        self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Ok(vec![0.1, 0.2, 0.3])
+    }
+}
+
+#[tokio::test]
+async fn test_unchanged_content_skips_reembedding() {
+    use brainvault_backend::db::barq_vector::IndexOutcome;
+
+    let embedder = Arc::new(CountingEmbedder(Default::default()));
+    let data_path = std::env::temp_dir().join("brainvault-hash-test");
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", data_path.to_str().unwrap())
+        .with_embedder(embedder.clone())
+        .with_dimension(3);
+
+    assert_eq!(client.index_document("hash-doc", "v1").await.unwrap(), IndexOutcome::Indexed);
+    assert_eq!(client.index_document("hash-doc", "v1").await.unwrap(), IndexOutcome::Unchanged);
+    assert_eq!(client.index_document("hash-doc", "v2").await.unwrap(), IndexOutcome::Indexed);
+    assert_eq!(embedder.0.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_stored_revision_hash_survives_restart() {
+    use brainvault_backend::db::barq_vector::IndexOutcome;
+
+    let embedder = Arc::new(CountingEmbedder(Default::default()));
+    let dir = std::env::temp_dir().join(format!("brainvault-hash-restart-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let open = || BarqVectorClient::connect("http://127.0.0.1:9", dir.to_str().unwrap())
+        .with_embedder(embedder.clone())
+        .with_dimension(3);
+
+    assert_eq!(open().index_document("restart-doc", "travel policy").await.unwrap(), IndexOutcome::Indexed);
+    // A fresh client checks the hash recorded with the current revision on disk
+    let reopened = open();
+    assert_eq!(reopened.index_document("restart-doc", "travel policy").await.unwrap(), IndexOutcome::Unchanged);
+    assert_eq!(reopened.index_document("restart-doc", "travel policy, revised").await.unwrap(), IndexOutcome::Indexed);
+    assert_eq!(embedder.0.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_embedding_updates_are_logged_not_rewritten() {
+    use brainvault_backend::core::llm::embeddings::LocalEmbedder;
+
+    let dir = std::env::temp_dir().join(format!("brainvault-embedding-log-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let open = || BarqVectorClient::connect("http://127.0.0.1:9", dir.to_str().unwrap())
+        .with_embedder(Arc::new(LocalEmbedder::new(64)))
+        .with_dimension(64);
+
+    let client = open();
+    client.index_document("log-k8s", "Autoscaling a Kubernetes cluster with node pools").await.unwrap();
+    client.index_document("log-lunch", "Office lunch menu for Friday").await.unwrap();
+    client.delete_document("log-lunch").await.unwrap();
+    // Single documents only append; the full cache file is never written
+    assert!(!dir.join("embedding_cache.json").exists());
+    assert_eq!(std::fs::read_to_string(dir.join("embedding_log.jsonl")).unwrap().lines().count(), 3);
+
+    let hits = open().dense_search_in("kubernetes autoscaling", 5, None).await.unwrap().expect("query is embeddable");
+    assert_eq!(hits.iter().map(|h| h.doc_id.as_str()).collect::<Vec<_>>(), vec!["log-k8s"]);
+}
+
+#[tokio::test]
+async fn test_updates_keep_versions_and_delete_tombstones() {
+    // Nothing persists under a missing directory, so history starts empty
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-version-test")
+        .with_embedder(Arc::new(CountingEmbedder(Default::default())))
+        .with_dimension(3);
+    client.index_document("policy", "remote work allowed twice weekly").await.unwrap();
+    client.index_document("policy", "remote work allowed three days weekly").await.unwrap();
+
+    let versions = client.list_versions("policy").await;
+    assert_eq!(versions.iter().map(|v| v.version).collect::<Vec<_>>(), vec![1, 2]);
+    assert_eq!(client.get_version("policy", 1).await.as_deref(), Some("remote work allowed twice weekly"));
+    assert_eq!(client.get_version("policy", 2).await.as_deref(), Some("remote work allowed three days weekly"));
+
+    client.delete_document("policy").await.unwrap();
+    assert!(client.is_deleted("policy").await);
+    assert!(client.get_document("policy").await.is_none());
+    assert!(client.bm25_search("remote work", 10).await.unwrap().is_empty());
+    assert_eq!(client.get_version("policy", 2).await.as_deref(), Some("remote work allowed three days weekly"));
+
+    let history = client.history_search("remote work", 10, &Default::default()).await;
+    let mut found: Vec<(&str, u32)> = history.iter().map(|hit| (hit.doc_id.as_str(), hit.version)).collect();
+    found.sort();
+    assert_eq!(found, vec![("policy", 1), ("policy", 2)]);
+    assert!(client.delete_document("policy").await.is_err());
+}
+
+#[tokio::test]
+async fn test_reingesting_identical_content_adds_no_version() {
+    use brainvault_backend::db::barq_vector::IndexOutcome;
+
+    // No embedder, so only the stored revisions can tell the content is unchanged
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-version-dedupe");
+    assert_eq!(client.index_document("handbook", "leave policy").await.unwrap(), IndexOutcome::Indexed);
+    assert_eq!(client.index_document("handbook", "leave policy").await.unwrap(), IndexOutcome::Unchanged);
+    assert_eq!(client.list_versions("handbook").await.len(), 1);
+    assert_eq!(client.index_document("handbook", "leave policy, revised").await.unwrap(), IndexOutcome::Indexed);
+    assert_eq!(client.list_versions("handbook").await.len(), 2);
+}
+
+#[tokio::test]
+async fn test_revisions_of_ids_containing_at_signs_stay_apart() {
+    let data_path = std::env::temp_dir().join(format!("brainvault-versions-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&data_path).unwrap();
+    let data_path = data_path.to_str().unwrap();
+    {
+        let client = BarqVectorClient::connect("http://127.0.0.1:9", data_path);
+        client.index_document("ops", "rota for march").await.unwrap();
+        client.index_document("ops", "rota for april").await.unwrap();
+        // Looks like revision 1 of `ops`, but is a document of its own
+        client.index_document("ops@1", "escalation contacts").await.unwrap();
+        client.index_document("ops@1", "escalation contacts, updated").await.unwrap();
+    }
+
+    // Revisions and archived content survive a restart, each under its own document
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", data_path);
+    assert_eq!(client.list_versions("ops").await.len(), 2);
+    assert_eq!(client.get_version("ops", 1).await.as_deref(), Some("rota for march"));
+    assert_eq!(client.get_version("ops@1", 1).await.as_deref(), Some("escalation contacts"));
+    assert_eq!(client.collection_of("ops@1").await.as_deref(), Some("default"));
+
+    let history = client.history_search("escalation", 10, &Default::default()).await;
+    assert_eq!(history.len(), 1);
+    assert_eq!((history[0].doc_id.as_str(), history[0].version), ("ops@1", 1));
+    std::fs::remove_dir_all(data_path).ok();
+}
+
+#[test]
+fn test_distance_metric_parsing_and_similarity() {
+    use brainvault_backend::db::barq_vector::DistanceMetric;
+
+    assert_eq!(DistanceMetric::parse("L2"), Ok(DistanceMetric::Euclidean));
+    assert_eq!(DistanceMetric::parse("dot"), Ok(DistanceMetric::DotProduct));
+    assert!(DistanceMetric::parse("manhattan").is_err());
+
+    assert_eq!(DistanceMetric::DotProduct.similarity(&[1.0, 2.0], &[3.0, 4.0]), 11.0);
+    assert_eq!(DistanceMetric::Euclidean.similarity(&[0.0, 0.0], &[3.0, 4.0]), 1.0 / 6.0);
+    assert_eq!(DistanceMetric::Euclidean.similarity(&[1.0], &[1.0, 2.0]), 0.0);
+}
+
+#[tokio::test]
+async fn test_invalid_distance_is_rejected_before_creating_collection() {
+    std::env::set_var("VECTOR_DISTANCE", "manhattan");
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-distance-test");
+    std::env::remove_var("VECTOR_DISTANCE");
+
+    let err = client.ensure_collection().await.expect_err("unsupported metric");
+    assert!(err.contains("manhattan"));
+}
+
+#[tokio::test]
+async fn test_corpus_stats_count_live_content() {
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-stats-test")
+        .with_embedder(Arc::new(CountingEmbedder(Default::default())))
+        .with_dimension(3);
+    client.index_document("stats-a", "abcd").await.unwrap();
+    client.index_document("stats-b", "größe").await.unwrap();
+    client.index_document("stats-c", "gone soon").await.unwrap();
+    client.delete_document("stats-c").await.unwrap();
+
+    let stats = client.corpus_stats().await;
+    assert_eq!(stats.documents, 2);
+    assert_eq!(stats.content_bytes, 4 + "größe".len());
+    assert_eq!(stats.deleted_documents, 1);
+    assert_eq!(stats.collections, 1);
+}
+
+/// Embedding provider that is always down
+struct FailingEmbedder;
+
+#[async_trait]
+impl EmbeddingProvider for FailingEmbedder {
+    fn name(&self) -> &str { "test" }
+    fn model(&self) -> &str { "test-embedding" }
+    fn dimension(&self) -> usize { 3 }
+    async fn embed(&self, _text: &str) -> Result<Vec<f32>, ProviderError> {
+        Err(ProviderError::new(None, "provider unavailable"))
+    }
+}
+
+#[tokio::test]
+async fn test_warm_up_embeds_documents_without_vectors() {
+    let offline = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-warmup-test")
+        .with_embedder(Arc::new(FailingEmbedder))
+        .with_dimension(3);
+    offline.index_document("warm-doc", "stored while embeddings were down").await.unwrap();
+    assert_eq!(offline.warmup_status().state, "disabled");
+
+    let embedder = Arc::new(CountingEmbedder(Default::default()));
+    let client = offline.with_embedder(embedder.clone());
+    client.warm_up(true).await;
+
+    let status = client.warmup_status();
+    assert_eq!(status.state, "done");
+    assert_eq!((status.missing, status.embedded, status.failed), (1, 1, 0));
+    assert_eq!(embedder.0.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    // Now embedded, so re-ingesting the same content is a no-op
+    assert_eq!(client.index_document("warm-doc", "stored while embeddings were down").await.unwrap(),
+        brainvault_backend::db::barq_vector::IndexOutcome::Unchanged);
+}
+
+/// Fails with `status` for the first `limited` calls, then returns vectors
+struct ThrottledEmbedder {
+    limited: usize,
+    status: u16,
+    calls: std::sync::atomic::AtomicUsize,
+}
+
+#[async_trait]
+impl EmbeddingProvider for ThrottledEmbedder {
+    fn name(&self) -> &str { "test" }
+    fn model(&self) -> &str { "test-embedding" }
+    fn dimension(&self) -> usize { 3 }
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, ProviderError> {
+        let call = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        match text {
+            "bad" => Err(ProviderError::new(Some(400), "Embedding Error 400 Bad Request")),
+            _ if call < self.limited => Err(ProviderError::new(Some(self.status), "Embedding Error from provider")),
+            _ => Ok(vec![0.1, 0.2, 0.3]),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_rate_limited_embedder_backs_off_for_all_callers() {
+    use brainvault_backend::core::llm::embeddings::RateLimitedEmbedder;
+    use brainvault_backend::core::llm::retry::RetryPolicy;
+
+    let inner = Arc::new(ThrottledEmbedder { limited: 1, status: 429, calls: Default::default() });
+    let policy = RetryPolicy { base_delay_ms: 100, jitter_ms: 0, ..RetryPolicy::default() };
+    let embedder = RateLimitedEmbedder::new(inner.clone(), policy);
+
+    let started = std::time::Instant::now();
+    let (first, second) = tokio::join!(embedder.embed("a"), async {
+        // Starts after the first call has been rate limited, so it waits out the same pause
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        embedder.embed("b").await
+    });
+    assert!(first.is_ok() && second.is_ok());
+    assert!(started.elapsed() >= std::time::Duration::from_millis(100));
+    assert_eq!(inner.calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+    // Other errors aren't retried
+    assert!(embedder.embed("bad").await.is_err());
+    assert_eq!(inner.calls.load(std::sync::atomic::Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn test_rate_limited_embedder_is_the_only_retry_layer() {
+    use brainvault_backend::core::llm::embeddings::RateLimitedEmbedder;
+    use brainvault_backend::core::llm::retry::RetryPolicy;
+
+    // Server errors are retried here too, within the same budget
+    let inner = Arc::new(ThrottledEmbedder { limited: 2, status: 503, calls: Default::default() });
+    let policy = RetryPolicy { max_retries: 2, base_delay_ms: 10, jitter_ms: 0, ..RetryPolicy::default() };
+    let embedder = RateLimitedEmbedder::new(inner.clone(), policy.clone());
+    assert!(embedder.embed("a").await.is_ok());
+    assert_eq!(inner.calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+    let inner = Arc::new(ThrottledEmbedder { limited: 5, status: 503, calls: Default::default() });
+    let embedder = RateLimitedEmbedder::new(inner.clone(), policy);
+    assert!(embedder.embed("a").await.is_err());
+    assert_eq!(inner.calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_collection_scopes_vector_and_lexical_search() {
+    use brainvault_backend::db::barq_vector::LexicalOptions;
+
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-collection-test")
+        .with_embedder(Arc::new(CountingEmbedder(Default::default())))
+        .with_dimension(3);
+    client.index_document_into("team-a-policy", "remote work policy", None, Some("team-a")).await.unwrap();
+    client.index_document_into("team-b-policy", "remote work policy", None, Some("team-b")).await.unwrap();
+    client.index_document("shared-policy", "remote work policy").await.unwrap();
+
+    // Barq is unreachable, so this exercises the local cosine fallback
+    let hits = client.semantic_search_in("remote work", 10, Some("team-a")).await.unwrap();
+    let ids: Vec<&str> = hits.iter().map(|h| h.doc_id.as_str()).collect();
+    assert_eq!(ids, vec!["team-a-policy"]);
+
+    let options = LexicalOptions { collection: Some("default".to_string()), ..LexicalOptions::default() };
+    let hits = client.bm25_search_with("remote work", 10, &options).await.unwrap();
+    let ids: Vec<&str> = hits.iter().map(|h| h.doc_id.as_str()).collect();
+    assert_eq!(ids, vec!["shared-policy"]);
+
+    assert_eq!(client.bm25_search("remote work", 10).await.unwrap().len(), 3);
+    assert_eq!(client.corpus_stats().await.collections, 3);
+}
+
+#[tokio::test]
+async fn test_embedding_cache_evicts_least_recently_used() {
+    use brainvault_backend::core::llm::embeddings::CachedEmbedder;
+
+    let inner = Arc::new(CountingEmbedder(Default::default()));
+    let cache = CachedEmbedder::new(inner.clone(), 2);
+    let calls = || inner.0.load(std::sync::atomic::Ordering::SeqCst);
+
+    cache.embed("alpha").await.unwrap();
+    cache.embed("alpha").await.unwrap();
+    assert_eq!(calls(), 1);
+
+    cache.embed("beta").await.unwrap();
+    cache.embed("alpha").await.unwrap(); // alpha is now the most recently used
+    cache.embed("gamma").await.unwrap(); // evicts beta
+    assert_eq!(calls(), 3);
+    assert_eq!(cache.len(), 2);
+
+    cache.embed("alpha").await.unwrap();
+    assert_eq!(calls(), 3);
+    cache.embed("beta").await.unwrap();
+    assert_eq!(calls(), 4);
+}
+
+/// Answers successive HTTP requests with `responses` in order; returns the base URL
+async fn stub_barq(responses: Vec<(u16, &'static str)>) -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        for (status, body) in responses {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 8192];
+            let _ = socket.read(&mut request).await;
+            let response = format!(
+                "HTTP/1.1 {} Stub\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status, body.len(), body
+            );
+            let _ = socket.write_all(response.as_bytes()).await;
+        }
+    });
+    format!("http://{}", addr)
+}
+
+#[tokio::test]
+async fn test_ensure_collection_distinguishes_statuses() {
+    use brainvault_backend::core::llm::retry::RetryPolicy;
+    use brainvault_backend::db::barq_vector::DistanceMetric;
+
+    let fast_retry = RetryPolicy { base_delay_ms: 10, jitter_ms: 0, ..RetryPolicy::default() };
+    let client = |url: &str| BarqVectorClient::connect(url, "/nonexistent/brainvault-collection-status-test")
+        .with_http_client(reqwest::Client::new())
+        .with_distance(DistanceMetric::Cosine)
+        .with_retry_policy(fast_retry.clone());
+
+    let url = stub_barq(vec![(409, "exists")]).await;
+    assert!(client(&url).ensure_collection().await.is_ok());
+
+    let url = stub_barq(vec![(400, "dimension 1536 does not match 768")]).await;
+    let err = client(&url).ensure_collection().await.expect_err("4xx is a hard error");
+    assert!(err.contains("400") && err.contains("does not match 768"));
+
+    // 5xx is retried until Barq recovers
+    let url = stub_barq(vec![(503, "starting"), (503, "starting"), (201, "")]).await;
+    assert!(client(&url).ensure_collection().await.is_ok());
+}
+
+#[tokio::test]
+async fn test_sparse_search_weights_rare_terms_and_tracks_deletes() {
+    use brainvault_backend::core::sparse_vector::{term_counts, SparseIndex};
+    use brainvault_backend::db::barq_vector::LexicalOptions;
+
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-sparse-test")
+        .with_embedder(Arc::new(CountingEmbedder(Default::default())))
+        .with_dimension(3);
+    client.index_document("sparse-airship", "quarterly zeppelin maintenance schedule").await.unwrap();
+    client.index_document("sparse-budget", "quarterly budget review").await.unwrap();
+    client.index_document("sparse-hiring", "quarterly hiring plan").await.unwrap();
+
+    let hits = client.sparse_search_with("quarterly zeppelin", 10, &LexicalOptions::default()).await.unwrap();
+    assert_eq!(hits.len(), 3);
+    assert_eq!(hits[0].doc_id, "sparse-airship");
+    assert!(hits.iter().all(|h| h.score > 0.0 && h.score <= 1.0 + f32::EPSILON));
+    assert!(hits[0].score > 2.0 * hits[1].score);
+
+    client.delete_document("sparse-airship").await.unwrap();
+    let hits = client.sparse_search_with("zeppelin", 10, &LexicalOptions::default()).await.unwrap();
+    assert!(hits.is_empty());
+
+    let mut index = SparseIndex::default();
+    let terms = |text: &str| text.split(' ').map(String::from).collect::<Vec<_>>();
+    index.insert("a", term_counts(&terms("policy remote")));
+    index.insert("b", term_counts(&terms("policy office policy")));
+    assert!(index.idf("remote") > index.idf("policy"));
+    assert!((index.average_length() - 2.5).abs() < 1e-6);
+    index.remove("a");
+    assert!(!index.contains_term("remote"));
+    assert!((index.average_length() - 3.0).abs() < 1e-6);
+}
+
+#[tokio::test]
+async fn test_sparse_search_credits_term_frequency() {
+    use brainvault_backend::db::barq_vector::LexicalOptions;
+
+    async fn search(client: BarqVectorClient) -> Vec<brainvault_backend::db::barq_vector::SearchHit> {
+        client.index_document("tf-repeated", "zeppelin zeppelin zeppelin hangar").await.unwrap();
+        client.index_document("tf-single", "zeppelin hangar crew roster").await.unwrap();
+        client.index_document("tf-other", "quarterly budget review").await.unwrap();
+        client.sparse_search_with("zeppelin", 10, &LexicalOptions::default()).await.unwrap()
+    }
+    let client = || BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-sparse-tf-test")
+        .with_embedder(Arc::new(CountingEmbedder(Default::default())))
+        .with_dimension(3);
+
+    // Same length, so only the number of mentions separates them
+    let hits = search(client()).await;
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].doc_id, "tf-repeated");
+    assert!(hits[0].score > hits[1].score);
+    assert!(hits[0].score <= 1.0);
+
+    // Presence-only weighting scores every mention the same
+    let hits = search(client().with_term_frequency(0.0, 1.2)).await;
+    assert_eq!(hits.len(), 2);
+    assert!((hits[0].score - hits[1].score).abs() < 1e-6);
+}
+
+#[tokio::test]
+async fn test_local_embeddings_are_deterministic_and_searchable_offline() {
+    use brainvault_backend::core::llm::embeddings::LocalEmbedder;
+
+    let embedder = LocalEmbedder::new(64);
+    let a = embedder.embed("Kubernetes cluster autoscaling").await.unwrap();
+    assert_eq!(a.len(), 64);
+    assert_eq!(a, LocalEmbedder::new(64).embed("Kubernetes cluster autoscaling").await.unwrap());
+    assert!((a.iter().map(|v| v * v).sum::<f32>() - 1.0).abs() < 1e-4);
+    assert!(embedder.embed("  ...  ").await.is_err());
+
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", "/nonexistent/brainvault-local-embedding-test")
+        .with_embedder(Arc::new(embedder))
+        .with_dimension(64);
+    client.index_document("local-k8s", "Autoscaling a Kubernetes cluster with node pools").await.unwrap();
+    client.index_document("local-lunch", "Office lunch menu for Friday").await.unwrap();
+
+    let hits = client.dense_search_in("kubernetes autoscaling", 2, None).await.unwrap().expect("query is embeddable");
+    assert_eq!(hits[0].doc_id, "local-k8s");
+    assert!(hits[0].score > hits.get(1).map_or(0.0, |h| h.score));
+}
+
+#[tokio::test]
+async fn test_collections_get_their_own_barq_index_created_once() {
+    let url = stub_barq(vec![(201, ""), (200, ""), (200, "")]).await;
+    let client = BarqVectorClient::connect(&url, "/nonexistent/brainvault-tenant-collection-test")
+        .with_http_client(reqwest::Client::new())
+        .with_embedder(Arc::new(CountingEmbedder(Default::default())))
+        .with_dimension(3)
+        .with_collection_name("tenant_docs");
+
+    assert_eq!(client.barq_collection(None), "tenant_docs");
+    assert_eq!(client.barq_collection(Some("default")), "tenant_docs");
+    assert_eq!(client.barq_collection(Some("legal")), "tenant_docs__legal");
+
+    // The first document in "legal" creates its collection; the second reuses it
+    client.index_document_into("legal-a", "NDA template", None, Some("legal")).await.unwrap();
+    client.index_document_into("legal-b", "Retention policy", None, Some("legal")).await.unwrap();
+    assert_eq!(client.ready_collections(), vec!["tenant_docs__legal".to_string()]);
+    assert_eq!(client.collection_of("legal-b").await.as_deref(), Some("legal"));
+}
+
+#[tokio::test]
+async fn test_model_change_requires_reindex_into_fresh_collections() {
+    use brainvault_backend::core::llm::embeddings::LocalEmbedder;
+    use brainvault_backend::core::llm::retry::RetryPolicy;
+
+    let data_path = std::env::temp_dir().join(format!("brainvault-reindex-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&data_path).unwrap();
+    let client = |embedder: Arc<dyn EmbeddingProvider>, dimension: usize| {
+        BarqVectorClient::connect("http://127.0.0.1:9", data_path.to_str().unwrap())
+            .with_embedder(embedder)
+            .with_dimension(dimension)
+            .with_collection_name("models_test")
+            .with_retry_policy(RetryPolicy { max_retries: 0, base_delay_ms: 10, jitter_ms: 0, ..RetryPolicy::default() })
+    };
+
+    let old = client(Arc::new(CountingEmbedder(Default::default())), 3);
+    old.index_document("reindex-doc", "Quarterly zeppelin maintenance").await.unwrap();
+    assert_eq!(old.collection_model("models_test").unwrap().model, "test-embedding");
+
+    // Same data, new model: writes are refused and semantic search steps aside
+    let upgraded = client(Arc::new(LocalEmbedder::new(16)), 16);
+    let err = upgraded.index_document("another-doc", "Budget review").await.expect_err("models must not mix");
+    assert!(err.contains("reindex"));
+    assert!(upgraded.dense_search_in("zeppelin", 5, None).await.unwrap().is_none());
+
+    let report = upgraded.reindex().await.unwrap();
+    assert_eq!((report.generation, report.reindexed, report.model.as_str()), (1, 1, "local-hash"));
+    assert_eq!(report.collections, vec!["models_test_g1".to_string()]);
+    assert_eq!(report.previous_collections, vec!["models_test".to_string()]);
+    assert!(upgraded.model_mismatch(None).is_none());
+
+    let hits = upgraded.dense_search_in("zeppelin maintenance", 5, None).await.unwrap().expect("vectors match the model");
+    assert_eq!(hits[0].doc_id, "reindex-doc");
+    assert!(upgraded.index_document("another-doc", "Budget review").await.is_ok());
+}
+
+#[tokio::test]
+async fn test_barq_server_errors_trip_the_circuit() {
+    use brainvault_backend::db::circuit_breaker::CircuitBreaker;
+
+    // Collection creation succeeds, then the insert answers 500
+    let url = stub_barq(vec![(201, ""), (500, "internal error")]).await;
+    let client = BarqVectorClient::connect(&url, "/nonexistent/brainvault-server-error-test")
+        .with_http_client(reqwest::Client::new())
+        .with_embedder(Arc::new(CountingEmbedder(Default::default())))
+        .with_dimension(3)
+        .with_circuit_breaker(CircuitBreaker::new(1, std::time::Duration::from_secs(60)));
+
+    client.index_document("breaker-5xx", "stored locally when Barq fails").await.unwrap();
+    assert_eq!(client.circuit_status().state, "open");
+    assert_eq!(client.circuit_status().consecutive_failures, 1);
+}
+
+/// `LocalEmbedder` that takes a while, so other work can interleave with a reindex
+struct SlowEmbedder(brainvault_backend::core::llm::embeddings::LocalEmbedder);
+
+#[async_trait]
+impl EmbeddingProvider for SlowEmbedder {
+    fn name(&self) -> &str { self.0.name() }
+    fn model(&self) -> &str { self.0.model() }
+    fn dimension(&self) -> usize { self.0.dimension() }
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, ProviderError> {
+        tokio::time::sleep(std::time::Duration::from_millis(30)).await;
+        self.0.embed(text).await
+    }
+}
+
+#[tokio::test]
+async fn test_reindex_keeps_writes_made_while_it_runs() {
+    use brainvault_backend::core::llm::embeddings::LocalEmbedder;
+    use brainvault_backend::core::llm::retry::RetryPolicy;
+
+    let data_path = std::env::temp_dir().join(format!("brainvault-reindex-race-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&data_path).unwrap();
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", data_path.to_str().unwrap())
+        .with_embedder(Arc::new(SlowEmbedder(LocalEmbedder::new(16))))
+        .with_dimension(16)
+        .with_retry_policy(RetryPolicy { max_retries: 0, base_delay_ms: 10, jitter_ms: 0, ..RetryPolicy::default() });
+    client.index_document("race-zeppelin", "Quarterly zeppelin maintenance").await.unwrap();
+    client.index_document("race-budget", "Annual budget review").await.unwrap();
+    client.index_document("race-lunch", "Office lunch menu").await.unwrap();
+
+    let writes = async {
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        client.index_document("race-late", "Submarine hull inspection checklist").await.unwrap();
+        client.delete_document("race-lunch").await.unwrap();
+    };
+    let (report, ()) = tokio::join!(client.reindex(), writes);
+    let report = report.unwrap();
+
+    assert_eq!(report.reindexed, 3);
+    assert!(report.failed.is_empty());
+    let hits = client.dense_search_in("submarine hull inspection", 5, None).await.unwrap().expect("query is embeddable");
+    assert_eq!(hits[0].doc_id, "race-late");
+    assert!(hits.iter().all(|h| h.doc_id != "race-lunch"));
+
+    let status = client.reindex_status();
+    assert!(!status.running);
+    assert_eq!(status.last_report.map(|r| r.generation), Some(1));
+}
+
+#[tokio::test]
+async fn test_openai_compatible_embeddings_name_the_configured_model() {
+    use brainvault_backend::core::llm::nafs_provider::embedding_request;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        // Headers and the JSON body may arrive in separate reads
+        let mut request = String::new();
+        let mut chunk = [0u8; 8192];
+        while !(request.contains("\r\n\r\n") && request.ends_with('}')) {
+            let n = socket.read(&mut chunk).await.unwrap();
+            if n == 0 {
+                break;
+            }
+            request.push_str(&String::from_utf8_lossy(&chunk[..n]));
+        }
+        let body = r#"{"data":[{"embedding":[0.25,0.5,0.75]}]}"#;
+        let response = format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+        socket.write_all(response.as_bytes()).await.unwrap();
+        request
+    });
+
+    let vector = embedding_request(&format!("http://{}/v1", addr), "key", "nomic-embed-text", "hello").await.unwrap();
+    assert_eq!(vector, vec![0.25, 0.5, 0.75]);
+    let request = server.await.unwrap();
+    assert!(request.starts_with("POST /v1/embeddings"));
+    assert!(request.contains(r#""model":"nomic-embed-text""#));
+}
+
+/// Never finishes an embedding, so a warm-up stays in its re-embedding phase
+struct StalledEmbedder;
+
+#[async_trait]
+impl EmbeddingProvider for StalledEmbedder {
+    fn name(&self) -> &str { "test" }
+    fn model(&self) -> &str { "test-embedding" }
+    fn dimension(&self) -> usize { 3 }
+    async fn embed(&self, _text: &str) -> Result<Vec<f32>, ProviderError> {
+        tokio::time::sleep(std::time::Duration::from_secs(3600)).await;
+        Ok(vec![0.1, 0.2, 0.3])
+    }
+}
+
+#[tokio::test]
+async fn test_ready_while_warm_up_reembeds() {
+    let dir = std::env::temp_dir().join(format!("brainvault-reembed-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("vector_cache.json"), r#"{"stalled-doc": "content still waiting for a vector"}"#).unwrap();
+    let client = BarqVectorClient::connect("http://127.0.0.1:9", dir.to_str().unwrap())
+        .with_embedder(Arc::new(StalledEmbedder))
+        .with_dimension(3);
+
+    let warm_up = client.spawn_warm_up(true);
+    let reembedding = tokio::time::timeout(std::time::Duration::from_secs(30), async {
+        while client.warmup_status().state != "reembedding" {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+    }).await;
+    assert!(reembedding.is_ok());
+    assert!(client.is_ready());
+    let status = client.warmup_status();
+    assert_eq!((status.missing, status.embedded), (1, 0));
+    warm_up.abort();
+}
@@ -3,7 +3,7 @@
 import React from "react";
 import { useParams } from "next/navigation";
 import useSWR from "swr";
-import { api, TaskResponse } from "@/lib/api";
+import { api, resultText, TaskResponse } from "@/lib/api";
 import {
     Loader2,
     CheckCircle2,
@@ -83,7 +83,7 @@ export default function AgentTaskDetail() {
                     {task.status === 'Completed' && (
                         <div className="p-4 rounded-lg bg-green-500/10 border border-green-500/20 text-green-400 max-w-sm">
                             <p className="font-semibold text-sm mb-1">Final Result</p>
-                            <p className="text-sm opacity-90">{task.result}</p>
+                            <p className="text-sm opacity-90 whitespace-pre-wrap">{resultText(task.result)}</p>
                         </div>
                     )}
                 </div>
@@ -11,7 +11,7 @@ import {
     ArrowRight
 } from "lucide-react";
 import Link from "next/link";
-import { api, TaskResponse } from "@/lib/api";
+import { api, resultText, TaskResponse } from "@/lib/api";
 import useSWR from "swr";
 import { clsx } from "clsx";
 
@@ -161,7 +161,7 @@ export default function AgentsPage() {
                                         </Link>
                                     </div>
                                     <p className="text-foreground font-medium line-clamp-2">
-                                        {task.result || task.audit_log.find(l => l.action === "SUBMITTED")?.details || "Processing..."}
+                                        {resultText(task.result) || task.audit_log.find(l => l.action === "SUBMITTED")?.details || "Processing..."}
                                     </p>
 
                                     {/* Simple progress bar simulation */}
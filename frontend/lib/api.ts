@@ -43,10 +43,17 @@ export interface AuditLogEntry {
 export interface TaskResponse {
     task_id: string;
     status: TaskStatus;
-    result: string | null;
+    /** Plain text, or structured JSON for tasks submitted with `response_format: "Json"` */
+    result: unknown;
     audit_log: AuditLogEntry[];
 }
 
+/** Displayable form of a task result: strings as-is, structured results as indented JSON */
+export function resultText(result: unknown): string | null {
+    if (result === null || result === undefined) return null;
+    return typeof result === "string" ? result : JSON.stringify(result, null, 2);
+}
+
 export interface TaskRequest {
     description: string;
 }